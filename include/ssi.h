@@ -0,0 +1,72 @@
+/* SPDX-License-Identifier: Apache-2.0 */
+
+#ifndef SSI_H
+#define SSI_H
+
+/* Generated with cbindgen from src/ffi.rs; do not edit manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Operation has succeeded.
+#define SSI_OK 0
+
+// A required pointer argument is null.
+#define SSI_ERR_NULL -1
+
+// String argument is not a valid UTF-8 string.
+#define SSI_ERR_UTF8 -2
+
+// Identity or public key string can't be parsed.
+#define SSI_ERR_PARSE -3
+
+// Signature doesn't match the public key and the message.
+#define SSI_ERR_INVALID_SIG -4
+
+// Identity is not signed.
+#define SSI_ERR_UNSIGNED -5
+
+// Signature algorithm is not supported.
+#define SSI_ERR_UNSUPPORTED -6
+
+// Opaque handle to an identity public key.
+typedef struct SsiPubHandle SsiPubHandle;
+
+// Parses SSI identity URI and verifies its self-signature.
+//
+// Returns [`SSI_OK`] if the identity is valid and signed.
+//
+// # Safety
+//
+// `uri_ptr` must point to `len` bytes of readable memory.
+int32_t ssi_verify(const uint8_t *uri_ptr, uintptr_t len);
+
+// Parses public key from its string representation, storing a newly
+// allocated handle into `out`. The handle must be released with
+// [`ssi_pub_free`].
+//
+// # Safety
+//
+// `ptr` must point to `len` bytes of readable memory; `out` must be a valid
+// pointer for writes.
+int32_t ssi_pub_from_str(const uint8_t *ptr, uintptr_t len, SsiPubHandle **out);
+
+// Releases public key handle. Does nothing if `pk` is null.
+//
+// # Safety
+//
+// `pk` must be either null or a handle returned by [`ssi_pub_from_str`]
+// which was not released before.
+void ssi_pub_free(SsiPubHandle *pk);
+
+// Verifies 64-byte signature over a 32-byte message digest.
+//
+// # Safety
+//
+// `pk` must be a valid handle returned by [`ssi_pub_from_str`]; `msg32` and
+// `sig64` must point to 32 and 64 bytes of readable memory, respectively.
+int32_t ssi_sig_verify(const SsiPubHandle *pk, const uint8_t *msg32, const uint8_t *sig64);
+
+#endif /* SSI_H */
@@ -0,0 +1,96 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bitcoin-style addresses for identity keys.
+
+use bech32::{hrp, segwit, Hrp};
+
+use crate::bip340::taproot_output_key;
+use crate::{Algo, Chain, SsiPub};
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum AddressError {
+    /// addresses can be constructed only for BIP340 keys, while the key uses {0}.
+    UnsupportedAlgo(Algo),
+    /// chain {0} has no known address format.
+    UnsupportedChain(Chain),
+    /// the key is not a valid secp256k1 point.
+    InvalidPubkey,
+    /// unable to encode address - {0}
+    Encode(String),
+}
+
+impl SsiPub {
+    /// Renders bech32m pay-to-taproot address for the `chain`, which can be
+    /// spent by the key with a key-path spending (i.e. the key is used as the
+    /// BIP86 internal key, tweaked without a script tree).
+    ///
+    /// Liquid addresses are unconfidential.
+    pub fn to_taproot_address(&self, chain: Chain) -> Result<String, AddressError> {
+        if *self.algo() != Algo::Bip340 {
+            return Err(AddressError::UnsupportedAlgo(*self.algo()));
+        }
+        let hrp = match chain {
+            Chain::Bitcoin => hrp::BC,
+            Chain::Signet => hrp::TB,
+            Chain::Regtest => hrp::BCRT,
+            Chain::Liquid => Hrp::parse_unchecked("ex"),
+            Chain::Other(_) => return Err(AddressError::UnsupportedChain(chain)),
+        };
+        let key =
+            taproot_output_key(self.to_byte_array()).map_err(|_| AddressError::InvalidPubkey)?;
+        segwit::encode_v1(hrp, &key).map_err(|e| AddressError::Encode(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::hex::FromHex;
+    use amplify::Bytes32;
+
+    use super::*;
+
+    #[test]
+    fn bip86_address() {
+        let key =
+            Bytes32::from_hex("cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115")
+                .unwrap();
+        let pk = SsiPub::with(Chain::Bitcoin, Algo::Bip340, key.to_byte_array());
+        assert_eq!(
+            pk.to_taproot_address(Chain::Bitcoin).unwrap(),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+        assert!(pk.to_taproot_address(Chain::Signet).unwrap().starts_with("tb1p"));
+        assert!(pk.to_taproot_address(Chain::Regtest).unwrap().starts_with("bcrt1p"));
+        assert!(pk.to_taproot_address(Chain::Liquid).unwrap().starts_with("ex1p"));
+        assert_eq!(
+            pk.to_taproot_address(Chain::Other(0xFE)),
+            Err(AddressError::UnsupportedChain(Chain::Other(0xFE)))
+        );
+
+        let pk = SsiPub::with(Chain::Bitcoin, Algo::Ed25519, key.to_byte_array());
+        assert_eq!(
+            pk.to_taproot_address(Chain::Bitcoin),
+            Err(AddressError::UnsupportedAlgo(Algo::Ed25519))
+        );
+    }
+}
@@ -0,0 +1,181 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [`proptest`] strategies generating keys, UIDs, expiry dates and complete
+//! signed identities, used for property-based testing of the SSI text form.
+
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Utc};
+use ec25519::{KeyPair, Seed};
+use proptest::collection::btree_set;
+use proptest::option;
+use proptest::prelude::*;
+use proptest::string::string_regex;
+
+use crate::{
+    Bip340Secret, Chain, Commitment, Ed25519Secret, Ssi, SsiPub, SsiSecret, Uid,
+    SSI_COMMENT_MAX_LEN,
+};
+
+/// Latest expiry date generated by [`arb_expiry`], which is 2100-01-01.
+const MAX_EXPIRY: i64 = 4_102_444_800;
+
+/// Generates one of the chains known to this version.
+pub fn arb_chain() -> impl Strategy<Value = Chain> {
+    prop_oneof![
+        Just(Chain::Bitcoin),
+        Just(Chain::Liquid),
+        Just(Chain::Signet),
+        Just(Chain::Regtest)
+    ]
+}
+
+/// Generates Ed25519 and BIP340 secret keys.
+pub fn arb_secret() -> impl Strategy<Value = SsiSecret> {
+    prop_oneof![
+        (arb_chain(), any::<[u8; 32]>()).prop_map(|(chain, seed)| {
+            let sk = KeyPair::from_seed(Seed::new(seed)).sk;
+            SsiSecret::from(Ed25519Secret::with(chain, sk))
+        }),
+        (arb_chain(), any::<[u8; 32]>()).prop_filter_map("invalid secp256k1 key", |(chain, key)| {
+            Bip340Secret::with(chain, key).ok().map(SsiSecret::from)
+        }),
+    ]
+}
+
+/// Generates public keys of [`arb_secret`].
+pub fn arb_ssi_pub() -> impl Strategy<Value = SsiPub> { arb_secret().prop_map(|sk| sk.to_public()) }
+
+/// Generates UIDs with names, comments and ids containing URI delimiters and
+/// other characters requiring percent-encoding, including non-ASCII letters.
+///
+/// Characters which can't be represented in a UID (angle brackets, spaces in
+/// the id, parentheses in the name, and colons in the schema) are not
+/// generated.
+pub fn arb_uid() -> impl Strategy<Value = Uid> {
+    (
+        "([\\w.,'+&=?/%#-]([\\w .,'+&=?/%#-]{0,14}[\\w.,'+&=?/%#-])?)?",
+        option::of("[A-Za-z0-9À-ſ一-丿.,'+&=?/%#-]{1,12}"),
+        "[a-z][a-z0-9+.-]{0,8}",
+        "[A-Za-z0-9À-ſ@.,_~/=&?+%#-]{1,24}",
+    )
+        .prop_map(|(name, comment, schema, id)| Uid {
+            name,
+            comment,
+            schema,
+            id,
+            primary: false,
+        })
+}
+
+/// Generates expiry dates with a second precision, including plain dates,
+/// which are serialized in a short form.
+pub fn arb_expiry() -> impl Strategy<Value = DateTime<Utc>> {
+    prop_oneof![
+        (0..MAX_EXPIRY).prop_map(|secs| DateTime::from_timestamp(secs, 0).expect("valid time")),
+        (0..MAX_EXPIRY / 86_400).prop_map(|days| {
+            DateTime::from_timestamp(days * 86_400 + 86_399, 0).expect("valid time")
+        }),
+    ]
+}
+
+/// Generates identities signed with both commitment versions, having up to
+/// four UIDs (with an optional primary one), an optional expiry date and an
+/// optional comment.
+pub fn arb_ssi() -> impl Strategy<Value = Ssi> {
+    (
+        arb_secret(),
+        btree_set(arb_uid(), 0..4),
+        any::<bool>(),
+        option::of(arb_expiry()),
+        option::of(
+            string_regex(&format!("[^\\p{{Cc}}]{{0,{}}}", SSI_COMMENT_MAX_LEN / 4))
+                .expect("valid regex"),
+        ),
+        any::<bool>(),
+    )
+        .prop_map(|(secret, uids, primary, expiry, comment, legacy)| {
+            let mut uids = uids.into_iter().collect::<Vec<_>>();
+            if let Some(uid) = uids.first_mut().filter(|_| primary) {
+                uid.primary = true;
+            }
+            let uids = uids.into_iter().collect::<BTreeSet<_>>();
+            let mut ssi = Ssi::new(uids, expiry, &secret);
+            ssi.comment = comment;
+            if legacy {
+                ssi.commitment = Commitment::Legacy;
+            }
+            ssi.sig = Some(secret.sign(ssi.to_message()));
+            ssi
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{IntegrityStatus, UidEncoding};
+
+    proptest! {
+        #[test]
+        fn uid_roundtrip(uid in arb_uid()) {
+            prop_assert_eq!(Uid::from_str(&uid.to_string()).unwrap(), uid);
+        }
+
+        #[test]
+        fn ssi_roundtrip(ssi in arb_ssi()) {
+            let s = ssi.to_string();
+            let parsed = Ssi::from_str(&s).unwrap();
+            prop_assert_eq!(&parsed, &ssi);
+            prop_assert_eq!(parsed.to_string(), s);
+        }
+
+        #[test]
+        fn message_invariant(ssi in arb_ssi()) {
+            let msg = ssi.to_message();
+            let parsed = Ssi::from_str(&ssi.to_string()).unwrap();
+            prop_assert_eq!(parsed.to_message(), msg);
+            let parsed = Ssi::from_str(&parsed.to_string()).unwrap();
+            prop_assert_eq!(parsed.to_message(), msg);
+            prop_assert_eq!(parsed.integrity(), IntegrityStatus::SelfSigned);
+        }
+
+        #[test]
+        fn strict_encoding_roundtrip(ssi in arb_ssi()) {
+            let s = ssi.to_string_with(UidEncoding::Strict);
+            prop_assert_eq!(Ssi::from_str(&s).unwrap(), ssi);
+        }
+
+        #[test]
+        fn canonical_roundtrip(ssi in arb_ssi()) {
+            let canonical = ssi.canonical_string();
+            prop_assert_eq!(ssi.to_string(), canonical.clone());
+            let parsed = Ssi::from_str(&canonical).unwrap();
+            prop_assert_eq!(&parsed, &ssi);
+            prop_assert_eq!(parsed.canonical_string(), canonical);
+            prop_assert_eq!(parsed.sig, ssi.sig);
+            prop_assert_eq!(parsed.to_message(), ssi.to_message());
+            prop_assert_eq!(parsed.integrity(), IntegrityStatus::SelfSigned);
+        }
+    }
+}
@@ -21,21 +21,195 @@
 
 use std::cmp::Ordering;
 use std::hash::{Hash, Hasher};
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::mpsc;
+use std::thread;
 
 use secp256k1::schnorr::Signature;
-use secp256k1::{Keypair, Message, SecretKey, XOnlyPublicKey, SECP256K1};
+use secp256k1::{Keypair, Message, Parity, PublicKey, Scalar, SecretKey, XOnlyPublicKey, SECP256K1};
+use sha2::{Digest, Sha256};
 
-use crate::{Algo, Chain, InvalidPubkey, InvalidSig, SsiPub, SsiSig};
+use crate::{Algo, Chain, Fingerprint, InvalidPubkey, InvalidSig, SsiPub, SsiSig};
 
-#[derive(Clone, Eq, PartialEq, From)]
+const KEY_AGG_LIST_TAG: &[u8] = b"KeyAgg list";
+const KEY_AGG_COEF_TAG: &[u8] = b"KeyAgg coefficient";
+
+/// Implementation of BIP340 signature scheme.
+///
+/// Allows to select between `secp256k1` (default) and pure-Rust `k256`
+/// (with `pure-rust` feature) libraries without changing public APIs.
+pub(crate) trait Bip340Backend {
+    fn is_valid_secret(sk: &[u8; 32]) -> bool;
+    fn public_key(sk: &[u8; 32]) -> [u8; 32];
+    /// Full public key point in SEC1 uncompressed form.
+    fn public_key_uncompressed(sk: &[u8; 32]) -> [u8; 65];
+    /// Adds `tweak * G` to the point with the even Y coordinate having `pk`
+    /// as its X coordinate, returning X coordinate of the resulting point.
+    #[cfg(any(feature = "address", test))]
+    fn add_tweak(pk: [u8; 32], tweak: [u8; 32]) -> Result<[u8; 32], InvalidPubkey>;
+    /// Sums points with the even Y coordinate having `pks` as their X
+    /// coordinates, each multiplied by the scalar from `coefs` with the same
+    /// index, returning X coordinate of the sum.
+    fn combine(pks: &[[u8; 32]], coefs: &[[u8; 32]]) -> Result<[u8; 32], InvalidPubkey>;
+    fn sign(sk: &[u8; 32], msg: [u8; 32], aux_rand: [u8; 32]) -> [u8; 64];
+    fn verify(pk: [u8; 32], msg: [u8; 32], sig: [u8; 64]) -> Result<(), InvalidSig>;
+}
+
+#[cfg_attr(feature = "pure-rust", allow(dead_code))]
+pub(crate) struct Secp256k1Backend;
+
+impl Bip340Backend for Secp256k1Backend {
+    fn is_valid_secret(sk: &[u8; 32]) -> bool { SecretKey::from_slice(sk).is_ok() }
+
+    fn public_key(sk: &[u8; 32]) -> [u8; 32] {
+        let sk = SecretKey::from_slice(sk).expect("invalid BIP340 secret key");
+        let (pk, _) = sk.x_only_public_key(SECP256K1);
+        pk.serialize()
+    }
+
+    fn public_key_uncompressed(sk: &[u8; 32]) -> [u8; 65] {
+        let sk = SecretKey::from_slice(sk).expect("invalid BIP340 secret key");
+        sk.public_key(SECP256K1).serialize_uncompressed()
+    }
+
+    #[cfg(any(feature = "address", test))]
+    fn add_tweak(pk: [u8; 32], tweak: [u8; 32]) -> Result<[u8; 32], InvalidPubkey> {
+        let pk = XOnlyPublicKey::from_slice(&pk).map_err(|_| InvalidPubkey)?;
+        let tweak = Scalar::from_be_bytes(tweak).map_err(|_| InvalidPubkey)?;
+        let (pk, _) = pk.add_tweak(SECP256K1, &tweak).map_err(|_| InvalidPubkey)?;
+        Ok(pk.serialize())
+    }
+
+    fn combine(pks: &[[u8; 32]], coefs: &[[u8; 32]]) -> Result<[u8; 32], InvalidPubkey> {
+        let points = pks
+            .iter()
+            .zip(coefs)
+            .map(|(pk, coef)| {
+                let pk = XOnlyPublicKey::from_slice(pk).map_err(|_| InvalidPubkey)?;
+                let coef = Scalar::from_be_bytes(*coef).map_err(|_| InvalidPubkey)?;
+                pk.public_key(Parity::Even).mul_tweak(SECP256K1, &coef).map_err(|_| InvalidPubkey)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let points = points.iter().collect::<Vec<_>>();
+        let sum = PublicKey::combine_keys(&points).map_err(|_| InvalidPubkey)?;
+        Ok(sum.x_only_public_key().0.serialize())
+    }
+
+    fn sign(sk: &[u8; 32], msg: [u8; 32], aux_rand: [u8; 32]) -> [u8; 64] {
+        let msg = Message::from_digest(msg);
+        let keypair = Keypair::from_seckey_slice(SECP256K1, sk).expect("invalid BIP340 secret key");
+        let sig = SECP256K1.sign_schnorr_with_aux_rand(&msg, &keypair, &aux_rand);
+        sig.serialize()
+    }
+
+    fn verify(pk: [u8; 32], msg: [u8; 32], sig: [u8; 64]) -> Result<(), InvalidSig> {
+        let sig = Signature::from_slice(&sig).map_err(|_| InvalidSig::InvalidData)?;
+        let msg = Message::from_digest(msg);
+        let pk = XOnlyPublicKey::from_slice(&pk).map_err(|_| InvalidPubkey)?;
+        sig.verify(&msg, &pk).map_err(|_| InvalidSig::InvalidSig)
+    }
+}
+
+#[cfg(feature = "pure-rust")]
+pub(crate) struct K256Backend;
+
+#[cfg(feature = "pure-rust")]
+impl Bip340Backend for K256Backend {
+    fn is_valid_secret(sk: &[u8; 32]) -> bool { k256::schnorr::SigningKey::from_bytes(sk).is_ok() }
+
+    fn public_key(sk: &[u8; 32]) -> [u8; 32] {
+        let sk = k256::schnorr::SigningKey::from_bytes(sk).expect("invalid BIP340 secret key");
+        sk.verifying_key().to_bytes().into()
+    }
+
+    fn public_key_uncompressed(sk: &[u8; 32]) -> [u8; 65] {
+        use k256::elliptic_curve::sec1::ToEncodedPoint;
+
+        let sk = k256::SecretKey::from_slice(sk).expect("invalid BIP340 secret key");
+        let point = sk.public_key().to_encoded_point(false);
+        point.as_bytes().try_into().expect("uncompressed point")
+    }
+
+    #[cfg(any(feature = "address", test))]
+    fn add_tweak(pk: [u8; 32], tweak: [u8; 32]) -> Result<[u8; 32], InvalidPubkey> {
+        use k256::elliptic_curve::point::AffineCoordinates;
+        use k256::elliptic_curve::PrimeField;
+        use k256::{ProjectivePoint, Scalar};
+
+        let pk = k256::schnorr::VerifyingKey::from_bytes(&pk).map_err(|_| InvalidPubkey)?;
+        let tweak = Option::<Scalar>::from(Scalar::from_repr(tweak.into())).ok_or(InvalidPubkey)?;
+        let point = ProjectivePoint::from(*pk.as_affine()) + ProjectivePoint::GENERATOR * tweak;
+        Ok(point.to_affine().x().into())
+    }
+
+    fn combine(pks: &[[u8; 32]], coefs: &[[u8; 32]]) -> Result<[u8; 32], InvalidPubkey> {
+        use k256::elliptic_curve::point::AffineCoordinates;
+        use k256::elliptic_curve::PrimeField;
+        use k256::{ProjectivePoint, Scalar};
+
+        let mut sum = ProjectivePoint::IDENTITY;
+        for (pk, coef) in pks.iter().zip(coefs) {
+            let pk = k256::schnorr::VerifyingKey::from_bytes(pk).map_err(|_| InvalidPubkey)?;
+            let coef =
+                Option::<Scalar>::from(Scalar::from_repr((*coef).into())).ok_or(InvalidPubkey)?;
+            sum += ProjectivePoint::from(*pk.as_affine()) * coef;
+        }
+        if sum == ProjectivePoint::IDENTITY {
+            return Err(InvalidPubkey);
+        }
+        Ok(sum.to_affine().x().into())
+    }
+
+    fn sign(sk: &[u8; 32], msg: [u8; 32], aux_rand: [u8; 32]) -> [u8; 64] {
+        let sk = k256::schnorr::SigningKey::from_bytes(sk).expect("invalid BIP340 secret key");
+        let sig = sk
+            .sign_raw(&msg, &aux_rand)
+            .expect("BIP340 signing with valid key");
+        sig.to_bytes()
+    }
+
+    fn verify(pk: [u8; 32], msg: [u8; 32], sig: [u8; 64]) -> Result<(), InvalidSig> {
+        let sig = k256::schnorr::Signature::try_from(sig.as_slice())
+            .map_err(|_| InvalidSig::InvalidData)?;
+        let pk = k256::schnorr::VerifyingKey::from_bytes(&pk).map_err(|_| InvalidPubkey)?;
+        pk.verify_raw(&msg, &sig).map_err(|_| InvalidSig::InvalidSig)
+    }
+}
+
+#[cfg(not(feature = "pure-rust"))]
+type Backend = Secp256k1Backend;
+#[cfg(feature = "pure-rust")]
+type Backend = K256Backend;
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display("invalid BIP340 secret key")]
+pub struct InvalidSecret;
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum AggError {
+    /// no keys to aggregate.
+    NoKeys,
+
+    /// key {0} is not a BIP340 key and can't be aggregated.
+    NotBip340(Fingerprint),
+
+    /// keys to aggregate belong to different chains.
+    ChainMismatch,
+
+    /// aggregated key is invalid.
+    InvalidKey,
+}
+
+#[derive(Clone, Eq, PartialEq)]
 pub struct Bip340Secret {
     pub chain: Chain,
     pub algo: Algo,
-    pub(crate) key: SecretKey,
+    pub(crate) key: [u8; 32],
 }
 
 impl Ord for Bip340Secret {
-    fn cmp(&self, other: &Self) -> Ordering { self.0.secret_bytes().cmp(&other.0.secret_bytes()) }
+    fn cmp(&self, other: &Self) -> Ordering { self.key.cmp(&other.key) }
 }
 
 impl PartialOrd for Bip340Secret {
@@ -43,40 +217,134 @@ impl PartialOrd for Bip340Secret {
 }
 
 impl Hash for Bip340Secret {
-    fn hash<H: Hasher>(&self, state: &mut H) { self.0.secret_bytes().hash(state) }
+    fn hash<H: Hasher>(&self, state: &mut H) { self.key.hash(state) }
 }
 
 impl Bip340Secret {
     pub fn new(chain: Chain) -> Self {
-        use rand::thread_rng;
-        let key = SecretKey::new(&mut thread_rng());
-        Self {
+        use rand::{thread_rng, RngCore};
+        let mut key = [0u8; 32];
+        loop {
+            thread_rng().fill_bytes(&mut key);
+            if let Ok(sk) = Self::with(chain, key) {
+                return sk;
+            }
+        }
+    }
+
+    /// Generates a key whose fingerprint starts with the given `prefix`,
+    /// searching in parallel on all available CPU cores.
+    ///
+    /// The prefix is a case-insensitive hex string, which may have an odd
+    /// number of nibbles (like `CAF`). Each prefix nibble multiplies the
+    /// expected number of generated keys by 16: a 2-nibble prefix takes ~256
+    /// attempts, 4 nibbles ~65 thousand, 6 nibbles ~16.8 million and 8
+    /// nibbles ~4.3 billion attempts.
+    ///
+    /// The search can be stopped from another thread by setting `cancel`, in
+    /// which case `None` is returned.
+    ///
+    /// # Panics
+    ///
+    /// If the prefix is not a hex string or is longer than the 12-nibble
+    /// fingerprint.
+    pub fn new_vanity(chain: Chain, prefix: &str, cancel: &AtomicBool) -> Option<Self> {
+        let nibbles = prefix
+            .chars()
+            .map(|c| c.to_digit(16).expect("vanity prefix is not a hex string") as u8)
+            .collect::<Vec<_>>();
+        assert!(nibbles.len() <= 12, "vanity prefix is longer than the key fingerprint");
+        let matches = |pk: [u8; 32]| {
+            nibbles.iter().enumerate().all(|(i, nibble)| {
+                let byte = pk[i / 2];
+                *nibble == if i % 2 == 0 { byte >> 4 } else { byte & 0x0F }
+            })
+        };
+
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let found = AtomicBool::new(false);
+        let (tx, rx) = mpsc::channel();
+        thread::scope(|scope| {
+            for _ in 0..threads {
+                let tx = tx.clone();
+                let found = &found;
+                let matches = &matches;
+                scope.spawn(move || {
+                    while !found.load(atomic::Ordering::Relaxed)
+                        && !cancel.load(atomic::Ordering::Relaxed)
+                    {
+                        let sk = Self::new(chain);
+                        if matches(sk.to_public().to_byte_array()) {
+                            found.store(true, atomic::Ordering::Relaxed);
+                            // Other threads may have found a key at the same time
+                            let _ = tx.send(sk);
+                        }
+                    }
+                });
+            }
+        });
+        rx.try_recv().ok()
+    }
+
+    pub fn with(chain: Chain, key: [u8; 32]) -> Result<Self, InvalidSecret> {
+        if !Backend::is_valid_secret(&key) {
+            return Err(InvalidSecret);
+        }
+        Ok(Self {
             chain,
             algo: Algo::Bip340,
             key,
-        }
+        })
     }
 
-    pub fn to_public(&self, chain: Chain, algo: Algo) -> SsiPub {
-        let (pk, _) = self.key.x_only_public_key(SECP256K1);
-        let data = pk.serialize();
-        SsiPub::with(chain, algo, data)
+    pub fn to_public(&self) -> SsiPub {
+        SsiPub::with(self.chain, self.algo, Backend::public_key(&self.key))
+    }
+
+    /// Returns the public key in SEC1 compressed form, which, unlike the
+    /// x-only form used by [`SsiPub`], preserves the parity of the Y
+    /// coordinate.
+    ///
+    /// [`SsiPub`] keeps just the X coordinate, dropping the parity bit: BIP340
+    /// defines the key as the point with the even Y coordinate, so the
+    /// canonical point is recovered from [`SsiPub`] by prefixing its 32 bytes
+    /// with `0x02`. The secret key may correspond to the odd point, in which
+    /// case this method returns a key with `0x03` prefix.
+    pub fn to_sec_pubkey(&self) -> [u8; 33] {
+        let full = self.to_sec_pubkey_uncompressed();
+        let mut key = [0u8; 33];
+        key[0] = 0x02 | (full[64] & 1);
+        key[1..].copy_from_slice(&full[1..33]);
+        key
+    }
+
+    /// Returns the public key in SEC1 uncompressed form (see
+    /// [`Bip340Secret::to_sec_pubkey`] on the parity of the point).
+    pub fn to_sec_pubkey_uncompressed(&self) -> [u8; 65] {
+        Backend::public_key_uncompressed(&self.key)
     }
 
     pub fn sign(&self, msg: [u8; 32]) -> SsiSig {
-        let msg = Message::from_digest(msg);
-        let keypair = Keypair::from_secret_key(SECP256K1, &self.0);
-        let sig = SECP256K1.sign_schnorr(&msg, &keypair);
-        SsiSig::from(sig.serialize())
+        use rand::{thread_rng, RngCore};
+        let mut aux_rand = [0u8; 32];
+        thread_rng().fill_bytes(&mut aux_rand);
+        SsiSig::from(Backend::sign(&self.key, msg, aux_rand))
+    }
+
+    /// Signs the message using BIP340 deterministic nonce generation, i.e.
+    /// with all-zero auxiliary randomness. Signatures produced by this method
+    /// are identical for identical key and message pairs.
+    ///
+    /// Use [`Bip340Secret::sign`] unless reproducible signatures are required,
+    /// or there is no good source of randomness.
+    pub fn sign_deterministic(&self, msg: [u8; 32]) -> SsiSig {
+        SsiSig::from(Backend::sign(&self.key, msg, [0u8; 32]))
     }
 }
 
 impl SsiPub {
     pub fn verify_bip360(self, msg: [u8; 32], sig: SsiSig) -> Result<(), InvalidSig> {
-        let sig = Signature::from_slice(sig.as_slice()).map_err(|_| InvalidSig::InvalidData)?;
-        let msg = Message::from_digest(msg);
-        let pk = XOnlyPublicKey::try_from(self)?;
-        sig.verify(&msg, &pk).map_err(|_| InvalidSig::InvalidSig)
+        Backend::verify(self.to_byte_array(), msg, sig.to_byte_array())
     }
 }
 
@@ -90,7 +358,275 @@ impl TryFrom<SsiPub> for XOnlyPublicKey {
 
 impl SsiPub {
     pub fn from_bip340(key: XOnlyPublicKey) -> Self {
-        let bytes = key.serialize();
-        Self::from(bytes)
+        Self::with(Chain::default(), Algo::Bip340, key.serialize())
+    }
+
+    /// Aggregates BIP340 keys of co-signers into a single key with MuSig2 key
+    /// aggregation (BIP327). A signature produced by the co-signers with the
+    /// MuSig2 protocol is a regular BIP340 signature for the aggregated key.
+    ///
+    /// Keys are sorted before the aggregation, so the aggregated key doesn't
+    /// depend on their order.
+    pub fn aggregate(keys: &[SsiPub]) -> Result<SsiPub, AggError> {
+        let first = keys.first().ok_or(AggError::NoKeys)?;
+        if let Some(key) = keys.iter().find(|key| *key.algo() != Algo::Bip340) {
+            return Err(AggError::NotBip340(key.fingerprint()));
+        }
+        if keys.iter().any(|key| key.chain() != first.chain()) {
+            return Err(AggError::ChainMismatch);
+        }
+        let mut pks = keys.iter().map(SsiPub::to_byte_array).collect::<Vec<_>>();
+        pks.sort_unstable();
+        let coefs = key_agg_coefs(&pks);
+        let agg = Backend::combine(&pks, &coefs).map_err(|_| AggError::InvalidKey)?;
+        Ok(SsiPub::with(*first.chain(), Algo::Bip340, agg))
+    }
+
+    /// Verifies signatures of the co-signers over the message.
+    ///
+    /// If there are multiple keys, all of them are BIP340 and there is a
+    /// single signature, it is verified once against the aggregated key (see
+    /// [`SsiPub::aggregate`]). Otherwise, like for ed25519 co-signers, there
+    /// must be a signature by each of the keys, in the same order as the keys.
+    pub fn verify_cosigned(
+        keys: &[SsiPub],
+        msg: [u8; 32],
+        sigs: &[SsiSig],
+    ) -> Result<(), InvalidSig> {
+        let bip340 = keys.iter().all(|key| *key.algo() == Algo::Bip340);
+        if let [sig] = sigs {
+            if bip340 && keys.len() > 1 {
+                let agg = SsiPub::aggregate(keys).map_err(|_| InvalidPubkey)?;
+                return agg.verify(msg, *sig);
+            }
+        }
+        if keys.is_empty() || sigs.len() != keys.len() {
+            return Err(InvalidSig::InvalidData);
+        }
+        keys.iter().zip(sigs).try_for_each(|(key, sig)| key.verify(msg, *sig))
+    }
+}
+
+fn tagged_engine(tag: &[u8]) -> Sha256 {
+    let tag = Sha256::digest(tag);
+    let mut engine = Sha256::new();
+    engine.update(tag);
+    engine.update(tag);
+    engine
+}
+
+/// Computes MuSig2 key aggregation coefficients (BIP327) for the x-only keys,
+/// which are lifted to the points with the even Y coordinate.
+fn key_agg_coefs(pks: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    let mut engine = tagged_engine(KEY_AGG_LIST_TAG);
+    for pk in pks {
+        engine.update([0x02]);
+        engine.update(pk);
+    }
+    let list: [u8; 32] = engine.finalize().into();
+    let second = pks.iter().find(|pk| *pk != &pks[0]);
+    pks.iter()
+        .map(|pk| {
+            if Some(pk) == second {
+                let mut one = [0u8; 32];
+                one[31] = 1;
+                return one;
+            }
+            let mut engine = tagged_engine(KEY_AGG_COEF_TAG);
+            engine.update(list);
+            engine.update([0x02]);
+            engine.update(pk);
+            engine.finalize().into()
+        })
+        .collect()
+}
+
+/// Computes BIP86 taproot output key for a key-path-only spending of the
+/// internal key `pk`.
+#[cfg(any(feature = "address", test))]
+pub(crate) fn taproot_output_key(pk: [u8; 32]) -> Result<[u8; 32], InvalidPubkey> {
+    let tag = Sha256::digest(b"TapTweak");
+    let mut engine = Sha256::new();
+    engine.update(tag);
+    engine.update(tag);
+    engine.update(pk);
+    Backend::add_tweak(pk, engine.finalize().into())
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::hex::FromHex;
+    use amplify::Bytes32;
+
+    use super::*;
+
+    #[test]
+    fn deterministic_sig() {
+        let sk = Bip340Secret::new(Chain::Bitcoin);
+        let msg = [0xA5; 32];
+        let sig1 = sk.sign_deterministic(msg);
+        let sig2 = sk.sign_deterministic(msg);
+        assert_eq!(sig1, sig2);
+        sk.to_public().verify_bip360(msg, sig1).unwrap();
+        assert_ne!(sk.sign_deterministic([0x5A; 32]), sig1);
+    }
+
+    #[test]
+    fn vanity() {
+        let cancel = AtomicBool::new(false);
+        let sk = Bip340Secret::new_vanity(Chain::Signet, "cA", &cancel).unwrap();
+        let pk = sk.to_public();
+        assert_eq!(pk.to_byte_array()[0], 0xCA);
+        assert_eq!(pk.chain(), &Chain::Signet);
+
+        let sk = Bip340Secret::new_vanity(Chain::Bitcoin, "CAF", &cancel).unwrap();
+        let pk = sk.to_public().to_byte_array();
+        assert_eq!((pk[0], pk[1] >> 4), (0xCA, 0xF));
+        assert_eq!(sk.chain, Chain::Bitcoin);
+        assert!(Bip340Secret::new_vanity(Chain::Bitcoin, "", &cancel).is_some());
+
+        cancel.store(true, atomic::Ordering::Relaxed);
+        assert!(Bip340Secret::new_vanity(Chain::Bitcoin, "CAFEBABE", &cancel).is_none());
+    }
+
+    #[test]
+    fn sec_pubkey() {
+        let sk = Bip340Secret::new(Chain::Bitcoin);
+        let pk = sk.to_public().to_byte_array();
+        let compressed = sk.to_sec_pubkey();
+        let uncompressed = sk.to_sec_pubkey_uncompressed();
+        assert_eq!(compressed[1..], pk);
+        assert_eq!(uncompressed[1..33], pk);
+        assert_eq!(uncompressed[0], 0x04);
+        assert_eq!(compressed[0] == 0x03, uncompressed[64] & 1 == 1);
+    }
+
+    /// Computes secret key for the aggregated key, which is known only when all
+    /// co-signer secrets are at hand, like in tests.
+    fn aggregate_secret(keys: &[Bip340Secret]) -> Bip340Secret {
+        let mut pks = keys.iter().map(|sk| sk.to_public().to_byte_array()).collect::<Vec<_>>();
+        pks.sort_unstable();
+        let coefs = key_agg_coefs(&pks);
+        let mut agg: Option<SecretKey> = None;
+        for sk in keys {
+            let pk = sk.to_public().to_byte_array();
+            let coef = coefs[pks.iter().position(|p| *p == pk).unwrap()];
+            let mut sk = SecretKey::from_slice(&sk.key).unwrap();
+            if sk.x_only_public_key(SECP256K1).1 == Parity::Odd {
+                sk = sk.negate();
+            }
+            let sk = sk.mul_tweak(&Scalar::from_be_bytes(coef).unwrap()).unwrap();
+            agg = Some(match agg {
+                None => sk,
+                Some(agg) => agg.add_tweak(&Scalar::from(sk)).unwrap(),
+            });
+        }
+        Bip340Secret::with(Chain::Bitcoin, agg.unwrap().secret_bytes()).unwrap()
+    }
+
+    #[test]
+    fn aggregate() {
+        let keys = (0..3).map(|_| Bip340Secret::new(Chain::Bitcoin)).collect::<Vec<_>>();
+        let mut pks = keys.iter().map(Bip340Secret::to_public).collect::<Vec<_>>();
+        let agg = SsiPub::aggregate(&pks).unwrap();
+        assert_eq!(agg.algo(), &Algo::Bip340);
+        assert_eq!(aggregate_secret(&keys).to_public(), agg);
+        pks.reverse();
+        assert_eq!(SsiPub::aggregate(&pks).unwrap(), agg);
+        assert_ne!(SsiPub::aggregate(&pks[..2]).unwrap(), agg);
+
+        let msg = [0xA5; 32];
+        let sig = aggregate_secret(&keys).sign(msg);
+        SsiPub::verify_cosigned(&pks, msg, &[sig]).unwrap();
+        agg.verify(msg, sig).unwrap();
+        assert_eq!(SsiPub::verify_cosigned(&pks, [0x5A; 32], &[sig]), Err(InvalidSig::InvalidSig));
+        assert_eq!(SsiPub::verify_cosigned(&pks[..2], msg, &[sig]), Err(InvalidSig::InvalidSig));
+
+        let sigs = keys.iter().rev().map(|sk| sk.sign(msg)).collect::<Vec<_>>();
+        SsiPub::verify_cosigned(&pks, msg, &sigs).unwrap();
+        assert_eq!(SsiPub::verify_cosigned(&pks, msg, &sigs[..2]), Err(InvalidSig::InvalidData));
+        assert_eq!(SsiPub::verify_cosigned(&[], msg, &[]), Err(InvalidSig::InvalidData));
+    }
+
+    #[test]
+    fn key_agg_vectors() {
+        // BIP327 key_agg_vectors.json, limited to the keys with the even Y coordinate, since
+        // x-only keys are lifted to such points
+        let x0 =
+            Bytes32::from_hex("F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9")
+                .unwrap();
+        let pk = SsiPub::with(Chain::Bitcoin, Algo::Bip340, x0.to_byte_array());
+        let agg = SsiPub::aggregate(&[pk, pk, pk]).unwrap();
+        let expected =
+            Bytes32::from_hex("B436E3BAD62B8CD409969A224731C193D051162D8C5AE8B109306127DA3AA935")
+                .unwrap();
+        assert_eq!(agg.to_byte_array(), expected.to_byte_array());
+    }
+
+    #[test]
+    fn aggregate_errors() {
+        use crate::SsiSecret;
+
+        let bip340 = Bip340Secret::new(Chain::Bitcoin);
+        let ed25519 = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let pks = [bip340.to_public(), ed25519.to_public()];
+        assert_eq!(SsiPub::aggregate(&[]), Err(AggError::NoKeys));
+        assert_eq!(SsiPub::aggregate(&pks), Err(AggError::NotBip340(pks[1].fingerprint())));
+        let signet = Bip340Secret::new(Chain::Signet).to_public();
+        assert_eq!(SsiPub::aggregate(&[pks[0], signet]), Err(AggError::ChainMismatch));
+
+        let msg = [0xA5; 32];
+        let sigs = [bip340.sign(msg), ed25519.sign(msg)];
+        SsiPub::verify_cosigned(&pks, msg, &sigs).unwrap();
+        assert_eq!(SsiPub::verify_cosigned(&pks, msg, &sigs[..1]), Err(InvalidSig::InvalidData));
+    }
+
+    #[test]
+    fn bip86_output_key() {
+        let internal =
+            Bytes32::from_hex("cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115")
+                .unwrap();
+        let output = taproot_output_key(internal.to_byte_array()).unwrap();
+        let expected =
+            Bytes32::from_hex("a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c")
+                .unwrap();
+        assert_eq!(output, expected.to_byte_array());
+    }
+
+    #[test]
+    #[cfg(feature = "pure-rust")]
+    fn cross_backend() {
+        let sk = Bip340Secret::new(Chain::Bitcoin);
+        let msg = [0xA5; 32];
+        let pk = Secp256k1Backend::public_key(&sk.key);
+        assert_eq!(K256Backend::public_key(&sk.key), pk);
+
+        let aux_rand = [0x42; 32];
+        let sig = Secp256k1Backend::sign(&sk.key, msg, aux_rand);
+        K256Backend::verify(pk, msg, sig).unwrap();
+        assert_eq!(K256Backend::sign(&sk.key, msg, aux_rand), sig);
+
+        let sig = K256Backend::sign(&sk.key, msg, [0u8; 32]);
+        Secp256k1Backend::verify(pk, msg, sig).unwrap();
+        assert_eq!(sig, sk.sign_deterministic(msg).to_byte_array());
+        assert_eq!(
+            K256Backend::verify(pk, [0x5A; 32], sig),
+            Secp256k1Backend::verify(pk, [0x5A; 32], sig)
+        );
+
+        assert_eq!(
+            K256Backend::public_key_uncompressed(&sk.key),
+            Secp256k1Backend::public_key_uncompressed(&sk.key)
+        );
+        assert_eq!(
+            K256Backend::add_tweak(pk, [0x42; 32]),
+            Secp256k1Backend::add_tweak(pk, [0x42; 32])
+        );
+
+        let other = Bip340Secret::new(Chain::Bitcoin).to_public().to_byte_array();
+        let pks = [pk, other];
+        let coefs = key_agg_coefs(&pks);
+        assert_eq!(K256Backend::combine(&pks, &coefs), Secp256k1Backend::combine(&pks, &coefs));
+        assert_eq!(K256Backend::combine(&[], &[]), Err(InvalidPubkey));
     }
 }
@@ -0,0 +1,362 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compact binary identity card for the exchange of identities over
+//! low-capacity media, like NFC tags and QR codes.
+//!
+//! # Layout
+//!
+//! | Bytes   | Data                                                                  |
+//! |---------|-----------------------------------------------------------------------|
+//! | 34      | public key: algorithm, chain and the 32-byte key                      |
+//! | 1       | flags (see below)                                                     |
+//! | 4       | expiry as a number of days since 1970-01-01, LE, if flag bit 0 is set |
+//! | 1       | number of UIDs, up to [`CARD_MAX_UIDS`]                               |
+//! | 1       | index of the primary UID, or `0xFF` if there is none                  |
+//! | 1 + len | each UID in `name schema:id` form, prefixed with its length           |
+//! | 64      | self-signature                                                        |
+//!
+//! Flags:
+//! - bit 0: identity has expiry;
+//! - bit 1: identity uses [`Commitment::Legacy`];
+//! - bit 2: self-signature is tagged with the key algorithm.
+//!
+//! Expiry is stored with a day precision, thus only identities expiring at
+//! the end of a day (which is the case for all identities with expiry given
+//! as a plain date) can be packed into a card. Identity comments, key usage
+//! restrictions, key rotation links, service endpoints, avatar images,
+//! subkeys, proof-of-work nonces, unknown parameters and endorsements are not a
+//! part of the card.
+
+use std::str::FromStr;
+
+use chrono::{Days, NaiveDate};
+
+use crate::identity::end_of_day;
+use crate::{Commitment, InvalidSig, KeyUsage, Ssi, SsiPub, SsiSig, Uid, UidParseError};
+
+/// Maximal number of UIDs in an identity card.
+pub const CARD_MAX_UIDS: usize = 254;
+
+const FLAG_EXPIRY: u8 = 0x01;
+const FLAG_LEGACY: u8 = 0x02;
+const FLAG_TAGGED_SIG: u8 = 0x04;
+const NO_PRIMARY: u8 = 0xFF;
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, From)]
+#[display(doc_comments)]
+pub enum CardError {
+    /// identity without a self-signature can't be packed into a card.
+    Unsigned,
+    /// identity comments are not supported by identity cards.
+    Comment,
+    /// key usage restrictions are not supported by identity cards.
+    KeyUsage,
+    /// key rotation links are not supported by identity cards.
+    Rotation,
+    /// service endpoints are not supported by identity cards.
+    Services,
+    /// proof-of-work nonces are not supported by identity cards.
+    ProofOfWork,
+    /// avatar images are not supported by identity cards.
+    Avatar,
+    /// subkeys are not supported by identity cards.
+    Subkeys,
+    /// parameters unknown to this version can't be packed into a card.
+    UnknownParams,
+    /// identity expiry must be the end of a day after 1970-01-01 to be packed
+    /// into a card.
+    Expiry,
+    /// identity has {0} UIDs, while cards may contain up to 254 UIDs.
+    TooManyUids(usize),
+    /// UID '{0}' exceeds 255 bytes.
+    UidTooLong(Uid),
+    /// identity card data are truncated.
+    Truncated,
+    /// identity card contains unknown flags {0:#04x}.
+    UnknownFlags(u8),
+    /// identity card contains invalid primary UID index {0}.
+    InvalidPrimary(u8),
+    /// identity card contains extra data after the signature.
+    ExtraData,
+
+    #[from]
+    /// identity card contains invalid UID - {0}
+    InvalidUid(UidParseError),
+
+    #[from]
+    /// identity card signature doesn't match the identity data - {0}
+    WrongSig(InvalidSig),
+}
+
+impl std::error::Error for CardError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CardError::InvalidUid(err) => Some(err),
+            CardError::WrongSig(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Compact binary representation of a signed identity; see the module
+/// documentation for the layout.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct IdentityCard(Vec<u8>);
+
+impl IdentityCard {
+    /// Wraps card data read from some media. The data are validated during
+    /// the conversion into [`Ssi`].
+    pub fn from_bytes(data: impl Into<Vec<u8>>) -> Self { Self(data.into()) }
+
+    pub fn as_slice(&self) -> &[u8] { &self.0 }
+
+    pub fn into_bytes(self) -> Vec<u8> { self.0 }
+}
+
+fn epoch() -> NaiveDate { NaiveDate::from_ymd_opt(1970, 1, 1).expect("valid date") }
+
+impl TryFrom<&Ssi> for IdentityCard {
+    type Error = CardError;
+
+    fn try_from(ssi: &Ssi) -> Result<Self, Self::Error> {
+        let sig = ssi.sig.ok_or(CardError::Unsigned)?;
+        if ssi.comment.is_some() {
+            return Err(CardError::Comment);
+        }
+        if !ssi.usage.is_all() {
+            return Err(CardError::KeyUsage);
+        }
+        if ssi.next.is_some() || ssi.prev.is_some() {
+            return Err(CardError::Rotation);
+        }
+        if !ssi.services.is_empty() {
+            return Err(CardError::Services);
+        }
+        if ssi.pow.is_some() {
+            return Err(CardError::ProofOfWork);
+        }
+        if ssi.avatar.is_some() {
+            return Err(CardError::Avatar);
+        }
+        if !ssi.subkeys.is_empty() {
+            return Err(CardError::Subkeys);
+        }
+        if !ssi.extras.is_empty() {
+            return Err(CardError::UnknownParams);
+        }
+        if ssi.uids.len() > CARD_MAX_UIDS {
+            return Err(CardError::TooManyUids(ssi.uids.len()));
+        }
+
+        let mut flags = 0u8;
+        let mut days = None;
+        if let Some(expiry) = ssi.expiry {
+            let date = expiry.date_naive();
+            if expiry != end_of_day(date) {
+                return Err(CardError::Expiry);
+            }
+            let count = u32::try_from((date - epoch()).num_days()).map_err(|_| CardError::Expiry)?;
+            days = Some(count);
+            flags |= FLAG_EXPIRY;
+        }
+        if ssi.commitment == Commitment::Legacy {
+            flags |= FLAG_LEGACY;
+        }
+        match sig.algo() {
+            None => {}
+            Some(algo) if algo == *ssi.pk.algo() => flags |= FLAG_TAGGED_SIG,
+            Some(_) => return Err(InvalidSig::InvalidData.into()),
+        }
+
+        let mut data = <[u8; 34]>::from(ssi.pk).to_vec();
+        data.push(flags);
+        if let Some(days) = days {
+            data.extend(days.to_le_bytes());
+        }
+        data.push(ssi.uids.len() as u8);
+        let primary = ssi.uids.iter().position(|uid| uid.primary);
+        data.push(primary.map(|pos| pos as u8).unwrap_or(NO_PRIMARY));
+        for uid in &ssi.uids {
+            let s = format!("{uid:#}");
+            let len = u8::try_from(s.len()).map_err(|_| CardError::UidTooLong(uid.clone()))?;
+            data.push(len);
+            data.extend(s.as_bytes());
+        }
+        data.extend(sig.to_byte_array());
+        Ok(Self(data))
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CardError> {
+        if self.0.len() < len {
+            return Err(CardError::Truncated);
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn byte(&mut self) -> Result<u8, CardError> { Ok(self.take(1)?[0]) }
+
+    fn array<const LEN: usize>(&mut self) -> Result<[u8; LEN], CardError> {
+        Ok(self.take(LEN)?.try_into().expect("checked length"))
+    }
+}
+
+/// Reconstructs the identity, verifying its self-signature.
+impl TryFrom<&IdentityCard> for Ssi {
+    type Error = CardError;
+
+    fn try_from(card: &IdentityCard) -> Result<Self, Self::Error> {
+        let mut reader = Reader(&card.0);
+        let pk = SsiPub::from(reader.array::<34>()?);
+        let flags = reader.byte()?;
+        if flags & !(FLAG_EXPIRY | FLAG_LEGACY | FLAG_TAGGED_SIG) != 0 {
+            return Err(CardError::UnknownFlags(flags));
+        }
+        let expiry = if flags & FLAG_EXPIRY != 0 {
+            let days = u32::from_le_bytes(reader.array::<4>()?);
+            let date = epoch()
+                .checked_add_days(Days::new(days as u64))
+                .ok_or(CardError::Expiry)?;
+            Some(end_of_day(date))
+        } else {
+            None
+        };
+        let count = reader.byte()? as usize;
+        let primary = reader.byte()?;
+        if primary != NO_PRIMARY && primary as usize >= count {
+            return Err(CardError::InvalidPrimary(primary));
+        }
+        let mut uids = Vec::with_capacity(count);
+        for _ in 0..count {
+            let len = reader.byte()? as usize;
+            let s = std::str::from_utf8(reader.take(len)?)
+                .map_err(|err| CardError::InvalidUid(err.into()))?;
+            uids.push(Uid::from_str(s)?);
+        }
+        if let Some(uid) = uids.get_mut(primary as usize) {
+            uid.primary = true;
+        }
+        let sig = reader.array::<64>()?;
+        let sig = match flags & FLAG_TAGGED_SIG {
+            0 => SsiSig::from(sig),
+            _ => SsiSig::tagged(*pk.algo(), sig),
+        };
+        if !reader.0.is_empty() {
+            return Err(CardError::ExtraData);
+        }
+
+        let ssi = Ssi {
+            pk,
+            uids: uids.into_iter().collect(),
+            expiry,
+            comment: None,
+            usage: KeyUsage::ALL,
+            services: bmap![],
+            avatar: None,
+            subkeys: vec![],
+            next: None,
+            prev: None,
+            pow: None,
+            extras: bmap![],
+            commitment: if flags & FLAG_LEGACY != 0 {
+                Commitment::Legacy
+            } else {
+                Commitment::Binary
+            },
+            sig: Some(sig),
+            endorsements: vec![],
+        };
+        ssi.integrity().into_result()?;
+        Ok(ssi)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_expiry, Algo, Chain, SsiSecret};
+
+    fn identity_with_secret() -> (Ssi, SsiSecret) {
+        let secret = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let mut mail = Uid::from_str("Alice <mailto:alice@example.com>").unwrap();
+        mail.primary = true;
+        let web = Uid::from_str("https:alice.example.com").unwrap();
+        let ssi = Ssi::new(bset![mail, web], Some(parse_expiry("2030-01-01").unwrap()), &secret);
+        (ssi, secret)
+    }
+
+    fn identity() -> Ssi { identity_with_secret().0 }
+
+    #[test]
+    fn roundtrip() {
+        let (ssi, secret) = identity_with_secret();
+        let card = IdentityCard::try_from(&ssi).unwrap();
+        assert!(card.as_slice().len() < ssi.to_string().len());
+        assert_eq!(Ssi::try_from(&card).unwrap(), ssi);
+
+        let mut legacy = ssi.without_signature();
+        legacy.expiry = None;
+        legacy.commitment = Commitment::Legacy;
+        legacy.sig = Some(secret.sign(legacy.to_message()));
+        let card = IdentityCard::try_from(&legacy).unwrap();
+        assert_eq!(Ssi::try_from(&card).unwrap(), legacy);
+
+        let mut forged = legacy.clone();
+        forged.sig = Some(SsiSecret::new(Algo::Ed25519, Chain::Bitcoin).sign(legacy.to_message()));
+        let card = IdentityCard::try_from(&forged).unwrap();
+        assert!(matches!(Ssi::try_from(&card), Err(CardError::WrongSig(_))));
+    }
+
+    #[test]
+    fn invalid() {
+        let ssi = identity();
+        let data = IdentityCard::try_from(&ssi).unwrap().into_bytes();
+
+        let truncated = IdentityCard::from_bytes(&data[..data.len() - 1]);
+        assert_eq!(Ssi::try_from(&truncated), Err(CardError::Truncated));
+
+        let mut extra = data.clone();
+        extra.push(0);
+        assert_eq!(Ssi::try_from(&IdentityCard::from_bytes(extra)), Err(CardError::ExtraData));
+
+        let mut tampered = data.clone();
+        tampered[34] &= !FLAG_EXPIRY;
+        tampered.drain(35..39);
+        assert!(matches!(
+            Ssi::try_from(&IdentityCard::from_bytes(tampered)),
+            Err(CardError::WrongSig(_))
+        ));
+
+        let mut commented = ssi.clone();
+        commented.comment = Some(s!("work"));
+        assert_eq!(IdentityCard::try_from(&commented), Err(CardError::Comment));
+
+        let mut noon = ssi.clone();
+        noon.expiry = Some(parse_expiry("2030-01-01T12:00:00Z").unwrap());
+        assert_eq!(IdentityCard::try_from(&noon), Err(CardError::Expiry));
+    }
+}
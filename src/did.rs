@@ -0,0 +1,182 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `did:key` representation of identity public keys.
+
+use std::iter;
+
+use crate::{Algo, Chain, SsiPub};
+
+pub const DID_KEY_PREFIX: &str = "did:key:";
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+pub(crate) const MULTIBASE_BASE58BTC: char = 'z';
+const MULTICODEC_ED25519: [u8; 2] = [0xED, 0x01];
+const MULTICODEC_SECP256K1: [u8; 2] = [0xE7, 0x01];
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum DidError {
+    /// '{0}' is not a did:key identifier.
+    NotDidKey(String),
+    /// did:key '{0}' has invalid multibase encoding.
+    Encoding(String),
+    /// did:key '{0}' uses unsupported key type.
+    UnsupportedKey(String),
+    /// did:key '{0}' contains invalid public key.
+    InvalidPubkey(String),
+    /// {0} keys can't be represented as did:key.
+    UnsupportedAlgo(Algo),
+}
+
+pub(crate) fn base58_encode(data: &[u8]) -> String {
+    let zeros = data.iter().take_while(|b| **b == 0).count();
+    // base58 digits in little-endian order
+    let mut digits = Vec::<u8>::with_capacity(data.len() * 138 / 100 + 1);
+    for byte in &data[zeros..] {
+        let mut carry = *byte as u32;
+        for digit in &mut digits {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    iter::repeat('1')
+        .take(zeros)
+        .chain(digits.iter().rev().map(|d| BASE58_ALPHABET[*d as usize] as char))
+        .collect()
+}
+
+fn base58_decode(s: &str) -> Option<Vec<u8>> {
+    let zeros = s.bytes().take_while(|c| *c == b'1').count();
+    // decoded bytes in little-endian order
+    let mut bytes = Vec::<u8>::with_capacity(s.len());
+    for c in s.bytes().skip(zeros) {
+        let mut carry = BASE58_ALPHABET.iter().position(|a| *a == c)? as u32;
+        for byte in &mut bytes {
+            carry += (*byte as u32) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    let mut data = vec![0u8; zeros];
+    data.extend(bytes.iter().rev());
+    Some(data)
+}
+
+impl SsiPub {
+    /// Represents the key as `did:key` identifier.
+    ///
+    /// Only Ed25519 and ECDSA keys have `did:key` representation. The chain
+    /// is not a part of the identifier.
+    pub fn to_did_key(&self) -> Result<String, DidError> {
+        let mut data = Vec::with_capacity(35);
+        match self.algo() {
+            Algo::Ed25519 => data.extend(MULTICODEC_ED25519),
+            Algo::Ecdsa => {
+                data.extend(MULTICODEC_SECP256K1);
+                data.push(0x02);
+            }
+            algo => return Err(DidError::UnsupportedAlgo(*algo)),
+        }
+        data.extend(self.to_byte_array());
+        Ok(format!("{DID_KEY_PREFIX}{MULTIBASE_BASE58BTC}{}", base58_encode(&data)))
+    }
+
+    /// Parses `did:key` identifier, ignoring DID URL fragment, if present.
+    ///
+    /// Since `did:key` doesn't commit to a chain, the key is returned for the
+    /// default chain.
+    pub fn from_did_key(did: &str) -> Result<Self, DidError> {
+        let (id, _) = did.split_once('#').unwrap_or((did, ""));
+        let encoded = id
+            .strip_prefix(DID_KEY_PREFIX)
+            .ok_or_else(|| DidError::NotDidKey(did.to_owned()))?;
+        let data = encoded
+            .strip_prefix(MULTIBASE_BASE58BTC)
+            .and_then(base58_decode)
+            .ok_or_else(|| DidError::Encoding(did.to_owned()))?;
+
+        let (algo, key) = match data.split_at(2.min(data.len())) {
+            (codec, key) if codec == MULTICODEC_ED25519 && key.len() == 32 => (Algo::Ed25519, key),
+            (codec, [0x02, key @ ..]) if codec == MULTICODEC_SECP256K1 && key.len() == 32 => {
+                (Algo::Ecdsa, key)
+            }
+            (codec, [0x03, ..]) if codec == MULTICODEC_SECP256K1 => {
+                // Identity keys always have even Y coordinate
+                return Err(DidError::InvalidPubkey(did.to_owned()));
+            }
+            _ => return Err(DidError::UnsupportedKey(did.to_owned())),
+        };
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(key);
+        Ok(SsiPub::with(Chain::default(), algo, bytes))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SsiSecret;
+
+    #[test]
+    fn base58() {
+        assert_eq!(base58_encode(b"Hello World!"), "2NEpo7TZRRrLZSi2U");
+        assert_eq!(base58_encode(&[0, 0, 0x28, 0x7f, 0xb4, 0xcd]), "11233QC4");
+        assert_eq!(base58_decode("11233QC4").unwrap(), [0, 0, 0x28, 0x7f, 0xb4, 0xcd]);
+        assert_eq!(base58_decode("2NEpo7TZRRrLZSi2U").unwrap(), b"Hello World!");
+        assert_eq!(base58_decode("0OIl"), None);
+    }
+
+    #[test]
+    fn did_key() {
+        let pk = SsiPub::with(Chain::Bitcoin, Algo::Ed25519, [0u8; 32]);
+        assert_eq!(
+            pk.to_did_key().unwrap(),
+            "did:key:z6MkeTG3bFFSLYVU7VqhgZxqr6YzpaGrQtFMh1uvqGy1vDnP"
+        );
+        let pk = SsiPub::with(Chain::Bitcoin, Algo::Ecdsa, [0u8; 32]);
+        assert_eq!(
+            pk.to_did_key().unwrap(),
+            "did:key:zQ3shMQnkqiyfujhRPGFFqSEeD2yV9kUcmyBiu2fT2BXfFPMH"
+        );
+
+        for algo in [Algo::Ed25519, Algo::Ecdsa] {
+            let pk = SsiSecret::new(algo, Chain::Bitcoin).to_public();
+            let did = pk.to_did_key().unwrap();
+            assert_eq!(SsiPub::from_did_key(&did), Ok(pk));
+            assert_eq!(SsiPub::from_did_key(&format!("{did}#{}", &did[8..])), Ok(pk));
+        }
+
+        let pk = SsiSecret::new(Algo::Bip340, Chain::Bitcoin).to_public();
+        assert_eq!(pk.to_did_key(), Err(DidError::UnsupportedAlgo(Algo::Bip340)));
+        assert!(matches!(SsiPub::from_did_key("did:web:example.com"), Err(DidError::NotDidKey(_))));
+        assert!(matches!(SsiPub::from_did_key("did:key:zOOO"), Err(DidError::Encoding(_))));
+    }
+}
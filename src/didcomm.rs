@@ -0,0 +1,585 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! DIDComm v2 encrypted messages.
+//!
+//! Messages are JWEs in the general JSON serialization, encrypted with
+//! `A256CBC-HS512`. The content encryption key is wrapped for each recipient
+//! with `A256KW` under a key agreed with `ECDH-ES` (anoncrypt) or `ECDH-1PU`
+//! (authcrypt, which also authenticates the sender). Key agreement uses X25519
+//! keys derived from Ed25519 identity keys; other algorithms are not
+//! supported. Keys are referenced by `did:key` URLs with the X25519 key
+//! agreement key as a fragment.
+
+use aes_gcm::aes::Aes256;
+use aes_kw::KekAes256;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use ec25519::x25519;
+use hmac::{Hmac, Mac};
+use rand::{random, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::did::{base58_encode, MULTIBASE_BASE58BTC};
+use crate::{Algo, DidError, SignerError, SsiPair, SsiPub, SsiQuery, SsiRuntime, SsiSecret};
+
+pub const DIDCOMM_ENCRYPTED_TYP: &str = "application/didcomm-encrypted+json";
+
+const ALG_ANONCRYPT: &str = "ECDH-ES+A256KW";
+const ALG_AUTHCRYPT: &str = "ECDH-1PU+A256KW";
+const ENC_A256CBC_HS512: &str = "A256CBC-HS512";
+const MULTICODEC_X25519: [u8; 2] = [0xEC, 0x01];
+
+type Aes256CbcEnc = cbc::Encryptor<Aes256>;
+type Aes256CbcDec = cbc::Decryptor<Aes256>;
+
+#[derive(Debug, Display, From)]
+#[display(doc_comments)]
+pub enum DidCommError {
+    /// message must have at least one recipient.
+    NoRecipients,
+    /// {0} keys can't be used for DIDComm encryption.
+    UnsupportedAlgo(Algo),
+    /// key {0} has no X25519 representation.
+    InvalidPubkey(SsiPub),
+    /// X25519 key agreement has failed due to a weak key.
+    KeyAgreement,
+    /// key id '{0}' doesn't reference X25519 key of the identity.
+    InvalidKid(String),
+    /// DIDComm message is not a valid JWE.
+    Malformed,
+    /// message uses unsupported key management algorithm '{0}'.
+    UnknownAlg(String),
+    /// message uses unsupported content encryption algorithm '{0}'.
+    UnknownEnc(String),
+    /// authcrypt message doesn't specify sender key id.
+    NoSkid,
+    /// sender key id doesn't match `apu` header.
+    SkidMismatch,
+    /// recipient key ids don't match `apv` header.
+    ApvMismatch,
+    /// none of the message recipients is known to the runtime.
+    NoRecipient,
+    /// unable to unwrap content encryption key: the message is corrupted, or
+    /// it wasn't sent by the key specified in `skid`.
+    KeyUnwrap,
+    /// message ciphertext is corrupted.
+    Decryption,
+
+    #[from]
+    #[display(inner)]
+    Did(DidError),
+
+    #[from]
+    #[display(inner)]
+    Signer(SignerError),
+
+    #[from]
+    /// message has invalid base64 encoding - {0}
+    Base64(base64::DecodeError),
+
+    #[from]
+    /// message has invalid JSON - {0}
+    Json(serde_json::Error),
+}
+
+impl std::error::Error for DidCommError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DidCommError::Did(err) => Some(err),
+            DidCommError::Signer(err) => Some(err),
+            DidCommError::Base64(err) => Some(err),
+            DidCommError::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Decrypted DIDComm message.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Unpacked {
+    pub plaintext: Vec<u8>,
+    /// Local identity the message was decrypted with.
+    pub recipient: SsiPub,
+    /// Authenticated sender of an authcrypt message. Since `did:key` doesn't
+    /// commit to a chain, the key is returned for the default chain.
+    pub sender: Option<SsiPub>,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+struct EphemeralKey {
+    kty: String,
+    crv: String,
+    x: String,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+struct ProtectedHeader {
+    typ: String,
+    alg: String,
+    enc: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    skid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    apu: Option<String>,
+    apv: String,
+    epk: EphemeralKey,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+struct RecipientHeader {
+    kid: String,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+struct Recipient {
+    header: RecipientHeader,
+    encrypted_key: String,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+struct Jwe {
+    protected: String,
+    recipients: Vec<Recipient>,
+    iv: String,
+    ciphertext: String,
+    tag: String,
+}
+
+fn b64(data: impl AsRef<[u8]>) -> String { URL_SAFE_NO_PAD.encode(data) }
+
+fn b64_decode(s: &str) -> Result<Vec<u8>, DidCommError> { Ok(URL_SAFE_NO_PAD.decode(s)?) }
+
+fn x25519_pub(pk: SsiPub) -> Result<x25519::PublicKey, DidCommError> {
+    if *pk.algo() != Algo::Ed25519 {
+        return Err(DidCommError::UnsupportedAlgo(*pk.algo()));
+    }
+    let edpk = ec25519::PublicKey::try_from(pk).map_err(|_| DidCommError::InvalidPubkey(pk))?;
+    x25519::PublicKey::from_ed25519(&edpk).map_err(|_| DidCommError::InvalidPubkey(pk))
+}
+
+fn x25519_secret(sk: &SsiSecret) -> Result<x25519::SecretKey, DidCommError> {
+    match sk {
+        SsiSecret::Ed25519(ed) => x25519::SecretKey::from_ed25519(&ed.key)
+            .map_err(|_| DidCommError::InvalidPubkey(ed.to_public())),
+        _ => Err(DidCommError::UnsupportedAlgo(sk.algorithm())),
+    }
+}
+
+fn ecdh(pk: &x25519::PublicKey, sk: &x25519::SecretKey) -> Result<[u8; 32], DidCommError> {
+    Ok(*pk.dh(sk).map_err(|_| DidCommError::KeyAgreement)?)
+}
+
+/// Returns DID URL of the X25519 key agreement key derived from the identity
+/// key.
+fn key_id(pk: SsiPub) -> Result<String, DidCommError> {
+    let x = x25519_pub(pk)?;
+    let did = pk.to_did_key()?;
+    let data = [&MULTICODEC_X25519[..], &x[..]].concat();
+    Ok(format!("{did}#{MULTIBASE_BASE58BTC}{}", base58_encode(&data)))
+}
+
+/// Parses key id, checking that its fragment references X25519 key derived
+/// from the identity key.
+fn parse_key_id(kid: &str) -> Result<SsiPub, DidCommError> {
+    let pk = SsiPub::from_did_key(kid)?;
+    if key_id(pk)? != kid {
+        return Err(DidCommError::InvalidKid(kid.to_owned()));
+    }
+    Ok(pk)
+}
+
+/// Commitment to the sorted list of recipient key ids, used as `apv`.
+fn apv(kids: &[&str]) -> [u8; 32] {
+    let mut kids = kids.to_vec();
+    kids.sort_unstable();
+    Sha256::digest(kids.join(".")).into()
+}
+
+/// Concat KDF (NIST SP 800-56A) with SHA-256 producing a single 256-bit key.
+///
+/// The `cctag` is used in ECDH-1PU key wrapping mode only.
+fn concat_kdf(z: &[u8], alg: &str, apu: &[u8], apv: &[u8], cctag: Option<&[u8]>) -> [u8; 32] {
+    let mut engine = Sha256::new();
+    engine.update(1u32.to_be_bytes());
+    engine.update(z);
+    for field in [alg.as_bytes(), apu, apv] {
+        engine.update((field.len() as u32).to_be_bytes());
+        engine.update(field);
+    }
+    engine.update(256u32.to_be_bytes());
+    if let Some(cctag) = cctag {
+        engine.update((cctag.len() as u32).to_be_bytes());
+        engine.update(cctag);
+    }
+    engine.finalize().into()
+}
+
+/// Derives key encryption key from the ephemeral (`ze`) and, for authcrypt,
+/// static (`zs`) shared secrets.
+fn derive_kek(
+    header: &ProtectedHeader,
+    ze: [u8; 32],
+    zs: Option<[u8; 32]>,
+    tag: &[u8],
+) -> Result<[u8; 32], DidCommError> {
+    let apu = header.apu.as_deref().map(b64_decode).transpose()?.unwrap_or_default();
+    let apv = b64_decode(&header.apv)?;
+    Ok(match zs {
+        Some(zs) => concat_kdf(&[ze, zs].concat(), &header.alg, &apu, &apv, Some(tag)),
+        None => concat_kdf(&ze, &header.alg, &apu, &apv, None),
+    })
+}
+
+/// AES key wrap (RFC 3394) of a key, which length must be a multiple of 8.
+fn key_wrap(kek: &[u8; 32], key: &[u8]) -> Vec<u8> {
+    KekAes256::from(*kek)
+        .wrap_vec(key)
+        .expect("key length must be a multiple of 8")
+}
+
+/// AES key unwrap (RFC 3394), returning `None` if the integrity check fails.
+fn key_unwrap(kek: &[u8; 32], wrapped: &[u8]) -> Option<Vec<u8>> {
+    KekAes256::from(*kek).unwrap_vec(wrapped).ok()
+}
+
+fn cbc_hs512_mac(mac_key: &[u8], iv: &[u8; 16], aad: &[u8], ciphertext: &[u8]) -> Hmac<Sha512> {
+    let al = (aad.len() as u64 * 8).to_be_bytes();
+    let mut mac: Hmac<Sha512> = Mac::new_from_slice(mac_key).expect("HMAC accepts any key length");
+    mac.update(aad);
+    mac.update(iv);
+    mac.update(ciphertext);
+    mac.update(&al);
+    mac
+}
+
+/// `A256CBC-HS512` authenticated encryption (RFC 7518 section 5.2).
+fn cbc_hs512_encrypt(
+    cek: &[u8; 64],
+    iv: &[u8; 16],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> (Vec<u8>, [u8; 32]) {
+    let (mac_key, enc_key) = cek.split_at(32);
+    let data =
+        Aes256CbcEnc::new(enc_key.into(), iv.into()).encrypt_padded_vec_mut::<Pkcs7>(plaintext);
+    let mac = cbc_hs512_mac(mac_key, iv, aad, &data)
+        .finalize()
+        .into_bytes();
+    let mut tag = [0u8; 32];
+    tag.copy_from_slice(&mac[..32]);
+    (data, tag)
+}
+
+/// `A256CBC-HS512` authenticated decryption (RFC 7518 section 5.2),
+/// returning `None` if the authentication tag or padding is invalid.
+fn cbc_hs512_decrypt(
+    cek: &[u8],
+    iv: &[u8; 16],
+    aad: &[u8],
+    ciphertext: &[u8],
+    tag: &[u8],
+) -> Option<Vec<u8>> {
+    if cek.len() != 64 || tag.len() != 32 {
+        return None;
+    }
+    let (mac_key, enc_key) = cek.split_at(32);
+    cbc_hs512_mac(mac_key, iv, aad, ciphertext)
+        .verify_truncated_left(tag)
+        .ok()?;
+    Aes256CbcDec::new(enc_key.into(), iv.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+        .ok()
+}
+
+/// Encrypts DIDComm message to the given recipients.
+///
+/// If the sender is provided, the message is authcrypted, allowing
+/// recipients to authenticate the sender; otherwise it is anoncrypted.
+/// Returns JWE in the general JSON serialization.
+pub fn pack_encrypted(
+    plaintext: &[u8],
+    to: &[SsiPub],
+    from: Option<&SsiPair>,
+) -> Result<String, DidCommError> {
+    let skid = from.map(|pair| key_id(pair.pk)).transpose()?;
+    pack(plaintext, to, from, skid)
+}
+
+fn pack(
+    plaintext: &[u8],
+    to: &[SsiPub],
+    from: Option<&SsiPair>,
+    skid: Option<String>,
+) -> Result<String, DidCommError> {
+    if to.is_empty() {
+        return Err(DidCommError::NoRecipients);
+    }
+    let sender = from.map(|pair| x25519_secret(&pair.sk)).transpose()?;
+    let recipients = to
+        .iter()
+        .map(|pk| Ok((key_id(*pk)?, x25519_pub(*pk)?)))
+        .collect::<Result<Vec<_>, DidCommError>>()?;
+    let kids = recipients.iter().map(|(kid, _)| kid.as_str()).collect::<Vec<_>>();
+
+    let epk = x25519::KeyPair::generate();
+    let alg = if sender.is_some() { ALG_AUTHCRYPT } else { ALG_ANONCRYPT };
+    let header = ProtectedHeader {
+        typ: DIDCOMM_ENCRYPTED_TYP.to_owned(),
+        alg: alg.to_owned(),
+        enc: ENC_A256CBC_HS512.to_owned(),
+        apu: skid.as_ref().map(b64),
+        skid,
+        apv: b64(apv(&kids)),
+        epk: EphemeralKey {
+            kty: s!("OKP"),
+            crv: s!("X25519"),
+            x: b64(*epk.pk),
+        },
+    };
+    let protected = b64(serde_json::to_vec(&header)?);
+
+    let mut cek = [0u8; 64];
+    rand::thread_rng().fill_bytes(&mut cek);
+    let iv: [u8; 16] = random();
+    let (ciphertext, tag) = cbc_hs512_encrypt(&cek, &iv, protected.as_bytes(), plaintext);
+
+    let recipients = recipients
+        .into_iter()
+        .map(|(kid, pk)| {
+            let ze = ecdh(&pk, &epk.sk)?;
+            let zs = sender.as_ref().map(|sk| ecdh(&pk, sk)).transpose()?;
+            let kek = derive_kek(&header, ze, zs, &tag)?;
+            Ok(Recipient {
+                header: RecipientHeader { kid },
+                encrypted_key: b64(key_wrap(&kek, &cek)),
+            })
+        })
+        .collect::<Result<_, DidCommError>>()?;
+
+    let jwe = Jwe {
+        protected,
+        recipients,
+        iv: b64(iv),
+        ciphertext: b64(ciphertext),
+        tag: b64(tag),
+    };
+    Ok(serde_json::to_string(&jwe)?)
+}
+
+/// Decrypts DIDComm message with the secret key of the first recipient known
+/// to the runtime, reporting the sender of authcrypt messages.
+///
+/// NB: The sender is authenticated as the holder of the key, but it's up to
+/// the caller to check whether the key belongs to a trusted party.
+pub fn unpack(jwe: &str, runtime: &SsiRuntime, passwd: &str) -> Result<Unpacked, DidCommError> {
+    let jwe = serde_json::from_str::<Jwe>(jwe)?;
+    let header = serde_json::from_slice::<ProtectedHeader>(&b64_decode(&jwe.protected)?)?;
+    if header.enc != ENC_A256CBC_HS512 {
+        return Err(DidCommError::UnknownEnc(header.enc));
+    }
+    let kids = jwe.recipients.iter().map(|r| r.header.kid.as_str()).collect::<Vec<_>>();
+    if b64_decode(&header.apv)? != apv(&kids) {
+        return Err(DidCommError::ApvMismatch);
+    }
+    let sender = match (header.alg.as_str(), &header.skid) {
+        (ALG_AUTHCRYPT, Some(skid)) => {
+            if header.apu.as_deref() != Some(b64(skid).as_str()) {
+                return Err(DidCommError::SkidMismatch);
+            }
+            Some(parse_key_id(skid)?)
+        }
+        (ALG_AUTHCRYPT, None) => return Err(DidCommError::NoSkid),
+        (ALG_ANONCRYPT, _) => None,
+        (alg, _) => return Err(DidCommError::UnknownAlg(alg.to_owned())),
+    };
+    if header.epk.kty != "OKP" || header.epk.crv != "X25519" {
+        return Err(DidCommError::Malformed);
+    }
+    let epk = x25519::PublicKey::from_slice(&b64_decode(&header.epk.x)?)
+        .map_err(|_| DidCommError::Malformed)?;
+
+    let mut found = None;
+    let mut signer_err = None;
+    for recipient in &jwe.recipients {
+        let Ok(pk) = parse_key_id(&recipient.header.kid) else {
+            continue;
+        };
+        match runtime.find_signer(SsiQuery::Fp(pk.fingerprint()), passwd) {
+            Ok(pair) if pair.pk.to_byte_array() == pk.to_byte_array() => {
+                found = Some((recipient, pair));
+                break;
+            }
+            Ok(_) | Err(SignerError::UnknownIdentity) => {}
+            Err(err) => signer_err = Some(err),
+        }
+    }
+    let (recipient, pair) = match (found, signer_err) {
+        (Some(found), _) => found,
+        (None, Some(err)) => return Err(err.into()),
+        (None, None) => return Err(DidCommError::NoRecipient),
+    };
+
+    let sk = x25519_secret(&pair.sk)?;
+    let ze = ecdh(&epk, &sk)?;
+    let zs = sender.map(|pk| ecdh(&x25519_pub(pk)?, &sk)).transpose()?;
+    let tag = b64_decode(&jwe.tag)?;
+    let kek = derive_kek(&header, ze, zs, &tag)?;
+    let cek = key_unwrap(&kek, &b64_decode(&recipient.encrypted_key)?)
+        .ok_or(DidCommError::KeyUnwrap)?;
+    let iv = <[u8; 16]>::try_from(b64_decode(&jwe.iv)?).map_err(|_| DidCommError::Malformed)?;
+    let ciphertext = b64_decode(&jwe.ciphertext)?;
+    let plaintext = cbc_hs512_decrypt(&cek, &iv, jwe.protected.as_bytes(), &ciphertext, &tag)
+        .ok_or(DidCommError::Decryption)?;
+
+    Ok(Unpacked {
+        plaintext,
+        recipient: pair.pk,
+        sender,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::hex::{FromHex, ToHex};
+
+    use super::*;
+    use crate::{Chain, Ssi};
+
+    fn identity(runtime: &mut SsiRuntime) -> SsiPair {
+        let sk = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let ssi = Ssi::new(bset![], None, &sk);
+        runtime.identities.insert(ssi.clone());
+        runtime.secrets.insert(sk.conceal(""));
+        SsiPair::new(ssi, sk)
+    }
+
+    #[test]
+    fn key_wrap_rfc3394() {
+        let mut kek = [0u8; 32];
+        kek.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+        let key = Vec::<u8>::from_hex(
+            "00112233445566778899aabbccddeeff000102030405060708090a0b0c0d0e0f",
+        )
+        .unwrap();
+        let wrapped = key_wrap(&kek, &key);
+        assert_eq!(
+            wrapped.to_hex(),
+            "28c9f404c4b810f4cbccb35cfb87f8263f5786e2d80ed326cbc7f0e71a99f43bfb988b9b7a02dd21"
+        );
+        assert_eq!(key_unwrap(&kek, &wrapped).unwrap(), key);
+        assert_eq!(key_unwrap(&[0u8; 32], &wrapped), None);
+    }
+
+    #[test]
+    fn cbc_hs512_rfc7518() {
+        // RFC 7518 appendix B.3
+        let mut cek = [0u8; 64];
+        cek.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+        let iv = <[u8; 16]>::from_hex("1af38c2dc2b96ffdd86694092341bc04").unwrap();
+        let plaintext = b"A cipher system must not be required to be secret, and it must be able \
+                          to fall into the hands of the enemy without inconvenience";
+        let aad = b"The second principle of Auguste Kerckhoffs";
+        let (ciphertext, tag) = cbc_hs512_encrypt(&cek, &iv, aad, plaintext);
+        assert_eq!(
+            ciphertext.to_hex(),
+            "4affaaadb78c31c5da4b1b590d10ffbd3dd8d5d302423526912da037ecbcc7bd822c301dd67c373bccb584\
+             ad3e9279c2e6d12a1374b77f077553df829410446b36ebd97066296ae6427ea75c2e0846a11a09ccf5370d\
+             c80bfecbad28c73f09b3a3b75e662a2594410ae496b2e2e6609e31e6e02cc837f053d21f37ff4f51950bbe\
+             2638d09dd7a4930930806d0703b1f6"
+        );
+        assert_eq!(
+            tag.to_hex(),
+            "4dd3b4c088a7f45c216839645b2012bf2e6269a8c56a816dbc1b267761955bc5"
+        );
+        assert_eq!(cbc_hs512_decrypt(&cek, &iv, aad, &ciphertext, &tag).unwrap(), plaintext);
+        assert_eq!(cbc_hs512_decrypt(&cek, &iv, b"aad", &ciphertext, &tag), None);
+    }
+
+    #[test]
+    fn anoncrypt() {
+        let mut runtime = SsiRuntime::default();
+        let alice = identity(&mut runtime);
+        let bob = SsiPair::from(SsiSecret::new(Algo::Ed25519, Chain::Bitcoin));
+
+        let jwe = pack_encrypted(b"{\"body\":{}}", &[bob.pk, alice.pk], None).unwrap();
+        let unpacked = unpack(&jwe, &runtime, "").unwrap();
+        assert_eq!(unpacked.plaintext, b"{\"body\":{}}");
+        assert_eq!(unpacked.recipient, alice.pk);
+        assert_eq!(unpacked.sender, None);
+
+        let runtime = SsiRuntime::default();
+        assert!(matches!(unpack(&jwe, &runtime, ""), Err(DidCommError::NoRecipient)));
+    }
+
+    #[test]
+    fn authcrypt() {
+        let mut runtime = SsiRuntime::default();
+        let alice = identity(&mut runtime);
+        let bob = SsiPair::from(SsiSecret::new(Algo::Ed25519, Chain::Bitcoin));
+
+        let jwe = pack_encrypted(b"hello", &[alice.pk], Some(&bob)).unwrap();
+        let unpacked = unpack(&jwe, &runtime, "").unwrap();
+        assert_eq!(unpacked.plaintext, b"hello");
+        assert_eq!(unpacked.recipient, alice.pk);
+        assert_eq!(unpacked.sender.unwrap().to_byte_array(), bob.pk.to_byte_array());
+
+        assert!(matches!(
+            unpack(&jwe, &runtime, "wrong"),
+            Err(DidCommError::Signer(SignerError::WrongPassword))
+        ));
+    }
+
+    #[test]
+    fn skid_mismatch() {
+        let mut runtime = SsiRuntime::default();
+        let alice = identity(&mut runtime);
+        let bob = SsiPair::from(SsiSecret::new(Algo::Ed25519, Chain::Bitcoin));
+        let eve = SsiPair::from(SsiSecret::new(Algo::Ed25519, Chain::Bitcoin));
+
+        let skid = key_id(bob.pk).unwrap();
+        let forged = pack(b"hello", &[alice.pk], Some(&eve), Some(skid.clone())).unwrap();
+        assert!(matches!(unpack(&forged, &runtime, ""), Err(DidCommError::KeyUnwrap)));
+
+        let jwe = pack_encrypted(b"hello", &[alice.pk], Some(&eve)).unwrap();
+        let mut jwe = serde_json::from_str::<Jwe>(&jwe).unwrap();
+        let mut header =
+            serde_json::from_slice::<ProtectedHeader>(&b64_decode(&jwe.protected).unwrap())
+                .unwrap();
+        header.skid = Some(skid);
+        jwe.protected = b64(serde_json::to_vec(&header).unwrap());
+        let jwe = serde_json::to_string(&jwe).unwrap();
+        assert!(matches!(unpack(&jwe, &runtime, ""), Err(DidCommError::SkidMismatch)));
+    }
+
+    #[test]
+    fn unsupported_algo() {
+        let ecdsa = SsiPair::from(SsiSecret::new(Algo::Ecdsa, Chain::Bitcoin));
+        assert!(matches!(
+            pack_encrypted(b"hello", &[ecdsa.pk], None),
+            Err(DidCommError::UnsupportedAlgo(Algo::Ecdsa))
+        ));
+        assert!(matches!(pack_encrypted(b"hello", &[], None), Err(DidCommError::NoRecipients)));
+    }
+}
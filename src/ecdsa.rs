@@ -0,0 +1,322 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use amplify::Bytes;
+use baid64::{Baid64ParseError, DisplayBaid64, FromBaid64Str};
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId, Signature};
+use secp256k1::{Message, Parity, PublicKey, SecretKey, SECP256K1};
+
+use crate::{Algo, Chain, InvalidPubkey, InvalidSig, SsiCert, SsiPub, SsiSig, VerifyError};
+
+/// Secret key for ECDSA signatures over secp256k1 curve.
+///
+/// Since identity public keys are 32 bytes long, the key is always normalized
+/// to have a public key with an even Y coordinate, which allows to represent
+/// it with just its X coordinate.
+#[derive(Clone, Eq, PartialEq)]
+pub struct EcdsaSecret {
+    pub chain: Chain,
+    pub algo: Algo,
+    pub(crate) key: SecretKey,
+}
+
+impl Ord for EcdsaSecret {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.secret_bytes().cmp(&other.key.secret_bytes())
+    }
+}
+
+impl PartialOrd for EcdsaSecret {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl Hash for EcdsaSecret {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.key.secret_bytes().hash(state) }
+}
+
+impl EcdsaSecret {
+    pub fn new(chain: Chain) -> Self {
+        use rand::thread_rng;
+        let key = SecretKey::new(&mut thread_rng());
+        Self::with(chain, key)
+    }
+
+    pub fn with(chain: Chain, key: SecretKey) -> Self {
+        let key = match key.x_only_public_key(SECP256K1) {
+            (_, Parity::Odd) => key.negate(),
+            (_, Parity::Even) => key,
+        };
+        Self {
+            chain,
+            algo: Algo::Ecdsa,
+            key,
+        }
+    }
+
+    pub fn to_public(&self) -> SsiPub {
+        let (pk, _) = self.key.x_only_public_key(SECP256K1);
+        SsiPub::with(self.chain, self.algo, pk.serialize())
+    }
+
+    /// Returns the public key in SEC1 uncompressed form. Since the key is
+    /// normalized, the Y coordinate is always even.
+    pub fn to_sec_pubkey_uncompressed(&self) -> [u8; 65] {
+        self.key.public_key(SECP256K1).serialize_uncompressed()
+    }
+
+    /// Signs the message.
+    ///
+    /// The nonce is ground such that the signature has zero recovery id,
+    /// which allows to recover the public key from the signature alone (see
+    /// [`SsiSig::recover`]).
+    pub fn sign(&self, msg: [u8; 32]) -> SsiSig {
+        let msg = Message::from_digest(msg);
+        let mut counter = 0u64;
+        loop {
+            let mut noncedata = [0u8; 32];
+            noncedata[..8].copy_from_slice(&counter.to_le_bytes());
+            let sig = SECP256K1.sign_ecdsa_recoverable_with_noncedata(&msg, &self.key, &noncedata);
+            let (recid, data) = sig.serialize_compact();
+            if recid.to_i32() == 0 {
+                return SsiSig::from(data);
+            }
+            counter += 1;
+        }
+    }
+
+    /// Creates signature from which the signer public key can be recovered.
+    pub fn sign_recoverable(&self, msg: [u8; 32]) -> RecoverableSig {
+        let msg = Message::from_digest(msg);
+        let sig = SECP256K1.sign_ecdsa_recoverable(&msg, &self.key);
+        let (recid, data) = sig.serialize_compact();
+        let mut bytes = [0u8; 65];
+        bytes[..64].copy_from_slice(&data);
+        bytes[64] = recid.to_i32() as u8;
+        RecoverableSig::from(bytes)
+    }
+}
+
+impl SsiPub {
+    pub fn verify_ecdsa(self, msg: [u8; 32], sig: SsiSig) -> Result<(), InvalidSig> {
+        let sig = Signature::from_compact(sig.as_slice()).map_err(|_| InvalidSig::InvalidData)?;
+        let msg = Message::from_digest(msg);
+        let pk = PublicKey::try_from(self)?;
+        SECP256K1
+            .verify_ecdsa(&msg, &sig, &pk)
+            .map_err(|_| InvalidSig::InvalidSig)
+    }
+}
+
+impl TryFrom<SsiPub> for PublicKey {
+    type Error = InvalidPubkey;
+
+    fn try_from(ssi: SsiPub) -> Result<Self, Self::Error> {
+        let mut data = [0x02; 33];
+        data[1..].copy_from_slice(&ssi.to_byte_array());
+        Self::from_slice(&data).map_err(|_| InvalidPubkey)
+    }
+}
+
+/// ECDSA signature with public key recovery information, consisting of 64
+/// bytes of compact signature followed by a single byte of recovery id.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, From)]
+pub struct RecoverableSig(#[from([u8; 65])] Bytes<65>);
+
+impl RecoverableSig {
+    pub fn as_slice(&self) -> &[u8] { self.0.as_slice() }
+
+    /// Returns the signature without recovery information.
+    pub fn to_sig(&self) -> SsiSig {
+        let mut sig = [0u8; 64];
+        sig.copy_from_slice(&self.0.as_slice()[..64]);
+        SsiSig::from(sig)
+    }
+
+    /// Recovers public key of the signer.
+    ///
+    /// Since the signature doesn't commit to a chain, the key is returned for
+    /// the default chain; its fingerprint matches the signer fingerprint.
+    ///
+    /// Errors with [`InvalidSig::Malleated`] for signatures with high S value.
+    pub fn recover(&self, msg: [u8; 32]) -> Result<SsiPub, InvalidSig> {
+        let data = &self.0.as_slice()[..64];
+        let sig = Signature::from_compact(data).map_err(|_| InvalidSig::InvalidData)?;
+        let mut normalized = sig;
+        normalized.normalize_s();
+        if normalized != sig {
+            return Err(InvalidSig::Malleated);
+        }
+        let recid = RecoveryId::from_i32(self.0.as_slice()[64] as i32)
+            .map_err(|_| InvalidSig::InvalidData)?;
+        let sig = RecoverableSignature::from_compact(data, recid)
+            .map_err(|_| InvalidSig::InvalidData)?;
+        let msg = Message::from_digest(msg);
+        let pk = SECP256K1
+            .recover_ecdsa(&msg, &sig)
+            .map_err(|_| InvalidSig::InvalidSig)?;
+        let (pk, parity) = pk.x_only_public_key();
+        if parity == Parity::Odd {
+            return Err(InvalidSig::InvalidPubkey);
+        }
+        Ok(SsiPub::with(Chain::default(), Algo::Ecdsa, pk.serialize()))
+    }
+}
+
+impl SsiSig {
+    /// Recovers public key of the signer from an ECDSA signature produced by
+    /// [`EcdsaSecret::sign`]. Like with [`RecoverableSig::recover`], the key is
+    /// returned for the default chain.
+    ///
+    /// Untagged signatures are assumed to be ECDSA signatures; for tagged
+    /// signatures the algorithm from the tag is used.
+    pub fn recover(&self, msg: [u8; 32]) -> Result<SsiPub, VerifyError> {
+        self.recover_with(self.algo().unwrap_or(Algo::Ecdsa), msg)
+    }
+
+    /// Recovers public key of the signer, provided the signature algorithm.
+    ///
+    /// Errors with [`VerifyError::UnsupportedAlgo`] for algorithms which don't
+    /// support key recovery, like BIP340 and Ed25519.
+    pub fn recover_with(&self, algo: Algo, msg: [u8; 32]) -> Result<SsiPub, VerifyError> {
+        if algo != Algo::Ecdsa {
+            return Err(VerifyError::UnsupportedAlgo(algo.to_u8()));
+        }
+        let mut data = [0u8; 65];
+        data[..64].copy_from_slice(self.as_slice());
+        Ok(RecoverableSig::from(data).recover(msg)?)
+    }
+}
+
+impl DisplayBaid64<65> for RecoverableSig {
+    const HRI: &'static str = "";
+    const CHUNKING: bool = false;
+    const PREFIX: bool = false;
+    const EMBED_CHECKSUM: bool = false;
+    const MNEMONIC: bool = false;
+
+    fn to_baid64_payload(&self) -> [u8; 65] { self.0.to_byte_array() }
+}
+
+impl FromBaid64Str<65> for RecoverableSig {}
+
+impl FromStr for RecoverableSig {
+    type Err = Baid64ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::from_baid64_str(s) }
+}
+
+impl Display for RecoverableSig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { self.fmt_baid64(f) }
+}
+
+impl SsiCert {
+    /// Constructs certificate from a message digest and a recoverable
+    /// signature, recovering the signer identity from the signature.
+    pub fn from_recoverable(msg: [u8; 32], sig: RecoverableSig) -> Result<Self, InvalidSig> {
+        let pk = sig.recover(msg)?;
+        Ok(SsiCert {
+            fp: pk.fingerprint(),
+            pk: Some(pk),
+            subject: None,
+            msg: msg.into(),
+            sig: sig.to_sig(),
+            signed_at: None,
+            timestamp: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ORDER: [u8; 32] = [
+        0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+        0xFE, 0xBA, 0xAE, 0xDC, 0xE6, 0xAF, 0x48, 0xA0, 0x3B, 0xBF, 0xD2, 0x5E, 0x8C, 0xD0, 0x36,
+        0x41, 0x41,
+    ];
+
+    #[test]
+    fn sign_verify() {
+        let sk = EcdsaSecret::new(Chain::Bitcoin);
+        let msg = [0xA5; 32];
+        let sig = sk.sign(msg);
+        sk.to_public().verify(msg, sig).unwrap();
+        assert_eq!(sk.to_public().verify([0x5A; 32], sig), Err(InvalidSig::InvalidSig));
+    }
+
+    #[test]
+    fn recover() {
+        let sk = EcdsaSecret::new(Chain::Bitcoin);
+        let msg = [0xA5; 32];
+        let sig = sk.sign_recoverable(msg);
+        assert_eq!(RecoverableSig::from_str(&sig.to_string()).unwrap(), sig);
+
+        let pk = sig.recover(msg).unwrap();
+        assert_eq!(pk, sk.to_public());
+        assert_eq!(pk.fingerprint(), sk.to_public().fingerprint());
+        pk.verify(msg, sig.to_sig()).unwrap();
+
+        let cert = SsiCert::from_recoverable(msg, sig).unwrap();
+        assert_eq!(cert.fp, sk.to_public().fingerprint());
+        cert.verify().unwrap();
+    }
+
+    #[test]
+    fn recover_sig() {
+        let key = SecretKey::from_slice(&[0x17; 32]).unwrap();
+        let sk = EcdsaSecret::with(Chain::Bitcoin, key);
+        let msg = [0xA5; 32];
+        let sig = sk.sign(msg);
+        assert_eq!(sig.recover(msg).unwrap(), sk.to_public());
+        assert_eq!(sig.recover_with(Algo::Ecdsa, msg).unwrap(), sk.to_public());
+        assert!(matches!(
+            sig.recover_with(Algo::Bip340, msg),
+            Err(VerifyError::UnsupportedAlgo(0))
+        ));
+        assert_ne!(sig.recover([0x5A; 32]).ok(), Some(sk.to_public()));
+        let tagged = SsiSig::tagged(Algo::Bip340, sig.to_byte_array());
+        assert!(matches!(tagged.recover(msg), Err(VerifyError::UnsupportedAlgo(0))));
+    }
+
+    #[test]
+    fn recover_malleated() {
+        let sk = EcdsaSecret::new(Chain::Bitcoin);
+        let msg = [0xA5; 32];
+        let mut data = sk.sign_recoverable(msg).as_slice().to_vec();
+
+        // s' = n - s
+        let mut borrow = 0i16;
+        for i in (0..32).rev() {
+            let diff = ORDER[i] as i16 - data[32 + i] as i16 - borrow;
+            borrow = (diff < 0) as i16;
+            data[32 + i] = diff.rem_euclid(256) as u8;
+        }
+        data[64] ^= 1;
+
+        let sig = RecoverableSig::from(<[u8; 65]>::try_from(data).unwrap());
+        assert_eq!(sig.recover(msg), Err(InvalidSig::Malleated));
+    }
+}
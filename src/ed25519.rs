@@ -23,27 +23,84 @@ use std::cmp::Ordering;
 use std::hash::{Hash, Hasher};
 use std::io;
 
-use amplify::confinement::ConfinedVec;
-use amplify::{ByteArray, FromSliceError};
+use amplify::Bytes64;
+use ec25519::edwards25519::{
+    ge_scalarmult_base, is_identity, sc_muladd, sc_reduce, sc_reject_noncanonical, GeP2, GeP3,
+};
 use ec25519::{KeyPair, PublicKey, SecretKey, Seed, Signature};
-use strict_encoding::{StrictDeserialize, StrictEncode, StrictSerialize};
+use sha2::{Digest, Sha512};
+use strict_encoding::{
+    DecodeError, ReadStruct, StrictDecode, StrictDeserialize, StrictDumb, StrictEncode,
+    StrictProduct, StrictSerialize, StrictStruct, StrictType, TypeName, TypedRead, TypedWrite,
+    WriteStruct,
+};
 
 use crate::{Algo, Chain, InvalidPubkey, InvalidSig, SsiPub, SsiSig, LIB_NAME_SSI};
 
+const DOM2_PREFIX: &[u8] = b"SigEd25519 no Ed25519 collisions";
+const DOM2_PREHASH: u8 = 1;
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display("Ed25519 signature context must not exceed 255 bytes")]
+pub struct ContextTooLong;
+
+fn dom2(engine: &mut Sha512, context: &[u8]) -> Result<(), ContextTooLong> {
+    let len = u8::try_from(context.len()).map_err(|_| ContextTooLong)?;
+    engine.update(DOM2_PREFIX);
+    engine.update([DOM2_PREHASH, len]);
+    engine.update(context);
+    Ok(())
+}
+
 #[derive(Clone, Eq, PartialEq, From)]
-#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
-#[strict_type(lib = LIB_NAME_SSI)]
 pub struct Ed25519Secret {
     pub chain: Chain,
     pub algo: Algo,
     pub(crate) key: SecretKey,
 }
 
+impl StrictType for Ed25519Secret {
+    const STRICT_LIB_NAME: &'static str = LIB_NAME_SSI;
+    fn strict_name() -> Option<TypeName> { Some(tn!("Ed25519Secret")) }
+}
+impl StrictDumb for Ed25519Secret {
+    fn strict_dumb() -> Self { Self::with(Chain::default(), SecretKey::new([0u8; 64])) }
+}
+impl StrictProduct for Ed25519Secret {}
+impl StrictStruct for Ed25519Secret {
+    const ALL_FIELDS: &'static [&'static str] = &["chain", "algo", "key"];
+}
+// The secret key type of `ec25519` has no strict encoding, thus the key is
+// encoded as its 64 bytes
+impl StrictEncode for Ed25519Secret {
+    fn strict_encode<W: TypedWrite>(&self, writer: W) -> io::Result<W> {
+        writer.write_struct::<Self>(|w| {
+            Ok(w.write_field(fname!("chain"), &self.chain)?
+                .write_field(fname!("algo"), &self.algo)?
+                .write_field(fname!("key"), &Bytes64::from(*self.key))?
+                .complete())
+        })
+    }
+}
+impl StrictDecode for Ed25519Secret {
+    fn strict_decode(reader: &mut impl TypedRead) -> Result<Self, DecodeError> {
+        reader.read_struct(|r| {
+            let chain = r.read_field(fname!("chain"))?;
+            let algo = r.read_field(fname!("algo"))?;
+            let key = r.read_field::<Bytes64>(fname!("key"))?;
+            Ok(Self {
+                chain,
+                algo,
+                key: SecretKey::new(key.to_byte_array()),
+            })
+        })
+    }
+}
 impl StrictSerialize for Ed25519Secret {}
 impl StrictDeserialize for Ed25519Secret {}
 
 impl Ord for Ed25519Secret {
-    fn cmp(&self, other: &Self) -> Ordering { self.serialize().cmp(other.serialize()) }
+    fn cmp(&self, other: &Self) -> Ordering { self.key[..].cmp(&other.key[..]) }
 }
 
 impl PartialOrd for Ed25519Secret {
@@ -51,36 +108,7 @@ impl PartialOrd for Ed25519Secret {
 }
 
 impl Hash for Ed25519Secret {
-    fn hash<H: Hasher>(&self, state: &mut H) { self.serialize().hash(state) }
-}
-
-impl ByteArray<34> for Ed25519Secret {
-    fn from_byte_array(val: impl Into<[u8; 34]>) -> Self {
-        Self::from_strict_serialized::<34>(ConfinedVec::from_slice_checked(&val.into())).unwrap()
-    }
-
-    fn from_slice(slice: impl AsRef<[u8]>) -> Result<Self, FromSliceError> {
-        let len = slice.as_ref().len();
-        if len != 34 {
-            return Err(FromSliceError {
-                expected: 34,
-                actual: len,
-            });
-        }
-        Ok(Self::from_strict_serialized::<34>(ConfinedVec::from_slice_checked(&slice.as_ref()))
-            .unwrap())
-    }
-
-    fn from_slice_unsafe(slice: impl AsRef<[u8]>) -> Self {
-        Self::from_strict_serialized::<34>(ConfinedVec::from_slice_checked(&slice.as_ref()))
-            .unwrap()
-    }
-
-    fn to_byte_array(&self) -> [u8; 34] {
-        let mut array = [0u8; 34];
-        array.copy_from_slice(&self.to_strict_serialized::<34>().unwrap());
-        array
-    }
+    fn hash<H: Hasher>(&self, state: &mut H) { self.key[..].hash(state) }
 }
 
 impl Ed25519Secret {
@@ -94,6 +122,14 @@ impl Ed25519Secret {
         }
     }
 
+    pub fn with(chain: Chain, key: SecretKey) -> Self {
+        Self {
+            chain,
+            algo: Algo::Ed25519,
+            key,
+        }
+    }
+
     pub fn to_public(&self) -> SsiPub {
         let pk = self.key.public_key();
         SsiPub::with(self.chain, self.algo, *pk)
@@ -103,6 +139,53 @@ impl Ed25519Secret {
         let sig = self.key.sign(msg, None);
         SsiSig::from(*sig)
     }
+
+    /// Signs SHA-512 hash of a message using Ed25519ph scheme (RFC 8032),
+    /// with an optional context string for domain separation. The context is
+    /// a part of the signed data, and must be provided for the verification.
+    ///
+    /// Since only the message hash is required, this allows to sign large
+    /// messages in a streaming fashion.
+    pub fn sign_prehashed(
+        &self,
+        prehash: [u8; 64],
+        context: &[u8],
+    ) -> Result<SsiSig, ContextTooLong> {
+        let seed = &self.key[..32];
+        let pk = &self.key[32..];
+        let mut az: [u8; 64] = Sha512::digest(seed).into();
+        az[0] &= 248;
+        az[31] &= 63;
+        az[31] |= 64;
+
+        let mut engine = Sha512::new();
+        dom2(&mut engine, context)?;
+        engine.update(&az[32..]);
+        engine.update(prehash);
+        let mut nonce: [u8; 64] = engine.finalize().into();
+        sc_reduce(&mut nonce);
+
+        let mut sig = [0u8; 64];
+        sig[..32].copy_from_slice(&ge_scalarmult_base(&nonce[..32]).to_bytes());
+
+        let mut engine = Sha512::new();
+        dom2(&mut engine, context)?;
+        engine.update(&sig[..32]);
+        engine.update(pk);
+        engine.update(prehash);
+        let mut hram: [u8; 64] = engine.finalize().into();
+        sc_reduce(&mut hram);
+
+        sc_muladd(&mut sig[32..], &hram[..32], &az[..32], &nonce[..32]);
+        Ok(SsiSig::from(sig))
+    }
+
+    /// Signs message of an arbitrary length (unlike [`Ed25519Secret::sign`],
+    /// which signs 32-byte digests).
+    #[cfg(feature = "vc")]
+    pub(crate) fn sign_message(&self, msg: impl AsRef<[u8]>) -> SsiSig {
+        SsiSig::from(*self.key.sign(msg, None))
+    }
 }
 
 impl SsiPub {
@@ -114,6 +197,58 @@ impl SsiPub {
             InvalidSig::InvalidSig
         })
     }
+
+    /// Verifies signature produced by [`Ed25519Secret::sign_message`].
+    #[cfg(feature = "vc")]
+    pub(crate) fn verify_ed25519_message(
+        self,
+        msg: impl AsRef<[u8]>,
+        sig: SsiSig,
+    ) -> Result<(), InvalidSig> {
+        let sig = Signature::from_slice(sig.as_slice()).map_err(|_| InvalidSig::InvalidData)?;
+        let pk = PublicKey::try_from(self)?;
+        pk.verify(msg, &sig).map_err(|_| InvalidSig::InvalidSig)
+    }
+}
+
+impl SsiPub {
+    /// Verifies Ed25519ph signature (see [`Ed25519Secret::sign_prehashed`])
+    /// over SHA-512 hash of a message and a context string.
+    pub fn verify_prehashed(
+        self,
+        prehash: [u8; 64],
+        context: &[u8],
+        sig: SsiSig,
+    ) -> Result<(), InvalidSig> {
+        if self.algo() != &Algo::Ed25519 {
+            return Err(InvalidSig::UnsupportedAlgo(self.algo().to_u8()));
+        }
+        let pk = self.to_byte_array();
+        let sig = sig.to_byte_array();
+        sc_reject_noncanonical(&sig[32..]).map_err(|_| InvalidSig::InvalidData)?;
+        if is_identity(&pk) || pk == [0u8; 32] {
+            return Err(InvalidSig::InvalidPubkey);
+        }
+        let a = GeP3::from_bytes_negate_vartime(&pk).ok_or(InvalidSig::InvalidPubkey)?;
+        let mut r = [0u8; 32];
+        r.copy_from_slice(&sig[..32]);
+        let expected_r = GeP3::from_bytes_vartime(&r).ok_or(InvalidSig::InvalidSig)?;
+
+        let mut engine = Sha512::new();
+        dom2(&mut engine, context).map_err(|_| InvalidSig::InvalidData)?;
+        engine.update(r);
+        engine.update(pk);
+        engine.update(prehash);
+        let mut hram: [u8; 64] = engine.finalize().into();
+        sc_reduce(&mut hram);
+
+        let r = GeP2::double_scalarmult_vartime(&hram[..32], a, &sig[32..]);
+        if (expected_r - GeP3::from(r)).has_small_order() {
+            Ok(())
+        } else {
+            Err(InvalidSig::InvalidSig)
+        }
+    }
 }
 
 impl TryFrom<SsiPub> for PublicKey {
@@ -125,5 +260,67 @@ impl TryFrom<SsiPub> for PublicKey {
 }
 
 impl SsiPub {
-    pub fn from_ed25519(key: PublicKey) -> Self { Self::from(*key) }
+    pub fn from_ed25519(key: PublicKey) -> Self {
+        Self::with(Chain::default(), Algo::Ed25519, *key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::hex::FromHex;
+
+    use super::*;
+
+    fn rfc8032_secret() -> Ed25519Secret {
+        let seed = Vec::<u8>::from_hex(
+            "833fe62409237b9d62ec77587520911e9a759cec1d19755b7da901b96dca3d42",
+        )
+        .unwrap();
+        let pair = KeyPair::from_seed(Seed::from_slice(&seed).unwrap());
+        Ed25519Secret::with(Chain::Bitcoin, pair.sk)
+    }
+
+    #[test]
+    fn strict_encoding() {
+        let sk = rfc8032_secret();
+        let data = sk.to_strict_serialized::<66>().unwrap();
+        assert_eq!(data.len(), 66);
+        let decoded = Ed25519Secret::from_strict_serialized::<66>(data).unwrap();
+        assert!(decoded == sk);
+        assert_eq!(decoded.to_public(), sk.to_public());
+    }
+
+    #[test]
+    fn ed25519ph_rfc8032() {
+        let sk = rfc8032_secret();
+        let pk = sk.to_public();
+        assert_eq!(
+            pk.to_byte_array().to_vec(),
+            Vec::<u8>::from_hex("ec172b93ad5e563bf4932c70e1245034c35467ef2efd4d64ebf819683467e2bf")
+                .unwrap()
+        );
+        let prehash: [u8; 64] = Sha512::digest(b"abc").into();
+        let sig = sk.sign_prehashed(prehash, b"").unwrap();
+        assert_eq!(
+            sig.to_byte_array().to_vec(),
+            Vec::<u8>::from_hex(
+                "98a70222f0b8121aa9d30f813d683f809e462b469c7ff87639499bb94e6dae41\
+                 31f85042463c2a355a2003d062adf5aaa10b8c61e636062aaad11c2a26083406"
+            )
+            .unwrap()
+        );
+        pk.verify_prehashed(prehash, b"", sig).unwrap();
+    }
+
+    #[test]
+    fn ed25519ph_context() {
+        let sk = rfc8032_secret();
+        let pk = sk.to_public();
+        let prehash: [u8; 64] = Sha512::digest(b"large document").into();
+        let sig = sk.sign_prehashed(prehash, b"ssi-test").unwrap();
+        pk.verify_prehashed(prehash, b"ssi-test", sig).unwrap();
+        assert_eq!(pk.verify_prehashed(prehash, b"ssi-other", sig), Err(InvalidSig::InvalidSig));
+        assert_eq!(pk.verify_prehashed(prehash, b"", sig), Err(InvalidSig::InvalidSig));
+        assert_eq!(sk.sign_prehashed(prehash, &[0u8; 256]), Err(ContextTooLong));
+    }
 }
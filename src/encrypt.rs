@@ -27,18 +27,24 @@ use amplify::confinement::{Confined, SmallOrdMap, U64 as U64MAX};
 use amplify::Bytes32;
 use armor::{ArmorHeader, ArmorParseError, AsciiArmor};
 use ec25519::{edwards25519, KeyPair, Seed};
+use hkdf::Hkdf;
+use pbkdf2::pbkdf2_hmac;
 use rand::random;
 use sha2::{Digest, Sha256};
 use strict_encoding::{StrictDeserialize, StrictSerialize};
 
-use crate::{Algo, InvalidPubkey, SsiPair, SsiPub, LIB_NAME_SSI};
+use crate::{Algo, InvalidPubkey, Ssi, SsiPair, SsiPub, LIB_NAME_SSI};
 
 #[derive(Copy, Clone, Debug, Display, Error)]
 pub enum EncryptionError {
     #[display("the number of receivers exceeds 2^16.")]
     TooManyReceivers,
+    #[display("no receivers are given.")]
+    NoReceivers,
     #[display("invalid public key {0}.")]
     InvalidPubkey(SsiPub),
+    #[display("key {0} is not authorized for encryption.")]
+    UsageNotAllowed(SsiPub),
 }
 
 #[derive(Copy, Clone, Debug, Display, Error, From)]
@@ -50,6 +56,8 @@ pub enum DecryptionError {
     #[from(aes_gcm::Error)]
     #[display("unable to decrypt data.")]
     Decrypt,
+    #[display("the message is encrypted for {0}.")]
+    WrapMismatch(KeyWrap),
 }
 
 #[derive(Clone, Debug, From)]
@@ -66,6 +74,8 @@ impl AsRef<[u8]> for SymmetricKey {
 }
 
 impl SymmetricKey {
+    // `Default` is not implemented since it would conflict with `StrictDumb`
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         loop {
             let key = random::<[u8; 32]>();
@@ -74,6 +84,56 @@ impl SymmetricKey {
             }
         }
     }
+
+    /// Derives key from an input keying material `ikm` (like ECDH shared
+    /// secret) using HKDF-SHA256 (RFC 5869).
+    ///
+    /// Unlike [`SymmetricKey::new`], the derived key is not guaranteed to be a
+    /// valid curve point, and thus can be used only for data encryption, but
+    /// not with [`SsiPub::encrypt_key`].
+    pub fn derive(ikm: &[u8], salt: &[u8], info: &[u8]) -> SymmetricKey {
+        let mut okm = [0u8; 32];
+        Hkdf::<Sha256>::new(Some(salt), ikm)
+            .expand(info, &mut okm)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        Self::from(okm)
+    }
+}
+
+/// Number of PBKDF2-HMAC-SHA256 rounds used to derive keys from passwords.
+pub const PASSWORD_ROUNDS: u32 = 600_000;
+
+/// Method of protecting the data encryption key of an [`Encrypted`] message.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Display)]
+#[derive(StrictType, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_SSI, tags = custom)]
+pub enum KeyWrap {
+    /// The key is encrypted to each of the receivers public keys.
+    #[default]
+    #[display("public keys")]
+    #[strict_type(tag = 0)]
+    PublicKey,
+    /// The key is derived from a password with PBKDF2-HMAC-SHA256 using the
+    /// given salt and number of rounds.
+    #[display("a password")]
+    #[strict_type(tag = 1)]
+    Password { salt: [u8; 16], rounds: u32 },
+    /// The data are encrypted with a symmetric key shared out of band.
+    #[display("a symmetric key")]
+    #[strict_type(tag = 2)]
+    Symmetric,
+}
+
+/// Information on what is needed to decrypt an [`Encrypted`] message (see
+/// [`Encrypted::recipient_kind`]).
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum RecipientKind {
+    /// The password the message was encrypted with.
+    Password,
+    /// The secret key for the public key of a receiver.
+    PublicKey(SsiPub),
+    /// The symmetric key shared out of band.
+    Symmetric,
 }
 
 #[derive(Clone, Debug, Display)]
@@ -84,6 +144,31 @@ pub struct Encrypted {
     pub keys: SmallOrdMap<SsiPub, (Bytes32, Bytes32)>,
     pub nonce: [u8; 12],
     pub data: Confined<Vec<u8>, 0, U64MAX>,
+    pub wrap: KeyWrap,
+}
+
+/// Layout of messages produced by earlier versions, which were always
+/// encrypted to public keys and lacked the key wrap method.
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_SSI)]
+struct LegacyEncrypted {
+    keys: SmallOrdMap<SsiPub, (Bytes32, Bytes32)>,
+    nonce: [u8; 12],
+    data: Confined<Vec<u8>, 0, U64MAX>,
+}
+
+impl StrictSerialize for LegacyEncrypted {}
+impl StrictDeserialize for LegacyEncrypted {}
+
+impl From<LegacyEncrypted> for Encrypted {
+    fn from(legacy: LegacyEncrypted) -> Self {
+        Self {
+            keys: legacy.keys,
+            nonce: legacy.nonce,
+            data: legacy.data,
+            wrap: KeyWrap::PublicKey,
+        }
+    }
 }
 
 impl StrictSerialize for Encrypted {}
@@ -94,7 +179,12 @@ impl AsciiArmor for Encrypted {
     const PLATE_TITLE: &'static str = "SSI MESSAGE";
 
     fn ascii_armored_headers(&self) -> Vec<ArmorHeader> {
-        vec![ArmorHeader::with("Receivers", self.keys.keys().map(|pk| pk.to_string()))]
+        match self.wrap {
+            KeyWrap::PublicKey => {
+                vec![ArmorHeader::with("Receivers", self.keys.keys().map(|pk| pk.to_string()))]
+            }
+            KeyWrap::Password { .. } | KeyWrap::Symmetric => vec![],
+        }
     }
 
     fn to_ascii_armored_data(&self) -> Vec<u8> {
@@ -105,8 +195,14 @@ impl AsciiArmor for Encrypted {
 
     fn with_headers_data(_headers: Vec<ArmorHeader>, data: Vec<u8>) -> Result<Self, Self::Err> {
         // TODO: Check receivers list
-        Ok(Self::from_strict_serialized::<U64MAX>(Confined::from_checked(data))
-            .expect("64 bits will never fail"))
+        let data = Confined::from_checked(data);
+        let encrypted = Self::from_strict_serialized::<U64MAX>(data.clone())
+            .or_else(|_| LegacyEncrypted::from_strict_serialized::<U64MAX>(data).map(Self::from))
+            .map_err(|_| ArmorParseError::WrongStructure)?;
+        if encrypted.wrap == KeyWrap::PublicKey && encrypted.keys.is_empty() {
+            return Err(ArmorParseError::WrongStructure);
+        }
+        Ok(encrypted)
     }
 }
 
@@ -129,15 +225,109 @@ impl Encrypted {
                 .map_err(|_| EncryptionError::InvalidPubkey(pk))?;
             keys.insert(pk, (msg, Bytes32::from_slice_unsafe(c1.as_slice())));
         }
+        if keys.is_empty() {
+            return Err(EncryptionError::NoReceivers);
+        }
         let (nonce, msg) = encrypt(source, key);
         Ok(Self {
             keys: Confined::try_from(keys).map_err(|_| EncryptionError::TooManyReceivers)?,
             nonce: nonce.into(),
             data: Confined::from_checked(msg),
+            wrap: KeyWrap::PublicKey,
         })
     }
 
+    /// Encrypts data to the keys of the receiver identities, refusing keys
+    /// which are not authorized for encryption (see [`Ssi::can_encrypt`]).
+    pub fn encrypt_to<'a>(
+        source: Vec<u8>,
+        receivers: impl IntoIterator<Item = &'a Ssi>,
+    ) -> Result<Self, EncryptionError> {
+        let receivers = receivers
+            .into_iter()
+            .map(|ssi| match ssi.can_encrypt() {
+                true => Ok(ssi.pk),
+                false => Err(EncryptionError::UsageNotAllowed(ssi.pk)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::encrypt(source, receivers)
+    }
+
+    /// Encrypts data with a key derived from the password using a random salt
+    /// and [`PASSWORD_ROUNDS`] of PBKDF2.
+    pub fn with_password(source: Vec<u8>, passwd: &str) -> Self {
+        Self::with_password_rounds(source, passwd, PASSWORD_ROUNDS)
+    }
+
+    fn with_password_rounds(source: Vec<u8>, passwd: &str, rounds: u32) -> Self {
+        let wrap = KeyWrap::Password {
+            salt: random(),
+            rounds,
+        };
+        let (nonce, msg) = encrypt(source, password_key(passwd, wrap));
+        Self {
+            keys: none!(),
+            nonce: nonce.into(),
+            data: Confined::from_checked(msg),
+            wrap,
+        }
+    }
+
+    /// Encrypts data with a symmetric key, which is shared with the receivers
+    /// out of band.
+    pub fn with_key(source: Vec<u8>, key: &SymmetricKey) -> Self {
+        let (nonce, msg) = encrypt(source, key);
+        Self {
+            keys: none!(),
+            nonce: nonce.into(),
+            data: Confined::from_checked(msg),
+            wrap: KeyWrap::Symmetric,
+        }
+    }
+
+    /// Tells what is needed to decrypt the message, allowing user interfaces
+    /// to prompt for a password or a key without trying to decrypt.
+    ///
+    /// For messages encrypted to multiple receivers the first of them is
+    /// reported; all of them are listed in [`Encrypted::keys`].
+    ///
+    /// # Panics
+    ///
+    /// If the message is encrypted to public keys but has no receivers, which
+    /// is never the case for the messages created or parsed by this library.
+    pub fn recipient_kind(&self) -> RecipientKind {
+        match self.wrap {
+            KeyWrap::PublicKey => RecipientKind::PublicKey(
+                *self
+                    .keys
+                    .keys()
+                    .next()
+                    .expect("messages encrypted to public keys always have receivers"),
+            ),
+            KeyWrap::Password { .. } => RecipientKind::Password,
+            KeyWrap::Symmetric => RecipientKind::Symmetric,
+        }
+    }
+
+    pub fn decrypt_with_password(&self, passwd: &str) -> Result<Vec<u8>, DecryptionError> {
+        if !matches!(self.wrap, KeyWrap::Password { .. }) {
+            return Err(DecryptionError::WrapMismatch(self.wrap));
+        }
+        let key = password_key(passwd, self.wrap);
+        Ok(decrypt(self.data.as_slice(), self.nonce.into(), key)?)
+    }
+
+    pub fn decrypt_with_key(&self, key: &SymmetricKey) -> Result<Vec<u8>, DecryptionError> {
+        if self.wrap != KeyWrap::Symmetric {
+            return Err(DecryptionError::WrapMismatch(self.wrap));
+        }
+        Ok(decrypt(self.data.as_slice(), self.nonce.into(), key)?)
+    }
+
     pub fn decrypt(&self, pair: impl Into<SsiPair>) -> Result<Vec<u8>, DecryptionError> {
+        if self.wrap != KeyWrap::PublicKey {
+            return Err(DecryptionError::WrapMismatch(self.wrap));
+        }
         let pair = pair.into();
         let (msg, c1) = self
             .keys
@@ -160,7 +350,7 @@ impl SsiPub {
     ) -> Result<(Bytes32, ec25519::PublicKey), InvalidPubkey> {
         match self.algo() {
             Algo::Ed25519 => self.encrypt_key_ed25519(key),
-            Algo::Bip340 | Algo::Other(_) => Err(InvalidPubkey),
+            Algo::Bip340 | Algo::Ecdsa | Algo::Other(_) => Err(InvalidPubkey),
         }
     }
 
@@ -185,6 +375,11 @@ impl SsiPub {
 }
 
 impl SsiPair {
+    /// Decrypts data encrypted to the pair public key.
+    pub fn decrypt_from(&self, encrypted: &Encrypted) -> Result<Vec<u8>, DecryptionError> {
+        encrypted.decrypt(self.clone())
+    }
+
     pub fn decrypt_key(
         &self,
         encrypted_message: Bytes32,
@@ -192,7 +387,7 @@ impl SsiPair {
     ) -> Result<SymmetricKey, InvalidPubkey> {
         match self.pk.algo() {
             Algo::Ed25519 => self.decrypt_key_ed25519(encrypted_message, c1),
-            Algo::Bip340 | Algo::Other(_) => Err(InvalidPubkey),
+            Algo::Bip340 | Algo::Ecdsa | Algo::Other(_) => Err(InvalidPubkey),
         }
     }
 
@@ -210,6 +405,15 @@ impl SsiPair {
     }
 }
 
+fn password_key(passwd: &str, wrap: KeyWrap) -> [u8; 32] {
+    let KeyWrap::Password { salt, rounds } = wrap else {
+        unreachable!("password key is derived only for password-wrapped messages")
+    };
+    let mut key = [0u8; 32];
+    pbkdf2_hmac::<Sha256>(passwd.as_bytes(), &salt, rounds, &mut key);
+    key
+}
+
 pub fn encrypt(source: Vec<u8>, key: impl AsRef<[u8]>) -> (Nonce<Aes256Gcm>, Vec<u8>) {
     let key = Sha256::digest(key.as_ref());
     let key = aes_gcm::Key::<Aes256Gcm>::from_slice(key.as_slice());
@@ -239,6 +443,25 @@ mod test {
     use super::*;
     use crate::{Chain, SsiSecret};
 
+    #[test]
+    fn hkdf() {
+        // RFC 5869 test case 1 (first 32 bytes of OKM)
+        let salt = (0x00..=0x0c).collect::<Vec<u8>>();
+        let info = (0xf0..=0xf9).collect::<Vec<u8>>();
+        let key = SymmetricKey::derive(&[0x0b; 22], &salt, &info);
+        assert_eq!(
+            key.0.to_string(),
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf"
+        );
+
+        // RFC 5869 test case 3: empty salt and info
+        let key = SymmetricKey::derive(&[0x0b; 22], &[], &[]);
+        assert_eq!(
+            key.0.to_string(),
+            "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d"
+        );
+    }
+
     #[test]
     fn aes_roundcrypt() {
         let key = "Some key";
@@ -265,7 +488,99 @@ mod test {
         let source = b"Message to encrypt";
 
         let encrypted = Encrypted::encrypt(source.to_vec(), [key.to_public()]).unwrap();
-        let decrypted = encrypted.decrypt(key).unwrap();
+        let decrypted = encrypted.decrypt(key.clone()).unwrap();
         assert_eq!(decrypted, source);
+        assert_eq!(SsiPair::from(key).decrypt_from(&encrypted).unwrap(), source);
+    }
+
+    #[test]
+    fn recipient_kind() {
+        let source = b"Message to encrypt";
+        let sk = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let pk = sk.to_public();
+        let key = SymmetricKey::new();
+
+        let to_pk = Encrypted::encrypt(source.to_vec(), [pk]).unwrap();
+        let to_passwd = Encrypted::with_password_rounds(source.to_vec(), "passwd", 1000);
+        let to_key = Encrypted::with_key(source.to_vec(), &key);
+        let cases = [
+            (to_pk.clone(), RecipientKind::PublicKey(pk)),
+            (to_passwd.clone(), RecipientKind::Password),
+            (to_key.clone(), RecipientKind::Symmetric),
+        ];
+        for (encrypted, kind) in cases {
+            assert_eq!(encrypted.recipient_kind(), kind);
+            let parsed = Encrypted::from_str(&encrypted.to_string()).unwrap();
+            assert_eq!(parsed.recipient_kind(), kind);
+            assert_eq!(parsed.wrap, encrypted.wrap);
+        }
+
+        assert_eq!(to_passwd.decrypt_with_password("passwd").unwrap(), source);
+        assert!(to_passwd.decrypt_with_password("wrong").is_err());
+        assert!(matches!(
+            to_passwd.decrypt_with_key(&key),
+            Err(DecryptionError::WrapMismatch(KeyWrap::Password { rounds: 1000, .. }))
+        ));
+        assert!(matches!(to_passwd.decrypt(sk.clone()), Err(DecryptionError::WrapMismatch(_))));
+
+        assert_eq!(to_key.decrypt_with_key(&key).unwrap(), source);
+        assert!(to_key.decrypt_with_key(&SymmetricKey::new()).is_err());
+        assert!(to_key.decrypt_with_password("passwd").is_err());
+        assert!(matches!(to_key.decrypt(sk), Err(DecryptionError::WrapMismatch(_))));
+        assert!(matches!(
+            to_pk.decrypt_with_key(&key),
+            Err(DecryptionError::WrapMismatch(KeyWrap::PublicKey))
+        ));
+        assert!(matches!(
+            Encrypted::encrypt(source.to_vec(), []),
+            Err(EncryptionError::NoReceivers)
+        ));
+    }
+
+    #[test]
+    fn password_salt() {
+        let a = Encrypted::with_password(b"Message".to_vec(), "passwd");
+        assert!(matches!(a.wrap, KeyWrap::Password {
+            rounds: PASSWORD_ROUNDS,
+            ..
+        }));
+        assert_eq!(a.decrypt_with_password("passwd").unwrap(), b"Message");
+
+        let b = Encrypted::with_password_rounds(b"Message".to_vec(), "passwd", 1000);
+        let c = Encrypted::with_password_rounds(b"Message".to_vec(), "passwd", 1000);
+        assert_ne!(b.wrap, c.wrap);
+    }
+
+    #[test]
+    fn encrypt_to_usage() {
+        use crate::{KeyUsage, Uid};
+
+        let sk = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let uid = Uid::from_str("Alice <mailto:alice@example.com>").unwrap();
+        let mut ssi = Ssi::new(bset![uid], None, &sk);
+        let encrypted = Encrypted::encrypt_to(b"Message".to_vec(), [&ssi]).unwrap();
+        assert_eq!(encrypted.decrypt(sk.clone()).unwrap(), b"Message");
+
+        ssi.usage = KeyUsage::SIGN;
+        ssi.resign(&sk).unwrap();
+        assert!(matches!(
+            Encrypted::encrypt_to(b"Message".to_vec(), [&ssi]),
+            Err(EncryptionError::UsageNotAllowed(pk)) if pk == ssi.pk
+        ));
+    }
+
+    #[test]
+    fn legacy_layout() {
+        let pk = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin).to_public();
+        let encrypted = Encrypted::encrypt(b"Message".to_vec(), [pk]).unwrap();
+        let legacy = LegacyEncrypted {
+            keys: encrypted.keys.clone(),
+            nonce: encrypted.nonce,
+            data: encrypted.data.clone(),
+        };
+        let data = legacy.to_strict_serialized::<U64MAX>().unwrap().release();
+        let parsed = Encrypted::with_headers_data(vec![], data).unwrap();
+        assert_eq!(parsed.recipient_kind(), RecipientKind::PublicKey(pk));
+        assert_eq!(parsed.data, encrypted.data);
     }
 }
@@ -0,0 +1,213 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! C ABI for verification of identities and signatures.
+//!
+//! The C header is generated with `cbindgen --config cbindgen.toml --output
+//! include/ssi.h`.
+
+use std::ptr;
+use std::str::FromStr;
+
+use crate::{InvalidSig, Ssi, SsiParseError, SsiPub, SsiSig};
+
+/// Operation has succeeded.
+pub const SSI_OK: i32 = 0;
+/// A required pointer argument is null.
+pub const SSI_ERR_NULL: i32 = -1;
+/// String argument is not a valid UTF-8 string.
+pub const SSI_ERR_UTF8: i32 = -2;
+/// Identity or public key string can't be parsed.
+pub const SSI_ERR_PARSE: i32 = -3;
+/// Signature doesn't match the public key and the message.
+pub const SSI_ERR_INVALID_SIG: i32 = -4;
+/// Identity is not signed.
+pub const SSI_ERR_UNSIGNED: i32 = -5;
+/// Signature algorithm is not supported.
+pub const SSI_ERR_UNSUPPORTED: i32 = -6;
+
+/// Opaque handle to an identity public key.
+pub struct SsiPubHandle(SsiPub);
+
+fn sig_err(err: InvalidSig) -> i32 {
+    match err {
+        InvalidSig::UnsupportedAlgo(_) | InvalidSig::CannotVerify => SSI_ERR_UNSUPPORTED,
+        InvalidSig::InvalidData | InvalidSig::InvalidPubkey => SSI_ERR_PARSE,
+        InvalidSig::InvalidSig | InvalidSig::Malleated | InvalidSig::AlgoMismatch => {
+            SSI_ERR_INVALID_SIG
+        }
+    }
+}
+
+unsafe fn read_str<'a>(ptr: *const u8, len: usize) -> Result<&'a str, i32> {
+    if ptr.is_null() {
+        return Err(SSI_ERR_NULL);
+    }
+    let data = std::slice::from_raw_parts(ptr, len);
+    std::str::from_utf8(data).map_err(|_| SSI_ERR_UTF8)
+}
+
+/// Parses SSI identity URI and verifies its self-signature.
+///
+/// Returns [`SSI_OK`] if the identity is valid and signed.
+///
+/// # Safety
+///
+/// `uri_ptr` must point to `len` bytes of readable memory.
+#[no_mangle]
+pub unsafe extern "C" fn ssi_verify(uri_ptr: *const u8, len: usize) -> i32 {
+    let s = match read_str(uri_ptr, len) {
+        Ok(s) => s,
+        Err(code) => return code,
+    };
+    match Ssi::from_str(s) {
+        Ok(_) => SSI_OK,
+        Err(SsiParseError::Unsigned) => SSI_ERR_UNSIGNED,
+        Err(SsiParseError::WrongSig(err)) => sig_err(err),
+        Err(_) => SSI_ERR_PARSE,
+    }
+}
+
+/// Parses public key from its string representation, storing a newly
+/// allocated handle into `out`. The handle must be released with
+/// [`ssi_pub_free`].
+///
+/// # Safety
+///
+/// `ptr` must point to `len` bytes of readable memory; `out` must be a valid
+/// pointer for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ssi_pub_from_str(
+    ptr: *const u8,
+    len: usize,
+    out: *mut *mut SsiPubHandle,
+) -> i32 {
+    if out.is_null() {
+        return SSI_ERR_NULL;
+    }
+    *out = ptr::null_mut();
+    let s = match read_str(ptr, len) {
+        Ok(s) => s,
+        Err(code) => return code,
+    };
+    match SsiPub::from_str(s) {
+        Ok(pk) => {
+            *out = Box::into_raw(Box::new(SsiPubHandle(pk)));
+            SSI_OK
+        }
+        Err(_) => SSI_ERR_PARSE,
+    }
+}
+
+/// Releases public key handle. Does nothing if `pk` is null.
+///
+/// # Safety
+///
+/// `pk` must be either null or a handle returned by [`ssi_pub_from_str`]
+/// which was not released before.
+#[no_mangle]
+pub unsafe extern "C" fn ssi_pub_free(pk: *mut SsiPubHandle) {
+    if !pk.is_null() {
+        drop(Box::from_raw(pk));
+    }
+}
+
+/// Verifies 64-byte signature over a 32-byte message digest.
+///
+/// # Safety
+///
+/// `pk` must be a valid handle returned by [`ssi_pub_from_str`]; `msg32` and
+/// `sig64` must point to 32 and 64 bytes of readable memory, respectively.
+#[no_mangle]
+pub unsafe extern "C" fn ssi_sig_verify(
+    pk: *const SsiPubHandle,
+    msg32: *const u8,
+    sig64: *const u8,
+) -> i32 {
+    if pk.is_null() || msg32.is_null() || sig64.is_null() {
+        return SSI_ERR_NULL;
+    }
+    let mut msg = [0u8; 32];
+    msg.copy_from_slice(std::slice::from_raw_parts(msg32, 32));
+    let mut sig = [0u8; 64];
+    sig.copy_from_slice(std::slice::from_raw_parts(sig64, 64));
+    match (*pk).0.verify(msg, SsiSig::from(sig)) {
+        Ok(()) => SSI_OK,
+        Err(err) => sig_err(err),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::hex::FromHex;
+
+    use super::*;
+    use crate::{Algo, Chain, SsiSecret};
+
+    #[test]
+    fn verify() {
+        let secret = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let ssi = Ssi::new(bset![], None, &secret).to_string();
+        assert_eq!(unsafe { ssi_verify(ssi.as_ptr(), ssi.len()) }, SSI_OK);
+        assert_eq!(unsafe { ssi_verify(ptr::null(), 0) }, SSI_ERR_NULL);
+        assert_eq!(unsafe { ssi_verify(ssi.as_ptr(), 10) }, SSI_ERR_PARSE);
+
+        let pk = secret.to_public().to_string();
+        let mut handle = ptr::null_mut();
+        assert_eq!(unsafe { ssi_pub_from_str(pk.as_ptr(), pk.len(), &mut handle) }, SSI_OK);
+        let msg = [0xA5u8; 32];
+        let sig = secret.sign(msg).to_byte_array();
+        assert_eq!(unsafe { ssi_sig_verify(handle, msg.as_ptr(), sig.as_ptr()) }, SSI_OK);
+        let msg = [0x5Au8; 32];
+        assert_eq!(
+            unsafe { ssi_sig_verify(handle, msg.as_ptr(), sig.as_ptr()) },
+            SSI_ERR_INVALID_SIG
+        );
+        unsafe { ssi_pub_free(handle) };
+    }
+
+    #[test]
+    fn fixed_vectors() {
+        // Same vectors as in `tests/ffi/smoke.c`: the key is from RFC 8032 test 1
+        let pk = "ssi:4xVNMfW1-Wh4AjzL-B~jF9P0-Sw3k5oy-zgc8oMl-0XfezmT-H6W";
+        let ssi = format!(
+            "{pk}?sig=UrDbWenDqG2DFBok95L84SZBkqdMbzyCT52_yu5nEWx3woJkg\
+             zQwNYw4VrY9wWbM3i4WNuZlEaPzN8bXsVlY0W"
+        );
+        assert_eq!(unsafe { ssi_verify(ssi.as_ptr(), ssi.len()) }, SSI_OK);
+        assert_eq!(unsafe { ssi_verify(pk.as_ptr(), pk.len()) }, SSI_ERR_UNSIGNED);
+
+        let mut handle = ptr::null_mut();
+        assert_eq!(unsafe { ssi_pub_from_str(pk.as_ptr(), pk.len(), &mut handle) }, SSI_OK);
+        let key =
+            Vec::<u8>::from_hex("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a")
+                .unwrap();
+        assert_eq!(unsafe { &*handle }.0.to_byte_array().to_vec(), key);
+        let msg = [0xA5u8; 32];
+        let sig = Vec::<u8>::from_hex(
+            "cab8b876599d1487ee5ff9fdefb0168da666e8d613cc84128dd3e2a493e56e64\
+             d58d869cc8b5eab5c1905d23d3f2f141294be508a760c308d1a540b5eefebc02",
+        )
+        .unwrap();
+        assert_eq!(unsafe { ssi_sig_verify(handle, msg.as_ptr(), sig.as_ptr()) }, SSI_OK);
+        unsafe { ssi_pub_free(handle) };
+    }
+}
@@ -19,19 +19,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{self, Display, Formatter};
+use std::ops::{BitOr, Range};
 use std::str::{FromStr, Utf8Error};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use baid64::Baid64ParseError;
-use chrono::{DateTime, Utc};
+use amplify::hex::{FromHex, ToHex};
+use baid64::{Baid64ParseError, DisplayBaid64};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
 use fluent_uri::Uri;
-use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use percent_encoding::{
+    percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS, NON_ALPHANUMERIC,
+};
 use sha2::{Digest, Sha256};
 
-use crate::{InvalidSig, SsiPub, SsiSecret, SsiSig};
+use crate::{
+    Baid64Error, Baid64ErrorKind, Fingerprint, InvalidSig, SignerError, SsiPub, SsiSecret, SsiSig,
+    VerifyError,
+};
 
-#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[derive(Clone, Eq, PartialEq, Debug, Display, From)]
 #[display(doc_comments)]
 pub enum UidParseError {
     #[from]
@@ -41,192 +49,3684 @@ pub enum UidParseError {
     NoId(String),
     /// UID '{0}' without identity schema
     NoSchema(String),
+    /// UID comment '{0}' must not contain parentheses or angle brackets
+    InvalidComment(String),
 }
 
-#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
-#[display("{name} <{schema}:{id}>", alt = "{name} {schema}:{id}")]
+impl std::error::Error for UidParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UidParseError::Utf8(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
 pub struct Uid {
+    /// Human-readable name, which may be empty.
     pub name: String,
+    /// Comment following the name in parentheses, like in OpenPGP user IDs
+    /// (`Alice (work) <mailto:alice@example.com>`).
+    pub comment: Option<String>,
     pub schema: String,
     pub id: String,
+    /// Whether the UID is the primary one (see [`Ssi::primary_uid`]). Only
+    /// a single UID of an identity may be primary.
+    pub primary: bool,
 }
 
 impl Uid {
     pub fn from_url_str(s: &str) -> Result<Self, UidParseError> {
-        let s = percent_decode_str(s).decode_utf8()?.replace('+', " ");
+        // `+` must be replaced before decoding, since `%2B` stands for a literal plus sign
+        let s = percent_decode_str(&s.replace('+', " ")).decode_utf8()?.into_owned();
         Self::parse_str(&s)
     }
 
+    /// Parses UID in `name (comment) schema:id` form, where the name, the
+    /// comment and the space separator before `schema:id` may be omitted.
     fn parse_str(s: &str) -> Result<Self, UidParseError> {
-        let (name, rest) = s
-            .rsplit_once(' ')
-            .ok_or_else(|| UidParseError::NoId(s.to_string()))?;
+        let (name, rest) = s.rsplit_once(' ').unwrap_or(("", s));
+        if rest.is_empty() {
+            return Err(UidParseError::NoId(s.to_string()));
+        }
         let (schema, id) = rest
             .split_once(':')
             .ok_or_else(|| UidParseError::NoSchema(rest.to_owned()))?;
+        let (name, comment) = split_comment(name)?;
         Ok(Self {
             name: name.to_owned(),
+            comment: comment.map(str::to_owned),
             schema: schema.to_owned(),
             id: id.to_owned(),
+            primary: false,
         })
     }
+
+    /// Name followed by the comment in parentheses, if there is a comment.
+    pub fn display_name(&self) -> String {
+        match (&self.comment, self.name.is_empty()) {
+            (None, _) => self.name.clone(),
+            (Some(comment), true) => format!("({comment})"),
+            (Some(comment), false) => format!("{} ({comment})", self.name),
+        }
+    }
+}
+
+/// Splits trailing parenthesized comment from a UID name. Parentheses not
+/// separated from the rest of the name with a space are kept in the name.
+fn split_comment(name: &str) -> Result<(&str, Option<&str>), UidParseError> {
+    let Some(inner) = name.strip_suffix(')') else {
+        return Ok((name, None));
+    };
+    let Some(open) = inner.find('(') else {
+        return Ok((name, None));
+    };
+    let name = match &inner[..open] {
+        "" => "",
+        prefix => match prefix.strip_suffix(' ') {
+            Some(name) => name,
+            None => return Ok((name, None)),
+        },
+    };
+    let comment = &inner[open + 1..];
+    if comment.contains(['(', ')', '<', '>']) {
+        return Err(UidParseError::InvalidComment(comment.to_owned()));
+    }
+    Ok((name, Some(comment)))
+}
+
+/// UID is displayed as `name (comment) <schema:id>`, or in the alternative
+/// form (`{:#}`) as `name (comment) schema:id`. UIDs without a name and a
+/// comment are displayed as `schema:id` in both forms.
+impl Display for Uid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = self.display_name();
+        if name.is_empty() {
+            write!(f, "{}:{}", self.schema, self.id)
+        } else if f.alternate() {
+            write!(f, "{name} {}:{}", self.schema, self.id)
+        } else {
+            write!(f, "{name} <{}:{}>", self.schema, self.id)
+        }
+    }
 }
 
 impl FromStr for Uid {
     type Err = UidParseError;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::parse_str(&s.replace(['<', '>'], "")) }
+    /// Parses UID in both display forms, stripping angle brackets around
+    /// `schema:id`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_suffix('>').and_then(|s| s.rsplit_once('<')) {
+            Some((name, id)) => Self::parse_str(&format!("{name}{id}")),
+            None => Self::parse_str(s),
+        }
+    }
 }
 
-#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
-pub struct Ssi {
-    pub pk: SsiPub,
-    pub uids: BTreeSet<Uid>,
-    pub expiry: Option<DateTime<Utc>>,
-    pub sig: Option<SsiSig>,
+/// Changes between two versions of the same identity, produced by
+/// [`Ssi::diff`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct SsiDiff {
+    pub added_uids: BTreeSet<Uid>,
+    pub removed_uids: BTreeSet<Uid>,
+    /// Previous and new expiry, if the expiry has changed.
+    #[allow(clippy::type_complexity)]
+    pub expiry_change: Option<(Option<DateTime<Utc>>, Option<DateTime<Utc>>)>,
+    pub sig_changed: bool,
 }
 
-impl Ssi {
-    pub fn new(uids: BTreeSet<Uid>, expiry: Option<DateTime<Utc>>, secret: &SsiSecret) -> Self {
-        let mut me = Self {
-            pk: secret.to_public(),
-            uids,
-            expiry,
-            sig: None,
-        };
-        me.sig = Some(secret.sign(me.to_message()));
-        me
+impl SsiDiff {
+    /// Detects whether there are no changes between the identities.
+    pub fn is_empty(&self) -> bool {
+        self.added_uids.is_empty() &&
+            self.removed_uids.is_empty() &&
+            self.expiry_change.is_none() &&
+            !self.sig_changed
     }
+}
 
-    pub fn to_message(&self) -> [u8; 32] {
-        let s = self.to_string();
-        let (mut s, _) = s.rsplit_once("sig=").unwrap_or_else(|| (s.as_str(), ""));
-        s = s.trim_end_matches(&['&', '?']);
-        let msg = Sha256::digest(s);
-        Sha256::digest(msg).into()
+/// Parses identity expiry date, which is either a plain date in `YYYY-MM-DD`
+/// format, meaning the end of that day in UTC, or an RFC 3339 timestamp.
+pub fn parse_expiry(s: &str) -> Result<DateTime<Utc>, chrono::ParseError> {
+    match NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        Ok(date) => Ok(end_of_day(date)),
+        Err(_) => Ok(DateTime::parse_from_rfc3339(s)?.to_utc()),
     }
+}
 
-    pub fn check_integrity(&self) -> Result<bool, InvalidSig> {
-        match self.sig {
-            Some(sig) => {
-                self.pk.verify(self.to_message(), sig)?;
-                Ok(true)
-            }
-            None => Ok(false),
+fn check_comment(comment: &str) -> Result<(), CommentError> {
+    if comment.len() > SSI_COMMENT_MAX_LEN {
+        return Err(CommentError::TooLong(comment.len()));
+    }
+    if comment.chars().any(char::is_control) {
+        return Err(CommentError::ControlChars);
+    }
+    Ok(())
+}
+
+pub(crate) fn end_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(23, 59, 59).expect("valid time").and_utc()
+}
+
+/// Formats identity expiry date in a form accepted by [`parse_expiry`]: as a
+/// plain date if it is the end of the day, and as RFC 3339 timestamp
+/// otherwise.
+fn format_expiry(expiry: DateTime<Utc>) -> String {
+    if expiry == end_of_day(expiry.date_naive()) {
+        expiry.format("%Y-%m-%d").to_string()
+    } else {
+        expiry.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+    }
+}
+
+/// Validity of an identity at some moment of time (see [`Ssi::validity`]).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(doc_comments)]
+pub enum Validity {
+    /// valid.
+    Valid,
+    /// expired since {since}.
+    Expired { since: DateTime<Utc> },
+    /// not signed.
+    NotSigned,
+    /// invalid signature - {0}
+    BadSignature(InvalidSig),
+}
+
+impl Validity {
+    pub fn is_valid(self) -> bool { self == Validity::Valid }
+}
+
+/// Status of the identity self-signature and certifications of its subkeys
+/// (see [`Ssi::integrity`]).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(doc_comments)]
+pub enum IntegrityStatus {
+    /// signed by the identity key.
+    SelfSigned,
+    /// not signed.
+    Unsigned,
+    /// invalid signature - {0}
+    SignatureInvalid(InvalidSig),
+    /// signed with a key of a different algorithm than the identity key.
+    SignerMismatch,
+}
+
+impl IntegrityStatus {
+    pub fn is_self_signed(self) -> bool { self == IntegrityStatus::SelfSigned }
+
+    /// Detects whether the identity carries a signature which doesn't verify.
+    /// Unsigned identities are not invalid.
+    pub fn is_invalid(self) -> bool {
+        matches!(self, IntegrityStatus::SignatureInvalid(_) | IntegrityStatus::SignerMismatch)
+    }
+
+    /// Converts the status into the form returned by the deprecated
+    /// [`Ssi::check_integrity`].
+    pub fn into_result(self) -> Result<bool, InvalidSig> {
+        match self {
+            IntegrityStatus::SelfSigned => Ok(true),
+            IntegrityStatus::Unsigned => Ok(false),
+            IntegrityStatus::SignatureInvalid(err) => Err(err),
+            IntegrityStatus::SignerMismatch => Err(InvalidSig::AlgoMismatch),
         }
     }
 }
 
-#[derive(Debug, Display, Error, From)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
 #[display(doc_comments)]
-pub enum SsiParseError {
-    #[from]
-    #[display(inner)]
-    InvalidUri(fluent_uri::ParseError),
-    /// SSI must be a valid URI containing schema part.
-    NoUriScheme,
-    /// SSI must start with 'ssi:' prefix (URI scheme).
-    InvalidScheme(String),
-    /// SSI contains invalid attribute '{0}'.
-    InvalidQueryParam(String),
-    /// SSI contains unknown attribute '{0}'.
-    UnknownParam(String),
-    /// SSI contains multiple expiration dates.
-    RepeatedExpiry,
-    /// SSI contains multiple signatures.
-    RepeatedSig,
+pub enum UidEditError {
+    /// the secret key doesn't match the identity.
+    KeyMismatch,
+    /// identity already contains UID '{0}'.
+    Duplicate(Uid),
+    /// identity doesn't contain UID '{0}'.
+    Unknown(Uid),
+    /// identity already has a primary UID.
+    RepeatedPrimary,
+    /// identity must retain at least one UID.
+    LastUid,
+}
 
-    #[from]
-    /// SSI contains {0}
-    InvalidUid(UidParseError),
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum RenewError {
+    /// the secret key doesn't match the identity.
+    KeyMismatch,
+    /// the new expiry date precedes the current one; shortening identity
+    /// validity requires the force flag.
+    ShortenedExpiry,
+    /// the identity has no expiry date to renew.
+    NoExpiry,
+}
 
+/// Reference to the successor key of a rotated identity.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, From)]
+pub enum KeyLink {
     #[from]
-    /// SSI contains signature not matching the provided data - {0}
-    WrongSig(InvalidSig),
-
+    Pub(SsiPub),
     #[from]
-    /// SSI contains non-parsable expiration date - {0}
-    WrongExpiry(chrono::ParseError),
+    Fp(Fingerprint),
+}
 
-    /// SSI contains non-parsable public key - {0}
-    InvalidPub(Baid64ParseError),
-    /// SSI contains non-parsable signature - {0}
-    InvalidSig(Baid64ParseError),
+impl KeyLink {
+    pub fn fingerprint(&self) -> Fingerprint {
+        match self {
+            KeyLink::Pub(pk) => pk.fingerprint(),
+            KeyLink::Fp(fp) => *fp,
+        }
+    }
+
+    /// Checks whether the link points to the key. Links containing just the
+    /// fingerprint match any key with the same fingerprint.
+    pub fn matches(&self, pk: &SsiPub) -> bool {
+        match self {
+            KeyLink::Pub(link) => link == pk,
+            KeyLink::Fp(fp) => *fp == pk.fingerprint(),
+        }
+    }
 }
 
-impl FromStr for Ssi {
-    type Err = SsiParseError;
+/// Displays the key without `ssi:` prefix, as it is used inside SSI URIs.
+impl Display for KeyLink {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyLink::Pub(pk) => f.write_str(&pk.to_uri_string()["ssi:".len()..]),
+            KeyLink::Fp(fp) => write!(f, "{fp}"),
+        }
+    }
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let uri = Uri::parse(s)?;
+impl FromStr for KeyLink {
+    type Err = Baid64Error;
 
-        let scheme = uri.scheme().ok_or(SsiParseError::NoUriScheme)?;
-        if scheme.as_str() != "ssi" {
-            return Err(SsiParseError::InvalidScheme(scheme.to_string()));
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.len() {
+            8 => Ok(KeyLink::Fp(Fingerprint::from_str(s)?)),
+            _ => SsiPub::from_str(s).map(KeyLink::Pub),
         }
+    }
+}
 
-        let pk = uri.path().as_str();
-        let pk = SsiPub::from_str(pk).map_err(SsiParseError::InvalidPub)?;
+#[derive(Clone, Eq, PartialEq, Debug, Display, From)]
+#[display(doc_comments)]
+pub enum RotationError {
+    /// the predecessor identity has no valid self-signature.
+    BrokenOld,
+    /// the successor identity has no valid self-signature.
+    BrokenNew,
+    /// the predecessor identity doesn't link to the successor key.
+    NoForwardLink,
+    /// the successor identity doesn't link back to the predecessor key.
+    NoBackwardLink,
 
-        let query = uri.query().unwrap_or_default().as_str();
+    #[from]
+    /// the predecessor key signature in the successor identity is invalid - {0}
+    InvalidLink(InvalidSig),
+}
 
-        let mut expiry = None;
-        let mut sig = None;
-        let mut uids = bset![];
-        for p in query.split('&') {
-            let (k, v) = p
-                .split_once('=')
-                .ok_or_else(|| SsiParseError::InvalidQueryParam(p.to_owned()))?;
-            match k {
-                "expiry" if expiry.is_none() => {
-                    expiry = Some(DateTime::parse_from_str(v, "%Y-%m-%d")?.to_utc())
-                }
-                "expiry" => return Err(SsiParseError::RepeatedExpiry),
-                "uid" => {
-                    uids.insert(Uid::from_url_str(v)?);
-                }
-                "sig" if sig.is_none() => {
-                    sig = Some(SsiSig::from_str(v).map_err(SsiParseError::InvalidSig)?)
-                }
-                "sig" => return Err(SsiParseError::RepeatedSig),
-                other => return Err(SsiParseError::UnknownParam(other.to_owned())),
-            }
+impl std::error::Error for RotationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RotationError::InvalidLink(err) => Some(err),
+            _ => None,
         }
+    }
+}
 
-        let ssi = Self {
-            pk,
-            uids,
-            expiry,
-            sig,
-        };
-        ssi.check_integrity()?;
+/// Computes the message signed by an identity with the given key, UIDs and
+/// expiry, and no other data (no comment, usage restrictions, services etc.),
+/// using [`Commitment::Binary`]. Equals to [`Ssi::to_message`] of such
+/// identity.
+pub fn identity_message_hash(
+    pk: SsiPub,
+    uids: &BTreeSet<Uid>,
+    expiry: Option<DateTime<Utc>>,
+) -> [u8; 32] {
+    Ssi {
+        pk,
+        uids: uids.clone(),
+        expiry,
+        comment: None,
+        usage: KeyUsage::ALL,
+        services: bmap![],
+        avatar: None,
+        subkeys: vec![],
+        next: None,
+        prev: None,
+        pow: None,
+        extras: bmap![],
+        commitment: Commitment::Binary,
+        sig: None,
+        endorsements: vec![],
+    }
+    .to_message()
+}
 
-        Ok(ssi)
+/// Tag of the hash signed by the primary key to certify a subkey (see
+/// [`Ssi::add_subkey`]).
+pub const SSI_SUBKEY_TAG: &str = "urn:ubideco:ssi:subkey:v1";
+
+/// Maximal number of subkeys of an identity.
+pub const SSI_SUBKEYS_MAX: usize = 16;
+
+/// Subkey of an identity, like a per-device key, with its certification by
+/// the identity primary key. Displayed as `subkey=<key>:<signature>` URI
+/// query parameter.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct SignedSubkey {
+    pub pk: SsiPub,
+    /// Signature of the primary key over [`SignedSubkey::message`].
+    pub sig: SsiSig,
+}
+
+impl SignedSubkey {
+    /// Message binding the subkey to the `primary` key.
+    pub fn message(primary: SsiPub, sub: SsiPub) -> [u8; 32] {
+        let tag = Sha256::digest(SSI_SUBKEY_TAG);
+        let mut engine = Sha256::new();
+        engine.update(tag);
+        engine.update(tag);
+        engine.update(<[u8; 34]>::from(primary));
+        engine.update(<[u8; 34]>::from(sub));
+        engine.finalize().into()
+    }
+
+    /// Verifies the subkey certification by the `primary` key.
+    pub fn verify(&self, primary: SsiPub) -> Result<(), InvalidSig> {
+        primary.verify(Self::message(primary, self.pk), self.sig)
     }
 }
 
-impl Display for Ssi {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        const SET: &AsciiSet = &CONTROLS.add(b'?').add(b'&').add(b'+').add(b'=');
+#[derive(Clone, Eq, PartialEq, Debug, Display, From)]
+#[display(doc_comments)]
+pub enum SubkeyError {
+    /// the secret key doesn't match the identity.
+    KeyMismatch,
+    /// identity primary key can't be its own subkey.
+    Primary,
+    /// identity already contains subkey {0}.
+    Duplicate(SsiPub),
+    /// Identity has more than [`SSI_SUBKEYS_MAX`] subkeys.
+    #[display("identity can't have more than {SSI_SUBKEYS_MAX} subkeys.")]
+    TooMany,
+    /// {0} is not a subkey of the identity.
+    Unknown(SsiPub),
 
-        let mut sep = '?';
-        write!(f, "{}", self.pk)?;
+    #[from]
+    /// subkey certification by the primary key is invalid - {0}
+    InvalidBinding(InvalidSig),
+}
 
-        for uid in &self.uids {
-            let uid = uid.to_string().replace(['<', '>'], "");
-            write!(f, "{sep}uid={}", utf8_percent_encode(&uid, SET).to_string().replace(' ', "+"),)?;
-            sep = '&';
+impl std::error::Error for SubkeyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SubkeyError::InvalidBinding(err) => Some(err),
+            _ => None,
         }
+    }
+}
 
-        if let Some(expiry) = self.expiry {
-            write!(f, "{sep}expiry={}&", expiry.format("%Y-%m-%d"))?;
-            sep = '&';
+/// Verifies that `new` identity is a result of a key rotation from the `old`
+/// one (see [`Ssi::rotate_to`]): both identities must be validly self-signed,
+/// the old one must link forward to the new key, and the new one must link
+/// back to the old key with a signature by the old key.
+pub fn verify_rotation(old: &Ssi, new: &Ssi) -> Result<(), RotationError> {
+    if !old.integrity().is_self_signed() {
+        return Err(RotationError::BrokenOld);
+    }
+    if !new.integrity().is_self_signed() {
+        return Err(RotationError::BrokenNew);
+    }
+    if !old.next.is_some_and(|next| next.matches(&new.pk)) {
+        return Err(RotationError::NoForwardLink);
+    }
+    let Some((prev, sig)) = new.prev.filter(|(prev, _)| *prev == old.pk) else {
+        return Err(RotationError::NoBackwardLink);
+    };
+    prev.verify(new.to_message(), sig)?;
+    Ok(())
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display("identities with different keys {0} and {1} can't be compared")]
+pub struct DiffKeyMismatch(pub SsiPub, pub SsiPub);
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum MergeError {
+    /// identities with different keys {0} and {1} can't be merged.
+    KeyMismatch(SsiPub, SsiPub),
+    /// the secret key doesn't match the identity.
+    SecretMismatch,
+}
+
+/// Maximal length of an identity comment, in bytes.
+pub const SSI_COMMENT_MAX_LEN: usize = 256;
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum CommentError {
+    /// Comment exceeds [`SSI_COMMENT_MAX_LEN`] bytes.
+    #[display("comment is {0} bytes long, exceeding the limit of {SSI_COMMENT_MAX_LEN} bytes.")]
+    TooLong(usize),
+    /// comment must not contain control characters.
+    ControlChars,
+}
+
+/// Number of attempts after which [`Ssi::mine_pow`] checks for cancellation.
+pub const SSI_POW_CANCEL_INTERVAL: u64 = 0x1000;
+
+fn leading_zero_bits(digest: [u8; 32]) -> u32 {
+    let mut bits = 0;
+    for byte in digest {
+        bits += byte.leading_zeros();
+        if byte != 0 {
+            break;
         }
+    }
+    bits
+}
 
-        if let Some(sig) = self.sig {
-            write!(f, "{sep}sig={}", sig)?;
+/// Maximal number of service endpoints in an identity, keeping identities
+/// small enough to fit into a QR code.
+pub const SSI_SERVICES_MAX: usize = 8;
+/// Maximal length of a service endpoint URI, in bytes.
+pub const SSI_SERVICE_URI_MAX_LEN: usize = 256;
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum ServiceError {
+    /// service type '{0}' must be non-empty and consist of ASCII letters,
+    /// digits, '-', '_' and '.'.
+    InvalidType(String),
+    /// service endpoint '{0}' is not a valid absolute URI.
+    InvalidUri(String),
+    /// Service endpoint URI exceeds [`SSI_SERVICE_URI_MAX_LEN`] bytes.
+    #[display(
+        "service endpoint URI is {0} bytes long, exceeding the limit of {SSI_SERVICE_URI_MAX_LEN} \
+         bytes."
+    )]
+    TooLong(usize),
+    /// Identity has more than [`SSI_SERVICES_MAX`] service endpoints.
+    #[display("identity can't advertise more than {SSI_SERVICES_MAX} service endpoints.")]
+    TooMany,
+}
+
+/// Avatar image the identity commits to by its SHA256 hash, without
+/// embedding the image (see [`Ssi::set_avatar`]). Displayed as
+/// `img=<hash-hex>[@<url>]` URI query parameter.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Avatar {
+    pub hash: [u8; 32],
+    /// Location of the image. The location is a hint not covered by the
+    /// signature, such that the image may be moved to another hosting
+    /// without re-signing the identity.
+    pub url: Option<ServiceUri>,
+}
+
+/// Absolute URI of a service endpoint advertised by an identity, like a
+/// DIDComm endpoint, a keyserver or a Nostr relay.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Display)]
+#[display("{0}")]
+pub struct ServiceUri(String);
+
+impl ServiceUri {
+    pub fn as_str(&self) -> &str { &self.0 }
+}
+
+impl FromStr for ServiceUri {
+    type Err = ServiceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() > SSI_SERVICE_URI_MAX_LEN {
+            return Err(ServiceError::TooLong(s.len()));
         }
+        match Uri::parse(s) {
+            Ok(uri) if uri.scheme().is_some() => Ok(ServiceUri(s.to_owned())),
+            _ => Err(ServiceError::InvalidUri(s.to_owned())),
+        }
+    }
+}
+
+fn check_service_type(kind: &str) -> Result<(), ServiceError> {
+    if kind.is_empty() ||
+        !kind
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(ServiceError::InvalidType(kind.to_owned()));
+    }
+    Ok(())
+}
+
+/// Usages the identity key is authorized for. Identities without explicit
+/// usage restrictions may be used for everything ([`KeyUsage::ALL`]).
+///
+/// Displayed as a comma-separated list of usage names (`sign`, `encrypt`).
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct KeyUsage(u8);
+
+impl KeyUsage {
+    /// Signing messages and certifying other identities.
+    pub const SIGN: KeyUsage = KeyUsage(0x01);
+    /// Key agreement for encryption.
+    pub const ENCRYPT: KeyUsage = KeyUsage(0x02);
+    pub const ALL: KeyUsage = KeyUsage(0x03);
+
+    pub const fn bits(self) -> u8 { self.0 }
+
+    pub const fn contains(self, other: KeyUsage) -> bool { self.0 & other.0 == other.0 }
 
+    pub const fn is_all(self) -> bool { self.0 == Self::ALL.0 }
+}
+
+impl Default for KeyUsage {
+    fn default() -> Self { KeyUsage::ALL }
+}
+
+impl BitOr for KeyUsage {
+    type Output = KeyUsage;
+
+    fn bitor(self, rhs: Self) -> Self::Output { KeyUsage(self.0 | rhs.0) }
+}
+
+impl Display for KeyUsage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let names = [(KeyUsage::SIGN, "sign"), (KeyUsage::ENCRYPT, "encrypt")]
+            .into_iter()
+            .filter(|(usage, _)| self.contains(*usage))
+            .map(|(_, name)| name);
+        for (no, name) in names.enumerate() {
+            if no > 0 {
+                f.write_str(",")?;
+            }
+            f.write_str(name)?;
+        }
         Ok(())
     }
 }
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display("unknown key usage '{0}'")]
+pub struct UnknownKeyUsage(pub String);
+
+impl FromStr for KeyUsage {
+    type Err = UnknownKeyUsage;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut usage = KeyUsage(0);
+        for name in s.split(',') {
+            usage = usage |
+                match name {
+                    "sign" => KeyUsage::SIGN,
+                    "encrypt" => KeyUsage::ENCRYPT,
+                    _ => return Err(UnknownKeyUsage(name.to_owned())),
+                };
+        }
+        Ok(usage)
+    }
+}
+
+/// Tag of the hash used by [`Commitment::Binary`].
+pub const SSI_COMMITMENT_TAG: &str = "urn:ubideco:ssi:identity:v2";
+
+/// Method of committing to the identity data (key, UIDs and expiry) in the
+/// self-signature and endorsements. Displayed as `v` parameter of the
+/// identity URI; identities without it use the legacy commitment.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Display)]
+pub enum Commitment {
+    /// Double SHA256 hash of the identity URI text without signatures.
+    #[display("1")]
+    Legacy,
+
+    /// Hash tagged with [`SSI_COMMITMENT_TAG`] of a deterministic binary
+    /// serialization of the identity data, which doesn't depend on the text
+    /// formatting.
+    #[default]
+    #[display("2")]
+    Binary,
+}
+
+/// Self-sovereign identity.
+///
+/// Derived `Eq`, `Ord` and `Hash` cover all fields, including the signature
+/// and endorsements. Since signatures may be randomized, the same identity
+/// re-signed with the same key becomes a different value; use
+/// [`Ssi::content_hash`] or [`Ssi::same_identity`] to detect such copies.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Ssi {
+    pub pk: SsiPub,
+    pub uids: BTreeSet<Uid>,
+    pub expiry: Option<DateTime<Utc>>,
+    /// Free-text note about the identity, like "work key, laptop" (see
+    /// [`Ssi::set_comment`]).
+    pub comment: Option<String>,
+    /// Usages the key is authorized for (see [`Ssi::can_sign`] and
+    /// [`Ssi::can_encrypt`]).
+    pub usage: KeyUsage,
+    /// Service endpoints grouped by the service type, like `didcomm` or
+    /// `keyserver` (see [`Ssi::add_service`]).
+    pub services: BTreeMap<String, Vec<ServiceUri>>,
+    /// Avatar image the identity commits to (see [`Ssi::set_avatar`]).
+    pub avatar: Option<Avatar>,
+    /// Subkeys certified by the identity key (see [`Ssi::add_subkey`]).
+    pub subkeys: Vec<SignedSubkey>,
+    /// Key the identity was rotated to (see [`Ssi::rotate_to`]).
+    pub next: Option<KeyLink>,
+    /// Key the identity was rotated from, with its signature over the
+    /// identity message.
+    pub prev: Option<(SsiPub, SsiSig)>,
+    /// Proof-of-work nonce making mass creation of identities costly (see
+    /// [`Ssi::mine_pow`]).
+    pub pow: Option<u64>,
+    /// Parameters unknown to this version, preserved by [`ParseMode::Lenient`]
+    /// parsing with their values kept in the URI-encoded form. They are
+    /// covered by the signature and are serialized in their sorted order.
+    pub extras: BTreeMap<String, String>,
+    pub commitment: Commitment,
+    pub sig: Option<SsiSig>,
+    /// Signatures of third parties over the identity (see
+    /// [`Ssi::verify_endorsement`]). Methods changing the signed data, such as
+    /// [`Ssi::set_expiry`], remove them.
+    pub endorsements: Vec<(Fingerprint, SsiSig)>,
+}
+
+/// Builder of new identities, which allows to give the expiry either as an
+/// absolute date or relative to the current time.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct SsiBuilder {
+    uids: BTreeSet<Uid>,
+    expiry: Option<DateTime<Utc>>,
+}
+
+impl SsiBuilder {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn uid(mut self, uid: Uid) -> Self {
+        self.uids.insert(uid);
+        self
+    }
+
+    pub fn expiry(mut self, expiry: DateTime<Utc>) -> Self {
+        self.expiry = Some(expiry);
+        self
+    }
+
+    /// Sets the expiry to the current time plus `duration`. The time is
+    /// taken when this method is called, not when the identity is signed.
+    pub fn valid_for(self, duration: chrono::Duration) -> Self {
+        self.expiry(Utc::now() + duration)
+    }
+
+    /// Signs the identity, refusing to sign already expired ones (see
+    /// [`Ssi::try_new`]).
+    pub fn sign(self, secret: &SsiSecret) -> Result<Ssi, SignerError> {
+        Ssi::try_new(self.uids, self.expiry, secret, Utc::now())
+    }
+}
+
+impl Ssi {
+    /// Constructs and signs a new identity without checking its expiry date.
+    /// Prefer [`Ssi::try_new`], which refuses to sign already expired
+    /// identities.
+    pub fn new(uids: BTreeSet<Uid>, expiry: Option<DateTime<Utc>>, secret: &SsiSecret) -> Self {
+        Self::new_unchecked(uids, expiry, secret)
+    }
+
+    /// Constructs and signs a new identity, checking that its expiry date is
+    /// after `now`.
+    pub fn try_new(
+        uids: BTreeSet<Uid>,
+        expiry: Option<DateTime<Utc>>,
+        secret: &SsiSecret,
+        now: DateTime<Utc>,
+    ) -> Result<Self, SignerError> {
+        if let Some(expiry) = expiry.filter(|expiry| *expiry <= now) {
+            return Err(SignerError::Expired(expiry));
+        }
+        Ok(Self::new_unchecked(uids, expiry, secret))
+    }
+
+    /// Constructs and signs a new identity with an arbitrary expiry date, which
+    /// may be in the past. Useful for backfilling historical records.
+    pub fn new_unchecked(
+        uids: BTreeSet<Uid>,
+        expiry: Option<DateTime<Utc>>,
+        secret: &SsiSecret,
+    ) -> Self {
+        let mut me = Self {
+            pk: secret.to_public(),
+            uids,
+            expiry,
+            comment: None,
+            usage: KeyUsage::ALL,
+            services: bmap![],
+            avatar: None,
+            subkeys: vec![],
+            next: None,
+            prev: None,
+            pow: None,
+            extras: bmap![],
+            commitment: Commitment::Binary,
+            sig: None,
+            endorsements: vec![],
+        };
+        me.sig = Some(secret.sign(me.to_message()));
+        me
+    }
+
+    /// Returns a copy of the identity with the signature removed.
+    pub fn without_signature(&self) -> Ssi {
+        let mut ssi = self.clone();
+        ssi.sig = None;
+        ssi
+    }
+
+    /// Signs the identity in its current form with a fresh signature,
+    /// replacing the existing one. The commitment is kept, so endorsements
+    /// made over the same data remain valid.
+    ///
+    /// Errors with [`SignerError::KeyMismatch`] if the secret doesn't
+    /// correspond to the identity key; in this case the identity is left
+    /// unchanged.
+    pub fn resign(&mut self, secret: &SsiSecret) -> Result<(), SignerError> {
+        if secret.to_public() != self.pk {
+            return Err(SignerError::KeyMismatch);
+        }
+        self.sig = Some(secret.sign(self.to_message()));
+        Ok(())
+    }
+
+    /// Re-signs the identity after a change of the signed data, removing the
+    /// endorsements, which don't cover the new data. Identities using the
+    /// legacy commitment are upgraded to [`Commitment::Binary`].
+    fn resign_changed(&mut self, secret: &SsiSecret) -> Result<(), SignerError> {
+        if secret.to_public() != self.pk {
+            return Err(SignerError::KeyMismatch);
+        }
+        self.commitment = Commitment::Binary;
+        self.resign(secret)?;
+        self.endorsements.clear();
+        Ok(())
+    }
+
+    /// Adds UID to the identity, re-signing it with the `secret`.
+    ///
+    /// The identity is left unchanged on errors.
+    pub fn add_uid(&mut self, uid: Uid, secret: &SsiSecret) -> Result<(), UidEditError> {
+        if secret.to_public() != self.pk {
+            return Err(UidEditError::KeyMismatch);
+        }
+        if self.uids.contains(&uid) {
+            return Err(UidEditError::Duplicate(uid));
+        }
+        if uid.primary && self.uids.iter().any(|uid| uid.primary) {
+            return Err(UidEditError::RepeatedPrimary);
+        }
+        self.uids.insert(uid);
+        self.resign_changed(secret).expect("key is checked");
+        Ok(())
+    }
+
+    /// Removes UID from the identity, re-signing it with the `secret`. The
+    /// last UID of an identity can't be removed.
+    ///
+    /// The identity is left unchanged on errors.
+    pub fn remove_uid(&mut self, uid: &Uid, secret: &SsiSecret) -> Result<(), UidEditError> {
+        if secret.to_public() != self.pk {
+            return Err(UidEditError::KeyMismatch);
+        }
+        if !self.uids.contains(uid) {
+            return Err(UidEditError::Unknown(uid.clone()));
+        }
+        if self.uids.len() == 1 {
+            return Err(UidEditError::LastUid);
+        }
+        self.uids.remove(uid);
+        self.resign_changed(secret).expect("key is checked");
+        Ok(())
+    }
+
+    /// Moves the identity expiry date forward, or removes it entirely with
+    /// `None`, re-signing the identity with the `secret`. Already expired
+    /// identities may be renewed this way.
+    ///
+    /// Refuses to move the expiry backwards; use [`Ssi::set_expiry`] with the
+    /// `force` flag for that. The identity is left unchanged on errors.
+    pub fn extend_expiry(
+        &mut self,
+        new_expiry: Option<DateTime<Utc>>,
+        secret: &SsiSecret,
+    ) -> Result<(), RenewError> {
+        self.set_expiry(new_expiry, secret, false)
+    }
+
+    /// Sets the identity expiry date, re-signing the identity with the
+    /// `secret`. Unless `force` is set, the expiry can't be moved backwards.
+    ///
+    /// The identity is left unchanged on errors.
+    pub fn set_expiry(
+        &mut self,
+        new_expiry: Option<DateTime<Utc>>,
+        secret: &SsiSecret,
+        force: bool,
+    ) -> Result<(), RenewError> {
+        if secret.to_public() != self.pk {
+            return Err(RenewError::KeyMismatch);
+        }
+        let shortened = match (self.expiry, new_expiry) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(current), Some(new)) => new < current,
+        };
+        if shortened && !force {
+            return Err(RenewError::ShortenedExpiry);
+        }
+        self.expiry = new_expiry;
+        self.resign_changed(secret).expect("key is checked");
+        Ok(())
+    }
+
+    /// Returns a copy of the identity with the expiry moved forward by
+    /// `extend_by`, re-signed with the `secret`. Already expired identities
+    /// are renewed starting from the current time.
+    ///
+    /// Identities without expiry can't be renewed and result in
+    /// [`RenewError::NoExpiry`]; see [`Ssi::set_expiry`] for adding expiry.
+    pub fn renew(
+        &self,
+        secret: &SsiSecret,
+        extend_by: chrono::Duration,
+    ) -> Result<Ssi, RenewError> {
+        let Some(expiry) = self.expiry else {
+            return Err(RenewError::NoExpiry);
+        };
+        let mut renewed = self.clone();
+        renewed.set_expiry(Some(expiry.max(Utc::now()) + extend_by), secret, false)?;
+        Ok(renewed)
+    }
+
+    /// Rotates the identity to a new key, returning the old identity linked
+    /// forward to the new key and re-signed with the `secret`, and a new
+    /// identity with the same UIDs, expiry, comment and key usage, linked back
+    /// to the old key and signed by both keys.
+    ///
+    /// The resulting pair passes [`verify_rotation`].
+    pub fn rotate_to(
+        &self,
+        secret: &SsiSecret,
+        new_secret: &SsiSecret,
+    ) -> Result<(Ssi, Ssi), SignerError> {
+        let mut old = self.clone();
+        old.next = Some(KeyLink::Pub(new_secret.to_public()));
+        old.resign_changed(secret)?;
+
+        let mut new = Ssi {
+            pk: new_secret.to_public(),
+            uids: self.uids.clone(),
+            expiry: self.expiry,
+            comment: self.comment.clone(),
+            usage: self.usage,
+            services: self.services.clone(),
+            avatar: self.avatar.clone(),
+            // Subkeys are certified by the old key and must be certified anew
+            subkeys: vec![],
+            next: None,
+            prev: None,
+            pow: None,
+            extras: self.extras.clone(),
+            commitment: Commitment::Binary,
+            sig: None,
+            endorsements: vec![],
+        };
+        // The message doesn't depend on the predecessor signature, thus it can be computed with a
+        // placeholder
+        new.prev = Some((self.pk, SsiSig::from([0u8; 64])));
+        let msg = new.to_message();
+        new.prev = Some((self.pk, secret.sign(msg)));
+        new.sig = Some(new_secret.sign(msg));
+        Ok((old, new))
+    }
+
+    /// Checks the identity validity at the moment `at`. The identity is valid
+    /// strictly before its expiry: at the exact expiry moment it is already
+    /// [`Validity::Expired`]. Signature problems take precedence over the
+    /// expiry.
+    pub fn validity(&self, at: DateTime<Utc>) -> Validity {
+        match self.integrity().into_result() {
+            Ok(true) => {}
+            Ok(false) => return Validity::NotSigned,
+            Err(err) => return Validity::BadSignature(err),
+        }
+        match self.expiry {
+            Some(since) if since <= at => Validity::Expired { since },
+            _ => Validity::Valid,
+        }
+    }
+
+    /// Checks the identity validity at the current time (see
+    /// [`Ssi::validity`]).
+    pub fn validity_now(&self) -> Validity { self.validity(Utc::now()) }
+
+    /// Returns the number of whole days left until the identity expires, which
+    /// is negative for expired identities, or `None` if the identity never
+    /// expires.
+    pub fn days_until_expiry(&self, now: DateTime<Utc>) -> Option<i64> {
+        self.expiry.map(|expiry| (expiry - now).num_days())
+    }
+
+    /// Adds service endpoint of the given type, checking the type syntax and
+    /// that the identity doesn't exceed [`SSI_SERVICES_MAX`] endpoints.
+    ///
+    /// Service endpoints are covered by the signature, so the identity must be
+    /// re-signed afterwards (see [`Ssi::resign`]).
+    pub fn add_service(&mut self, kind: &str, uri: ServiceUri) -> Result<(), ServiceError> {
+        check_service_type(kind)?;
+        if self.services_count() >= SSI_SERVICES_MAX {
+            return Err(ServiceError::TooMany);
+        }
+        self.services.entry(kind.to_owned()).or_default().push(uri);
+        Ok(())
+    }
+
+    fn services_count(&self) -> usize { self.services.values().map(Vec::len).sum() }
+
+    /// Sets the avatar image by its SHA256 `hash` with an optional location
+    /// hint, re-signing the identity with the `secret`.
+    ///
+    /// The identity is left unchanged on errors.
+    pub fn set_avatar(
+        &mut self,
+        hash: [u8; 32],
+        url: Option<ServiceUri>,
+        secret: &SsiSecret,
+    ) -> Result<(), SignerError> {
+        if secret.to_public() != self.pk {
+            return Err(SignerError::KeyMismatch);
+        }
+        self.avatar = Some(Avatar { hash, url });
+        self.resign_changed(secret)
+    }
+
+    /// Adds a subkey certified by the `primary` identity key, re-signing the
+    /// identity. A subkey may be revoked (see [`crate::RevocationSet`])
+    /// without rotating the identity key.
+    ///
+    /// The identity is left unchanged on errors.
+    pub fn add_subkey(&mut self, sub: SsiPub, primary: &SsiSecret) -> Result<(), SubkeyError> {
+        if primary.to_public() != self.pk {
+            return Err(SubkeyError::KeyMismatch);
+        }
+        if sub == self.pk {
+            return Err(SubkeyError::Primary);
+        }
+        if self.subkeys.iter().any(|subkey| subkey.pk == sub) {
+            return Err(SubkeyError::Duplicate(sub));
+        }
+        if self.subkeys.len() >= SSI_SUBKEYS_MAX {
+            return Err(SubkeyError::TooMany);
+        }
+        let sig = primary.sign(SignedSubkey::message(self.pk, sub));
+        self.subkeys.push(SignedSubkey { pk: sub, sig });
+        self.resign_changed(primary).expect("key is checked");
+        Ok(())
+    }
+
+    /// Removes the subkey, re-signing the identity with the `primary` key.
+    ///
+    /// The identity is left unchanged on errors.
+    pub fn remove_subkey(&mut self, sub: &SsiPub, primary: &SsiSecret) -> Result<(), SubkeyError> {
+        if primary.to_public() != self.pk {
+            return Err(SubkeyError::KeyMismatch);
+        }
+        let Some(pos) = self.subkeys.iter().position(|subkey| subkey.pk == *sub) else {
+            return Err(SubkeyError::Unknown(*sub));
+        };
+        self.subkeys.remove(pos);
+        self.resign_changed(primary).expect("key is checked");
+        Ok(())
+    }
+
+    /// Verifies that the key is a subkey of the identity certified by the
+    /// identity key.
+    pub fn verify_subkey(&self, sub: &SsiPub) -> Result<(), SubkeyError> {
+        let subkey = self
+            .subkeys
+            .iter()
+            .find(|subkey| subkey.pk == *sub)
+            .ok_or(SubkeyError::Unknown(*sub))?;
+        Ok(subkey.verify(self.pk)?)
+    }
+
+    /// Checks whether the image data match the identity avatar hash. Returns
+    /// `false` for identities without avatar.
+    pub fn verify_avatar(&self, image: impl AsRef<[u8]>) -> bool {
+        let hash = <[u8; 32]>::from(Sha256::digest(image));
+        self.avatar.as_ref().is_some_and(|avatar| avatar.hash == hash)
+    }
+
+    /// Copy of the identity without the signature and the avatar location,
+    /// which is not covered by the signature.
+    fn without_avatar_url(&self) -> Ssi {
+        let mut ssi = self.without_signature();
+        if let Some(avatar) = &mut ssi.avatar {
+            avatar.url = None;
+        }
+        ssi
+    }
+
+    /// Searches for a proof-of-work nonce giving the identity at least
+    /// `difficulty_bits` of [`Ssi::pow_difficulty`], checking the `cancel`
+    /// flag every [`SSI_POW_CANCEL_INTERVAL`] attempts.
+    ///
+    /// Returns the nonce, which is also set to the identity, or `None` if the
+    /// search was cancelled, leaving the identity unchanged. The nonce is
+    /// covered by the signature, so the identity must be re-signed afterwards
+    /// (see [`Ssi::resign`]).
+    pub fn mine_pow(&mut self, difficulty_bits: u32, cancel: &AtomicBool) -> Option<u64> {
+        let engine = self.pow_engine();
+        for nonce in 0..=u64::MAX {
+            if nonce % SSI_POW_CANCEL_INTERVAL == 0 && cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            let mut engine = engine.clone();
+            engine.update(nonce.to_le_bytes());
+            if leading_zero_bits(engine.finalize().into()) >= difficulty_bits {
+                self.pow = Some(nonce);
+                return Some(nonce);
+            }
+        }
+        None
+    }
+
+    /// Proof-of-work difficulty of the identity, which is the number of
+    /// leading zero bits of SHA256 hash of the binary identity data committed
+    /// by [`Commitment::Binary`] (see [`Ssi::to_message`]) without the nonce,
+    /// followed by the little-endian nonce. The difficulty thus doesn't depend
+    /// on the identity commitment version. Identities without the nonce have
+    /// zero difficulty.
+    ///
+    /// Verification takes a single hash regardless of the difficulty.
+    pub fn pow_difficulty(&self) -> u32 {
+        let Some(nonce) = self.pow else {
+            return 0;
+        };
+        let mut engine = self.pow_engine();
+        engine.update(nonce.to_le_bytes());
+        leading_zero_bits(engine.finalize().into())
+    }
+
+    fn pow_engine(&self) -> Sha256 {
+        let mut unmined = self.clone();
+        unmined.pow = None;
+        let mut engine = Sha256::new();
+        engine.update(unmined.commitment_data());
+        engine
+    }
+
+    /// Sets or removes the identity comment, checking that it doesn't exceed
+    /// [`SSI_COMMENT_MAX_LEN`] and has no control characters.
+    ///
+    /// The comment is covered by the signature, so the identity must be
+    /// re-signed afterwards (see [`Ssi::resign`]).
+    pub fn set_comment(&mut self, comment: Option<String>) -> Result<(), CommentError> {
+        if let Some(comment) = &comment {
+            check_comment(comment)?;
+        }
+        self.comment = comment;
+        Ok(())
+    }
+
+    /// Returns expiry date as a unix timestamp (seconds since the epoch), for
+    /// users who don't want to depend on `chrono` types.
+    pub fn expiry_unix(&self) -> Option<i64> { self.expiry.map(|expiry| expiry.timestamp()) }
+
+    /// Returns UID to be shown as the identity name: the one marked as
+    /// primary, if any, or otherwise the first UID with `mailto` schema, or
+    /// just the first UID.
+    pub fn primary_uid(&self) -> Option<&Uid> {
+        self.uids
+            .iter()
+            .find(|uid| uid.primary)
+            .or_else(|| self.uids.iter().find(|uid| uid.schema == "mailto"))
+            .or_else(|| self.uids.first())
+    }
+
+    /// Iterates over UIDs grouped by their schema and ordered by id within
+    /// each schema.
+    ///
+    /// This ordering is for presentation purposes only: the signed message
+    /// (see [`Ssi::to_message`]) always uses the canonical order of the
+    /// `uids` set, which is not affected by this method.
+    pub fn uids_by_schema(&self) -> impl Iterator<Item = &Uid> {
+        let mut uids = self.uids.iter().collect::<Vec<_>>();
+        uids.sort_by(|a, b| (&a.schema, &a.id, &a.name).cmp(&(&b.schema, &b.id, &b.name)));
+        uids.into_iter()
+    }
+
+    /// Checks whether two identities have the same key and the same values
+    /// of all the committed fields, i.e. the same signed data.
+    ///
+    /// Unlike `==`, this ignores the signature, endorsements and the
+    /// commitment method, such that copies of one identity signed at
+    /// different moments (with different signature randomness) are detected
+    /// as the same identity.
+    pub fn same_identity(&self, other: &Ssi) -> bool {
+        self.pk == other.pk &&
+            self.uids == other.uids &&
+            self.expiry == other.expiry &&
+            self.comment == other.comment &&
+            self.usage == other.usage &&
+            self.services == other.services &&
+            self.avatar.as_ref().map(|avatar| avatar.hash) ==
+                other.avatar.as_ref().map(|avatar| avatar.hash) &&
+            self.subkeys == other.subkeys &&
+            self.extras == other.extras &&
+            self.next == other.next &&
+            self.prev.map(|(pk, _)| pk) == other.prev.map(|(pk, _)| pk) &&
+            self.pow == other.pow
+    }
+
+    /// Computes changes from `self` to the `other` version of the same
+    /// identity.
+    pub fn diff(&self, other: &Ssi) -> Result<SsiDiff, DiffKeyMismatch> {
+        if self.pk != other.pk {
+            return Err(DiffKeyMismatch(self.pk, other.pk));
+        }
+        Ok(SsiDiff {
+            added_uids: other.uids.difference(&self.uids).cloned().collect(),
+            removed_uids: self.uids.difference(&other.uids).cloned().collect(),
+            expiry_change: (self.expiry != other.expiry).then_some((self.expiry, other.expiry)),
+            sig_changed: self.sig != other.sig,
+        })
+    }
+
+    /// Merges two copies of the same identity published with different UIDs:
+    /// the result contains UIDs from both copies and the later of their
+    /// expiry dates (no expiry being the latest). The primary UID of `self`
+    /// takes precedence over the one from `other`; all other data are taken
+    /// from `self`.
+    ///
+    /// If `other` adds nothing to `self`, `self` is returned as is, keeping
+    /// its signature, commitment and endorsements. Otherwise the merged
+    /// identity is re-signed if the `secret` is given, or left unsigned and
+    /// suitable for display only. Endorsements don't cover the merged data
+    /// and are removed from it.
+    pub fn merge(&self, other: &Ssi, secret: Option<&SsiSecret>) -> Result<Ssi, MergeError> {
+        if self.pk != other.pk {
+            return Err(MergeError::KeyMismatch(self.pk, other.pk));
+        }
+        if secret.is_some_and(|secret| secret.to_public() != self.pk) {
+            return Err(MergeError::SecretMismatch);
+        }
+
+        let primary = self
+            .uids
+            .iter()
+            .find(|uid| uid.primary)
+            .or_else(|| other.uids.iter().find(|uid| uid.primary));
+        let mut uids = self
+            .uids
+            .iter()
+            .chain(&other.uids)
+            .map(|uid| Uid { primary: false, ..uid.clone() })
+            .collect::<BTreeSet<_>>();
+        if let Some(primary) = primary {
+            uids.remove(&Uid { primary: false, ..primary.clone() });
+            uids.insert(primary.clone());
+        }
+        let expiry = match (self.expiry, other.expiry) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            _ => None,
+        };
+
+        if uids == self.uids && expiry == self.expiry {
+            return Ok(self.clone());
+        }
+        let mut merged = self.clone();
+        merged.uids = uids;
+        merged.expiry = expiry;
+        match secret {
+            Some(secret) => merged.resign_changed(secret).expect("key is checked"),
+            None => {
+                merged.sig = None;
+                merged.endorsements.clear();
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Computes message covered by the self-signature and endorsements,
+    /// according to the identity [`Commitment`].
+    ///
+    /// For [`Commitment::Binary`] the message is a SHA256 hash tagged with
+    /// [`SSI_COMMITMENT_TAG`] (i.e. `SHA256(SHA256(tag) || SHA256(tag) ||
+    /// data)`) of the following data, which doesn't depend on the text
+    /// representation of the identity:
+    ///
+    /// 1. public key as 34 bytes: algorithm, chain and the key itself;
+    /// 2. number of UIDs as `u32`, followed by each UID in the canonical order
+    ///    of the UID set: its name with the comment in parentheses, schema and
+    ///    id, each as a length-prefixed string, and the primary flag byte;
+    /// 3. expiry: `0` byte if absent, or `1` byte followed by the unix
+    ///    timestamp as `i64` and nanoseconds as `u32`;
+    /// 4. comment: `0` byte if absent, or `1` byte followed by the
+    ///    length-prefixed comment;
+    /// 5. key usage bits byte, unless all usages are allowed;
+    /// 6. optional items, each starting with a marker byte: `0x10`/`0x11`
+    ///    successor key fingerprint/key, `0x12` predecessor key, `0x20`
+    ///    services, `0x30` proof-of-work nonce, `0x40` unknown parameters,
+    ///    `0x50` avatar hash and `0x60` subkeys.
+    ///
+    /// Integers are little-endian; strings are UTF-8 prefixed with their
+    /// length in bytes as `u32`. This format is stable: any change to it
+    /// requires a new commitment version. See [`identity_message_hash`] for
+    /// computing the message of a plain identity without constructing it.
+    pub fn to_message(&self) -> [u8; 32] {
+        match self.commitment {
+            Commitment::Legacy => self.to_legacy_message(),
+            Commitment::Binary => {
+                let tag = Sha256::digest(SSI_COMMITMENT_TAG);
+                let mut engine = Sha256::new();
+                engine.update(tag);
+                engine.update(tag);
+                engine.update(self.commitment_data());
+                engine.finalize().into()
+            }
+        }
+    }
+
+    /// Hash of the signed identity content, which is stable across
+    /// re-signing and is suitable as a key for indexing and deduplication of
+    /// identities. Equals to [`Ssi::to_message`], thus it depends on the
+    /// commitment method used by the identity.
+    pub fn content_hash(&self) -> [u8; 32] { self.to_message() }
+
+    /// Legacy message, which is a hash of the canonical identity text with no
+    /// signatures.
+    ///
+    /// Trailing separators are trimmed, such that the message is the same for
+    /// identities signed by older versions, which emitted `&` after the
+    /// expiry date.
+    fn to_legacy_message(&self) -> [u8; 32] {
+        let mut unsigned = self.without_avatar_url();
+        unsigned.endorsements.clear();
+        let mut s = String::new();
+        unsigned
+            .write_uri(&mut s, UidEncoding::Query, true)
+            .expect("writing to a string never fails");
+        let msg = Sha256::digest(s.trim_end_matches(['&', '?']));
+        Sha256::digest(msg).into()
+    }
+
+    /// Serializes data committed by [`Commitment::Binary`]; see
+    /// [`Ssi::to_message`] for the specification.
+    fn commitment_data(&self) -> Vec<u8> {
+        let mut data = <[u8; 34]>::from(self.pk).to_vec();
+        data.extend((self.uids.len() as u32).to_le_bytes());
+        for uid in &self.uids {
+            for field in [&uid.display_name(), &uid.schema, &uid.id] {
+                data.extend((field.len() as u32).to_le_bytes());
+                data.extend(field.as_bytes());
+            }
+            data.push(uid.primary as u8);
+        }
+        match self.expiry {
+            None => data.push(0),
+            Some(expiry) => {
+                data.push(1);
+                data.extend(expiry.timestamp().to_le_bytes());
+                data.extend(expiry.timestamp_subsec_nanos().to_le_bytes());
+            }
+        }
+        match &self.comment {
+            None => data.push(0),
+            Some(comment) => {
+                data.push(1);
+                data.extend((comment.len() as u32).to_le_bytes());
+                data.extend(comment.as_bytes());
+            }
+        }
+        // Unrestricted usage is not committed to, keeping signatures of identities created before
+        // the usage was introduced valid
+        if !self.usage.is_all() {
+            data.push(self.usage.bits());
+        }
+        // Rotation links are committed to only when present, with markers distinct from the usage
+        // bits. The signature by the predecessor key is not committed, since it signs this data.
+        match self.next {
+            None => {}
+            Some(KeyLink::Fp(fp)) => {
+                data.push(0x10);
+                data.extend(fp.to_baid64_payload());
+            }
+            Some(KeyLink::Pub(pk)) => {
+                data.push(0x11);
+                data.extend(<[u8; 34]>::from(pk));
+            }
+        }
+        if let Some((pk, _)) = self.prev {
+            data.push(0x12);
+            data.extend(<[u8; 34]>::from(pk));
+        }
+        if !self.services.is_empty() {
+            data.push(0x20);
+            data.extend((self.services_count() as u32).to_le_bytes());
+            for (kind, uris) in &self.services {
+                for uri in uris {
+                    for field in [kind.as_str(), uri.as_str()] {
+                        data.extend((field.len() as u32).to_le_bytes());
+                        data.extend(field.as_bytes());
+                    }
+                }
+            }
+        }
+        if let Some(pow) = self.pow {
+            data.push(0x30);
+            data.extend(pow.to_le_bytes());
+        }
+        if !self.extras.is_empty() {
+            data.push(0x40);
+            data.extend((self.extras.len() as u32).to_le_bytes());
+            for (key, value) in &self.extras {
+                for field in [key, value] {
+                    data.extend((field.len() as u32).to_le_bytes());
+                    data.extend(field.as_bytes());
+                }
+            }
+        }
+        // The avatar location is a hint, which is not committed to
+        if let Some(avatar) = &self.avatar {
+            data.push(0x50);
+            data.extend(avatar.hash);
+        }
+        // Subkey certifications are signed by the identity key separately
+        if !self.subkeys.is_empty() {
+            data.push(0x60);
+            data.extend((self.subkeys.len() as u32).to_le_bytes());
+            for subkey in &self.subkeys {
+                data.extend(<[u8; 34]>::from(subkey.pk));
+            }
+        }
+        data
+    }
+
+    /// Checks whether the key may be used for signing.
+    pub fn can_sign(&self) -> bool { self.usage.contains(KeyUsage::SIGN) }
+
+    /// Checks whether the key may be used for encryption.
+    pub fn can_encrypt(&self) -> bool { self.usage.contains(KeyUsage::ENCRYPT) }
+
+    /// Verifies endorsement of the identity by a third party `signer`.
+    pub fn verify_endorsement(&self, signer: &SsiPub) -> Result<(), VerifyError> {
+        let fp = signer.fingerprint();
+        let (_, sig) = self
+            .endorsements
+            .iter()
+            .find(|(endorser, _)| *endorser == fp)
+            .ok_or(VerifyError::NoEndorsement(fp))?;
+        Ok(signer.verify(self.to_message(), *sig)?)
+    }
+
+    /// Checks the identity self-signature and certifications of its subkeys.
+    pub fn integrity(&self) -> IntegrityStatus {
+        let res = self
+            .subkeys
+            .iter()
+            .try_for_each(|subkey| subkey.verify(self.pk))
+            .and_then(|_| match self.sig {
+                Some(sig) => self.pk.verify(self.to_message(), sig).map(|_| true),
+                None => Ok(false),
+            });
+        match res {
+            Ok(true) => IntegrityStatus::SelfSigned,
+            Ok(false) => IntegrityStatus::Unsigned,
+            Err(InvalidSig::AlgoMismatch) => IntegrityStatus::SignerMismatch,
+            Err(err) => IntegrityStatus::SignatureInvalid(err),
+        }
+    }
+
+    /// Checks the identity self-signature and certifications of its subkeys,
+    /// returning `Ok(false)` for unsigned identities.
+    #[deprecated(since = "0.3.0", note = "use `Ssi::integrity` instead")]
+    pub fn check_integrity(&self) -> Result<bool, InvalidSig> { self.integrity().into_result() }
+}
+
+/// Limits applied when parsing SSI from a string, protecting from resource
+/// exhaustion on malicious inputs.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ParseLimits {
+    /// Maximum length of the whole SSI string, in bytes.
+    pub max_len: usize,
+    /// Maximum number of UIDs.
+    pub max_uids: usize,
+    /// Maximum length of a single query parameter, in bytes.
+    pub max_field_len: usize,
+}
+
+/// Handling of query parameters unknown to this version when parsing SSI (see
+/// [`Ssi::parse_with`]).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum ParseMode {
+    /// Unknown parameters are rejected with [`SsiParseError::UnknownParam`].
+    #[default]
+    Strict,
+    /// Unknown parameters are preserved in [`Ssi::extras`], such that
+    /// identities produced by future versions can be parsed and their
+    /// signatures verified.
+    Lenient,
+}
+
+/// Handling of identities without signature when parsing SSI (see
+/// [`Ssi::parse_with`]).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum UnsignedMode {
+    /// Identities without signature are rejected with
+    /// [`SsiParseError::Unsigned`].
+    #[default]
+    Reject,
+    /// Identities without signature are accepted, such that public-key-only
+    /// URIs can be displayed and own identities kept until signed; callers
+    /// must check [`Ssi::integrity`] before trusting them.
+    Accept,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits {
+            max_len: 16 * 1024,
+            max_uids: 64,
+            max_field_len: 1024,
+        }
+    }
+}
+
+#[derive(Debug, Display, From)]
+#[display(doc_comments)]
+pub enum SsiParseError {
+    /// SSI data exceeds size limits.
+    TooLarge,
+
+    /// SSI contains more than {0} UIDs.
+    TooManyUids(usize),
+    #[from]
+    #[display(inner)]
+    InvalidUri(fluent_uri::ParseError),
+    /// SSI must be a valid URI containing schema part.
+    NoUriScheme,
+    /// SSI must start with 'ssi:' prefix (URI scheme).
+    InvalidScheme(String),
+    /// SSI contains invalid attribute '{0}'.
+    InvalidQueryParam(String, Option<Range<usize>>),
+    /// SSI contains unknown attribute '{0}'.
+    UnknownParam(String, Option<Range<usize>>),
+    /// SSI contains multiple values of unknown attribute '{0}'.
+    RepeatedParam(String, Option<Range<usize>>),
+    /// SSI uses unsupported commitment version '{0}'.
+    UnsupportedVersion(String, Option<Range<usize>>),
+    /// SSI contains multiple commitment versions.
+    RepeatedVersion,
+    /// SSI contains multiple expiration dates.
+    RepeatedExpiry,
+    /// SSI contains multiple comments.
+    RepeatedComment,
+    /// SSI contains multiple key usage attributes.
+    RepeatedUsage,
+    /// SSI contains invalid service endpoint - {0}
+    InvalidService(ServiceError, Option<Range<usize>>),
+    /// SSI contains multiple successor key links.
+    RepeatedNext,
+    /// SSI contains multiple predecessor key links.
+    RepeatedPrev,
+    /// SSI contains multiple proof-of-work nonces.
+    RepeatedPow,
+    /// SSI contains multiple avatar images.
+    RepeatedAvatar,
+    /// SSI contains subkey {0} multiple times.
+    RepeatedSubkey(SsiPub),
+    /// SSI key rotation links require commitment version 2.
+    LegacyRotation,
+    /// SSI contains multiple signatures.
+    RepeatedSig,
+    /// SSI is not signed; unsigned identities are accepted only with
+    /// [`UnsignedMode::Accept`].
+    Unsigned,
+    /// SSI contains multiple primary UIDs.
+    RepeatedPrimaryUid,
+    /// SSI contains malformed endorsement '{0}'.
+    InvalidEndorsement(String, Option<Range<usize>>),
+    /// SSI contains multiple endorsements by {0}.
+    RepeatedEndorsement(Fingerprint),
+
+    #[from]
+    /// SSI contains {0}
+    InvalidUid(UidParseError),
+
+    #[from]
+    /// SSI contains signature not matching the provided data - {0}
+    WrongSig(InvalidSig),
+
+    #[from]
+    /// SSI contains non-parsable expiration date - {0}
+    WrongExpiry(chrono::ParseError),
+
+    #[from]
+    /// SSI contains invalid comment - {0}
+    InvalidComment(CommentError),
+
+    /// SSI contains non-parsable public key - {0}
+    InvalidPub(Baid64Error),
+    /// SSI references identity by its fingerprint only; use SsiRef to parse it.
+    FingerprintOnly,
+    /// SSI contains invalid key fingerprint - {0}
+    InvalidFingerprint(Baid64ParseError),
+    /// SSI contains non-parsable signature - {0}
+    InvalidSig(Baid64Error),
+
+    #[cfg(feature = "nostr")]
+    #[from]
+    /// SSI contains invalid nostr UID - {0}
+    InvalidNostr(crate::NostrError),
+}
+
+impl std::error::Error for SsiParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SsiParseError::InvalidUri(err) => Some(err),
+            SsiParseError::InvalidUid(err) => Some(err),
+            SsiParseError::WrongSig(err) => Some(err),
+            SsiParseError::WrongExpiry(err) => Some(err),
+            SsiParseError::InvalidComment(err) => Some(err),
+            SsiParseError::InvalidService(err, _) => Some(err),
+            SsiParseError::InvalidPub(err) => Some(err),
+            SsiParseError::InvalidFingerprint(err) => Some(err),
+            SsiParseError::InvalidSig(err) => Some(err),
+            #[cfg(feature = "nostr")]
+            SsiParseError::InvalidNostr(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl SsiParseError {
+    /// Byte range of the input string containing the error, if known. Allows
+    /// to point to the failing part of a long SSI URI when reporting errors.
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self {
+            SsiParseError::InvalidQueryParam(_, span)
+            | SsiParseError::UnknownParam(_, span)
+            | SsiParseError::RepeatedParam(_, span)
+            | SsiParseError::UnsupportedVersion(_, span)
+            | SsiParseError::InvalidEndorsement(_, span)
+            | SsiParseError::InvalidService(_, span) => span.clone(),
+            _ => None,
+        }
+    }
+
+    /// Class of the key, signature or fingerprint parse failure, if the error
+    /// is caused by one.
+    pub fn baid64_kind(&self) -> Option<Baid64ErrorKind> {
+        match self {
+            SsiParseError::InvalidFingerprint(err) => Some(Baid64ErrorKind::of(err)),
+            SsiParseError::InvalidPub(err) | SsiParseError::InvalidSig(err) => Some(err.kind()),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Ssi {
+    type Err = SsiParseError;
+
+    /// Parses SSI applying the default [`ParseLimits`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str_limited(s, ParseLimits::default())
+    }
+}
+
+impl Ssi {
+    pub fn from_str_limited(s: &str, limits: ParseLimits) -> Result<Self, SsiParseError> {
+        Self::parse_with(s, limits, ParseMode::Strict, UnsignedMode::Reject)
+    }
+
+    /// Parses SSI applying the default [`ParseLimits`] and preserving unknown
+    /// parameters (see [`ParseMode::Lenient`]).
+    pub fn from_str_lenient(s: &str) -> Result<Self, SsiParseError> {
+        Self::parse_with(s, ParseLimits::default(), ParseMode::Lenient, UnsignedMode::Reject)
+    }
+
+    /// Parses SSI applying the default [`ParseLimits`] and accepting
+    /// identities without signature (see [`UnsignedMode::Accept`]). Unknown
+    /// parameters are still rejected.
+    pub fn from_str_unsigned(s: &str) -> Result<Self, SsiParseError> {
+        Self::parse_with(s, ParseLimits::default(), ParseMode::Strict, UnsignedMode::Accept)
+    }
+
+    /// Parses SSI applying the given limits, handling unknown parameters
+    /// according to the `mode` and identities without signature according to
+    /// the `unsigned` mode.
+    pub fn parse_with(
+        s: &str,
+        limits: ParseLimits,
+        mode: ParseMode,
+        unsigned: UnsignedMode,
+    ) -> Result<Self, SsiParseError> {
+        if s.len() > limits.max_len {
+            return Err(SsiParseError::TooLarge);
+        }
+
+        let uri = Uri::parse(s)?;
+
+        let scheme = uri.scheme().ok_or(SsiParseError::NoUriScheme)?;
+        if scheme.as_str() != "ssi" {
+            return Err(SsiParseError::InvalidScheme(scheme.to_string()));
+        }
+
+        let pk = uri.path().as_str();
+        if pk.starts_with(SSI_REF_FP_PREFIX) {
+            return Err(SsiParseError::FingerprintOnly);
+        }
+        let pk = SsiPub::from_str(pk).map_err(SsiParseError::InvalidPub)?;
+
+        let query = uri.query().unwrap_or_default().as_str();
+        // The path of a valid SSI can't contain '?', so the first one starts the query
+        let mut pos = s.find('?').map(|i| i + 1).unwrap_or(s.len());
+
+        let mut commitment = None;
+        let mut expiry = None;
+        let mut comment = None;
+        let mut usage = None;
+        let mut services = BTreeMap::<String, Vec<ServiceUri>>::new();
+        let mut services_count = 0usize;
+        let mut next = None;
+        let mut prev = None;
+        let mut pow = None;
+        let mut avatar = None;
+        let mut subkeys = Vec::<SignedSubkey>::new();
+        let mut extras = BTreeMap::<String, String>::new();
+        let mut sig = None;
+        let mut uids = bset![];
+        let mut endorsements = Vec::<(Fingerprint, SsiSig)>::new();
+        // Empty segments are skipped: identities serialized by older versions
+        // contain `&&` after the expiry date.
+        for p in query.split('&') {
+            let start = pos;
+            pos += p.len() + 1;
+            if p.is_empty() {
+                continue;
+            }
+            let span = Some(start..start + p.len());
+            if p.len() > limits.max_field_len {
+                return Err(SsiParseError::TooLarge);
+            }
+            let (k, v) = p
+                .split_once('=')
+                .ok_or_else(|| SsiParseError::InvalidQueryParam(p.to_owned(), span.clone()))?;
+            match k {
+                "v" if commitment.is_none() => {
+                    commitment = match v {
+                        "1" => Some(Commitment::Legacy),
+                        "2" => Some(Commitment::Binary),
+                        _ => return Err(SsiParseError::UnsupportedVersion(v.to_owned(), span)),
+                    }
+                }
+                "v" => return Err(SsiParseError::RepeatedVersion),
+                "expiry" if expiry.is_none() => {
+                    expiry = Some(parse_expiry(v)?)
+                }
+                "comment" if comment.is_none() => {
+                    let v = v.replace('+', " ");
+                    let decoded = percent_decode_str(&v)
+                        .decode_utf8()
+                        .map_err(|_| SsiParseError::InvalidQueryParam(p.to_owned(), span))?;
+                    check_comment(&decoded)?;
+                    comment = Some(decoded.into_owned());
+                }
+                "comment" => return Err(SsiParseError::RepeatedComment),
+                "usage" if usage.is_none() => {
+                    let parsed = KeyUsage::from_str(v)
+                        .map_err(|_| SsiParseError::InvalidQueryParam(p.to_owned(), span))?;
+                    usage = Some(parsed);
+                }
+                "usage" => return Err(SsiParseError::RepeatedUsage),
+                "svc" => {
+                    let parse = || -> Result<_, ServiceError> {
+                        if services_count >= SSI_SERVICES_MAX {
+                            return Err(ServiceError::TooMany);
+                        }
+                        let (kind, uri) = v
+                            .split_once(':')
+                            .ok_or_else(|| ServiceError::InvalidType(v.to_owned()))?;
+                        check_service_type(kind)?;
+                        let uri = percent_decode_str(uri)
+                            .decode_utf8()
+                            .map_err(|_| ServiceError::InvalidUri(uri.to_owned()))?;
+                        Ok((kind, ServiceUri::from_str(&uri)?))
+                    };
+                    let (kind, uri) =
+                        parse().map_err(|err| SsiParseError::InvalidService(err, span))?;
+                    services.entry(kind.to_owned()).or_default().push(uri);
+                    services_count += 1;
+                }
+                "next" if next.is_none() => {
+                    let link = KeyLink::from_str(v)
+                        .map_err(|_| SsiParseError::InvalidQueryParam(p.to_owned(), span))?;
+                    next = Some(link);
+                }
+                "next" => return Err(SsiParseError::RepeatedNext),
+                "prev" if prev.is_none() => {
+                    let link = v
+                        .split_once(':')
+                        .and_then(|(pk, sig)| {
+                            Some((SsiPub::from_str(pk).ok()?, SsiSig::from_str(sig).ok()?))
+                        })
+                        .ok_or_else(|| SsiParseError::InvalidQueryParam(p.to_owned(), span))?;
+                    prev = Some(link);
+                }
+                "prev" => return Err(SsiParseError::RepeatedPrev),
+                "subkey" if subkeys.len() >= SSI_SUBKEYS_MAX => {
+                    return Err(SsiParseError::TooLarge)
+                }
+                "subkey" => {
+                    let (pk, sig) = v
+                        .split_once(':')
+                        .and_then(|(pk, sig)| {
+                            Some((SsiPub::from_str(pk).ok()?, SsiSig::from_str(sig).ok()?))
+                        })
+                        .ok_or_else(|| SsiParseError::InvalidQueryParam(p.to_owned(), span))?;
+                    if subkeys.iter().any(|subkey| subkey.pk == pk) {
+                        return Err(SsiParseError::RepeatedSubkey(pk));
+                    }
+                    subkeys.push(SignedSubkey { pk, sig });
+                }
+                "pow" if pow.is_none() => {
+                    // Only the canonical decimal form is accepted, such that the text is preserved
+                    let nonce = u64::from_str(v)
+                        .ok()
+                        .filter(|nonce| nonce.to_string() == v)
+                        .ok_or_else(|| SsiParseError::InvalidQueryParam(p.to_owned(), span))?;
+                    pow = Some(nonce);
+                }
+                "pow" => return Err(SsiParseError::RepeatedPow),
+                "img" if avatar.is_none() => {
+                    let parse = || -> Option<Avatar> {
+                        let (hex, url) = match v.split_once('@') {
+                            Some((hex, url)) => (hex, Some(url)),
+                            None => (v, None),
+                        };
+                        let hash = <[u8; 32]>::try_from(Vec::<u8>::from_hex(hex).ok()?).ok()?;
+                        // Only the canonical lowercase form is accepted, such that the text is
+                        // preserved
+                        if hash.as_slice().to_hex() != hex {
+                            return None;
+                        }
+                        let url = match url {
+                            None => None,
+                            Some(url) => {
+                                let url = percent_decode_str(url).decode_utf8().ok()?;
+                                Some(ServiceUri::from_str(&url).ok()?)
+                            }
+                        };
+                        Some(Avatar { hash, url })
+                    };
+                    let parsed = parse()
+                        .ok_or_else(|| SsiParseError::InvalidQueryParam(p.to_owned(), span))?;
+                    avatar = Some(parsed);
+                }
+                "img" => return Err(SsiParseError::RepeatedAvatar),
+                "expiry" => return Err(SsiParseError::RepeatedExpiry),
+                "uid" | "uid*" if uids.len() >= limits.max_uids => {
+                    return Err(SsiParseError::TooManyUids(limits.max_uids))
+                }
+                "uid" => {
+                    uids.insert(Uid::from_url_str(v)?);
+                }
+                "uid*" if uids.iter().any(|uid: &Uid| uid.primary) => {
+                    return Err(SsiParseError::RepeatedPrimaryUid)
+                }
+                "uid*" => {
+                    let mut uid = Uid::from_url_str(v)?;
+                    uid.primary = true;
+                    uids.insert(uid);
+                }
+                "sig" if sig.is_none() => {
+                    sig = Some(SsiSig::from_str(v).map_err(SsiParseError::InvalidSig)?)
+                }
+                "sig" => return Err(SsiParseError::RepeatedSig),
+                "cert" => {
+                    let (fp, sig) = v
+                        .split_once(':')
+                        .and_then(|(fp, sig)| {
+                            Some((Fingerprint::from_str(fp).ok()?, SsiSig::from_str(sig).ok()?))
+                        })
+                        .ok_or_else(|| SsiParseError::InvalidEndorsement(v.to_owned(), span))?;
+                    if endorsements.iter().any(|(known, _)| *known == fp) {
+                        return Err(SsiParseError::RepeatedEndorsement(fp));
+                    }
+                    endorsements.push((fp, sig));
+                }
+                other if mode == ParseMode::Lenient => {
+                    if extras.insert(other.to_owned(), v.to_owned()).is_some() {
+                        return Err(SsiParseError::RepeatedParam(other.to_owned(), span));
+                    }
+                }
+                other => return Err(SsiParseError::UnknownParam(other.to_owned(), span)),
+            }
+        }
+
+        if commitment.is_none() && (next.is_some() || prev.is_some()) {
+            return Err(SsiParseError::LegacyRotation);
+        }
+        let ssi = Self {
+            pk,
+            uids,
+            expiry,
+            comment,
+            usage: usage.unwrap_or_default(),
+            services,
+            avatar,
+            subkeys,
+            next,
+            prev,
+            pow,
+            extras,
+            commitment: commitment.unwrap_or(Commitment::Legacy),
+            sig,
+            endorsements,
+        };
+        #[cfg(feature = "nostr")]
+        ssi.check_nostr()?;
+        match ssi.integrity() {
+            IntegrityStatus::SelfSigned => {}
+            IntegrityStatus::Unsigned if unsigned == UnsignedMode::Accept => {}
+            IntegrityStatus::Unsigned => return Err(SsiParseError::Unsigned),
+            // Identities using algorithms unknown to this version are preserved; the error is
+            // reported once the signature is actually verified.
+            IntegrityStatus::SignatureInvalid(InvalidSig::UnsupportedAlgo(_)) => {}
+            status => {
+                status.into_result()?;
+            }
+        }
+
+        Ok(ssi)
+    }
+}
+
+/// Percent-encoding of UIDs in the `ssi:` URI (see [`Ssi::to_string_with`]).
+/// Both encodings are parsed back into the same identity.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Display)]
+#[display(lowercase)]
+pub enum UidEncoding {
+    /// Encodes only controls, `%`, characters delimiting URI query
+    /// parameters (`?`, `&`, `+` and `=`) and the fragment delimiter `#`,
+    /// replacing spaces with `+`. Used by the default [`Display`] of [`Ssi`].
+    #[default]
+    Query,
+    /// Encodes everything except ASCII letters, digits and `-._~:@`, making
+    /// the URI safe for embedding into a path segment or a fragment.
+    Strict,
+}
+
+/// Formats UID as `uid` (or `uid*` for the primary UID) URI query parameter.
+///
+/// With the `legacy` flag, the [`UidEncoding::Query`] escaping leaves `%`
+/// and `#` as is, like the original format over which [`Commitment::Legacy`]
+/// signatures are computed did. Such text may be not a valid URI, so it is
+/// used only for the legacy message.
+fn uid_param(uid: &Uid, encoding: UidEncoding, legacy: bool) -> String {
+    const QUERY: &AsciiSet =
+        &CONTROLS.add(b'%').add(b'?').add(b'&').add(b'+').add(b'=').add(b'#');
+    const LEGACY: &AsciiSet = &CONTROLS.add(b'?').add(b'&').add(b'+').add(b'=');
+    const STRICT: &AsciiSet = &NON_ALPHANUMERIC
+        .remove(b'-')
+        .remove(b'.')
+        .remove(b'_')
+        .remove(b'~')
+        .remove(b':')
+        .remove(b'@');
+    let key = if uid.primary { "uid*" } else { "uid" };
+    let uid = uid.to_string().replace(['<', '>'], "");
+    let uid = match encoding {
+        UidEncoding::Query if legacy => {
+            utf8_percent_encode(&uid, LEGACY).to_string().replace(' ', "+")
+        }
+        UidEncoding::Query => utf8_percent_encode(&uid, QUERY).to_string().replace(' ', "+"),
+        UidEncoding::Strict => utf8_percent_encode(&uid, STRICT).to_string(),
+    };
+    format!("{key}={uid}")
+}
+
+impl Ssi {
+    /// Canonical `ssi:` URI of the identity, which is what [`Display`] writes
+    /// and what [`Commitment::Legacy`] signatures are computed over (except
+    /// for `%` in UIDs, see below). Parsing the canonical string with
+    /// [`FromStr`] always gives back an equal identity, and formatting the
+    /// result gives back the same string.
+    ///
+    /// The URI consists of the public key followed by the query parameters in
+    /// the following order: `v` (omitted for legacy identities), `uid`
+    /// (including the primary `uid*`), `expiry`, `comment`, `usage` (omitted
+    /// if all usages are allowed), `svc`, `img`, `subkey`, `next`, `prev`,
+    /// `pow`, non-standard extra parameters, `sig` and `cert`. Repeated
+    /// parameters follow the order of the identity fields: UIDs are in the
+    /// order of the UID set, services are grouped by their type in
+    /// alphabetical order, extra parameters are sorted by name, and subkeys
+    /// and endorsements keep the order in which they were added.
+    ///
+    /// UIDs use the [`UidEncoding::Query`] escaping, while legacy signatures
+    /// are computed over the original escaping, which kept `%` and `#` as is;
+    /// comments percent-encode everything except alphanumerics and ` ,-._~`,
+    /// and then replace spaces with `+`; service and avatar URLs
+    /// percent-encode everything except alphanumerics and `-._~:/`. Non-ASCII
+    /// characters are always percent-encoded as UTF-8. Avatar hashes use lowercase hex, and expiry
+    /// dates are written as `YYYY-MM-DD` when they fall on the end of a day
+    /// and as RFC 3339 timestamps otherwise.
+    pub fn canonical_string(&self) -> String { self.to_string() }
+
+    /// Formats the identity as `ssi:` URI like [`Display`] does, but with the
+    /// given UID encoding. The identity message and signature do not depend on
+    /// the encoding.
+    pub fn to_string_with(&self, encoding: UidEncoding) -> String {
+        let mut s = String::new();
+        self.write_uri(&mut s, encoding, false).expect("writing to a string never fails");
+        s
+    }
+
+    fn write_uri(
+        &self,
+        f: &mut dyn fmt::Write,
+        encoding: UidEncoding,
+        legacy: bool,
+    ) -> fmt::Result {
+        let mut sep = '?';
+        write!(f, "{}", self.pk.to_uri_string())?;
+
+        if self.commitment != Commitment::Legacy {
+            write!(f, "{sep}v={}", self.commitment)?;
+            sep = '&';
+        }
+
+        for uid in &self.uids {
+            write!(f, "{sep}{}", uid_param(uid, encoding, legacy))?;
+            sep = '&';
+        }
+
+        if let Some(expiry) = self.expiry {
+            write!(f, "{sep}expiry={}", format_expiry(expiry))?;
+            sep = '&';
+        }
+
+        if let Some(comment) = &self.comment {
+            const COMMENT: &AsciiSet = &NON_ALPHANUMERIC
+                .remove(b' ')
+                .remove(b',')
+                .remove(b'-')
+                .remove(b'.')
+                .remove(b'_')
+                .remove(b'~');
+            let comment = utf8_percent_encode(comment, COMMENT).to_string().replace(' ', "+");
+            write!(f, "{sep}comment={comment}")?;
+            sep = '&';
+        }
+
+        if !self.usage.is_all() {
+            write!(f, "{sep}usage={}", self.usage)?;
+            sep = '&';
+        }
+
+        const SERVICE: &AsciiSet = &NON_ALPHANUMERIC
+            .remove(b'-')
+            .remove(b'.')
+            .remove(b'_')
+            .remove(b'~')
+            .remove(b':')
+            .remove(b'/');
+        for (kind, uris) in &self.services {
+            for uri in uris {
+                write!(f, "{sep}svc={kind}:{}", utf8_percent_encode(uri.as_str(), SERVICE))?;
+                sep = '&';
+            }
+        }
+
+        if let Some(avatar) = &self.avatar {
+            write!(f, "{sep}img={}", avatar.hash.as_slice().to_hex())?;
+            if let Some(url) = &avatar.url {
+                write!(f, "@{}", utf8_percent_encode(url.as_str(), SERVICE))?;
+            }
+            sep = '&';
+        }
+
+        for subkey in &self.subkeys {
+            let pk = KeyLink::Pub(subkey.pk);
+            write!(f, "{sep}subkey={pk}:{}", subkey.sig.to_uri_string())?;
+            sep = '&';
+        }
+
+        if let Some(next) = self.next {
+            write!(f, "{sep}next={next}")?;
+            sep = '&';
+        }
+
+        if let Some((pk, sig)) = self.prev {
+            write!(f, "{sep}prev={}:{}", KeyLink::Pub(pk), sig.to_uri_string())?;
+            sep = '&';
+        }
+
+        if let Some(pow) = self.pow {
+            write!(f, "{sep}pow={pow}")?;
+            sep = '&';
+        }
+
+        for (key, value) in &self.extras {
+            write!(f, "{sep}{key}={value}")?;
+            sep = '&';
+        }
+
+        if let Some(sig) = self.sig {
+            write!(f, "{sep}sig={}", sig.to_uri_string())?;
+            sep = '&';
+        }
+
+        for (fp, sig) in &self.endorsements {
+            write!(f, "{sep}cert={fp}:{}", sig.to_uri_string())?;
+            sep = '&';
+        }
+
+        Ok(())
+    }
+}
+
+/// SSI has two display forms:
+/// - the default one (`{}`) is the canonical `ssi:` URI, which is parseable
+///   with [`FromStr`] and which is used for the computation of the message
+///   covered by the identity self-signature;
+/// - the alternative one (`{:#}`) is a multi-line human-readable description
+///   of the identity, consisting of the key fingerprint, UIDs labeled with
+///   their schema, expiration date and the signature status. It can't be
+///   parsed back.
+impl Display for Ssi {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            writeln!(f, "fingerprint: {}", self.pk.fingerprint())?;
+            for uid in &self.uids {
+                let primary = if uid.primary { ", primary" } else { "" };
+                match uid.display_name().as_str() {
+                    "" => writeln!(f, "uid ({}{primary}): {}", uid.schema, uid.id)?,
+                    name => writeln!(f, "uid ({}{primary}): {name} <{}>", uid.schema, uid.id)?,
+                }
+            }
+            match self.expiry {
+                Some(expiry) => writeln!(f, "expiry: {}", expiry.format("%B %-d, %Y"))?,
+                None => writeln!(f, "expiry: never")?,
+            }
+            if let Some(comment) = &self.comment {
+                writeln!(f, "comment: {comment}")?;
+            }
+            if !self.usage.is_all() {
+                writeln!(f, "usage: {}", self.usage)?;
+            }
+            for (kind, uris) in &self.services {
+                for uri in uris {
+                    writeln!(f, "service ({kind}): {uri}")?;
+                }
+            }
+            if let Some(avatar) = &self.avatar {
+                write!(f, "avatar: {}", avatar.hash.as_slice().to_hex())?;
+                match &avatar.url {
+                    Some(url) => writeln!(f, " at {url}")?,
+                    None => writeln!(f)?,
+                }
+            }
+            for subkey in &self.subkeys {
+                writeln!(f, "subkey: {}", subkey.pk.fingerprint())?;
+            }
+            if let Some(next) = self.next {
+                writeln!(f, "rotated to: {}", next.fingerprint())?;
+            }
+            if let Some((pk, _)) = self.prev {
+                writeln!(f, "rotated from: {}", pk.fingerprint())?;
+            }
+            for (fp, _) in &self.endorsements {
+                writeln!(f, "endorsed by: {fp}")?;
+            }
+            let status = match self.integrity() {
+                IntegrityStatus::SelfSigned => "valid",
+                IntegrityStatus::Unsigned => "missing",
+                IntegrityStatus::SignatureInvalid(InvalidSig::UnsupportedAlgo(_)) => {
+                    "unsupported algorithm"
+                }
+                IntegrityStatus::SignatureInvalid(_) | IntegrityStatus::SignerMismatch => "invalid",
+            };
+            return write!(f, "signature: {status}");
+        }
+
+        self.write_uri(f, UidEncoding::Query, false)
+    }
+}
+
+/// Identities are serialized as their canonical `ssi:` URI string in both
+/// human-readable and binary formats, since there is no strict encoding for
+/// the whole identity. Deserialization applies the default [`ParseLimits`]
+/// and checks the identity integrity, like [`Ssi::from_str`] does.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Ssi {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Ssi {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SsiVisitor;
+
+        impl serde::de::Visitor<'_> for SsiVisitor {
+            type Value = Ssi;
+
+            fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+                f.write_str("ssi: URI of a self-sovereign identity")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ssi::from_str_unsigned(v).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(SsiVisitor)
+    }
+}
+
+/// Prefix of the `ssi:` URI path which references an identity by its key
+/// fingerprint instead of the full key (see [`SsiRef`]).
+pub const SSI_REF_FP_PREFIX: &str = "fp:";
+
+/// Reference to an identity, which may be given either in full or, when the
+/// key is not known yet and is to be resolved later, by the key fingerprint
+/// with UIDs as `ssi:fp:<fingerprint>?uid=...`.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum SsiRef {
+    Full(Ssi),
+    Fingerprint {
+        fp: Fingerprint,
+        uids: BTreeSet<Uid>,
+    },
+}
+
+impl SsiRef {
+    pub fn fingerprint(&self) -> Fingerprint {
+        match self {
+            SsiRef::Full(ssi) => ssi.pk.fingerprint(),
+            SsiRef::Fingerprint { fp, .. } => *fp,
+        }
+    }
+
+    pub fn uids(&self) -> &BTreeSet<Uid> {
+        match self {
+            SsiRef::Full(ssi) => &ssi.uids,
+            SsiRef::Fingerprint { uids, .. } => uids,
+        }
+    }
+
+    pub fn as_ssi(&self) -> Option<&Ssi> {
+        match self {
+            SsiRef::Full(ssi) => Some(ssi),
+            SsiRef::Fingerprint { .. } => None,
+        }
+    }
+
+    /// Checks the identity self-signature (see [`Ssi::integrity`]).
+    /// Fingerprint-only references are reported as having an invalid
+    /// signature with [`InvalidSig::CannotVerify`] error.
+    pub fn integrity(&self) -> IntegrityStatus {
+        match self {
+            SsiRef::Full(ssi) => ssi.integrity(),
+            SsiRef::Fingerprint { .. } => {
+                IntegrityStatus::SignatureInvalid(InvalidSig::CannotVerify)
+            }
+        }
+    }
+
+    /// Checks the identity self-signature, failing with
+    /// [`InvalidSig::CannotVerify`] for fingerprint-only references.
+    #[deprecated(since = "0.3.0", note = "use `SsiRef::integrity` instead")]
+    pub fn check_integrity(&self) -> Result<bool, InvalidSig> { self.integrity().into_result() }
+
+    fn from_fp_str(s: &str, limits: ParseLimits) -> Result<Self, SsiParseError> {
+        let uri = Uri::parse(s)?;
+        let fp = uri.path().as_str().trim_start_matches(SSI_REF_FP_PREFIX);
+        let fp = Fingerprint::from_str(fp).map_err(SsiParseError::InvalidFingerprint)?;
+
+        let query = uri.query().unwrap_or_default().as_str();
+        let mut pos = s.find('?').map(|i| i + 1).unwrap_or(s.len());
+        let mut uids = bset![];
+        for p in query.split('&') {
+            let start = pos;
+            pos += p.len() + 1;
+            if p.is_empty() {
+                continue;
+            }
+            let span = Some(start..start + p.len());
+            if p.len() > limits.max_field_len {
+                return Err(SsiParseError::TooLarge);
+            }
+            if uids.len() >= limits.max_uids {
+                return Err(SsiParseError::TooManyUids(limits.max_uids));
+            }
+            let (k, v) = p
+                .split_once('=')
+                .ok_or_else(|| SsiParseError::InvalidQueryParam(p.to_owned(), span.clone()))?;
+            let primary = match k {
+                "uid" => false,
+                "uid*" if uids.iter().any(|uid: &Uid| uid.primary) => {
+                    return Err(SsiParseError::RepeatedPrimaryUid)
+                }
+                "uid*" => true,
+                other => return Err(SsiParseError::UnknownParam(other.to_owned(), span)),
+            };
+            let mut uid = Uid::from_url_str(v)?;
+            uid.primary = primary;
+            uids.insert(uid);
+        }
+        Ok(SsiRef::Fingerprint { fp, uids })
+    }
+}
+
+impl From<Ssi> for SsiRef {
+    fn from(ssi: Ssi) -> Self { SsiRef::Full(ssi) }
+}
+
+impl FromStr for SsiRef {
+    type Err = SsiParseError;
+
+    /// Parses either a full identity or a fingerprint-only reference,
+    /// applying the default [`ParseLimits`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let limits = ParseLimits::default();
+        match Ssi::from_str_limited(s, limits) {
+            Ok(ssi) => Ok(SsiRef::Full(ssi)),
+            Err(SsiParseError::FingerprintOnly) => Self::from_fp_str(s, limits),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl Display for SsiRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (fp, uids) = match self {
+            SsiRef::Full(ssi) => return Display::fmt(ssi, f),
+            SsiRef::Fingerprint { fp, uids } => (fp, uids),
+        };
+        write!(f, "ssi:{SSI_REF_FP_PREFIX}{fp}")?;
+        let mut sep = '?';
+        for uid in uids {
+            write!(f, "{sep}{}", uid_param(uid, UidEncoding::Query, false))?;
+            sep = '&';
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Algo, Chain};
+
+    fn keygen() -> SsiSecret { SsiSecret::new(Algo::Ed25519, Chain::Bitcoin) }
+
+    fn alice_uid() -> Uid { Uid::from_str("Alice <mailto:alice@example.com>").unwrap() }
+
+    #[test]
+    fn content_hash() {
+        let secret = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        let uid = alice_uid();
+        let ssi = Ssi::new(bset![uid], None, &secret);
+        let mut resigned = ssi.clone();
+        resigned.sig = Some(SsiSig::from([7u8; 64]));
+        assert_ne!(resigned, ssi);
+        assert_eq!(resigned.content_hash(), ssi.content_hash());
+        assert_eq!(ssi.content_hash(), ssi.to_message());
+
+        let mut changed = ssi.clone();
+        changed.set_expiry(parse_expiry("2099-12-31").ok(), &secret, true).unwrap();
+        assert_ne!(changed.content_hash(), ssi.content_hash());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn integrity_status() {
+        let secret = keygen();
+        let uid = alice_uid();
+        let ssi = Ssi::new(bset![uid], None, &secret);
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+        assert_eq!(ssi.check_integrity(), Ok(true));
+
+        let unsigned = ssi.without_signature();
+        assert_eq!(unsigned.integrity(), IntegrityStatus::Unsigned);
+        assert_eq!(unsigned.check_integrity(), Ok(false));
+        assert!(matches!(Ssi::from_str(&unsigned.to_string()), Err(SsiParseError::Unsigned)));
+        assert!(matches!(
+            Ssi::from_str_lenient(&unsigned.to_string()),
+            Err(SsiParseError::Unsigned)
+        ));
+        assert_eq!(Ssi::from_str_unsigned(&unsigned.to_string()).unwrap(), unsigned);
+        let limits = ParseLimits::default();
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            assert!(matches!(
+                Ssi::parse_with(&unsigned.to_string(), limits, mode, UnsignedMode::Reject),
+                Err(SsiParseError::Unsigned)
+            ));
+            let parsed =
+                Ssi::parse_with(&unsigned.to_string(), limits, mode, UnsignedMode::Accept).unwrap();
+            assert_eq!(parsed, unsigned);
+        }
+        // accepting unsigned identities doesn't preserve unknown parameters
+        let mut extended = unsigned.clone();
+        extended.extras.insert(s!("color"), s!("blue"));
+        assert!(matches!(
+            Ssi::from_str_unsigned(&extended.to_string()),
+            Err(SsiParseError::UnknownParam(ref p, _)) if p == "color"
+        ));
+
+        let mut tampered = ssi.clone();
+        tampered.uids.insert(Uid::from_str("Mallory <mailto:mallory@example.com>").unwrap());
+        assert_eq!(tampered.integrity(), IntegrityStatus::SignatureInvalid(InvalidSig::InvalidSig));
+        assert_eq!(tampered.check_integrity(), Err(InvalidSig::InvalidSig));
+        assert!(Ssi::from_str(&tampered.to_string()).is_err());
+
+        let other = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        let mut mismatched = ssi.clone();
+        mismatched.sig =
+            Some(SsiSig::tagged(Algo::Bip340, other.sign(ssi.to_message()).to_byte_array()));
+        assert_eq!(mismatched.integrity(), IntegrityStatus::SignerMismatch);
+        assert_eq!(mismatched.check_integrity(), Err(InvalidSig::AlgoMismatch));
+        assert!(mismatched.integrity().is_invalid());
+        assert!(Ssi::from_str(&mismatched.to_string()).is_err());
+    }
+
+    #[test]
+    fn resign() {
+        let secret = keygen();
+        let uid = alice_uid();
+        let mut ssi = Ssi::new(bset![uid], None, &secret);
+        assert_eq!(ssi.without_signature().sig, None);
+        assert_eq!(ssi.without_signature().integrity(), IntegrityStatus::Unsigned);
+
+        ssi.uids
+            .insert(Uid::from_str("Alice <https:alice.example.com>").unwrap());
+        assert!(ssi.integrity().is_invalid());
+        ssi.resign(&secret).unwrap();
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+    }
+
+    #[test]
+    fn try_new() {
+        let secret = keygen();
+        let now = Utc::now();
+        let past = now - chrono::Duration::days(1);
+        let future = now + chrono::Duration::days(1);
+
+        let res = Ssi::try_new(bset![], Some(past), &secret, now);
+        assert_eq!(res, Err(SignerError::Expired(past)));
+        let res = Ssi::try_new(bset![], Some(now), &secret, now);
+        assert_eq!(res, Err(SignerError::Expired(now)));
+        let ssi = Ssi::try_new(bset![], Some(future), &secret, now).unwrap();
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+        Ssi::try_new(bset![], None, &secret, now).unwrap();
+
+        let ssi = Ssi::new_unchecked(bset![], Some(past), &secret);
+        assert_eq!(ssi.expiry, Some(past));
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+    }
+
+    #[test]
+    fn expiry_unix() {
+        let secret = keygen();
+        assert_eq!(Ssi::new(bset![], None, &secret).expiry_unix(), None);
+
+        let expiry = DateTime::from_timestamp(1_767_225_599, 0).unwrap();
+        let ssi = Ssi::new(bset![], Some(expiry), &secret);
+        assert_eq!(ssi.expiry_unix(), Some(1_767_225_599));
+        let expiry = DateTime::from_timestamp(-86_400, 0).unwrap();
+        let ssi = Ssi::new_unchecked(bset![], Some(expiry), &secret);
+        assert_eq!(ssi.expiry_unix(), Some(-86_400));
+    }
+
+    #[test]
+    fn expiry_roundtrip() {
+        let secret = keygen();
+        let uid = alice_uid();
+        let date = parse_expiry("2030-01-01").unwrap();
+        assert_eq!(date.to_rfc3339(), "2030-01-01T23:59:59+00:00");
+        let precise = Utc::now() + chrono::Duration::days(365);
+
+        for expiry in [date, precise] {
+            let ssi = Ssi::new(bset![uid.clone()], Some(expiry), &secret);
+            let s = ssi.to_string();
+            assert!(!s.contains("&&"));
+            let parsed = Ssi::from_str(&s).unwrap();
+            assert_eq!(parsed, ssi);
+            assert_eq!(parsed.expiry, Some(expiry));
+            assert_eq!(parsed.integrity(), IntegrityStatus::SelfSigned);
+            assert_eq!(parsed.to_string(), s);
+        }
+
+        let ssi = Ssi::new(bset![], Some(date), &secret);
+        assert!(ssi.to_string().contains("?v=2&expiry=2030-01-01&sig="));
+
+        assert_eq!(
+            parse_expiry("2030-01-01T12:00:00+02:00").unwrap(),
+            parse_expiry("2030-01-01T10:00:00Z").unwrap()
+        );
+        assert!(parse_expiry("2030-01-01 12:00").is_err());
+    }
+
+    #[test]
+    fn primary_uid() {
+        let secret = keygen();
+        let web = Uid::from_str("Alice <https:alice.example.com>").unwrap();
+        let mail = alice_uid();
+        let mut xmpp = Uid::from_str("Alice <xmpp:alice@example.com>").unwrap();
+
+        assert_eq!(Ssi::new(bset![], None, &secret).primary_uid(), None);
+        let ssi = Ssi::new(bset![web.clone()], None, &secret);
+        assert_eq!(ssi.primary_uid(), Some(&web));
+        let ssi = Ssi::new(bset![web.clone(), mail.clone(), xmpp.clone()], None, &secret);
+        assert_eq!(ssi.primary_uid(), Some(&mail));
+
+        xmpp.primary = true;
+        let ssi = Ssi::new(bset![web.clone(), mail.clone(), xmpp.clone()], None, &secret);
+        assert_eq!(ssi.primary_uid(), Some(&xmpp));
+
+        let s = ssi.to_string();
+        assert!(s.contains("&uid*=Alice+xmpp:alice@example.com"));
+        let parsed = Ssi::from_str(&s).unwrap();
+        assert_eq!(parsed, ssi);
+        assert_eq!(parsed.primary_uid(), Some(&xmpp));
+        assert_eq!(parsed.integrity(), IntegrityStatus::SelfSigned);
+
+        let mut unmarked = parsed.clone();
+        unmarked.uids = bset![web, mail, Uid::from_str("Alice <xmpp:alice@example.com>").unwrap()];
+        assert!(unmarked.integrity().is_invalid());
+
+        let repeated = s.replace("uid=Alice+mailto", "uid*=Alice+mailto");
+        assert!(matches!(Ssi::from_str(&repeated), Err(SsiParseError::RepeatedPrimaryUid)));
+    }
+
+    #[test]
+    fn unnamed_uid() {
+        let uid = Uid::from_str("mailto:alice@example.com").unwrap();
+        assert_eq!(uid.name, "");
+        assert_eq!(uid.to_string(), "mailto:alice@example.com");
+        assert_eq!(format!("{uid:#}"), "mailto:alice@example.com");
+        assert_eq!(Uid::from_str("<mailto:alice@example.com>").unwrap(), uid);
+
+        let named = Uid::from_str("Alice Example mailto:alice@example.com").unwrap();
+        assert_eq!(named.name, "Alice Example");
+        assert_eq!(named.to_string(), "Alice Example <mailto:alice@example.com>");
+        assert!(matches!(Uid::from_str("alice@example.com"), Err(UidParseError::NoSchema(_))));
+        assert!(matches!(Uid::from_str("Alice "), Err(UidParseError::NoId(_))));
+
+        let secret = keygen();
+        let ssi = Ssi::new(bset![uid, named], None, &secret);
+        let s = ssi.to_string();
+        assert!(s.contains("uid=mailto:alice@example.com&"));
+        let parsed = Ssi::from_str(&s).unwrap();
+        assert_eq!(parsed, ssi);
+        assert_eq!(parsed.to_string(), s);
+    }
+
+    #[test]
+    fn uid_comment() {
+        let uid = Uid::from_str("Alice Example (work laptop) <mailto:alice@example.com>").unwrap();
+        assert_eq!(uid.name, "Alice Example");
+        assert_eq!(uid.comment.as_deref(), Some("work laptop"));
+        assert_eq!(uid.to_string(), "Alice Example (work laptop) <mailto:alice@example.com>");
+        assert_eq!(Uid::from_str(&format!("{uid:#}")).unwrap(), uid);
+
+        let unnamed = Uid::from_str("(work) mailto:alice@example.com").unwrap();
+        assert_eq!(unnamed.name, "");
+        assert_eq!(unnamed.to_string(), "(work) <mailto:alice@example.com>");
+
+        let plain = Uid::from_str("f(x) <mailto:alice@example.com>").unwrap();
+        assert_eq!(plain.name, "f(x)");
+        assert_eq!(plain.comment, None);
+        assert_eq!(plain.to_string(), "f(x) <mailto:alice@example.com>");
+
+        assert!(matches!(
+            Uid::from_str("Alice (a (b)) <mailto:alice@example.com>"),
+            Err(UidParseError::InvalidComment(_))
+        ));
+        assert!(matches!(
+            Uid::from_str("Alice (a<b) <mailto:alice@example.com>"),
+            Err(UidParseError::InvalidComment(_))
+        ));
+
+        let secret = keygen();
+        let ssi = Ssi::new(bset![uid.clone(), unnamed], None, &secret);
+        let s = ssi.to_string();
+        assert!(s.contains("uid=Alice+Example+(work+laptop)+mailto:alice@example.com"));
+        let parsed = Ssi::from_str(&s).unwrap();
+        assert_eq!(parsed, ssi);
+        assert!(parsed.uids.contains(&uid));
+        assert_eq!(parsed.integrity(), IntegrityStatus::SelfSigned);
+    }
+
+    #[test]
+    fn roundtrip_combinations() {
+        let secret = keygen();
+        let alice = alice_uid();
+        let web = Uid::from_str("Alice <https:alice.example.com>").unwrap();
+        let date = parse_expiry("2030-01-01").unwrap();
+        let precise = parse_expiry("2030-01-01T12:30:00.5Z").unwrap();
+
+        for uids in [bset![], bset![alice.clone()], bset![alice.clone(), web.clone()]] {
+            for expiry in [None, Some(date), Some(precise)] {
+                let signed = Ssi::new(uids.clone(), expiry, &secret);
+                for ssi in [signed.clone(), signed.without_signature()] {
+                    let s = ssi.to_string();
+                    assert!(!s.contains("&&") && !s.ends_with(['&', '?']), "{s}");
+                    let parsed = Ssi::from_str_unsigned(&s).unwrap();
+                    assert_eq!(parsed, ssi, "{s}");
+                    assert_eq!(parsed.to_string(), s);
+                    let status = match ssi.sig {
+                        Some(_) => IntegrityStatus::SelfSigned,
+                        None => IntegrityStatus::Unsigned,
+                    };
+                    assert_eq!(parsed.integrity(), status, "{s}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn legacy_uid_escaping() {
+        let secret = keygen();
+        let uid = Uid::from_str("Bob 100% <mailto:bob@example.com>").unwrap();
+        let mut ssi = Ssi::new(bset![uid], None, &secret);
+        ssi.commitment = Commitment::Legacy;
+        // The original format didn't escape `%`
+        let original = format!("{}?uid=Bob+100%+mailto:bob@example.com", ssi.pk.to_uri_string());
+        ssi.sig = Some(secret.sign(Sha256::digest(Sha256::digest(&original)).into()));
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+
+        let s = ssi.to_string();
+        assert!(s.contains("uid=Bob+100%25+mailto"));
+        let parsed = Ssi::from_str(&s).unwrap();
+        assert_eq!(parsed, ssi);
+        assert_eq!(parsed.integrity(), IntegrityStatus::SelfSigned);
+    }
+
+    #[test]
+    fn legacy_expiry_separator() {
+        let secret = keygen();
+        let uid = alice_uid();
+        let mut ssi = Ssi {
+            pk: secret.to_public(),
+            uids: bset![uid],
+            expiry: Some(parse_expiry("2030-01-01").unwrap()),
+            comment: None,
+            usage: KeyUsage::ALL,
+            services: bmap![],
+            avatar: None,
+            subkeys: vec![],
+            next: None,
+            prev: None,
+            pow: None,
+            extras: bmap![],
+            commitment: Commitment::Legacy,
+            sig: None,
+            endorsements: vec![],
+        };
+        ssi.sig = Some(secret.sign(ssi.to_message()));
+
+        let legacy = ssi.to_string().replace("2030-01-01&sig=", "2030-01-01&&sig=");
+        assert_ne!(legacy, ssi.to_string());
+        let parsed = Ssi::from_str(&legacy).unwrap();
+        assert_eq!(parsed, ssi);
+        assert_eq!(parsed.integrity(), IntegrityStatus::SelfSigned);
+        assert_eq!(parsed.to_string(), ssi.to_string());
+    }
+
+    #[test]
+    fn error_span() {
+        let secret = keygen();
+        let uid = alice_uid();
+        let ssi = Ssi::new(bset![uid], None, &secret).to_string();
+
+        let s = ssi.replace("&sig=", "&&color=blue&sig=");
+        let err = Ssi::from_str(&s).unwrap_err();
+        assert!(matches!(err, SsiParseError::UnknownParam(ref p, _) if p == "color"));
+        assert_eq!(err.to_string(), "SSI contains unknown attribute 'color'.");
+        assert_eq!(&s[err.span().unwrap()], "color=blue");
+
+        let s = ssi.replace("&sig=", "&broken&sig=");
+        let err = Ssi::from_str(&s).unwrap_err();
+        assert_eq!(&s[err.span().unwrap()], "broken");
+
+        assert_eq!(Ssi::from_str("ssi:invalid").unwrap_err().span(), None);
+    }
+
+    #[test]
+    fn error_source() {
+        use std::error::Error;
+
+        let utf8 = String::from_utf8(vec![0xFF]).unwrap_err().utf8_error();
+        assert!(UidParseError::Utf8(utf8).source().is_some());
+        assert!(UidParseError::NoId(s!("Alice")).source().is_none());
+
+        let errors = [
+            SsiParseError::InvalidUri(Uri::parse("ssi:%").unwrap_err()),
+            SsiParseError::InvalidUid(UidParseError::NoSchema(s!("alice"))),
+            SsiParseError::WrongSig(InvalidSig::InvalidData),
+            SsiParseError::WrongExpiry(NaiveDate::from_str("never").unwrap_err()),
+            SsiParseError::InvalidComment(CommentError::ControlChars),
+            SsiParseError::InvalidPub(SsiPub::from_str("invalid").unwrap_err()),
+            SsiParseError::InvalidFingerprint(Fingerprint::from_str("invalid").unwrap_err()),
+            SsiParseError::InvalidSig(SsiSig::from_str("invalid").unwrap_err()),
+        ];
+        for err in errors {
+            assert!(err.source().is_some(), "{err}");
+        }
+        assert!(SsiParseError::TooLarge.source().is_none());
+    }
+
+    #[test]
+    fn baid64_error_kind() {
+        let secret = keygen();
+        let ssi = Ssi::new(bset![], None, &secret).to_string();
+        let (head, sig) = ssi.split_once("sig=").unwrap();
+
+        let err = Ssi::from_str(&format!("{head}sig=*{}", &sig[1..])).unwrap_err();
+        assert_eq!(err.baid64_kind(), Some(Baid64ErrorKind::Charset));
+        let err = Ssi::from_str(&format!("{head}sig={}", &sig[2..])).unwrap_err();
+        assert_eq!(err.baid64_kind(), Some(Baid64ErrorKind::Length));
+        let err = Ssi::from_str(&format!("{head}sig={}", secret.to_public())).unwrap_err();
+        assert_eq!(err.baid64_kind(), Some(Baid64ErrorKind::MismatchedType));
+        let err = SsiRef::from_str("ssi:fp:AAAA?uid=mailto:alice@example.com").unwrap_err();
+        assert_eq!(err.baid64_kind(), Some(Baid64ErrorKind::Length));
+        let err = Ssi::from_str("ssi:fp:AAAA?uid=mailto:alice@example.com").unwrap_err();
+        assert_eq!(err.baid64_kind(), None);
+    }
+
+    #[test]
+    fn comment() {
+        let secret = keygen();
+        let uid = alice_uid();
+        for text in ["work key, laptop", "ключ 🔑 a+b=c & d?", "100% #1"] {
+            let mut ssi = Ssi::new(bset![uid.clone()], None, &secret);
+            ssi.set_comment(Some(text.to_owned())).unwrap();
+            assert!(ssi.integrity().is_invalid());
+            ssi.resign(&secret).unwrap();
+
+            let s = ssi.to_string();
+            let parsed = Ssi::from_str(&s).unwrap();
+            assert_eq!(parsed.comment.as_deref(), Some(text));
+            assert_eq!(parsed, ssi);
+            assert_eq!(parsed.integrity(), IntegrityStatus::SelfSigned);
+            assert_eq!(parsed.to_string(), s);
+        }
+
+        let mut ssi = Ssi::new(bset![], None, &secret);
+        let max = "x".repeat(SSI_COMMENT_MAX_LEN);
+        ssi.set_comment(Some(max.clone())).unwrap();
+        assert_eq!(ssi.set_comment(Some(max.clone() + "x")), Err(CommentError::TooLong(257)));
+        assert_eq!(
+            CommentError::TooLong(257).to_string(),
+            "comment is 257 bytes long, exceeding the limit of 256 bytes."
+        );
+        assert_eq!(ssi.comment, Some(max));
+        assert_eq!(
+            ssi.set_comment(Some(s!("line\nbreak"))),
+            Err(CommentError::ControlChars)
+        );
+        ssi.set_comment(None).unwrap();
+
+        let s = Ssi::new(bset![], None, &secret).to_string();
+        let long = format!("{s}&comment={}", "x".repeat(SSI_COMMENT_MAX_LEN + 1));
+        assert!(matches!(
+            Ssi::from_str(&long),
+            Err(SsiParseError::InvalidComment(CommentError::TooLong(_)))
+        ));
+        let control = format!("{s}&comment=a%07b");
+        assert!(matches!(
+            Ssi::from_str(&control),
+            Err(SsiParseError::InvalidComment(CommentError::ControlChars))
+        ));
+    }
+
+    #[test]
+    fn commitment() {
+        let secret = keygen();
+        let tricky = Uid::from_str("Alice sig=x <mailto:sig=alice@example.com>").unwrap();
+        let ssi = Ssi::new(bset![tricky.clone()], None, &secret);
+        assert_eq!(ssi.commitment, Commitment::Binary);
+        let s = ssi.to_string();
+        assert!(s.contains("?v=2&uid="));
+        let parsed = Ssi::from_str(&s).unwrap();
+        assert_eq!(parsed, ssi);
+        assert_eq!(parsed.integrity(), IntegrityStatus::SelfSigned);
+
+        // The binary commitment doesn't depend on the text representation
+        let mut legacy = ssi.clone();
+        legacy.commitment = Commitment::Legacy;
+        assert_ne!(legacy.to_message(), ssi.to_message());
+        assert!(legacy.integrity().is_invalid());
+
+        // Stripping the version makes the parser use the legacy commitment
+        assert!(matches!(
+            Ssi::from_str(&s.replace("?v=2&", "?")),
+            Err(SsiParseError::WrongSig(_))
+        ));
+
+        legacy.resign(&secret).unwrap();
+        assert_eq!(legacy.commitment, Commitment::Legacy);
+        assert_eq!(legacy.integrity(), IntegrityStatus::SelfSigned);
+        let s = legacy.to_string();
+        assert!(!s.contains("v="));
+        let parsed = Ssi::from_str(&s).unwrap();
+        assert_eq!(parsed.commitment, Commitment::Legacy);
+        assert_eq!(parsed.integrity(), IntegrityStatus::SelfSigned);
+        // The legacy version may also be given explicitly, as it is displayed
+        let explicit = s.replacen('?', &format!("?v={}&", Commitment::Legacy), 1);
+        assert_eq!(Ssi::from_str(&explicit).unwrap(), parsed);
+
+        assert!(matches!(
+            Ssi::from_str(&ssi.to_string().replace("v=2", "v=3")),
+            Err(SsiParseError::UnsupportedVersion(..))
+        ));
+    }
+
+    #[test]
+    fn diff() {
+        let secret = keygen();
+        let alice = alice_uid();
+        let web = Uid::from_str("Alice <https:alice.example.com>").unwrap();
+        let old = Ssi::new(bset![alice.clone()], None, &secret);
+        assert!(old.diff(&old).unwrap().is_empty());
+
+        let expiry = Utc::now();
+        let new = Ssi::new(bset![web.clone()], Some(expiry), &secret);
+        let diff = old.diff(&new).unwrap();
+        assert_eq!(diff.added_uids, bset![web]);
+        assert_eq!(diff.removed_uids, bset![alice]);
+        assert_eq!(diff.expiry_change, Some((None, Some(expiry))));
+        assert!(diff.sig_changed);
+
+        let other = Ssi::new(bset![], None, &keygen());
+        assert_eq!(old.diff(&other), Err(DiffKeyMismatch(old.pk, other.pk)));
+    }
+
+    #[test]
+    fn same_identity() {
+        let secret = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        let uid = alice_uid();
+        let first = Ssi::new(bset![uid.clone()], None, &secret);
+        let second = Ssi::new(bset![uid.clone()], None, &secret);
+        assert_ne!(first.sig, second.sig);
+        assert_ne!(first, second);
+        assert!(first.same_identity(&second));
+        assert!(first.same_identity(&first.without_signature()));
+
+        let web = Uid::from_str("Alice <https:alice.example.com>").unwrap();
+        assert!(!first.same_identity(&Ssi::new(bset![uid.clone(), web], None, &secret)));
+        assert!(!first.same_identity(&Ssi::new(bset![uid.clone()], Some(Utc::now()), &secret)));
+        let other = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        assert!(!first.same_identity(&Ssi::new(bset![uid.clone()], None, &other)));
+        let mut restricted = first.clone();
+        restricted.usage = KeyUsage::SIGN;
+        assert!(!first.same_identity(&restricted));
+        let mut mined = first.clone();
+        mined.pow = Some(1);
+        assert!(!first.same_identity(&mined));
+    }
+
+    #[test]
+    fn key_rotation() {
+        let secret = keygen();
+        let new_secret = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        let uid = alice_uid();
+        let ssi = Ssi::new(bset![uid], Some(parse_expiry("2030-01-01").unwrap()), &secret);
+
+        let (old, new) = ssi.rotate_to(&secret, &new_secret).unwrap();
+        assert_eq!(old.next, Some(KeyLink::Pub(new_secret.to_public())));
+        assert_eq!(new.prev.map(|(pk, _)| pk), Some(ssi.pk));
+        assert_eq!(new.uids, ssi.uids);
+        assert_eq!(new.expiry, ssi.expiry);
+        verify_rotation(&old, &new).unwrap();
+        assert_eq!(verify_rotation(&new, &old), Err(RotationError::NoForwardLink));
+        assert_eq!(verify_rotation(&ssi, &new), Err(RotationError::NoForwardLink));
+
+        let old = Ssi::from_str(&old.to_string()).unwrap();
+        let new = Ssi::from_str(&new.to_string()).unwrap();
+        verify_rotation(&old, &new).unwrap();
+
+        let mut by_fp = ssi.clone();
+        by_fp.next = Some(KeyLink::Fp(new.pk.fingerprint()));
+        by_fp.resign(&secret).unwrap();
+        assert_eq!(Ssi::from_str(&by_fp.to_string()).unwrap(), by_fp);
+        verify_rotation(&by_fp, &new).unwrap();
+
+        let mut tampered = old.clone();
+        tampered.next = Some(KeyLink::Pub(secret.to_public()));
+        assert_eq!(verify_rotation(&tampered, &new), Err(RotationError::BrokenOld));
+        let mut forged = new.clone();
+        let other = keygen();
+        forged.prev = Some((ssi.pk, other.sign(new.to_message())));
+        assert!(matches!(verify_rotation(&old, &forged), Err(RotationError::InvalidLink(_))));
+        forged.prev = None;
+        forged.resign(&new_secret).unwrap();
+        assert_eq!(verify_rotation(&old, &forged), Err(RotationError::NoBackwardLink));
+        assert_eq!(ssi.rotate_to(&other, &new_secret), Err(SignerError::KeyMismatch));
+    }
+
+    #[test]
+    fn rotation_links_parse() {
+        let secret = keygen();
+        let new_secret = keygen();
+        let uid = alice_uid();
+        let ssi = Ssi::new(bset![uid], None, &secret);
+        let (old, new) = ssi.rotate_to(&secret, &new_secret).unwrap();
+        let old = old.to_string();
+        let new = new.to_string();
+
+        let (_, next) = old.split_once("&next=").unwrap();
+        let next = next.split('&').next().unwrap();
+        let repeated = old.replace("&sig=", &format!("&next={next}&sig="));
+        assert!(matches!(Ssi::from_str(&repeated), Err(SsiParseError::RepeatedNext)));
+        let malformed = old.replace(&format!("next={next}"), "next=invalid");
+        assert!(matches!(Ssi::from_str(&malformed), Err(SsiParseError::InvalidQueryParam(..))));
+
+        let (_, prev) = new.split_once("&prev=").unwrap();
+        let prev = prev.split('&').next().unwrap();
+        let repeated = new.replace("&sig=", &format!("&prev={prev}&sig="));
+        assert!(matches!(Ssi::from_str(&repeated), Err(SsiParseError::RepeatedPrev)));
+        let (pk, _) = prev.split_once(':').unwrap();
+        let malformed = new.replace(&format!("prev={prev}"), &format!("prev={pk}"));
+        assert!(matches!(Ssi::from_str(&malformed), Err(SsiParseError::InvalidQueryParam(..))));
+
+        let legacy = new.replace("?v=2&", "?");
+        assert!(matches!(Ssi::from_str(&legacy), Err(SsiParseError::LegacyRotation)));
+    }
+
+    #[test]
+    fn renew() {
+        let secret = keygen();
+        let web = Uid::from_str("Alice <https:example.com/alice>").unwrap();
+        let mail = alice_uid();
+        let ssi = SsiBuilder::new()
+            .uid(web.clone())
+            .uid(mail.clone())
+            .valid_for(chrono::Duration::days(90))
+            .sign(&secret)
+            .unwrap();
+        let expiry = ssi.expiry.unwrap();
+        let days = (expiry - Utc::now()).num_days();
+        assert!((89..=90).contains(&days));
+
+        let renewed = ssi.renew(&secret, chrono::Duration::days(30)).unwrap();
+        assert_eq!(renewed.uids, bset![web.clone(), mail]);
+        assert_eq!(renewed.expiry, Some(expiry + chrono::Duration::days(30)));
+        assert_eq!(renewed.integrity(), IntegrityStatus::SelfSigned);
+        assert_ne!(renewed.sig, ssi.sig);
+
+        let past = parse_expiry("2000-01-01").unwrap();
+        let expired = Ssi::new_unchecked(bset![web.clone()], Some(past), &secret);
+        let renewed = expired.renew(&secret, chrono::Duration::days(1)).unwrap();
+        assert!(renewed.validity_now().is_valid());
+
+        let other = keygen();
+        assert_eq!(ssi.renew(&other, chrono::Duration::days(1)), Err(RenewError::KeyMismatch));
+        let eternal = Ssi::new(bset![web.clone()], None, &secret);
+        assert_eq!(eternal.renew(&secret, chrono::Duration::days(1)), Err(RenewError::NoExpiry));
+        assert_eq!(
+            SsiBuilder::new().uid(web).expiry(past).sign(&secret),
+            Err(SignerError::Expired(past))
+        );
+    }
+
+    #[test]
+    fn merge() {
+        let secret = keygen();
+        let mut alice = alice_uid();
+        let web = Uid::from_str("Alice <https:example.com/alice>").unwrap();
+        let early = parse_expiry("2030-01-01").unwrap();
+        let late = parse_expiry("2031-01-01").unwrap();
+        let site = Ssi::new(bset![alice.clone(), web.clone()], Some(early), &secret);
+        alice.primary = true;
+        let email = Ssi::new(bset![alice.clone()], Some(late), &secret);
+
+        let view = site.merge(&email, None).unwrap();
+        assert_eq!(view.uids, bset![alice.clone(), web.clone()]);
+        assert_eq!(view.expiry, Some(late));
+        assert_eq!(view.sig, None);
+        assert_eq!(view.integrity(), IntegrityStatus::Unsigned);
+
+        let merged = site.merge(&email, Some(&secret)).unwrap();
+        assert!(merged.same_identity(&view));
+        assert_eq!(merged.integrity(), IntegrityStatus::SelfSigned);
+        assert_eq!(merged.primary_uid(), Some(&alice));
+        assert_eq!(email.merge(&site, Some(&secret)).unwrap().uids, merged.uids);
+
+        let eternal = Ssi::new(bset![web.clone()], None, &secret);
+        assert_eq!(site.merge(&eternal, None).unwrap().expiry, None);
+
+        // Nothing new: the signature is kept
+        assert_eq!(merged.merge(&site, None).unwrap(), merged);
+        assert_eq!(merged.merge(&site, Some(&secret)).unwrap(), merged);
+
+        let other = keygen();
+        let mallory = Ssi::new(bset![web], None, &other);
+        assert_eq!(site.merge(&mallory, None), Err(MergeError::KeyMismatch(site.pk, mallory.pk)));
+        assert_eq!(site.merge(&email, Some(&other)), Err(MergeError::SecretMismatch));
+    }
+
+    #[test]
+    fn merge_endorsed_legacy() {
+        let secret = keygen();
+        let certifier = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        let alice = alice_uid();
+        let web = Uid::from_str("Alice <https:example.com/alice>").unwrap();
+        let mut legacy = Ssi::new(bset![alice.clone()], None, &secret);
+        legacy.commitment = Commitment::Legacy;
+        legacy.resign(&secret).unwrap();
+        let fp = certifier.to_public().fingerprint();
+        legacy.endorsements.push((fp, certifier.sign(legacy.to_message())));
+
+        let copy = Ssi::new(bset![alice], None, &secret);
+        let same = legacy.merge(&copy, Some(&secret)).unwrap();
+        assert_eq!(same, legacy);
+        same.verify_endorsement(&certifier.to_public()).unwrap();
+
+        let web = Ssi::new(bset![web], None, &secret);
+        let merged = legacy.merge(&web, Some(&secret)).unwrap();
+        assert_eq!(merged.commitment, Commitment::Binary);
+        assert_eq!(merged.integrity(), IntegrityStatus::SelfSigned);
+        assert!(merged.endorsements.is_empty());
+    }
+
+    #[test]
+    fn validity() {
+        let secret = keygen();
+        let uid = alice_uid();
+        let expiry = parse_expiry("2030-01-01").unwrap();
+        let ssi = Ssi::new(bset![uid], Some(expiry), &secret);
+
+        let second = chrono::Duration::seconds(1);
+        assert_eq!(ssi.validity(expiry - second), Validity::Valid);
+        assert!(ssi.validity(expiry - second).is_valid());
+        assert_eq!(ssi.validity(expiry), Validity::Expired { since: expiry });
+        assert_eq!(ssi.validity(expiry + second), Validity::Expired { since: expiry });
+
+        let mut eternal = ssi.clone();
+        eternal.expiry = None;
+        eternal.resign(&secret).unwrap();
+        assert_eq!(eternal.validity(DateTime::<Utc>::MAX_UTC), Validity::Valid);
+        assert_eq!(eternal.validity_now(), Validity::Valid);
+
+        assert_eq!(ssi.without_signature().validity(expiry - second), Validity::NotSigned);
+        let mut tampered = ssi.clone();
+        tampered.uids.insert(Uid::from_str("Mallory <mailto:mallory@example.com>").unwrap());
+        assert!(matches!(tampered.validity(expiry + second), Validity::BadSignature(_)));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_uri() {
+        let secret = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        let uid = alice_uid();
+        let ssi = Ssi::new(bset![uid], parse_expiry("2030-01-01").ok(), &secret);
+
+        let json = serde_json::to_string(&ssi).unwrap();
+        assert_eq!(json, format!("\"{ssi}\""));
+        assert!(json.starts_with("\"ssi:"));
+        assert_eq!(serde_json::from_str::<Ssi>(&json).unwrap(), ssi);
+
+        let unsigned = ssi.without_signature();
+        let json = serde_json::to_string(&unsigned).unwrap();
+        assert_eq!(serde_json::from_str::<Ssi>(&json).unwrap(), unsigned);
+
+        let list = serde_json::to_value([&ssi, &ssi]).unwrap();
+        assert_eq!(serde_json::from_value::<Vec<Ssi>>(list).unwrap(), vec![ssi.clone(); 2]);
+
+        let tampered = json.replace("Alice", "Mallory");
+        assert!(serde_json::from_str::<Ssi>(&tampered).is_err());
+        assert!(serde_json::from_str::<Ssi>("\"ssi:invalid\"").is_err());
+        assert!(serde_json::from_str::<Ssi>("{}").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_binary() {
+        let secret = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        let uid = alice_uid();
+        let ssi = Ssi::new(bset![uid], parse_expiry("2030-01-01").ok(), &secret);
+
+        let data = bincode::serialize(&ssi).unwrap();
+        assert_eq!(data, bincode::serialize(&ssi.to_string()).unwrap());
+        assert_eq!(bincode::deserialize::<Ssi>(&data).unwrap(), ssi);
+
+        let tampered = bincode::serialize(&ssi.to_string().replace("Alice", "Mallory")).unwrap();
+        assert!(bincode::deserialize::<Ssi>(&tampered).is_err());
+    }
+
+    #[test]
+    fn uid_special_chars() {
+        let secret = keygen();
+        let uid = Uid::from_str("C++ 100% <mailto:alice+ssi@example.com>").unwrap();
+        let ssi = Ssi::new(bset![uid.clone()], None, &secret);
+        let s = ssi.to_string();
+        assert!(s.contains("uid=C%2B%2B+100%25+mailto:alice%2Bssi@example.com"));
+        let parsed = Ssi::from_str(&s).unwrap();
+        assert_eq!(parsed.uids, bset![uid]);
+        assert_eq!(parsed, ssi);
+
+        let uid = Uid::from_str("A+B 50% (x+y z) <mailto:a+b%c@example.com>").unwrap();
+        let ssi = Ssi::new(bset![uid.clone()], None, &secret);
+        for encoding in [UidEncoding::Query, UidEncoding::Strict] {
+            let s = ssi.to_string_with(encoding);
+            let parsed = Ssi::from_str(&s).unwrap();
+            assert_eq!(parsed.uids, bset![uid.clone()], "{s}");
+            assert_eq!(parsed.integrity(), IntegrityStatus::SelfSigned);
+            assert_eq!(parsed.to_string(), ssi.to_string());
+        }
+
+        // URIs produced by older versions use `+` for spaces only
+        let uid = Uid::from_url_str("Alice+Smith+mailto:alice@example.com").unwrap();
+        assert_eq!(uid.name, "Alice Smith");
+        assert_eq!(uid.id, "alice@example.com");
+    }
+
+    #[test]
+    fn lenient_parse() {
+        let secret = keygen();
+        let uid = alice_uid();
+        let mut ssi = Ssi::new(bset![uid], None, &secret);
+        ssi.extras.insert(s!("color"), s!("blue"));
+        ssi.extras.insert(s!("avatar"), s!("https:%2F%2Fexample.com%2Fa.png"));
+        ssi.resign(&secret).unwrap();
+        let s = ssi.to_string();
+        assert!(s.contains("&avatar=https:%2F%2Fexample.com%2Fa.png&color=blue&sig="));
+
+        assert!(matches!(
+            Ssi::from_str(&s),
+            Err(SsiParseError::UnknownParam(ref p, _)) if p == "avatar"
+        ));
+        let parsed = Ssi::from_str_lenient(&s).unwrap();
+        assert_eq!(parsed, ssi);
+        assert_eq!(parsed.to_string(), s);
+
+        let stripped = s.replace("&color=blue", "");
+        assert!(Ssi::from_str_lenient(&stripped).is_err());
+        let injected = s.replace("&sig=", "&size=xl&sig=");
+        assert!(Ssi::from_str_lenient(&injected).is_err());
+        let repeated = s.replace("&sig=", "&color=red&sig=");
+        assert!(matches!(
+            Ssi::from_str_lenient(&repeated),
+            Err(SsiParseError::RepeatedParam(ref p, _)) if p == "color"
+        ));
+    }
+
+    #[test]
+    fn uid_encoding() {
+        let secret = keygen();
+        let uid = Uid::from_str("Alice <https:example.com/alice#key>").unwrap();
+        let ssi = Ssi::new(bset![uid], None, &secret);
+
+        let query = ssi.to_string_with(UidEncoding::Query);
+        assert_eq!(query, ssi.to_string());
+        assert!(query.contains("uid=Alice+https:example.com/alice%23key"));
+        assert!(!query.contains('#'));
+        assert_eq!(Ssi::from_str(&query).unwrap(), ssi);
+
+        let strict = ssi.to_string_with(UidEncoding::Strict);
+        assert!(strict.contains("uid=Alice%20https:example.com%2Falice%23key"));
+        assert!(!strict.contains('#'));
+        assert_eq!(Ssi::from_str(&strict).unwrap(), ssi);
+        assert_eq!(UidEncoding::default(), UidEncoding::Query);
+    }
+
+    #[test]
+    fn proof_of_work() {
+        let secret = keygen();
+        let uid = alice_uid();
+        let mut ssi = Ssi::new(bset![uid], None, &secret);
+        assert_eq!(ssi.pow_difficulty(), 0);
+
+        let nonce = ssi.mine_pow(8, &AtomicBool::new(false)).unwrap();
+        assert_eq!(ssi.pow, Some(nonce));
+        assert!(ssi.pow_difficulty() >= 8);
+        assert!(ssi.integrity().is_invalid());
+        ssi.resign(&secret).unwrap();
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+
+        let s = ssi.to_string();
+        assert!(s.contains(&format!("&pow={nonce}&sig=")));
+        let parsed = Ssi::from_str(&s).unwrap();
+        assert_eq!(parsed, ssi);
+        assert_eq!(parsed.pow_difficulty(), ssi.pow_difficulty());
+        let legacy = Ssi { commitment: Commitment::Legacy, ..ssi.clone() };
+        assert_eq!(legacy.pow_difficulty(), ssi.pow_difficulty());
+
+        let stripped = s.replace(&format!("&pow={nonce}"), "");
+        assert!(Ssi::from_str(&stripped).is_err());
+        let repeated = s.replace("&sig=", &format!("&pow={nonce}&sig="));
+        assert!(matches!(Ssi::from_str(&repeated), Err(SsiParseError::RepeatedPow)));
+        let padded = s.replace(&format!("&pow={nonce}"), &format!("&pow=0{nonce}"));
+        assert!(matches!(Ssi::from_str(&padded), Err(SsiParseError::InvalidQueryParam(..))));
+
+        let mut cancelled = ssi.clone();
+        assert_eq!(cancelled.mine_pow(256, &AtomicBool::new(true)), None);
+        assert_eq!(cancelled, ssi);
+    }
+
+    #[test]
+    fn avatar() {
+        let secret = keygen();
+        let uid = alice_uid();
+        let mut ssi = Ssi::new(bset![uid], None, &secret);
+        let image = b"\x89PNG avatar image data";
+        let hash = <[u8; 32]>::from(Sha256::digest(image));
+        let hex = hash.as_slice().to_hex();
+        assert!(!ssi.verify_avatar(image));
+
+        let url = ServiceUri::from_str("https://example.com/img/a&b@2x.png").unwrap();
+        ssi.set_avatar(hash, Some(url.clone()), &secret).unwrap();
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+        assert!(ssi.verify_avatar(image));
+        assert!(!ssi.verify_avatar(b"other image"));
+
+        let s = ssi.to_string();
+        let encoded = "https://example.com/img/a%26b%402x.png";
+        assert!(s.contains(&format!("&img={hex}@{encoded}&sig=")));
+        let parsed = Ssi::from_str(&s).unwrap();
+        assert_eq!(parsed, ssi);
+        assert_eq!(parsed.avatar.as_ref().unwrap().url, Some(url));
+
+        // The location may be moved without re-signing, while the hash can't be changed
+        let moved = s.replace(encoded, "ipfs:bafybeigdyrzt");
+        assert_eq!(Ssi::from_str(&moved).unwrap().integrity(), IntegrityStatus::SelfSigned);
+        let removed = s.replace(&format!("@{encoded}"), "");
+        assert_eq!(Ssi::from_str(&removed).unwrap().integrity(), IntegrityStatus::SelfSigned);
+        let stripped = s.replace(&format!("&img={hex}@{encoded}"), "");
+        assert!(matches!(Ssi::from_str(&stripped), Err(SsiParseError::WrongSig(_))));
+        let tampered = s.replace(&hex, &[0u8; 32].as_slice().to_hex());
+        assert!(matches!(Ssi::from_str(&tampered), Err(SsiParseError::WrongSig(_))));
+
+        let repeated = s.replace("&sig=", &format!("&img={hex}&sig="));
+        assert!(matches!(Ssi::from_str(&repeated), Err(SsiParseError::RepeatedAvatar)));
+        let upper = s.replace(&hex, &hex.to_uppercase());
+        assert!(matches!(Ssi::from_str(&upper), Err(SsiParseError::InvalidQueryParam(..))));
+        let short = s.replace(&hex, &hex[2..]);
+        assert!(matches!(Ssi::from_str(&short), Err(SsiParseError::InvalidQueryParam(..))));
+        let relative = s.replace(encoded, "a.png");
+        assert!(matches!(Ssi::from_str(&relative), Err(SsiParseError::InvalidQueryParam(..))));
+
+        let other = keygen();
+        let before = ssi.clone();
+        assert_eq!(ssi.set_avatar([0; 32], None, &other), Err(SignerError::KeyMismatch));
+        assert_eq!(ssi, before);
+    }
+
+    #[test]
+    fn subkeys() {
+        let primary = keygen();
+        let laptop = keygen().to_public();
+        let phone = SsiSecret::new(Algo::Bip340, Chain::Bitcoin).to_public();
+        let uid = alice_uid();
+        let mut ssi = Ssi::new(bset![uid], None, &primary);
+
+        ssi.add_subkey(laptop, &primary).unwrap();
+        ssi.add_subkey(phone, &primary).unwrap();
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+        ssi.verify_subkey(&laptop).unwrap();
+        ssi.verify_subkey(&phone).unwrap();
+        assert_eq!(ssi.verify_subkey(&ssi.pk), Err(SubkeyError::Unknown(ssi.pk)));
+
+        let s = ssi.to_string();
+        assert!(s.contains("&subkey="));
+        let parsed = Ssi::from_str(&s).unwrap();
+        assert_eq!(parsed, ssi);
+        parsed.verify_subkey(&phone).unwrap();
+
+        // Subkeys are covered by the self-signature
+        let mut stripped = ssi.clone();
+        stripped.subkeys.pop();
+        assert!(stripped.integrity().is_invalid());
+
+        // Subkey certification must be made by the primary key
+        let mallory = keygen();
+        let mut forged = ssi.clone();
+        forged.subkeys[0].sig = mallory.sign(SignedSubkey::message(ssi.pk, laptop));
+        forged.resign(&primary).unwrap();
+        assert!(forged.integrity().is_invalid());
+        assert!(matches!(forged.verify_subkey(&laptop), Err(SubkeyError::InvalidBinding(_))));
+        assert!(matches!(Ssi::from_str(&forged.to_string()), Err(SsiParseError::WrongSig(_))));
+
+        let subkey = ssi.subkeys[0];
+        let repeated = s.replace(
+            "&sig=",
+            &format!("&subkey={}:{}&sig=", KeyLink::Pub(subkey.pk), subkey.sig.to_uri_string()),
+        );
+        assert!(matches!(
+            Ssi::from_str(&repeated),
+            Err(SsiParseError::RepeatedSubkey(pk)) if pk == laptop
+        ));
+
+        let before = ssi.clone();
+        assert_eq!(ssi.add_subkey(laptop, &primary), Err(SubkeyError::Duplicate(laptop)));
+        assert_eq!(ssi.add_subkey(ssi.pk, &primary), Err(SubkeyError::Primary));
+        assert_eq!(ssi.add_subkey(mallory.to_public(), &mallory), Err(SubkeyError::KeyMismatch));
+        assert_eq!(ssi, before);
+
+        ssi.remove_subkey(&laptop, &primary).unwrap();
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+        assert_eq!(ssi.verify_subkey(&laptop), Err(SubkeyError::Unknown(laptop)));
+        assert_eq!(ssi.remove_subkey(&laptop, &primary), Err(SubkeyError::Unknown(laptop)));
+    }
+
+    #[test]
+    fn message_test_vector() {
+        let pk = SsiPub::with(Chain::Bitcoin, Algo::Ed25519, [0x11; 32]);
+        let uids = bset![alice_uid()];
+        let expiry = parse_expiry("2030-01-01").unwrap();
+
+        let msg = identity_message_hash(pk, &uids, Some(expiry));
+        assert_eq!(
+            msg.as_slice().to_hex(),
+            "fbba7a2f2b947a5ccb33b5b6619749ca54c303e8d748330dd5fab768c2dbde95"
+        );
+        assert_eq!(
+            identity_message_hash(pk, &bset![], None).as_slice().to_hex(),
+            "5709b69f68bc434050b43734bed5c3f073224f4981aa2ecd1ae4f6c0265f4125"
+        );
+
+        let uri = pk.to_uri_string();
+        let s = format!("{uri}?v=2&uid=Alice+mailto:alice@example.com&expiry=2030-01-01");
+        let ssi = Ssi::from_str_unsigned(&s).unwrap();
+        assert_eq!(ssi.integrity(), IntegrityStatus::Unsigned);
+        assert_eq!(ssi.to_message(), msg);
+
+        let secret = keygen();
+        let signed = Ssi::new(uids.clone(), Some(expiry), &secret);
+        let pk = secret.to_public();
+        assert_eq!(signed.to_message(), identity_message_hash(pk, &uids, Some(expiry)));
+    }
+
+    #[test]
+    fn services() {
+        let secret = keygen();
+        let uid = alice_uid();
+        let mut ssi = Ssi::new(bset![uid], None, &secret);
+        let didcomm = ServiceUri::from_str("https://example.com/didcomm?a=1&b=%20").unwrap();
+        let relay = ServiceUri::from_str("wss://relay.example.com").unwrap();
+        ssi.add_service("didcomm", didcomm.clone()).unwrap();
+        ssi.add_service("nostr-relay", relay.clone()).unwrap();
+        ssi.resign(&secret).unwrap();
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+
+        let s = ssi.to_string();
+        assert!(s.contains("&svc=didcomm:https://example.com/didcomm%3Fa%3D1%26b%3D%2520&"));
+        assert!(s.contains("&svc=nostr-relay:wss://relay.example.com&"));
+        let parsed = Ssi::from_str(&s).unwrap();
+        assert_eq!(parsed, ssi);
+        assert_eq!(parsed.services["didcomm"], vec![didcomm]);
+        assert_eq!(parsed.services["nostr-relay"], vec![relay]);
+
+        let tampered = s.replace("relay.example.com", "evil.example.com");
+        assert!(Ssi::from_str(&tampered).is_err());
+
+        assert_eq!(
+            ssi.add_service("", ServiceUri::from_str("https://example.com").unwrap()),
+            Err(ServiceError::InvalidType(s!("")))
+        );
+        assert_eq!(ServiceUri::from_str("example"), Err(ServiceError::InvalidUri(s!("example"))));
+        let long = format!("https://example.com/{}", "a".repeat(SSI_SERVICE_URI_MAX_LEN));
+        assert_eq!(ServiceUri::from_str(&long), Err(ServiceError::TooLong(long.len())));
+        assert_eq!(
+            ServiceError::TooLong(257).to_string(),
+            "service endpoint URI is 257 bytes long, exceeding the limit of 256 bytes."
+        );
+        for no in ssi.services.values().map(Vec::len).sum::<usize>()..SSI_SERVICES_MAX {
+            let uri = ServiceUri::from_str(&format!("https://{no}.example.com")).unwrap();
+            ssi.add_service("web", uri).unwrap();
+        }
+        let uri = ServiceUri::from_str("https://extra.example.com").unwrap();
+        assert_eq!(ssi.add_service("web", uri), Err(ServiceError::TooMany));
+
+        let extra = "&svc=web:https://a.example.com".repeat(SSI_SERVICES_MAX - 1);
+        let too_many = s.replace("&sig=", &format!("{extra}&sig="));
+        assert!(matches!(
+            Ssi::from_str(&too_many),
+            Err(SsiParseError::InvalidService(ServiceError::TooMany, _))
+        ));
+        let invalid = s.replace("svc=nostr-relay:wss://", "svc=nostr-relay:");
+        assert!(matches!(
+            Ssi::from_str(&invalid),
+            Err(SsiParseError::InvalidService(ServiceError::InvalidUri(_), _))
+        ));
+    }
+
+    #[test]
+    fn edit_uids() {
+        let secret = keygen();
+        let mail = alice_uid();
+        let web = Uid::from_str("Alice <https:alice.example.com>").unwrap();
+        let mut ssi = Ssi::new(bset![mail.clone()], None, &secret);
+        let old = ssi.to_string();
+
+        ssi.add_uid(web.clone(), &secret).unwrap();
+        assert_eq!(ssi.uids, bset![mail.clone(), web.clone()]);
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+        let new = ssi.to_string();
+        assert_eq!(Ssi::from_str(&new).unwrap(), ssi);
+
+        let old_sig = Ssi::from_str(&old).unwrap().sig;
+        let mut stale = ssi.clone();
+        stale.sig = old_sig;
+        assert!(stale.integrity().is_invalid());
+        assert!(Ssi::from_str(&stale.to_string()).is_err());
+
+        let other = keygen();
+        let xmpp = Uid::from_str("Alice <xmpp:alice@example.com>").unwrap();
+        assert_eq!(ssi.add_uid(xmpp.clone(), &other), Err(UidEditError::KeyMismatch));
+        assert_eq!(ssi.add_uid(web.clone(), &secret), Err(UidEditError::Duplicate(web.clone())));
+        assert_eq!(ssi.remove_uid(&xmpp, &secret), Err(UidEditError::Unknown(xmpp)));
+        assert_eq!(ssi.to_string(), new);
+
+        ssi.remove_uid(&web, &secret).unwrap();
+        assert_eq!(ssi.uids, bset![mail.clone()]);
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+        assert_eq!(ssi.remove_uid(&mail, &secret), Err(UidEditError::LastUid));
+    }
+
+    #[test]
+    fn key_usage() {
+        assert_eq!(KeyUsage::from_str("sign,encrypt"), Ok(KeyUsage::ALL));
+        assert_eq!(KeyUsage::ALL.to_string(), "sign,encrypt");
+        assert_eq!(KeyUsage::ENCRYPT.to_string(), "encrypt");
+        assert!(KeyUsage::from_str("certify").is_err());
+
+        let secret = keygen();
+        let uid = alice_uid();
+        let mut ssi = Ssi::new(bset![uid], None, &secret);
+        assert!(ssi.can_sign() && ssi.can_encrypt());
+        assert!(!ssi.to_string().contains("usage="));
+        let unrestricted = ssi.to_message();
+
+        ssi.usage = KeyUsage::SIGN;
+        assert_ne!(ssi.to_message(), unrestricted);
+        assert!(ssi.integrity().is_invalid());
+        ssi.resign(&secret).unwrap();
+        assert!(ssi.can_sign() && !ssi.can_encrypt());
+
+        let s = ssi.to_string();
+        assert!(s.contains("&usage=sign&sig="));
+        let parsed = Ssi::from_str(&s).unwrap();
+        assert_eq!(parsed, ssi);
+        assert_eq!(parsed.integrity(), IntegrityStatus::SelfSigned);
+
+        let stripped = s.replace("&usage=sign", "");
+        assert!(matches!(Ssi::from_str(&stripped), Err(SsiParseError::WrongSig(_))));
+        let repeated = s.replace("&usage=sign", "&usage=sign&usage=sign");
+        assert!(matches!(Ssi::from_str(&repeated), Err(SsiParseError::RepeatedUsage)));
+        let invalid = s.replace("&usage=sign", "&usage=sign,certify");
+        assert!(matches!(Ssi::from_str(&invalid), Err(SsiParseError::InvalidQueryParam(..))));
+    }
+
+    #[test]
+    fn fingerprint_ref() {
+        let secret = keygen();
+        let uid = alice_uid();
+        let ssi = Ssi::new(bset![uid.clone()], None, &secret);
+        let fp = ssi.pk.fingerprint();
+
+        let s = format!("ssi:fp:{fp}?uid=Alice+mailto:alice@example.com");
+        assert!(matches!(Ssi::from_str(&s), Err(SsiParseError::FingerprintOnly)));
+        let reference = SsiRef::from_str(&s).unwrap();
+        assert_eq!(reference, SsiRef::Fingerprint {
+            fp,
+            uids: bset![uid.clone()]
+        });
+        assert_eq!(reference.fingerprint(), fp);
+        assert_eq!(reference.as_ssi(), None);
+        assert_eq!(
+            reference.integrity(),
+            IntegrityStatus::SignatureInvalid(InvalidSig::CannotVerify)
+        );
+        assert_eq!(reference.to_string(), s);
+
+        let full = SsiRef::from_str(&ssi.to_string()).unwrap();
+        assert_eq!(full, SsiRef::Full(ssi.clone()));
+        assert_eq!(full.fingerprint(), fp);
+        assert_eq!(full.uids(), reference.uids());
+        assert_eq!(full.integrity(), IntegrityStatus::SelfSigned);
+
+        assert!(matches!(
+            SsiRef::from_str(&format!("{s}&expiry=2030-01-01")),
+            Err(SsiParseError::UnknownParam(..))
+        ));
+        assert!(matches!(
+            SsiRef::from_str("ssi:fp:invalid?uid=Alice+mailto:alice@example.com"),
+            Err(SsiParseError::InvalidFingerprint(_))
+        ));
+    }
+
+    #[test]
+    fn endorsements() {
+        let secret = keygen();
+        let certifier = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        let other = keygen();
+        let uid = alice_uid();
+        let mut ssi = Ssi::new(bset![uid], None, &secret);
+        let msg = ssi.to_message();
+
+        let fp = certifier.to_public().fingerprint();
+        ssi.endorsements.push((fp, certifier.sign(msg)));
+        assert_eq!(ssi.to_message(), msg);
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+
+        let parsed = Ssi::from_str(&ssi.to_string()).unwrap();
+        assert_eq!(parsed, ssi);
+        parsed.verify_endorsement(&certifier.to_public()).unwrap();
+        assert!(matches!(
+            parsed.verify_endorsement(&other.to_public()),
+            Err(VerifyError::NoEndorsement(_))
+        ));
+
+        let repeated = format!("{ssi}&cert={fp}:{}", certifier.sign(msg));
+        assert!(matches!(
+            Ssi::from_str(&repeated),
+            Err(SsiParseError::RepeatedEndorsement(f)) if f == fp
+        ));
+        let malformed = format!("{ssi}&cert={fp}");
+        assert!(matches!(Ssi::from_str(&malformed), Err(SsiParseError::InvalidEndorsement(..))));
+
+        // Endorsements don't survive changes of the signed data
+        let mut expiring = ssi.clone();
+        expiring.set_expiry(Some(Utc::now() + chrono::Duration::days(1)), &secret, true).unwrap();
+        assert!(expiring.endorsements.is_empty());
+        let mut avatar = ssi.clone();
+        avatar.set_avatar([0xAA; 32], None, &secret).unwrap();
+        assert!(avatar.endorsements.is_empty());
+        let (old, new) = ssi.rotate_to(&secret, &other).unwrap();
+        assert!(old.endorsements.is_empty());
+        assert!(new.endorsements.is_empty());
+        // Unless the data stay the same
+        let mut resigned = ssi.clone();
+        resigned.resign(&secret).unwrap();
+        resigned.verify_endorsement(&certifier.to_public()).unwrap();
+
+        // Re-signing keeps the legacy commitment the endorsement is made over
+        let mut legacy = Ssi { commitment: Commitment::Legacy, endorsements: vec![], ..ssi };
+        legacy.resign(&secret).unwrap();
+        legacy.endorsements.push((fp, certifier.sign(legacy.to_message())));
+        let mut resigned = legacy.clone();
+        resigned.resign(&secret).unwrap();
+        assert_eq!(resigned.commitment, Commitment::Legacy);
+        resigned.verify_endorsement(&certifier.to_public()).unwrap();
+        // Changes of the signed data upgrade the commitment
+        legacy.set_expiry(Some(Utc::now() + chrono::Duration::days(1)), &secret, true).unwrap();
+        assert_eq!(legacy.commitment, Commitment::Binary);
+        assert_eq!(legacy.integrity(), IntegrityStatus::SelfSigned);
+    }
+
+    #[test]
+    fn parse_limits() {
+        let secret = keygen();
+        let ssi = Ssi::new(bset![], None, &secret);
+        let mut s = ssi.pk.to_uri_string();
+        let mut sep = '?';
+        for no in 0..100_000 {
+            s.push_str(&format!("{sep}uid=Name+{no}+mailto:{no}@example.com"));
+            sep = '&';
+        }
+        assert!(matches!(Ssi::from_str(&s), Err(SsiParseError::TooLarge)));
+
+        let limits = ParseLimits {
+            max_len: usize::MAX,
+            ..ParseLimits::default()
+        };
+        assert!(matches!(
+            Ssi::from_str_limited(&s, limits),
+            Err(SsiParseError::TooManyUids(64))
+        ));
+
+        let limits = ParseLimits {
+            max_len: usize::MAX,
+            max_uids: 1,
+            max_field_len: 8,
+        };
+        let uid = alice_uid();
+        let ssi = Ssi::new(bset![uid], None, &secret);
+        assert!(matches!(
+            Ssi::from_str_limited(&ssi.to_string(), limits),
+            Err(SsiParseError::TooLarge)
+        ));
+    }
+
+    #[test]
+    fn too_many_uids() {
+        let secret = keygen();
+        let ssi = Ssi::new(bset![], None, &secret);
+        let mut s = ssi.pk.to_uri_string();
+        let mut sep = '?';
+        for no in 0..100 {
+            s.push_str(&format!("{sep}uid=mailto:{no}@example.com"));
+            sep = '&';
+        }
+        assert!(matches!(Ssi::from_str(&s), Err(SsiParseError::TooManyUids(64))));
+
+        let limits = ParseLimits {
+            max_uids: 128,
+            ..ParseLimits::default()
+        };
+        assert!(!matches!(Ssi::from_str_limited(&s, limits), Err(SsiParseError::TooManyUids(_))));
+    }
+
+    #[test]
+    fn display_matrix() {
+        let secret = keygen();
+        let uid = alice_uid();
+        let expiry = parse_expiry("2099-12-31").unwrap();
+        for commitment in [Commitment::Binary, Commitment::Legacy] {
+            for mask in 0u8..8 {
+                let uids = if mask & 1 != 0 { bset![uid.clone()] } else { bset![] };
+                let expiry = (mask & 2 != 0).then_some(expiry);
+                let mut ssi = Ssi::new(uids, expiry, &secret);
+                ssi.commitment = commitment;
+                ssi.sig = (mask & 4 != 0).then(|| secret.sign(ssi.to_message()));
+
+                let s = ssi.to_string();
+                assert!(!s.contains("?&") && !s.contains("&&") && !s.ends_with(['?', '&']), "{s}");
+                assert_eq!(s.contains('?'), mask != 0 || commitment == Commitment::Binary, "{s}");
+                assert_eq!(Ssi::from_str_unsigned(&s).unwrap(), ssi, "{s}");
+            }
+        }
+    }
+
+    #[test]
+    fn display_alt() {
+        let secret = keygen();
+        let uid = alice_uid();
+        let ssi = Ssi::new(bset![uid], None, &secret);
+        let canonical = format!("{ssi}");
+        let alt = format!("{ssi:#}");
+        assert_ne!(canonical, alt);
+        assert_eq!(
+            alt,
+            format!(
+                "fingerprint: {}\nuid (mailto): Alice <alice@example.com>\nexpiry: \
+                 never\nsignature: valid",
+                ssi.pk.fingerprint()
+            )
+        );
+        assert!(format!("{:#}", ssi.without_signature()).ends_with("signature: missing"));
+        assert_eq!(Ssi::from_str(&canonical).unwrap(), ssi);
+        assert!(Ssi::from_str(&alt).is_err());
+    }
+
+    #[test]
+    fn uids_by_schema() {
+        let secret = keygen();
+        let uids = bset![
+            alice_uid(),
+            Uid::from_str("Bob <https:bob.example.com>").unwrap(),
+            Uid::from_str("Carol <mailto:carol@example.com>").unwrap(),
+            Uid::from_str("Dave <https:a.example.com>").unwrap()
+        ];
+        let ssi = Ssi::new(uids, None, &secret);
+        let msg = ssi.to_message();
+
+        let names = ssi.uids_by_schema().map(|uid| uid.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Dave", "Bob", "Alice", "Carol"]);
+        let names = ssi.uids.iter().map(|uid| uid.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Alice", "Bob", "Carol", "Dave"]);
+        assert_eq!(ssi.to_message(), msg);
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+    }
+
+    #[test]
+    fn extend_expiry() {
+        let secret = keygen();
+        let uid = alice_uid();
+        let now = parse_expiry("2025-06-01T00:00:00Z").unwrap();
+        let expired = parse_expiry("2025-01-01").unwrap();
+        let mut ssi = Ssi::new_unchecked(bset![uid], Some(expired), &secret);
+        assert_eq!(ssi.days_until_expiry(now), Some(-150));
+
+        let renewed = parse_expiry("2026-01-01").unwrap();
+        ssi.extend_expiry(Some(renewed), &secret).unwrap();
+        assert_eq!(ssi.expiry, Some(renewed));
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+        assert_eq!(ssi.days_until_expiry(now), Some(214));
+
+        let other = keygen();
+        let orig = ssi.clone();
+        assert_eq!(ssi.extend_expiry(None, &other), Err(RenewError::KeyMismatch));
+        assert_eq!(ssi.extend_expiry(Some(expired), &secret), Err(RenewError::ShortenedExpiry));
+        assert_eq!(ssi, orig);
+        ssi.set_expiry(Some(expired), &secret, true).unwrap();
+        assert_eq!(ssi.expiry, Some(expired));
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+
+        ssi.extend_expiry(None, &secret).unwrap();
+        assert_eq!(ssi.expiry, None);
+        assert_eq!(ssi.days_until_expiry(now), None);
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+        assert_eq!(ssi.extend_expiry(Some(renewed), &secret), Err(RenewError::ShortenedExpiry));
+    }
+
+    #[test]
+    fn resign_key_mismatch() {
+        let secret = keygen();
+        let other = keygen();
+        let uid = alice_uid();
+        let mut ssi = Ssi::new(bset![uid], None, &secret);
+        let sig = ssi.sig;
+        assert_eq!(ssi.resign(&other), Err(SignerError::KeyMismatch));
+        assert_eq!(ssi.sig, sig);
+    }
+}
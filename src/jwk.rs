@@ -0,0 +1,163 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! JSON Web Key (RFC 7517) representation of identity public keys.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde_json::{json, Value};
+
+use crate::{Algo, Chain, SsiPub};
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum JwkError {
+    /// {0} keys have no JWK representation.
+    UnsupportedAlgo(Algo),
+    /// JWK lacks required field '{0}'.
+    NoField(&'static str),
+    /// JWK key type '{0}' with curve '{1}' is not supported.
+    UnsupportedKey(String, String),
+    /// JWK contains invalid coordinate value.
+    InvalidCoordinate,
+    /// JWK contains invalid public key.
+    InvalidPubkey,
+    /// JWK key id '{0}' doesn't match the key fingerprint.
+    KidMismatch(String),
+}
+
+fn coordinate(jwk: &Value, field: &'static str) -> Result<[u8; 32], JwkError> {
+    let value = jwk[field].as_str().ok_or(JwkError::NoField(field))?;
+    let data = URL_SAFE_NO_PAD
+        .decode(value)
+        .map_err(|_| JwkError::InvalidCoordinate)?;
+    <[u8; 32]>::try_from(data).map_err(|_| JwkError::InvalidCoordinate)
+}
+
+impl SsiPub {
+    /// Represents the key as JWK: Ed25519 keys as `OKP` and ECDSA keys as
+    /// `EC` on the `secp256k1` curve. The key id (`kid`) is the key
+    /// fingerprint.
+    ///
+    /// BIP340 keys have no standard JWK representation and are not
+    /// supported.
+    pub fn to_jwk(&self) -> Result<Value, JwkError> {
+        let kid = self.fingerprint().to_string();
+        let x = URL_SAFE_NO_PAD.encode(self.to_byte_array());
+        match self.algo() {
+            Algo::Ed25519 => Ok(json!({ "kty": "OKP", "crv": "Ed25519", "x": x, "kid": kid })),
+            Algo::Ecdsa => {
+                let pk = secp256k1::PublicKey::try_from(*self)
+                    .map_err(|_| JwkError::InvalidPubkey)?;
+                let y = URL_SAFE_NO_PAD.encode(&pk.serialize_uncompressed()[33..]);
+                Ok(json!({ "kty": "EC", "crv": "secp256k1", "x": x, "y": y, "kid": kid }))
+            }
+            algo => Err(JwkError::UnsupportedAlgo(*algo)),
+        }
+    }
+
+    /// Parses key from JWK, checking that the key id (`kid`), if present,
+    /// matches the key fingerprint.
+    ///
+    /// Since JWK doesn't commit to a chain, the key is returned for the
+    /// default chain.
+    pub fn from_jwk(jwk: &Value) -> Result<Self, JwkError> {
+        let kty = jwk["kty"].as_str().ok_or(JwkError::NoField("kty"))?;
+        let crv = jwk["crv"].as_str().ok_or(JwkError::NoField("crv"))?;
+        let x = coordinate(jwk, "x")?;
+        let pk = match (kty, crv) {
+            ("OKP", "Ed25519") => SsiPub::with(Chain::default(), Algo::Ed25519, x),
+            ("EC", "secp256k1") => {
+                let y = coordinate(jwk, "y")?;
+                // Identity keys always have even Y coordinate
+                if y[31] & 1 != 0 {
+                    return Err(JwkError::InvalidPubkey);
+                }
+                let pk = SsiPub::with(Chain::default(), Algo::Ecdsa, x);
+                let point = secp256k1::PublicKey::try_from(pk)
+                    .map_err(|_| JwkError::InvalidPubkey)?;
+                if point.serialize_uncompressed()[33..] != y {
+                    return Err(JwkError::InvalidPubkey);
+                }
+                pk
+            }
+            _ => return Err(JwkError::UnsupportedKey(kty.to_owned(), crv.to_owned())),
+        };
+        if let Some(kid) = jwk.get("kid") {
+            if kid.as_str() != Some(pk.fingerprint().to_string().as_str()) {
+                return Err(JwkError::KidMismatch(kid.to_string()));
+            }
+        }
+        Ok(pk)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SsiSecret;
+
+    #[test]
+    fn ed25519() {
+        let pk = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin).to_public();
+        let jwk = pk.to_jwk().unwrap();
+        assert_eq!(jwk["kty"], "OKP");
+        assert_eq!(jwk["crv"], "Ed25519");
+        assert_eq!(jwk["kid"], pk.fingerprint().to_string());
+        assert_eq!(jwk.get("y"), None);
+        assert_eq!(SsiPub::from_jwk(&jwk).unwrap(), pk);
+    }
+
+    #[test]
+    fn secp256k1() {
+        let pk = SsiSecret::new(Algo::Ecdsa, Chain::Bitcoin).to_public();
+        let jwk = pk.to_jwk().unwrap();
+        assert_eq!(jwk["kty"], "EC");
+        assert_eq!(jwk["crv"], "secp256k1");
+        assert_eq!(jwk["kid"], pk.fingerprint().to_string());
+        assert_eq!(SsiPub::from_jwk(&jwk).unwrap(), pk);
+
+        let mut wrong_y = jwk.clone();
+        wrong_y["y"] = Value::from(URL_SAFE_NO_PAD.encode([0u8; 32]));
+        assert_eq!(SsiPub::from_jwk(&wrong_y), Err(JwkError::InvalidPubkey));
+    }
+
+    #[test]
+    fn kid() {
+        let pk = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin).to_public();
+        let other = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin).to_public();
+        let mut jwk = pk.to_jwk().unwrap();
+        jwk["kid"] = Value::from(other.fingerprint().to_string());
+        assert!(matches!(SsiPub::from_jwk(&jwk), Err(JwkError::KidMismatch(_))));
+
+        jwk.as_object_mut().unwrap().remove("kid");
+        assert_eq!(SsiPub::from_jwk(&jwk).unwrap(), pk);
+    }
+
+    #[test]
+    fn unsupported() {
+        let pk = SsiSecret::new(Algo::Bip340, Chain::Bitcoin).to_public();
+        assert_eq!(pk.to_jwk(), Err(JwkError::UnsupportedAlgo(Algo::Bip340)));
+        let x = URL_SAFE_NO_PAD.encode([1u8; 32]);
+        let jwk = json!({ "kty": "EC", "crv": "P-256", "x": x, "y": x });
+        assert!(matches!(SsiPub::from_jwk(&jwk), Err(JwkError::UnsupportedKey(_, _))));
+    }
+}
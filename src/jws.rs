@@ -0,0 +1,234 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compact JSON Web Signatures (RFC 7515) made with identity keys.
+//!
+//! Ed25519 keys sign with `EdDSA` and ECDSA keys with `ES256K` algorithms;
+//! BIP340 has no registered JWS algorithm and is not supported. The signer is
+//! identified by its `did:key` in the `kid` header field.
+
+use std::str::FromStr;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{Algo, DidError, InvalidSig, SsiPair, SsiPub, SsiSecret, SsiSig};
+
+pub const JWS_ALG_EDDSA: &str = "EdDSA";
+pub const JWS_ALG_ES256K: &str = "ES256K";
+
+#[derive(Debug, Display, From)]
+#[display(doc_comments)]
+pub enum JwsError {
+    /// {0} keys can't be used for JWS signatures.
+    UnsupportedAlgo(Algo),
+    /// JWS must consist of three dot-separated parts.
+    Malformed,
+    /// JWS uses unsupported algorithm '{0}'.
+    UnknownAlg(String),
+    /// JWS header doesn't identify the signer key.
+    NoKid,
+    /// JWS algorithm '{0}' doesn't match the signer key.
+    AlgMismatch(String),
+
+    #[from]
+    /// JWS has invalid base64 encoding - {0}
+    Base64(base64::DecodeError),
+
+    #[from]
+    /// JWS has invalid JSON header - {0}
+    Json(serde_json::Error),
+
+    #[from]
+    #[display(inner)]
+    Did(DidError),
+
+    #[from]
+    /// invalid JWS signature - {0}
+    InvalidSig(InvalidSig),
+}
+
+impl std::error::Error for JwsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            JwsError::Base64(err) => Some(err),
+            JwsError::Json(err) => Some(err),
+            JwsError::Did(err) => Some(err),
+            JwsError::InvalidSig(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub struct JwsHeader {
+    pub alg: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub typ: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub kid: Option<String>,
+}
+
+/// Parsed compact JWS.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Jws {
+    pub header: JwsHeader,
+    pub payload: Vec<u8>,
+    signing_input: String,
+    sig: SsiSig,
+}
+
+fn b64(data: impl AsRef<[u8]>) -> String { URL_SAFE_NO_PAD.encode(data) }
+
+impl Jws {
+    /// Parses JWS with a detached payload (RFC 7515 appendix F), i.e. with an
+    /// empty payload part, which is replaced with the provided `payload`.
+    pub fn from_detached(s: &str, payload: &[u8]) -> Result<Self, JwsError> {
+        match s.split('.').collect::<Vec<_>>()[..] {
+            [header, "", sig] => Self::from_str(&format!("{header}.{}.{sig}", b64(payload))),
+            _ => Err(JwsError::Malformed),
+        }
+    }
+
+    /// Returns key of the signer, as specified in the `kid` header field.
+    ///
+    /// NB: The key is not authenticated by the JWS itself; the caller must
+    /// check that it belongs to a trusted party.
+    pub fn signer(&self) -> Result<SsiPub, JwsError> {
+        let kid = self.header.kid.as_deref().ok_or(JwsError::NoKid)?;
+        Ok(SsiPub::from_did_key(kid)?)
+    }
+
+    /// Verifies JWS signature against the key from the `kid` header field,
+    /// returning the key.
+    pub fn verify(&self) -> Result<SsiPub, JwsError> {
+        let pk = self.signer()?;
+        self.verify_with(pk)?;
+        Ok(pk)
+    }
+
+    /// Verifies JWS signature against the given key.
+    pub fn verify_with(&self, pk: SsiPub) -> Result<(), JwsError> {
+        let input = self.signing_input.as_bytes();
+        match (self.header.alg.as_str(), pk.algo()) {
+            (JWS_ALG_EDDSA, Algo::Ed25519) => pk.verify_ed25519_message(input, self.sig)?,
+            (JWS_ALG_ES256K, Algo::Ecdsa) => {
+                pk.verify_ecdsa(Sha256::digest(input).into(), self.sig)?
+            }
+            (alg @ (JWS_ALG_EDDSA | JWS_ALG_ES256K), _) => {
+                return Err(JwsError::AlgMismatch(alg.to_owned()))
+            }
+            (alg, _) => return Err(JwsError::UnknownAlg(alg.to_owned())),
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Jws {
+    type Err = JwsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [header, payload, sig] = s.split('.').collect::<Vec<_>>()[..] else {
+            return Err(JwsError::Malformed);
+        };
+        let sig = <[u8; 64]>::try_from(URL_SAFE_NO_PAD.decode(sig)?)
+            .map_err(|_| InvalidSig::InvalidData)?;
+        Ok(Jws {
+            header: serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header)?)?,
+            payload: URL_SAFE_NO_PAD.decode(payload)?,
+            signing_input: format!("{header}.{payload}"),
+            sig: SsiSig::from(sig),
+        })
+    }
+}
+
+impl SsiPair {
+    /// Signs `payload` as a compact JWS, putting signer `did:key` into the
+    /// `kid` header field.
+    pub fn sign_jws(&self, payload: &[u8]) -> Result<String, JwsError> {
+        self.sign_jws_typed(None, payload)
+    }
+
+    /// Signs `payload` as a compact JWS with a given `typ` header field.
+    pub fn sign_jws_typed(&self, typ: Option<&str>, payload: &[u8]) -> Result<String, JwsError> {
+        let alg = match self.pk.algo() {
+            Algo::Ed25519 => JWS_ALG_EDDSA,
+            Algo::Ecdsa => JWS_ALG_ES256K,
+            algo => return Err(JwsError::UnsupportedAlgo(*algo)),
+        };
+        let header = JwsHeader {
+            alg: alg.to_owned(),
+            typ: typ.map(str::to_owned),
+            kid: Some(self.pk.to_did_key()?),
+        };
+        let input = format!("{}.{}", b64(serde_json::to_vec(&header)?), b64(payload));
+        let sig = match &self.sk {
+            SsiSecret::Ed25519(sk) => sk.sign_message(&input),
+            SsiSecret::Ecdsa(sk) => sk.sign(Sha256::digest(&input).into()),
+            SsiSecret::Bip340(_) => return Err(JwsError::UnsupportedAlgo(Algo::Bip340)),
+        };
+        Ok(format!("{input}.{}", b64(sig.to_byte_array())))
+    }
+
+    /// Signs `payload` as a compact JWS with a detached payload (RFC 7515
+    /// appendix F). Use [`Jws::from_detached`] to verify it.
+    pub fn sign_jws_detached(&self, payload: &[u8]) -> Result<String, JwsError> {
+        let jws = self.sign_jws(payload)?;
+        let (header, rest) = jws.split_once('.').expect("JWS always has three parts");
+        let (_, sig) = rest.split_once('.').expect("JWS always has three parts");
+        Ok(format!("{header}..{sig}"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Chain;
+
+    #[test]
+    fn sign_verify() {
+        for algo in [Algo::Ed25519, Algo::Ecdsa] {
+            let pair = SsiPair::from(SsiSecret::new(algo, Chain::Bitcoin));
+            let jws = pair.sign_jws(b"{\"claim\":42}").unwrap();
+            let parsed = Jws::from_str(&jws).unwrap();
+            assert_eq!(parsed.payload, b"{\"claim\":42}");
+            assert_eq!(parsed.verify().unwrap(), pair.pk);
+
+            let (header, rest) = jws.split_once('.').unwrap();
+            let (_, sig) = rest.split_once('.').unwrap();
+            let forged = format!("{header}.{}.{sig}", b64(b"{\"claim\":43}"));
+            assert!(matches!(
+                Jws::from_str(&forged).unwrap().verify(),
+                Err(JwsError::InvalidSig(InvalidSig::InvalidSig))
+            ));
+
+            let detached = pair.sign_jws_detached(b"payload").unwrap();
+            Jws::from_detached(&detached, b"payload").unwrap().verify().unwrap();
+            assert!(Jws::from_detached(&detached, b"other").unwrap().verify().is_err());
+        }
+
+        let pair = SsiPair::from(SsiSecret::new(Algo::Bip340, Chain::Bitcoin));
+        assert!(matches!(pair.sign_jws(b""), Err(JwsError::UnsupportedAlgo(Algo::Bip340))));
+        assert!(matches!(Jws::from_str("a.b"), Err(JwsError::Malformed)));
+    }
+}
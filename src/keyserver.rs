@@ -0,0 +1,231 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Retrieval and publication of identities on keyservers over HTTP.
+
+use std::io::Read;
+use std::str::FromStr;
+use std::time::Duration;
+
+use url::Url;
+
+use crate::{InvalidSig, Ssi, SsiParseError, SsiQuery, SsiRuntime};
+
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_RESPONSE_LEN: u64 = 64 * 1024;
+
+#[derive(Debug, Display, From)]
+#[display(doc_comments)]
+pub enum FetchError {
+    /// identity query '{0}' can't be resolved into a fingerprint.
+    InvalidQuery(SsiQuery),
+
+    /// the identity is not found on the keyserver.
+    NotFound,
+
+    /// the keyserver has returned malformed identity - {0}
+    #[from]
+    Malformed(SsiParseError),
+
+    /// the keyserver has returned identity without a self-signature.
+    Unsigned,
+
+    /// the keyserver has returned a different identity.
+    Mismatch,
+
+    /// keyserver request has failed - {0}
+    Network(String),
+}
+
+impl std::error::Error for FetchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FetchError::Malformed(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(doc_comments)]
+pub enum PublishError {
+    /// identity is not signed and can't be published.
+    Unsigned,
+
+    /// identity has invalid self-signature - {0}
+    Invalid(InvalidSig),
+
+    /// the keyserver has rejected the identity with HTTP status {status}.
+    Rejected { status: u16 },
+
+    /// keyserver request has failed - {0}
+    Network(String),
+}
+
+impl std::error::Error for PublishError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PublishError::Invalid(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl SsiRuntime {
+    /// Fetches identity from a keyserver by its fingerprint, using
+    /// `{url}/ssi/{fingerprint}` endpoint. The retrieved identity is verified
+    /// and stored in the runtime.
+    pub fn fetch(&mut self, query: &SsiQuery, url: &Url) -> Result<Ssi, FetchError> {
+        let fp = match query {
+            SsiQuery::Pub(pk) => pk.fingerprint(),
+            SsiQuery::Fp(fp) => *fp,
+            _ => return Err(FetchError::InvalidQuery(query.clone())),
+        };
+
+        let url = format!("{}/ssi/{fp}", url.as_str().trim_end_matches('/'));
+        let agent = ureq::AgentBuilder::new().timeout(FETCH_TIMEOUT).build();
+        let resp = match agent.get(&url).call() {
+            Ok(resp) => resp,
+            Err(ureq::Error::Status(404, _)) => return Err(FetchError::NotFound),
+            Err(err) => return Err(FetchError::Network(err.to_string())),
+        };
+        let mut body = String::new();
+        resp.into_reader()
+            .take(MAX_RESPONSE_LEN)
+            .read_to_string(&mut body)
+            .map_err(|err| FetchError::Network(err.to_string()))?;
+
+        let ssi = match Ssi::from_str(body.trim()) {
+            Ok(ssi) => ssi,
+            Err(SsiParseError::Unsigned) => return Err(FetchError::Unsigned),
+            Err(err) => return Err(err.into()),
+        };
+        if ssi.pk.fingerprint() != fp || matches!(query, SsiQuery::Pub(pk) if *pk != ssi.pk) {
+            return Err(FetchError::Mismatch);
+        }
+
+        self.identities.retain(|known| known.pk != ssi.pk);
+        self.identities.insert(ssi.clone());
+        Ok(ssi)
+    }
+
+    /// Publishes identity to a keyserver, using `{url}/ssi/{fingerprint}`
+    /// endpoint. Only signed identities passing integrity check are published;
+    /// secret keys never leave the runtime since [`Ssi`] carries just the
+    /// public key.
+    pub fn publish(&self, ssi: &Ssi, url: &Url) -> Result<(), PublishError> {
+        match ssi.integrity().into_result() {
+            Ok(true) => {}
+            Ok(false) => return Err(PublishError::Unsigned),
+            Err(err) => return Err(PublishError::Invalid(err)),
+        }
+
+        let url = format!("{}/ssi/{}", url.as_str().trim_end_matches('/'), ssi.pk.fingerprint());
+        let agent = ureq::AgentBuilder::new().timeout(FETCH_TIMEOUT).build();
+        match agent
+            .put(&url)
+            .set("Content-Type", "text/plain")
+            .send_string(&ssi.to_string())
+        {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(status, _)) => Err(PublishError::Rejected { status }),
+            Err(err) => Err(PublishError::Network(err.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+    use crate::{Algo, Chain, SsiSecret};
+
+    fn serve(status: &'static str, body: String) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf).unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        });
+        Url::parse(&format!("http://{addr}")).unwrap()
+    }
+
+    #[test]
+    fn fetch() {
+        let secret = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let ssi = Ssi::new(bset![], None, &secret);
+        let query = SsiQuery::Fp(ssi.pk.fingerprint());
+
+        let mut runtime = SsiRuntime::default();
+        let url = serve("200 OK", ssi.to_string());
+        assert_eq!(runtime.fetch(&query, &url).unwrap(), ssi);
+        assert_eq!(runtime.find_identity(ssi.pk), Some(&ssi));
+
+        let url = serve("404 Not Found", s!(""));
+        assert!(matches!(runtime.fetch(&query, &url), Err(FetchError::NotFound)));
+
+        let url = serve("200 OK", s!("garbage"));
+        assert!(matches!(runtime.fetch(&query, &url), Err(FetchError::Malformed(_))));
+
+        let other = Ssi::new(bset![], None, &SsiSecret::new(Algo::Ed25519, Chain::Bitcoin));
+        let url = serve("200 OK", other.to_string());
+        assert!(matches!(runtime.fetch(&query, &url), Err(FetchError::Mismatch)));
+
+        assert!(matches!(
+            runtime.fetch(&SsiQuery::Default, &url),
+            Err(FetchError::InvalidQuery(SsiQuery::Default))
+        ));
+    }
+
+    #[test]
+    fn publish() {
+        let secret = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let ssi = Ssi::new(bset![], None, &secret);
+        let runtime = SsiRuntime::default();
+
+        let url = serve("201 Created", s!(""));
+        runtime.publish(&ssi, &url).unwrap();
+
+        let url = serve("403 Forbidden", s!(""));
+        assert!(matches!(
+            runtime.publish(&ssi, &url),
+            Err(PublishError::Rejected { status: 403 })
+        ));
+
+        let mut unsigned = ssi.clone();
+        unsigned.sig = None;
+        assert!(matches!(runtime.publish(&unsigned, &url), Err(PublishError::Unsigned)));
+
+        let mut forged = ssi.clone();
+        forged.sig = Ssi::new(bset![], None, &SsiSecret::new(Algo::Ed25519, Chain::Bitcoin)).sig;
+        assert!(matches!(runtime.publish(&forged, &url), Err(PublishError::Invalid(_))));
+    }
+}
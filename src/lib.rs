@@ -26,22 +26,104 @@ extern crate strict_encoding;
 
 mod encrypt;
 mod identity;
+mod card;
 mod secret;
 mod public;
 mod bip340;
 mod ed25519;
+mod ecdsa;
+#[cfg(feature = "openpgp")]
+mod openpgp;
+#[cfg(feature = "nostr")]
+mod nostr;
+#[cfg(feature = "address")]
+mod address;
+#[cfg(feature = "qr")]
+mod qr;
+mod ots;
+mod pgp;
+mod schema;
+mod vcard;
+#[cfg(feature = "keyserver")]
+mod keyserver;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(feature = "serde")]
+mod jwk;
+#[cfg(feature = "vc")]
+mod did;
+#[cfg(feature = "vc")]
+mod jws;
+#[cfg(feature = "vc")]
+mod vc;
+#[cfg(feature = "vc")]
+mod sdjwt;
+#[cfg(feature = "didcomm")]
+mod didcomm;
+#[cfg(feature = "proptest")]
+mod arbitrary;
 
+mod revocation;
 mod runtime;
+mod sigfile;
 
-pub use bip340::Bip340Secret;
-pub use ed25519::Ed25519Secret;
-pub use encrypt::{decrypt, encrypt, DecryptionError, Encrypted, EncryptionError, SymmetricKey};
-pub use identity::{Ssi, SsiParseError, Uid, UidParseError};
+#[cfg(feature = "address")]
+pub use address::AddressError;
+#[cfg(feature = "proptest")]
+pub use arbitrary::{arb_chain, arb_expiry, arb_secret, arb_ssi, arb_ssi_pub, arb_uid};
+pub use bip340::{AggError, Bip340Secret, InvalidSecret};
+pub use card::{CardError, IdentityCard, CARD_MAX_UIDS};
+pub use ecdsa::{EcdsaSecret, RecoverableSig};
+pub use ed25519::{ContextTooLong, Ed25519Secret};
+pub use encrypt::{
+    decrypt, encrypt, DecryptionError, Encrypted, EncryptionError, KeyWrap, RecipientKind,
+    SymmetricKey, PASSWORD_ROUNDS,
+};
+#[cfg(feature = "vc")]
+pub use did::DidError;
+#[cfg(feature = "didcomm")]
+pub use didcomm::{pack_encrypted, unpack, DidCommError, Unpacked, DIDCOMM_ENCRYPTED_TYP};
+pub use identity::{
+    identity_message_hash, parse_expiry, verify_rotation, Avatar, CommentError, Commitment,
+    DiffKeyMismatch, IntegrityStatus, KeyLink, KeyUsage, MergeError, ParseLimits, ParseMode,
+    RenewError, RotationError, ServiceError, ServiceUri, SignedSubkey, Ssi, SsiBuilder, SsiDiff,
+    SsiParseError, SsiRef, SubkeyError, Uid, UidEditError, UidEncoding, UidParseError,
+    UnknownKeyUsage, UnsignedMode, Validity, SSI_COMMENT_MAX_LEN, SSI_COMMITMENT_TAG,
+    SSI_POW_CANCEL_INTERVAL, SSI_REF_FP_PREFIX, SSI_SERVICES_MAX, SSI_SERVICE_URI_MAX_LEN,
+    SSI_SUBKEYS_MAX, SSI_SUBKEY_TAG,
+};
+#[cfg(feature = "nostr")]
+pub use nostr::{NostrError, NOSTR_SCHEMA};
+#[cfg(feature = "qr")]
+pub use qr::QrKeyError;
+#[cfg(feature = "openpgp")]
+pub use openpgp::{OpenPgpError, OpenPgpImportError, OpenPgpKey};
+#[cfg(feature = "keyserver")]
+pub use keyserver::{FetchError, PublishError, FETCH_TIMEOUT};
+#[cfg(feature = "serde")]
+pub use jwk::JwkError;
+#[cfg(feature = "vc")]
+pub use jws::{Jws, JwsError, JwsHeader};
+pub use ots::{OtsAttestation, OtsError, OtsParseError, OtsProof};
+pub use pgp::{InvalidPgpFingerprint, PgpFingerprint, PGP_SCHEMA};
 pub use public::{
-    Algo, CertParseError, Chain, Fingerprint, InvalidPubkey, InvalidSig, SsiCert, SsiPub, SsiQuery,
-    SsiSig, UnknownAlgo, UnknownChain, VerifyError,
+    Algo, Baid64Error, Baid64ErrorKind, CertChain, CertParseError, Chain, Fingerprint,
+    FingerprintScheme, InvalidPrefix, InvalidPubkey, InvalidSig, InvalidTag, KeyFingerprint,
+    KeyResolver, SsiCert, SsiPub, SsiPubPrefix, SsiQuery, SsiSig, UnknownAlgo, UnknownChain,
+    VerifyError, VerifyingIdentity, SSI_PUB_PREFIX, SSI_SIGNED_AT_TAG, SSI_SIG_PREFIX,
+};
+pub use revocation::{RevocationReason, RevocationSet, SSI_REVOCATION_TAG};
+pub use runtime::{
+    AuditEvent, AuditSink, LoadError, SignerError, SsiRuntime, VerifyFileError, SSI_DIR,
+};
+pub use schema::{UidSchema, UidValidationError};
+#[cfg(feature = "vc")]
+pub use sdjwt::{SdJwt, SdJwtError, KB_JWT_TYP, SD_JWT_TYP};
+pub use secret::{
+    EncryptedSecret, RevealError, SecretParseError, SigningIdentity, SsiPair, SsiSecret,
 };
-pub use runtime::{LoadError, SignerError, SsiRuntime, SSI_DIR};
-pub use secret::{EncryptedSecret, RevealError, SecretParseError, SsiPair, SsiSecret};
+pub use sigfile::{SigFileError, SIG_FILE_BEGIN, SIG_FILE_END};
+#[cfg(feature = "vc")]
+pub use vc::{Credential, Presentation, Proof, VcError, VC_CONTEXT};
 
 pub const LIB_NAME_SSI: &str = "SSI";
@@ -31,11 +31,11 @@ use std::str::FromStr;
 use std::{fs, io};
 
 use armor::{ArmorParseError, AsciiArmor};
-use chrono::{DateTime, Utc};
+use chrono::Utc;
 use clap::Parser;
 use ssi::{
-    Algo, Chain, DecryptionError, Encrypted, EncryptionError, InvalidSig, LoadError, SignerError,
-    Ssi, SsiCert, SsiQuery, SsiRuntime, SsiSecret, Uid, UidParseError,
+    parse_expiry, Algo, Chain, DecryptionError, Encrypted, EncryptionError, InvalidSig, LoadError,
+    SignerError, Ssi, SsiCert, SsiQuery, SsiRuntime, SsiSecret, Uid, UidParseError, Validity,
 };
 
 #[derive(Parser, Clone, Debug)]
@@ -74,7 +74,8 @@ pub enum Command {
         #[clap(long, required_unless_present = "expiry")]
         no_expiry: bool,
 
-        /// Set expiration date for the identity (in YYYY-MM-DD format)
+        /// Set expiration date for the identity (in YYYY-MM-DD format, meaning
+        /// the end of the day in UTC, or as RFC 3339 timestamp)
         #[clap(conflicts_with = "no_expiry", required_unless_present = "no_expiry")]
         expiry: Option<String>,
     },
@@ -210,13 +211,16 @@ fn exec(command: Command) -> Result<(), CliError> {
                     Some(e) => print!("{}", e.format("%Y-%m-%d")),
                 }
                 print!("\t");
-                match ssi.check_integrity() {
-                    Ok(_) if ssi.expiry >= Some(now) => println!("expired"),
-                    Ok(_) => println!("valid"),
-                    Err(InvalidSig::InvalidPubkey) => println!("invalid pubkey"),
-                    Err(InvalidSig::InvalidSig) => println!("invalid"),
-                    Err(InvalidSig::InvalidData) => println!("broken"),
-                    Err(InvalidSig::UnsupportedAlgo(_)) => println!("unsupported"),
+                match ssi.validity(now) {
+                    Validity::Valid => println!("valid"),
+                    Validity::Expired { .. } => println!("expired"),
+                    Validity::NotSigned => println!("unsigned"),
+                    Validity::BadSignature(InvalidSig::InvalidPubkey) => println!("invalid pubkey"),
+                    Validity::BadSignature(InvalidSig::InvalidData) => println!("broken"),
+                    Validity::BadSignature(InvalidSig::UnsupportedAlgo(_)) => {
+                        println!("unsupported")
+                    }
+                    Validity::BadSignature(_) => println!("invalid"),
                 }
                 for uid in &ssi.uids {
                     println!("\t{uid}");
@@ -235,12 +239,7 @@ fn exec(command: Command) -> Result<(), CliError> {
             uid,
         } => {
             let expiry = expiry
-                .map(|expiry| {
-                    DateTime::parse_from_str(&expiry, "%Y-%m-%d")
-                        .as_ref()
-                        .map(DateTime::to_utc)
-                        .map_err(|_| CliError::InvalidExpiry)
-                })
+                .map(|expiry| parse_expiry(&expiry).map_err(|_| CliError::InvalidExpiry))
                 .transpose()?;
             let uids = uid
                 .iter()
@@ -257,7 +256,7 @@ fn exec(command: Command) -> Result<(), CliError> {
                 None => SsiSecret::new(algo, chain),
             };
 
-            let ssi = Ssi::new(uids, expiry, &secret);
+            let ssi = Ssi::try_new(uids, expiry, &secret, Utc::now())?;
             println!("{ssi}");
 
             runtime.secrets.insert(secret.conceal(passwd));
@@ -276,10 +275,11 @@ fn exec(command: Command) -> Result<(), CliError> {
 
             let passwd = rpassword::prompt_password("Password for the private key: ")
                 .map_err(CliError::Password)?;
-            let signer = runtime.find_signer(ssi, &passwd)?;
-            eprintln!("Using key {signer}");
             let msg = get_message(text, file)?;
-            let cert = signer.sign(msg);
+            let cert = runtime.sign(ssi, &passwd, msg)?;
+            if let Some(signer) = runtime.find_identity(cert.fp) {
+                eprintln!("Used key {signer}");
+            }
             if full {
                 println!("{cert:#}");
             } else {
@@ -294,7 +294,7 @@ fn exec(command: Command) -> Result<(), CliError> {
                 .map(|ssi| ssi.pk)
                 .or(signature.pk)
                 .ok_or(SignerError::UnknownIdentity)?;
-            match pk.verify(signature.msg.to_byte_array(), signature.sig) {
+            match pk.verify(signature.signed_message(), signature.sig) {
                 Ok(_) => eprintln!("valid"),
                 Err(err) => eprintln!("invalid: {err}"),
             }
@@ -305,11 +305,9 @@ fn exec(command: Command) -> Result<(), CliError> {
                 .map_err(CliError::Password)?;
             let mut identities = BTreeSet::new();
             for mut ssi in runtime.identities.iter().cloned() {
-                let Ok(secret) = runtime.find_signer(ssi.pk.fingerprint(), &passwd) else {
-                    identities.insert(ssi);
-                    continue;
-                };
-                ssi.sig = Some(secret.sk.sign(ssi.to_message()));
+                // identities without a matching secret or with a different password are kept
+                // as they are
+                let _ = runtime.sign_identity(&mut ssi, &passwd);
                 identities.insert(ssi);
             }
             runtime.identities = identities;
@@ -326,11 +324,10 @@ fn exec(command: Command) -> Result<(), CliError> {
                 .map(|query| {
                     runtime
                         .find_identity(query.clone())
-                        .map(|i| i.pk)
                         .ok_or(SignerError::UnknownIdentity)
                 })
                 .collect::<Result<Vec<_>, _>>()?;
-            let encrypted = Encrypted::encrypt(msg, receivers)?;
+            let encrypted = Encrypted::encrypt_to(msg, receivers)?;
             println!("{encrypted}");
         }
         Command::Decrypt { key, text, file } => {
@@ -0,0 +1,229 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversion of BIP340 identities into Nostr NIP-19 entities.
+
+use bech32::{Bech32, Hrp};
+
+use crate::{Algo, Chain, Ssi, SsiPub, Uid};
+
+pub const NOSTR_SCHEMA: &str = "nostr";
+
+const HRP_NPUB: Hrp = Hrp::parse_unchecked("npub");
+const HRP_NPROFILE: Hrp = Hrp::parse_unchecked("nprofile");
+
+const TLV_SPECIAL: u8 = 0;
+const TLV_RELAY: u8 = 1;
+
+#[derive(Clone, Debug, Display, From)]
+#[display(doc_comments)]
+pub enum NostrError {
+    /// Nostr keys must use BIP340 algorithm, while {0} key is provided.
+    UnsupportedAlgo(Algo),
+
+    #[from]
+    /// invalid bech32 encoding - {0}
+    Bech32(bech32::DecodeError),
+
+    /// unable to encode data with bech32 - {0}
+    Encode(String),
+
+    /// invalid NIP-19 entity type '{0}'.
+    InvalidHrp(String),
+
+    /// invalid NIP-19 data.
+    InvalidData,
+
+    /// nostr UID doesn't match the identity key.
+    KeyMismatch,
+}
+
+impl std::error::Error for NostrError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NostrError::Bech32(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl SsiPub {
+    /// Encodes BIP340 public key as Nostr `npub`.
+    pub fn to_npub(&self) -> Result<String, NostrError> {
+        self.check_nostr()?;
+        bech32::encode::<Bech32>(HRP_NPUB, &self.to_byte_array())
+            .map_err(|e| NostrError::Encode(e.to_string()))
+    }
+
+    /// Encodes BIP340 public key as Nostr `nprofile`, providing list of relay
+    /// hints.
+    pub fn to_nprofile(&self, relays: &[&str]) -> Result<String, NostrError> {
+        self.check_nostr()?;
+        let mut data = vec![TLV_SPECIAL, 32];
+        data.extend(self.to_byte_array());
+        for relay in relays {
+            let len = u8::try_from(relay.len()).map_err(|_| NostrError::InvalidData)?;
+            data.push(TLV_RELAY);
+            data.push(len);
+            data.extend(relay.as_bytes());
+        }
+        bech32::encode::<Bech32>(HRP_NPROFILE, &data).map_err(|e| NostrError::Encode(e.to_string()))
+    }
+
+    /// Parses Nostr `npub` into a BIP340 identity key. Since Nostr keys are
+    /// not bound to a chain, [`Chain::Bitcoin`] is used.
+    pub fn from_npub(s: &str) -> Result<Self, NostrError> {
+        let (hrp, data) = bech32::decode(s)?;
+        if hrp != HRP_NPUB {
+            return Err(NostrError::InvalidHrp(hrp.to_string()));
+        }
+        let key = <[u8; 32]>::try_from(data).map_err(|_| NostrError::InvalidData)?;
+        Ok(SsiPub::with(Chain::Bitcoin, Algo::Bip340, key))
+    }
+
+    /// Parses Nostr `nprofile` into a BIP340 identity key and a list of relay
+    /// hints.
+    pub fn from_nprofile(s: &str) -> Result<(Self, Vec<String>), NostrError> {
+        let (hrp, data) = bech32::decode(s)?;
+        if hrp != HRP_NPROFILE {
+            return Err(NostrError::InvalidHrp(hrp.to_string()));
+        }
+        let mut key = None;
+        let mut relays = vec![];
+        let mut data = data.as_slice();
+        while !data.is_empty() {
+            let [kind, len, ..] = *data else {
+                return Err(NostrError::InvalidData);
+            };
+            let len = len as usize;
+            let value = data.get(2..2 + len).ok_or(NostrError::InvalidData)?;
+            match kind {
+                TLV_SPECIAL => {
+                    key = Some(<[u8; 32]>::try_from(value).map_err(|_| NostrError::InvalidData)?)
+                }
+                TLV_RELAY => relays.push(String::from_utf8_lossy(value).to_string()),
+                // Unknown TLV types must be ignored according to NIP-19
+                _ => {}
+            }
+            data = &data[2 + len..];
+        }
+        let key = key.ok_or(NostrError::InvalidData)?;
+        Ok((SsiPub::with(Chain::Bitcoin, Algo::Bip340, key), relays))
+    }
+
+    fn check_nostr(&self) -> Result<(), NostrError> {
+        match self.algo() {
+            Algo::Bip340 => Ok(()),
+            other => Err(NostrError::UnsupportedAlgo(*other)),
+        }
+    }
+}
+
+impl Uid {
+    /// Parses Nostr key from a UID with `nostr` schema. The UID id may be
+    /// either `npub` or `nprofile`.
+    ///
+    /// Returns `None` if the UID uses a different schema.
+    pub fn nostr_pub(&self) -> Option<Result<SsiPub, NostrError>> {
+        if self.schema != NOSTR_SCHEMA {
+            return None;
+        }
+        Some(if self.id.starts_with("nprofile") {
+            SsiPub::from_nprofile(&self.id).map(|(pk, _)| pk)
+        } else {
+            SsiPub::from_npub(&self.id)
+        })
+    }
+}
+
+impl Ssi {
+    /// Checks that all UIDs with `nostr` schema are valid Nostr keys and, for
+    /// BIP340 identities, that they match the identity key.
+    pub fn check_nostr(&self) -> Result<(), NostrError> {
+        for uid in &self.uids {
+            let Some(pk) = uid.nostr_pub() else { continue };
+            let pk = pk?;
+            if self.pk.algo() == &Algo::Bip340 && pk.to_byte_array() != self.pk.to_byte_array() {
+                return Err(NostrError::KeyMismatch);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use amplify::Bytes32;
+
+    use super::*;
+    use crate::SsiSecret;
+
+    #[test]
+    fn nip19_npub() {
+        let npub = "npub10elfcs4fr0l0r8af98jlmgdh9c8tcxjvz9qkw038js35mp4dma8qzvjptg";
+        let key = Bytes32::from_str(
+            "7e7e9c42a91bfef19fa929e5fda1b72e0ebc1a4c1141673e2794234d86addf4e",
+        )
+        .unwrap();
+        let pk = SsiPub::from_npub(npub).unwrap();
+        assert_eq!(pk.to_byte_array(), key.to_byte_array());
+        assert_eq!(pk.to_npub().unwrap(), npub);
+    }
+
+    #[test]
+    fn nip19_nprofile() {
+        let nprofile = "nprofile1qqsrhuxx8l9ex335q7he0f09aej04zpazpl0ne2cgukyawd24mayt8gpp4mhxue69u\
+                        hhytnc9e3k7mgpz4mhxue69uhkg6nzv9ejuumpv34kytnrdaksjlyr9p";
+        let key = Bytes32::from_str(
+            "3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d",
+        )
+        .unwrap();
+        let (pk, relays) = SsiPub::from_nprofile(nprofile).unwrap();
+        assert_eq!(pk.to_byte_array(), key.to_byte_array());
+        assert_eq!(relays, vec!["wss://r.x.com", "wss://djbas.sadkb.com"]);
+        assert_eq!(pk.to_nprofile(&["wss://r.x.com", "wss://djbas.sadkb.com"]).unwrap(), nprofile);
+        assert!(matches!(SsiPub::from_npub(nprofile), Err(NostrError::InvalidHrp(_))));
+    }
+
+    #[test]
+    fn non_bip340() {
+        let pk = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin).to_public();
+        assert!(matches!(pk.to_npub(), Err(NostrError::UnsupportedAlgo(Algo::Ed25519))));
+    }
+
+    #[test]
+    fn nostr_uid() {
+        let secret = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        let npub = secret.to_public().to_npub().unwrap();
+        let uid = Uid::from_str(&format!("Alice <nostr:{npub}>")).unwrap();
+        let ssi = Ssi::new(bset![uid], None, &secret);
+        ssi.check_nostr().unwrap();
+        Ssi::from_str(&ssi.to_string()).unwrap();
+
+        let other = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        let uid = Uid::from_str(&format!("Alice <nostr:{npub}>")).unwrap();
+        let ssi = Ssi::new(bset![uid], None, &other);
+        assert!(matches!(ssi.check_nostr(), Err(NostrError::KeyMismatch)));
+        assert!(Ssi::from_str(&ssi.to_string()).is_err());
+    }
+}
@@ -0,0 +1,968 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Export of SSI identities as OpenPGP (RFC 4880) version 4 public keys and
+//! import of identities from existing OpenPGP public keys.
+
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use secp256k1::{Message, PublicKey, SecretKey, SECP256K1};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    Algo, Chain, Commitment, KeyUsage, PgpFingerprint, Ssi, SsiPair, SsiPub, SsiSecret, Uid,
+};
+
+const ARMOR_BEGIN: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----";
+const ARMOR_END: &str = "-----END PGP PUBLIC KEY BLOCK-----";
+
+const TAG_SIGNATURE: u8 = 2;
+const TAG_PUBLIC_KEY: u8 = 6;
+const TAG_USER_ID: u8 = 13;
+const TAG_PUBLIC_SUBKEY: u8 = 14;
+const TAG_USER_ATTRIBUTE: u8 = 17;
+
+const ALGO_ECDSA: u8 = 19;
+const ALGO_EDDSA: u8 = 22;
+const ALGO_ED25519: u8 = 27;
+const HASH_SHA256: u8 = 8;
+const SIG_GENERIC_CERT: u8 = 0x10;
+const SIG_POSITIVE_CERT: u8 = 0x13;
+const SIG_SUBKEY_BINDING: u8 = 0x18;
+const SIG_DIRECT_KEY: u8 = 0x1F;
+
+const SUBPKT_CREATION_TIME: u8 = 2;
+const SUBPKT_KEY_EXPIRY: u8 = 9;
+const SUBPKT_ISSUER: u8 = 16;
+const SUBPKT_PRIMARY_UID: u8 = 25;
+const SUBPKT_KEY_FLAGS: u8 = 27;
+const SUBPKT_ISSUER_FPR: u8 = 33;
+
+const KEY_FLAGS_CERTIFY_SIGN: u8 = 0x03;
+const KEY_FLAG_SIGN: u8 = 0x02;
+
+const OID_ED25519: [u8; 9] = [0x2B, 0x06, 0x01, 0x04, 0x01, 0xDA, 0x47, 0x0F, 0x01];
+const OID_SECP256K1: [u8; 5] = [0x2B, 0x81, 0x04, 0x00, 0x0A];
+const OID_CURVE25519: [u8; 10] = [0x2B, 0x06, 0x01, 0x04, 0x01, 0x97, 0x55, 0x01, 0x05, 0x01];
+const OID_NIST_P256: [u8; 8] = [0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x03, 0x01, 0x07];
+const OID_NIST_P384: [u8; 5] = [0x2B, 0x81, 0x04, 0x00, 0x22];
+const OID_NIST_P521: [u8; 5] = [0x2B, 0x81, 0x04, 0x00, 0x23];
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum OpenPgpError {
+    /// OpenPGP export is not supported for {0} keys.
+    UnsupportedAlgo(Algo),
+
+    /// the provided secret key doesn't match the identity.
+    KeyMismatch,
+
+    /// identity must be unsigned or have a valid self-signature to be exported.
+    InvalidIdentity,
+
+    /// identity expires before the OpenPGP key creation time.
+    Expired,
+
+    /// key creation time or expiry can't be represented as an OpenPGP
+    /// timestamp.
+    TimestampOutOfRange,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum OpenPgpImportError {
+    /// invalid ASCII armor of OpenPGP public key block.
+    InvalidArmor,
+
+    /// ASCII armor checksum doesn't match OpenPGP key data.
+    ArmorChecksum,
+
+    /// invalid or truncated OpenPGP packet.
+    InvalidPacket,
+
+    /// OpenPGP v{0} keys are not supported.
+    UnsupportedVersion(u8),
+
+    /// no OpenPGP public keys found.
+    NoKey,
+
+    /// OpenPGP key {0} has no Ed25519 or secp256k1 signing keys (found {1}).
+    UnsupportedAlgo(PgpFingerprint, String),
+}
+
+/// OpenPGP v4 transferable public key, containing a public key packet
+/// followed by user ID packets, each with its positive self-certification.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct OpenPgpKey {
+    fingerprint: [u8; 20],
+    data: Vec<u8>,
+}
+
+impl OpenPgpKey {
+    /// OpenPGP v4 key fingerprint.
+    pub fn fingerprint(&self) -> [u8; 20] { self.fingerprint }
+
+    /// OpenPGP v4 key id, which is the low 64 bits of the fingerprint.
+    pub fn key_id(&self) -> [u8; 8] {
+        let mut id = [0u8; 8];
+        id.copy_from_slice(&self.fingerprint[12..]);
+        id
+    }
+
+    /// Binary packet serialization of the key.
+    pub fn as_bytes(&self) -> &[u8] { &self.data }
+
+    /// ASCII-armored form of the key, as expected by `gpg --import`.
+    pub fn to_armored_string(&self) -> String { self.to_string() }
+}
+
+impl Display for OpenPgpKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{ARMOR_BEGIN}")?;
+        writeln!(f)?;
+        let data = STANDARD.encode(&self.data);
+        for line in data.as_bytes().chunks(64) {
+            writeln!(f, "{}", String::from_utf8_lossy(line))?;
+        }
+        let crc = crc24(&self.data).to_be_bytes();
+        writeln!(f, "={}", STANDARD.encode(&crc[1..]))?;
+        writeln!(f, "{ARMOR_END}")
+    }
+}
+
+impl Ssi {
+    /// Exports identity as an OpenPGP v4 public key.
+    ///
+    /// Each UID is exported as a separate OpenPGP user ID packet; UIDs using
+    /// `mailto` schema are converted into the conventional `Name <email>`
+    /// form. Since OpenPGP self-certifications must be made with the
+    /// identity key, the method requires the secret key for the identity.
+    /// Ed25519 identities produce EdDSA keys, BIP340 identities produce
+    /// ECDSA keys over secp256k1 curve.
+    ///
+    /// Since SSI doesn't have a notion of key creation time, it must be
+    /// provided by the caller; the OpenPGP fingerprint depends on it.
+    pub fn to_openpgp(
+        &self,
+        pair: &SsiPair,
+        created: DateTime<Utc>,
+    ) -> Result<OpenPgpKey, OpenPgpError> {
+        if pair.pk != self.pk || pair.sk.to_public() != self.pk {
+            return Err(OpenPgpError::KeyMismatch);
+        }
+        if self.integrity().is_invalid() {
+            return Err(OpenPgpError::InvalidIdentity);
+        }
+        let created_ts = u32::try_from(created.timestamp())
+            .map_err(|_| OpenPgpError::TimestampOutOfRange)?;
+        let expiry = match self.expiry {
+            Some(expiry) if expiry <= created => return Err(OpenPgpError::Expired),
+            Some(expiry) => Some(
+                u32::try_from(expiry.timestamp() - created.timestamp())
+                    .map_err(|_| OpenPgpError::TimestampOutOfRange)?,
+            ),
+            None => None,
+        };
+
+        let mut key_body = vec![4u8];
+        key_body.extend(created_ts.to_be_bytes());
+        let pk_algo = match &pair.sk {
+            SsiSecret::Ed25519(_) => {
+                key_body.push(ALGO_EDDSA);
+                key_body.push(OID_ED25519.len() as u8);
+                key_body.extend(OID_ED25519);
+                let mut point = vec![0x40];
+                point.extend(self.pk.to_byte_array());
+                write_mpi(&mut key_body, &point);
+                ALGO_EDDSA
+            }
+            SsiSecret::Bip340(_) | SsiSecret::Ecdsa(_) => {
+                let pk = PublicKey::from_secret_key(SECP256K1, &secp256k1_secret(&pair.sk));
+                key_body.push(ALGO_ECDSA);
+                key_body.push(OID_SECP256K1.len() as u8);
+                key_body.extend(OID_SECP256K1);
+                write_mpi(&mut key_body, &pk.serialize_uncompressed());
+                ALGO_ECDSA
+            }
+        };
+
+        let mut fpr_hasher = Sha1::new();
+        fpr_hasher.update([0x99]);
+        fpr_hasher.update((key_body.len() as u16).to_be_bytes());
+        fpr_hasher.update(&key_body);
+        let fingerprint: [u8; 20] = fpr_hasher.finalize().into();
+
+        let mut data = vec![];
+        write_packet(&mut data, TAG_PUBLIC_KEY, &key_body);
+
+        for (no, uid) in self.uids.iter().enumerate() {
+            let user_id = uid_to_openpgp(uid);
+            write_packet(&mut data, TAG_USER_ID, user_id.as_bytes());
+
+            let mut subpackets = vec![];
+            write_subpacket(&mut subpackets, SUBPKT_CREATION_TIME, &created_ts.to_be_bytes());
+            if let Some(expiry) = expiry {
+                write_subpacket(&mut subpackets, SUBPKT_KEY_EXPIRY, &expiry.to_be_bytes());
+            }
+            write_subpacket(&mut subpackets, SUBPKT_KEY_FLAGS, &[KEY_FLAGS_CERTIFY_SIGN]);
+            if no == 0 {
+                write_subpacket(&mut subpackets, SUBPKT_PRIMARY_UID, &[1]);
+            }
+            let mut issuer_fpr = vec![4u8];
+            issuer_fpr.extend(fingerprint);
+            write_subpacket(&mut subpackets, SUBPKT_ISSUER_FPR, &issuer_fpr);
+
+            let mut hashed = vec![4u8, SIG_POSITIVE_CERT, pk_algo, HASH_SHA256];
+            hashed.extend((subpackets.len() as u16).to_be_bytes());
+            hashed.extend(subpackets);
+
+            let mut hasher = Sha256::new();
+            hasher.update([0x99]);
+            hasher.update((key_body.len() as u16).to_be_bytes());
+            hasher.update(&key_body);
+            hasher.update([0xB4]);
+            hasher.update((user_id.len() as u32).to_be_bytes());
+            hasher.update(user_id.as_bytes());
+            hasher.update(&hashed);
+            hasher.update([0x04, 0xFF]);
+            hasher.update((hashed.len() as u32).to_be_bytes());
+            let digest: [u8; 32] = hasher.finalize().into();
+
+            let sig = match &pair.sk {
+                SsiSecret::Ed25519(sk) => sk.sign(digest).to_byte_array(),
+                SsiSecret::Bip340(_) | SsiSecret::Ecdsa(_) => SECP256K1
+                    .sign_ecdsa(&Message::from_digest(digest), &secp256k1_secret(&pair.sk))
+                    .serialize_compact(),
+            };
+
+            let mut unhashed = vec![];
+            write_subpacket(&mut unhashed, SUBPKT_ISSUER, &fingerprint[12..]);
+
+            let mut sig_body = hashed;
+            sig_body.extend((unhashed.len() as u16).to_be_bytes());
+            sig_body.extend(unhashed);
+            sig_body.extend(&digest[..2]);
+            write_mpi(&mut sig_body, &sig[..32]);
+            write_mpi(&mut sig_body, &sig[32..]);
+            write_packet(&mut data, TAG_SIGNATURE, &sig_body);
+        }
+
+        Ok(OpenPgpKey { fingerprint, data })
+    }
+}
+
+impl Ssi {
+    /// Imports identity from the first key of OpenPGP public key data, which
+    /// may be either binary or ASCII-armored. See
+    /// [`Ssi::from_openpgp_keyring`] for the details.
+    pub fn from_openpgp(data: &[u8], chain: Chain) -> Result<Ssi, OpenPgpImportError> {
+        Self::from_openpgp_keyring(data, chain)?
+            .into_iter()
+            .next()
+            .ok_or(OpenPgpImportError::NoKey)?
+    }
+
+    /// Imports identities from all keys of OpenPGP public key data (RFC 4880
+    /// or RFC 9580), which may be either binary or ASCII-armored. Errors
+    /// related to the data as a whole are returned as the outer error, while
+    /// keys which can't be converted are reported individually.
+    ///
+    /// The identity key is taken from the OpenPGP primary key if it is an
+    /// Ed25519 or secp256k1 key; otherwise the first such subkey capable of
+    /// signing is used. Since SSI uses x-only secp256k1 keys, the secret of
+    /// the OpenPGP secp256k1 key matches the imported key when used as
+    /// [`Algo::Ecdsa`] secret. User IDs in `Name (comment) <email>` form
+    /// are converted into `mailto` UIDs, user IDs with URIs are kept as is,
+    /// and user IDs which can't be converted are skipped. The key expiry is
+    /// taken from the latest self-signature.
+    ///
+    /// OpenPGP signatures are not verified. The returned identity is
+    /// unsigned and is marked as imported with a UID referencing the
+    /// fingerprint of the OpenPGP key (see [`Uid::pgp`]).
+    pub fn from_openpgp_keyring(
+        data: &[u8],
+        chain: Chain,
+    ) -> Result<Vec<Result<Ssi, OpenPgpImportError>>, OpenPgpImportError> {
+        let data = match std::str::from_utf8(data) {
+            Ok(s) if s.trim_start().starts_with("-----BEGIN ") => dearmor(s)?,
+            _ => data.to_vec(),
+        };
+        let packets = parse_packets(&data)?;
+
+        let mut keys = vec![];
+        let mut start = None;
+        for (no, (tag, _)) in packets.iter().enumerate() {
+            if *tag != TAG_PUBLIC_KEY {
+                continue;
+            }
+            if let Some(start) = start {
+                keys.push(&packets[start..no]);
+            }
+            start = Some(no);
+        }
+        match start {
+            Some(start) => keys.push(&packets[start..]),
+            None => return Err(OpenPgpImportError::NoKey),
+        }
+        Ok(keys
+            .into_iter()
+            .map(|packets| import_key(packets, chain))
+            .collect())
+    }
+}
+
+struct PgpKey<'a> {
+    fingerprint: PgpFingerprint,
+    created: u32,
+    algo: u8,
+    material: &'a [u8],
+}
+
+impl<'a> PgpKey<'a> {
+    fn parse(body: &'a [u8]) -> Result<Self, OpenPgpImportError> {
+        let (&version, rest) = body.split_first().ok_or(OpenPgpImportError::InvalidPacket)?;
+        if rest.len() < 5 {
+            return Err(OpenPgpImportError::InvalidPacket);
+        }
+        let created = read_be(&rest[..4]) as u32;
+        let algo = rest[4];
+        let (fingerprint, material) = match version {
+            4 => {
+                let len =
+                    u16::try_from(body.len()).map_err(|_| OpenPgpImportError::InvalidPacket)?;
+                let mut hasher = Sha1::new();
+                hasher.update([0x99]);
+                hasher.update(len.to_be_bytes());
+                hasher.update(body);
+                (PgpFingerprint::V4(hasher.finalize().into()), &rest[5..])
+            }
+            5 | 6 => {
+                let mut hasher = Sha256::new();
+                hasher.update([if version == 5 { 0x9A } else { 0x9B }]);
+                hasher.update((body.len() as u32).to_be_bytes());
+                hasher.update(body);
+                let material = rest.get(9..).ok_or(OpenPgpImportError::InvalidPacket)?;
+                (PgpFingerprint::V6(hasher.finalize().into()), material)
+            }
+            version => return Err(OpenPgpImportError::UnsupportedVersion(version)),
+        };
+        Ok(PgpKey {
+            fingerprint,
+            created,
+            algo,
+            material,
+        })
+    }
+
+    fn to_ssi_pub(&self, chain: Chain) -> Option<SsiPub> {
+        match self.algo {
+            ALGO_ED25519 => {
+                let key: [u8; 32] = self.material.get(..32)?.try_into().ok()?;
+                Some(SsiPub::with(chain, Algo::Ed25519, key))
+            }
+            ALGO_EDDSA => match read_curve(self.material)? {
+                (oid, [0x40, key @ ..]) if oid == OID_ED25519 => {
+                    Some(SsiPub::with(chain, Algo::Ed25519, <[u8; 32]>::try_from(key).ok()?))
+                }
+                _ => None,
+            },
+            ALGO_ECDSA => match read_curve(self.material)? {
+                (oid, [prefix, point @ ..]) if oid == OID_SECP256K1 => {
+                    let x = match (prefix, point.len()) {
+                        (0x04, 64) | (0x02 | 0x03, 32) => &point[..32],
+                        _ => return None,
+                    };
+                    Some(SsiPub::with(chain, Algo::Ecdsa, <[u8; 32]>::try_from(x).ok()?))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn algo_name(&self) -> String {
+        let name = match self.algo {
+            1..=3 => "RSA",
+            16 => "ElGamal",
+            17 => "DSA",
+            18 => "ECDH",
+            ALGO_ECDSA => "ECDSA",
+            ALGO_EDDSA => "EdDSA",
+            25 => "X25519",
+            26 => "X448",
+            ALGO_ED25519 => "Ed25519",
+            28 => "Ed448",
+            algo => return format!("algorithm {algo}"),
+        };
+        if !matches!(self.algo, 18 | ALGO_ECDSA | ALGO_EDDSA) {
+            return name.to_owned();
+        }
+        let curve = match read_curve(self.material) {
+            Some((oid, _)) if oid == OID_ED25519 => "Ed25519",
+            Some((oid, _)) if oid == OID_SECP256K1 => "secp256k1",
+            Some((oid, _)) if oid == OID_CURVE25519 => "Curve25519",
+            Some((oid, _)) if oid == OID_NIST_P256 => "P-256",
+            Some((oid, _)) if oid == OID_NIST_P384 => "P-384",
+            Some((oid, _)) if oid == OID_NIST_P521 => "P-521",
+            _ => "unknown curve",
+        };
+        format!("{name} {curve}")
+    }
+
+    fn expiry(&self, sigs: &[PgpSig]) -> Option<DateTime<Utc>> {
+        let sig = sigs.iter().max_by_key(|sig| sig.created)?;
+        let secs = sig.key_expiry.filter(|secs| *secs > 0)?;
+        DateTime::from_timestamp(self.created as i64 + secs as i64, 0)
+    }
+}
+
+#[derive(Default)]
+struct PgpSig {
+    kind: u8,
+    created: Option<u32>,
+    key_expiry: Option<u32>,
+    key_flags: Option<u8>,
+    primary_uid: bool,
+    issuers: Vec<Vec<u8>>,
+}
+
+impl PgpSig {
+    /// Parses v4 or v6 signature, returning `None` for other versions and
+    /// malformed signatures.
+    fn parse(body: &[u8]) -> Option<Self> {
+        let (&version, rest) = body.split_first()?;
+        let len_size = match version {
+            4 => 2,
+            6 => 4,
+            _ => return None,
+        };
+        let mut sig = PgpSig {
+            kind: *rest.first()?,
+            ..Default::default()
+        };
+        let mut data = rest.get(3..)?;
+        for hashed in [true, false] {
+            let len = read_be(data.get(..len_size)?);
+            let area = data.get(len_size..)?.get(..len)?;
+            sig.read_subpackets(area, hashed)?;
+            data = &data[len_size + len..];
+        }
+        Some(sig)
+    }
+
+    fn read_subpackets(&mut self, mut data: &[u8], hashed: bool) -> Option<()> {
+        while let Some(&first) = data.first() {
+            let (len, hdr) = match first {
+                0..=191 => (first as usize, 1),
+                192..=254 => ((((first as usize) - 192) << 8) + *data.get(1)? as usize + 192, 2),
+                255 => (read_be(data.get(1..5)?), 5),
+            };
+            let subpacket = data.get(hdr..)?.get(..len)?;
+            data = &data[hdr + len..];
+            let (&kind, value) = subpacket.split_first()?;
+            match (kind & 0x7F, hashed) {
+                (SUBPKT_ISSUER | SUBPKT_ISSUER_FPR, _) => self.issuers.push(value.to_vec()),
+                (_, false) => {}
+                (SUBPKT_CREATION_TIME, true) => {
+                    self.created = Some(read_be(value.get(..4)?) as u32)
+                }
+                (SUBPKT_KEY_EXPIRY, true) => {
+                    self.key_expiry = Some(read_be(value.get(..4)?) as u32)
+                }
+                (SUBPKT_KEY_FLAGS, true) => self.key_flags = value.first().copied(),
+                (SUBPKT_PRIMARY_UID, true) => self.primary_uid = value.first() == Some(&1),
+                _ => {}
+            }
+        }
+        Some(())
+    }
+
+    /// Checks issuer key id or fingerprint of the signature, if present.
+    fn is_issued_by(&self, fingerprint: &PgpFingerprint) -> bool {
+        let fp = fingerprint.as_slice();
+        let key_id = match fingerprint {
+            PgpFingerprint::V4(fp) => &fp[12..],
+            PgpFingerprint::V6(fp) => &fp[..8],
+        };
+        self.issuers.is_empty() ||
+            self.issuers
+                .iter()
+                .any(|issuer| issuer == key_id || issuer.get(1..) == Some(fp))
+    }
+}
+
+fn import_key(packets: &[(u8, &[u8])], chain: Chain) -> Result<Ssi, OpenPgpImportError> {
+    let primary = PgpKey::parse(packets[0].1)?;
+    let mut user_ids = vec![];
+    let mut current_uid = None;
+    let mut self_sigs = vec![];
+    let mut subkeys = vec![];
+    for (tag, body) in &packets[1..] {
+        match *tag {
+            TAG_USER_ID => {
+                current_uid = Some(user_ids.len());
+                user_ids.push((String::from_utf8_lossy(body), false));
+            }
+            TAG_USER_ATTRIBUTE => current_uid = None,
+            TAG_PUBLIC_SUBKEY => subkeys.push((PgpKey::parse(body)?, vec![])),
+            TAG_SIGNATURE => {
+                let Some(sig) = PgpSig::parse(body) else { continue };
+                if !sig.is_issued_by(&primary.fingerprint) {
+                    continue;
+                }
+                if let Some((_, sigs)) = subkeys.last_mut() {
+                    if sig.kind == SIG_SUBKEY_BINDING {
+                        sigs.push(sig);
+                    }
+                } else if matches!(sig.kind, SIG_GENERIC_CERT..=SIG_POSITIVE_CERT | SIG_DIRECT_KEY)
+                {
+                    if let Some(no) = current_uid.filter(|_| sig.primary_uid) {
+                        user_ids[no].1 = true;
+                    }
+                    self_sigs.push(sig);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let expiry = primary.expiry(&self_sigs);
+    let (pk, expiry) = match primary.to_ssi_pub(chain) {
+        Some(pk) => (pk, expiry),
+        None => {
+            let mut found = vec![format!("{} primary key", primary.algo_name())];
+            let mut selected = None;
+            for (subkey, sigs) in &subkeys {
+                let binding = sigs.iter().max_by_key(|sig| sig.created);
+                let signing = binding
+                    .and_then(|sig| sig.key_flags)
+                    .is_some_and(|flags| flags & KEY_FLAG_SIGN != 0);
+                match subkey.to_ssi_pub(chain) {
+                    Some(pk) if signing => {
+                        let sub_expiry = subkey.expiry(sigs);
+                        let expiry = match (expiry, sub_expiry) {
+                            (Some(a), Some(b)) => Some(a.min(b)),
+                            (a, b) => a.or(b),
+                        };
+                        selected = Some((pk, expiry));
+                        break;
+                    }
+                    Some(_) => found.push(format!("{} non-signing subkey", subkey.algo_name())),
+                    None => found.push(format!("{} subkey", subkey.algo_name())),
+                }
+            }
+            selected.ok_or_else(|| {
+                OpenPgpImportError::UnsupportedAlgo(primary.fingerprint, found.join(", "))
+            })?
+        }
+    };
+
+    let mut uids = BTreeSet::new();
+    let mut has_primary = false;
+    for (user_id, is_primary) in user_ids {
+        let Some(mut uid) = uid_from_openpgp(&user_id) else { continue };
+        uid.primary = is_primary && !has_primary;
+        has_primary |= uid.primary;
+        uids.insert(uid);
+    }
+    let name = uids
+        .iter()
+        .find(|uid| uid.primary)
+        .or_else(|| uids.iter().find(|uid| !uid.name.is_empty()))
+        .map(|uid| uid.name.clone())
+        .unwrap_or_default();
+    uids.insert(Uid::pgp(name, primary.fingerprint));
+
+    Ok(Ssi {
+        pk,
+        uids,
+        expiry,
+        comment: None,
+        usage: KeyUsage::ALL,
+        services: bmap![],
+        avatar: None,
+        subkeys: vec![],
+        next: None,
+        prev: None,
+        pow: None,
+        extras: bmap![],
+        commitment: Commitment::Binary,
+        sig: None,
+        endorsements: vec![],
+    })
+}
+
+/// Converts OpenPGP user id into UID, reversing [`uid_to_openpgp`].
+fn uid_from_openpgp(user_id: &str) -> Option<Uid> {
+    let user_id = user_id.trim();
+    let s = match user_id.strip_suffix('>').and_then(|s| s.rsplit_once('<')) {
+        Some((_, "")) => return None,
+        Some((_, id)) if id.contains(':') => user_id.to_owned(),
+        Some((name, email)) => format!("{name}<mailto:{email}>"),
+        None if user_id.contains('@') => format!("mailto:{user_id}"),
+        None => return None,
+    };
+    Uid::from_str(&s).ok()
+}
+
+/// Reads curve OID and the following MPI with the public point.
+fn read_curve(material: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (&oid_len, rest) = material.split_first()?;
+    let oid = rest.get(..oid_len as usize)?;
+    let mpi = &rest[oid_len as usize..];
+    let bits = read_be(mpi.get(..2)?);
+    let point = mpi.get(2..)?.get(..(bits + 7) / 8)?;
+    Some((oid, point))
+}
+
+fn read_be(data: &[u8]) -> usize { data.iter().fold(0, |acc, b| (acc << 8) | *b as usize) }
+
+fn dearmor(s: &str) -> Result<Vec<u8>, OpenPgpImportError> {
+    let mut data = vec![];
+    let mut lines = s.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        if line.is_empty() {
+            continue;
+        }
+        if line != ARMOR_BEGIN {
+            return Err(OpenPgpImportError::InvalidArmor);
+        }
+        let mut base64 = String::new();
+        let mut checksum = None;
+        loop {
+            match lines.next().ok_or(OpenPgpImportError::InvalidArmor)? {
+                ARMOR_END => break,
+                // empty separator line and armor headers
+                line if line.is_empty() || line.contains(':') => {}
+                line if line.len() == 5 && line.starts_with('=') => checksum = Some(&line[1..]),
+                line => base64.push_str(line),
+            }
+        }
+        let block = STANDARD
+            .decode(base64)
+            .map_err(|_| OpenPgpImportError::InvalidArmor)?;
+        if let Some(checksum) = checksum {
+            let crc = STANDARD
+                .decode(checksum)
+                .map_err(|_| OpenPgpImportError::InvalidArmor)?;
+            if crc.as_slice() != &crc24(&block).to_be_bytes()[1..] {
+                return Err(OpenPgpImportError::ArmorChecksum);
+            }
+        }
+        data.extend(block);
+    }
+    Ok(data)
+}
+
+/// Splits data into packets of both old and new format. Partial body lengths
+/// are not supported, since they are not allowed in OpenPGP keys.
+fn parse_packets(mut data: &[u8]) -> Result<Vec<(u8, &[u8])>, OpenPgpImportError> {
+    let mut packets = vec![];
+    while let Some(&hdr) = data.first() {
+        let (tag, len, skip) = match hdr {
+            0x00..=0x7F => return Err(OpenPgpImportError::InvalidPacket),
+            0xC0..=0xFF => match data.get(1).copied() {
+                Some(len @ 0..=191) => (hdr & 0x3F, len as usize, 2),
+                Some(len @ 192..=223) => {
+                    let low = *data.get(2).ok_or(OpenPgpImportError::InvalidPacket)? as usize;
+                    (hdr & 0x3F, (((len as usize) - 192) << 8) + low + 192, 3)
+                }
+                Some(255) => {
+                    let len = data.get(2..6).ok_or(OpenPgpImportError::InvalidPacket)?;
+                    (hdr & 0x3F, read_be(len), 6)
+                }
+                _ => return Err(OpenPgpImportError::InvalidPacket),
+            },
+            _ => match hdr & 0x03 {
+                3 => ((hdr >> 2) & 0x0F, data.len() - 1, 1),
+                size => {
+                    let size = 1usize << size;
+                    let len = data.get(1..1 + size).ok_or(OpenPgpImportError::InvalidPacket)?;
+                    ((hdr >> 2) & 0x0F, read_be(len), 1 + size)
+                }
+            },
+        };
+        let body = data
+            .get(skip..)
+            .and_then(|rest| rest.get(..len))
+            .ok_or(OpenPgpImportError::InvalidPacket)?;
+        packets.push((tag, body));
+        data = &data[skip + len..];
+    }
+    Ok(packets)
+}
+
+fn secp256k1_secret(sk: &SsiSecret) -> SecretKey {
+    SecretKey::from_slice(&sk.secret_bytes()).expect("invalid secp256k1 secret key")
+}
+
+fn uid_to_openpgp(uid: &Uid) -> String {
+    match uid.schema.as_str() {
+        "mailto" => match uid.display_name().as_str() {
+            "" => format!("<{}>", uid.id),
+            name => format!("{name} <{}>", uid.id),
+        },
+        _ => uid.to_string(),
+    }
+}
+
+fn write_packet(buf: &mut Vec<u8>, tag: u8, body: &[u8]) {
+    buf.push(0xC0 | tag);
+    let len = body.len();
+    if len < 192 {
+        buf.push(len as u8);
+    } else if len < 8384 {
+        let len = len - 192;
+        buf.push(((len >> 8) + 192) as u8);
+        buf.push((len & 0xFF) as u8);
+    } else {
+        buf.push(0xFF);
+        buf.extend((len as u32).to_be_bytes());
+    }
+    buf.extend(body);
+}
+
+fn write_subpacket(buf: &mut Vec<u8>, kind: u8, data: &[u8]) {
+    buf.push(data.len() as u8 + 1);
+    buf.push(kind);
+    buf.extend(data);
+}
+
+fn write_mpi(buf: &mut Vec<u8>, value: &[u8]) {
+    let value = match value.iter().position(|b| *b != 0) {
+        Some(pos) => &value[pos..],
+        None => &[],
+    };
+    let bits = match value.first() {
+        Some(first) => (value.len() as u16 - 1) * 8 + (8 - first.leading_zeros() as u16),
+        None => 0,
+    };
+    buf.extend(bits.to_be_bytes());
+    buf.extend(value);
+}
+
+fn crc24(data: &[u8]) -> u32 {
+    const CRC24_INIT: u32 = 0xB704CE;
+    const CRC24_POLY: u32 = 0x1864CFB;
+
+    let mut crc = CRC24_INIT;
+    for byte in data {
+        crc ^= (*byte as u32) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x1000000 != 0 {
+                crc ^= CRC24_POLY;
+            }
+        }
+    }
+    crc & 0xFFFFFF
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::SystemTime;
+
+    use amplify::hex::FromHex;
+    use sequoia_openpgp::parse::Parse;
+    use sequoia_openpgp::policy::StandardPolicy;
+    use sequoia_openpgp::Cert;
+
+    use super::*;
+
+    #[test]
+    fn crc24_vector() { assert_eq!(crc24(b"123456789"), 0x21CF02); }
+
+    #[test]
+    fn ed25519_export() {
+        let secret = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let uids = bset![
+            Uid::from_str("Alice <mailto:alice@example.com>").unwrap(),
+            Uid::from_str("Alice <https:example.com>").unwrap()
+        ];
+        let ssi = Ssi::new(uids, None, &secret);
+        let pair = SsiPair::new(ssi.clone(), secret);
+        let created = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let key = ssi.to_openpgp(&pair, created).unwrap();
+
+        let armored = key.to_armored_string();
+        assert!(armored.starts_with("-----BEGIN PGP PUBLIC KEY BLOCK-----\n\n"));
+        assert!(armored.ends_with("-----END PGP PUBLIC KEY BLOCK-----\n"));
+
+        let cert = Cert::from_bytes(key.as_bytes()).unwrap();
+        assert_eq!(cert.fingerprint().as_bytes(), key.fingerprint().as_slice());
+        assert_eq!(cert.userids().count(), 2);
+
+        // Only user IDs with a valid self-signature are returned for the policy
+        let policy = StandardPolicy::new();
+        let valid = cert.with_policy(&policy, SystemTime::from(created)).unwrap();
+        valid.alive().unwrap();
+        assert_eq!(valid.userids().count(), 2);
+        assert!(valid
+            .userids()
+            .any(|ua| ua.userid().value() == b"Alice <alice@example.com>"));
+        assert!(valid.primary_key().for_certification());
+        assert!(valid.primary_key().for_signing());
+    }
+
+    #[test]
+    fn export_timestamp_range() {
+        let secret = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let uid = Uid::from_str("Alice <mailto:alice@example.com>").unwrap();
+        let ssi = Ssi::new(bset![uid.clone()], None, &secret);
+        let pair = SsiPair::new(ssi.clone(), secret.clone());
+        let created = DateTime::from_timestamp(-1, 0).unwrap();
+        assert_eq!(ssi.to_openpgp(&pair, created), Err(OpenPgpError::TimestampOutOfRange));
+
+        let created = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let expiry = DateTime::from_timestamp(1_700_000_000 + u32::MAX as i64 + 1, 0);
+        let ssi = Ssi::new(bset![uid], expiry, &secret);
+        let pair = SsiPair::new(ssi.clone(), secret);
+        assert_eq!(ssi.to_openpgp(&pair, created), Err(OpenPgpError::TimestampOutOfRange));
+    }
+
+    const KEYRING: &str = include_str!("../tests/fixtures/keyring.asc");
+
+    fn fingerprint(ssi: &Ssi) -> Option<PgpFingerprint> {
+        ssi.uids.iter().find_map(Uid::pgp_fingerprint)
+    }
+
+    #[test]
+    fn import_keyring() {
+        let keys = Ssi::from_openpgp_keyring(KEYRING.as_bytes(), Chain::Bitcoin).unwrap();
+        assert_eq!(keys.len(), 4);
+
+        let alice = keys[0].as_ref().unwrap();
+        let key = <[u8; 32]>::from_hex(
+            "eb3222b0abd82355253f89cea2efa839480a2e52af44ce3509e4a0fca969f1c1",
+        )
+        .unwrap();
+        assert_eq!(alice.pk, SsiPub::with(Chain::Bitcoin, Algo::Ed25519, key));
+        assert_eq!(alice.expiry, DateTime::from_timestamp(2_051_265_600, 0));
+        assert_eq!(alice.sig, None);
+        assert_eq!(
+            fingerprint(alice),
+            PgpFingerprint::from_str("560A8E8809962F9FEECC1FEFCCAE375F4A9DCE5B").ok()
+        );
+        let uids = alice.uids.iter().map(Uid::to_string).collect::<BTreeSet<_>>();
+        assert_eq!(uids, bset![
+            s!("Alice Example (work) <mailto:alice@example.com>"),
+            s!("Alice <https://alice.example.com>"),
+            s!("mailto:alice@home.example"),
+            s!("Alice <pgp:560A8E8809962F9FEECC1FEFCCAE375F4A9DCE5B>")
+        ]);
+        let parsed = Ssi::from_str_unsigned(&alice.to_string()).unwrap();
+        assert_eq!(&parsed, alice);
+
+        // RSA primary key with Ed25519 signing subkey
+        let bob = keys[1].as_ref().unwrap();
+        let key = <[u8; 32]>::from_hex(
+            "5baff3d68f797a5e9962e110a6c67dc1991d880c595024210a492662e49fb5c0",
+        )
+        .unwrap();
+        assert_eq!(bob.pk, SsiPub::with(Chain::Bitcoin, Algo::Ed25519, key));
+        assert_eq!(bob.expiry, None);
+        assert_eq!(
+            fingerprint(bob),
+            PgpFingerprint::from_str("6B8F7AD97E4ED62C3BF9290D8ACF3DD393DE9393").ok()
+        );
+        assert!(bob.uids.contains(&Uid::from_str("Bob <mailto:bob@example.com>").unwrap()));
+
+        let fp = PgpFingerprint::from_str("9D493AD312332E99AB822996913484CDE59E580A").unwrap();
+        assert_eq!(
+            keys[2],
+            Err(OpenPgpImportError::UnsupportedAlgo(fp, s!("RSA primary key")))
+        );
+
+        let dave = keys[3].as_ref().unwrap();
+        let key = <[u8; 32]>::from_hex(
+            "d51aaffe1f1f69eb64e2a123efcce18e86bdbffae1e5884795ee591201269565",
+        )
+        .unwrap();
+        assert_eq!(dave.pk, SsiPub::with(Chain::Bitcoin, Algo::Ecdsa, key));
+
+        assert_eq!(Ssi::from_openpgp(KEYRING.as_bytes(), Chain::Bitcoin).as_ref(), Ok(alice));
+    }
+
+    #[test]
+    fn import_malformed() {
+        let data = dearmor(KEYRING).unwrap();
+        let keys = Ssi::from_openpgp_keyring(&data, Chain::Bitcoin).unwrap();
+        assert_eq!(keys, Ssi::from_openpgp_keyring(KEYRING.as_bytes(), Chain::Bitcoin).unwrap());
+
+        for len in 0..data.len() {
+            let _ = Ssi::from_openpgp_keyring(&data[..len], Chain::Bitcoin);
+        }
+        let mut corrupted = data.clone();
+        for pos in (0..corrupted.len()).step_by(7) {
+            corrupted[pos] ^= 0x55;
+            let _ = Ssi::from_openpgp_keyring(&corrupted, Chain::Bitcoin);
+        }
+
+        assert_eq!(Ssi::from_openpgp(&[], Chain::Bitcoin), Err(OpenPgpImportError::NoKey));
+        assert_eq!(
+            Ssi::from_openpgp(b"random", Chain::Bitcoin),
+            Err(OpenPgpImportError::InvalidPacket)
+        );
+        let checksum = KEYRING.lines().find(|line| line.starts_with('=')).unwrap();
+        let wrong = KEYRING.replace(checksum, "=AAAA");
+        assert_eq!(
+            Ssi::from_openpgp(wrong.as_bytes(), Chain::Bitcoin),
+            Err(OpenPgpImportError::ArmorChecksum)
+        );
+        let truncated = KEYRING.replace(ARMOR_END, "");
+        assert_eq!(
+            Ssi::from_openpgp(truncated.as_bytes(), Chain::Bitcoin),
+            Err(OpenPgpImportError::InvalidArmor)
+        );
+    }
+
+    #[test]
+    fn export_import() {
+        let secret = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let uids = bset![
+            Uid::from_str("Alice (work) <mailto:alice@example.com>").unwrap(),
+            Uid::from_str("Alice <https:example.com>").unwrap()
+        ];
+        let expiry = DateTime::from_timestamp(2_000_000_000, 0);
+        let ssi = Ssi::new(uids, expiry, &secret);
+        let pair = SsiPair::new(ssi.clone(), secret);
+        let created = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let key = ssi.to_openpgp(&pair, created).unwrap();
+
+        let imported = Ssi::from_openpgp(key.to_armored_string().as_bytes(), Chain::Bitcoin)
+            .unwrap();
+        assert_eq!(imported.pk, ssi.pk);
+        assert_eq!(imported.expiry, expiry);
+        assert_eq!(fingerprint(&imported), Some(PgpFingerprint::V4(key.fingerprint())));
+        assert_eq!(imported.uids.iter().filter(|uid| uid.primary).count(), 1);
+        let uids = imported.uids.iter().map(Uid::to_string).collect::<BTreeSet<_>>();
+        for uid in &ssi.uids {
+            assert!(uids.contains(&uid.to_string()));
+        }
+        assert_eq!(Ssi::from_openpgp(key.as_bytes(), Chain::Bitcoin), Ok(imported));
+    }
+}
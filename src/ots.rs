@@ -0,0 +1,581 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! OpenTimestamps proofs attached to SSI certificates.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use amplify::confinement::{Confined, U16 as U16MAX};
+use amplify::hex::{self, FromHex, ToHex};
+use sha2::{Digest, Sha256};
+
+use crate::{SsiCert, LIB_NAME_SSI};
+
+const TAG_ATTESTATION: u8 = 0x00;
+const TAG_SHA256: u8 = 0x08;
+const TAG_APPEND: u8 = 0xf0;
+const TAG_PREPEND: u8 = 0xf1;
+const TAG_FORK: u8 = 0xff;
+
+const ATTESTATION_BITCOIN: [u8; 8] = [0x05, 0x88, 0x96, 0x0d, 0x73, 0xd7, 0x19, 0x01];
+const ATTESTATION_PENDING: [u8; 8] = [0x83, 0xdf, 0xe3, 0x0d, 0x2e, 0xf9, 0x0c, 0x8e];
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum OtsError {
+    /// the timestamp proof is truncated.
+    Truncated,
+
+    /// the timestamp proof contains unsupported operation {0:#04x}.
+    UnsupportedOp(u8),
+
+    /// the timestamp proof contains trailing data.
+    TrailingData,
+
+    /// the timestamp proof is too large.
+    TooLarge,
+
+    /// the certificate has no timestamp proof.
+    NoProof,
+
+    /// the timestamp is not yet confirmed in the Bitcoin blockchain.
+    Pending,
+
+    /// the timestamp doesn't match block at height {0}.
+    Mismatch(u32),
+
+    /// the timestamp proof contains invalid block height {0}.
+    InvalidHeight(u64),
+
+    /// none of the calendars have accepted the timestamp request.
+    NoCalendars,
+
+    /// network error - {0}
+    Network(String),
+}
+
+/// Attestation of a commitment, which terminates a path in the OpenTimestamps
+/// proof.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum OtsAttestation {
+    /// Commitment is the merkle root of the Bitcoin block at a given height.
+    Bitcoin(u32),
+    /// Commitment is submitted to a calendar server and awaits confirmation.
+    Pending(String),
+    /// Attestation of an unknown type, which is preserved but not verified.
+    Unknown([u8; 8], Vec<u8>),
+}
+
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+enum OtsOp {
+    Sha256,
+    Append(Vec<u8>),
+    Prepend(Vec<u8>),
+}
+
+impl OtsOp {
+    fn apply(&self, msg: &[u8]) -> Vec<u8> {
+        match self {
+            OtsOp::Sha256 => Sha256::digest(msg).to_vec(),
+            OtsOp::Append(data) => [msg, data].concat(),
+            OtsOp::Prepend(data) => [data, msg].concat(),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+struct OtsTree {
+    attestations: Vec<OtsAttestation>,
+    ops: Vec<(OtsOp, OtsTree)>,
+}
+
+impl OtsTree {
+    fn parse(data: &mut &[u8], depth: usize) -> Result<Self, OtsError> {
+        if depth > 256 {
+            return Err(OtsError::TooLarge);
+        }
+        let mut tree = OtsTree::default();
+        loop {
+            let tag = read_byte(data)?;
+            if tag == TAG_FORK {
+                let tag = read_byte(data)?;
+                tree.parse_item(tag, data, depth)?;
+            } else {
+                tree.parse_item(tag, data, depth)?;
+                return Ok(tree);
+            }
+        }
+    }
+
+    fn parse_item(&mut self, tag: u8, data: &mut &[u8], depth: usize) -> Result<(), OtsError> {
+        let op = match tag {
+            TAG_ATTESTATION => {
+                let kind = <[u8; 8]>::try_from(read_slice(data, 8)?).expect("fixed length");
+                let payload = read_varbytes(data)?;
+                let attestation = match kind {
+                    ATTESTATION_BITCOIN => {
+                        let mut payload = payload.as_slice();
+                        let height = read_varuint(&mut payload)?;
+                        let height =
+                            u32::try_from(height).map_err(|_| OtsError::InvalidHeight(height))?;
+                        OtsAttestation::Bitcoin(height)
+                    }
+                    ATTESTATION_PENDING => {
+                        let mut payload = payload.as_slice();
+                        let uri = read_varbytes(&mut payload)?;
+                        OtsAttestation::Pending(String::from_utf8_lossy(&uri).to_string())
+                    }
+                    _ => OtsAttestation::Unknown(kind, payload),
+                };
+                self.attestations.push(attestation);
+                return Ok(());
+            }
+            TAG_SHA256 => OtsOp::Sha256,
+            TAG_APPEND => OtsOp::Append(read_varbytes(data)?),
+            TAG_PREPEND => OtsOp::Prepend(read_varbytes(data)?),
+            other => return Err(OtsError::UnsupportedOp(other)),
+        };
+        let subtree = OtsTree::parse(data, depth + 1)?;
+        self.ops.push((op, subtree));
+        Ok(())
+    }
+
+    #[cfg(any(feature = "ots", test))]
+    fn serialize(&self, buf: &mut Vec<u8>) {
+        let count = self.attestations.len() + self.ops.len();
+        let mut no = 0;
+        for attestation in &self.attestations {
+            no += 1;
+            if no < count {
+                buf.push(TAG_FORK);
+            }
+            buf.push(TAG_ATTESTATION);
+            match attestation {
+                OtsAttestation::Bitcoin(height) => {
+                    buf.extend(ATTESTATION_BITCOIN);
+                    let mut payload = vec![];
+                    write_varuint(&mut payload, *height as u64);
+                    write_varbytes(buf, &payload);
+                }
+                OtsAttestation::Pending(uri) => {
+                    buf.extend(ATTESTATION_PENDING);
+                    let mut payload = vec![];
+                    write_varbytes(&mut payload, uri.as_bytes());
+                    write_varbytes(buf, &payload);
+                }
+                OtsAttestation::Unknown(kind, payload) => {
+                    buf.extend(kind);
+                    write_varbytes(buf, payload);
+                }
+            }
+        }
+        for (op, subtree) in &self.ops {
+            no += 1;
+            if no < count {
+                buf.push(TAG_FORK);
+            }
+            match op {
+                OtsOp::Sha256 => buf.push(TAG_SHA256),
+                OtsOp::Append(data) => {
+                    buf.push(TAG_APPEND);
+                    write_varbytes(buf, data);
+                }
+                OtsOp::Prepend(data) => {
+                    buf.push(TAG_PREPEND);
+                    write_varbytes(buf, data);
+                }
+            }
+            subtree.serialize(buf);
+        }
+    }
+
+    fn walk(&self, msg: &[u8], f: &mut impl FnMut(&[u8], &OtsAttestation)) {
+        for attestation in &self.attestations {
+            f(msg, attestation);
+        }
+        for (op, subtree) in &self.ops {
+            subtree.walk(&op.apply(msg), f);
+        }
+    }
+
+    #[cfg(feature = "ots")]
+    fn upgrade(&mut self, msg: &[u8], calendar_urls: &[&str]) -> bool {
+        let mut upgraded = false;
+        for (op, subtree) in &mut self.ops {
+            upgraded |= subtree.upgrade(&op.apply(msg), calendar_urls);
+        }
+        // subtrees received from the calendars are added after the existing ones were
+        // upgraded, so they are not queried once more
+        let mut pending = vec![];
+        for attestation in &self.attestations {
+            let OtsAttestation::Pending(uri) = attestation else {
+                continue;
+            };
+            // the proof may come from an untrusted certificate, so only known calendars are
+            // contacted
+            let uri = uri.trim_end_matches('/');
+            if calendar_urls
+                .iter()
+                .all(|url| url.trim_end_matches('/') != uri)
+            {
+                continue;
+            }
+            if let Ok(tree) = fetch_upgrade(uri, msg) {
+                pending.push((attestation.clone(), tree));
+            }
+        }
+        for (attestation, tree) in pending {
+            self.attestations.retain(|a| a != &attestation);
+            self.attestations.extend(tree.attestations);
+            self.ops.extend(tree.ops);
+            upgraded = true;
+        }
+        upgraded
+    }
+}
+
+/// OpenTimestamps proof for a certificate, starting from
+/// [`SsiCert::timestamp_digest`].
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_SSI)]
+pub struct OtsProof(Confined<Vec<u8>, 0, U16MAX>);
+
+impl OtsProof {
+    #[cfg(any(feature = "ots", test))]
+    fn with(tree: &OtsTree) -> Result<Self, OtsError> {
+        let mut buf = vec![];
+        tree.serialize(&mut buf);
+        Confined::try_from(buf)
+            .map(Self)
+            .map_err(|_| OtsError::TooLarge)
+    }
+
+    fn tree(&self) -> Result<OtsTree, OtsError> {
+        let mut data = self.0.as_slice();
+        let tree = OtsTree::parse(&mut data, 0)?;
+        if !data.is_empty() {
+            return Err(OtsError::TrailingData);
+        }
+        Ok(tree)
+    }
+
+    pub fn from_slice(data: &[u8]) -> Result<Self, OtsError> {
+        let proof = Confined::try_from(data.to_vec())
+            .map(Self)
+            .map_err(|_| OtsError::TooLarge)?;
+        proof.tree()?;
+        Ok(proof)
+    }
+
+    pub fn as_slice(&self) -> &[u8] { self.0.as_slice() }
+
+    /// Lists all attestations in the proof.
+    pub fn attestations(&self) -> Result<Vec<OtsAttestation>, OtsError> {
+        let mut attestations = vec![];
+        self.tree()?
+            .walk(&[], &mut |_, attestation| attestations.push(attestation.clone()));
+        Ok(attestations)
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, From)]
+#[display(inner)]
+pub enum OtsParseError {
+    #[from]
+    Hex(hex::Error),
+    #[from]
+    Proof(OtsError),
+}
+
+impl std::error::Error for OtsParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OtsParseError::Hex(err) => Some(err),
+            OtsParseError::Proof(err) => Some(err),
+        }
+    }
+}
+
+impl FromStr for OtsProof {
+    type Err = OtsParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let data = Vec::<u8>::from_hex(s)?;
+        Ok(Self::from_slice(&data)?)
+    }
+}
+
+impl Display for OtsProof {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { f.write_str(&self.0.as_slice().to_hex()) }
+}
+
+impl SsiCert {
+    /// Digest which is timestamped by the OpenTimestamps proof. It commits both
+    /// to the signed message and the signature, such that the timestamp
+    /// proves the existence of the certificate itself, and not just the
+    /// message.
+    pub fn timestamp_digest(&self) -> [u8; 32] {
+        let mut engine = Sha256::new();
+        engine.update(self.msg.as_slice());
+        engine.update(self.sig.as_slice());
+        engine.finalize().into()
+    }
+
+    /// Verifies OpenTimestamps proof of the certificate, returning the lowest
+    /// Bitcoin block height attesting the certificate.
+    ///
+    /// The `block_header_source` must return the merkle root of the block at
+    /// a given height, in the byte order used in the serialized block header.
+    pub fn verify_timestamp(
+        &self,
+        block_header_source: impl Fn(u32) -> Option<[u8; 32]>,
+    ) -> Result<u32, OtsError> {
+        let proof = self.timestamp.as_ref().ok_or(OtsError::NoProof)?;
+        let mut result = Err(OtsError::Pending);
+        proof
+            .tree()?
+            .walk(&self.timestamp_digest(), &mut |msg, attestation| {
+                let OtsAttestation::Bitcoin(height) = attestation else {
+                    return;
+                };
+                if block_header_source(*height).as_ref().map(<[u8; 32]>::as_slice) != Some(msg) {
+                    if result.is_err() {
+                        result = Err(OtsError::Mismatch(*height));
+                    }
+                    return;
+                }
+                match result {
+                    Ok(h) if h <= *height => {}
+                    _ => result = Ok(*height),
+                }
+            });
+        result
+    }
+
+    /// Submits the certificate to OpenTimestamps calendar servers, storing
+    /// the pending attestations in the certificate. The proof must be
+    /// upgraded with [`SsiCert::upgrade`] once the calendars have committed
+    /// the timestamp into the Bitcoin blockchain.
+    #[cfg(feature = "ots")]
+    pub fn stamp(&mut self, calendar_urls: &[&str]) -> Result<(), OtsError> {
+        let digest = self.timestamp_digest();
+        let mut tree = OtsTree::default();
+        for url in calendar_urls {
+            let url = format!("{}/digest", url.trim_end_matches('/'));
+            let Ok(resp) = http_request(ureq::post(&url), Some(digest.as_slice())) else {
+                continue;
+            };
+            let mut data = resp.as_slice();
+            let Ok(subtree) = OtsTree::parse(&mut data, 0) else {
+                continue;
+            };
+            tree.attestations.extend(subtree.attestations);
+            tree.ops.extend(subtree.ops);
+        }
+        if tree.attestations.is_empty() && tree.ops.is_empty() {
+            return Err(OtsError::NoCalendars);
+        }
+        self.timestamp = Some(OtsProof::with(&tree)?);
+        Ok(())
+    }
+
+    /// Upgrades pending attestations in the timestamp proof by querying the
+    /// calendar servers. Returns whether any of the attestations were
+    /// upgraded.
+    ///
+    /// Only the calendars from `calendar_urls` are queried; pending
+    /// attestations of other calendars are left as is, since the proof may
+    /// come from an untrusted certificate.
+    #[cfg(feature = "ots")]
+    pub fn upgrade(&mut self, calendar_urls: &[&str]) -> Result<bool, OtsError> {
+        let proof = self.timestamp.as_ref().ok_or(OtsError::NoProof)?;
+        let mut tree = proof.tree()?;
+        if !tree.upgrade(&self.timestamp_digest(), calendar_urls) {
+            return Ok(false);
+        }
+        self.timestamp = Some(OtsProof::with(&tree)?);
+        Ok(true)
+    }
+}
+
+#[cfg(feature = "ots")]
+fn http_request(req: ureq::Request, body: Option<&[u8]>) -> Result<Vec<u8>, OtsError> {
+    use std::io::Read;
+    use std::time::Duration;
+
+    let req = req
+        .timeout(Duration::from_secs(10))
+        .set("Accept", "application/vnd.opentimestamps.v1");
+    let resp = match body {
+        Some(body) => req.send_bytes(body),
+        None => req.call(),
+    }
+    .map_err(|e| OtsError::Network(e.to_string()))?;
+    let mut data = vec![];
+    resp.into_reader()
+        .take(u16::MAX as u64)
+        .read_to_end(&mut data)
+        .map_err(|e| OtsError::Network(e.to_string()))?;
+    Ok(data)
+}
+
+#[cfg(feature = "ots")]
+fn fetch_upgrade(uri: &str, msg: &[u8]) -> Result<OtsTree, OtsError> {
+    let url = format!("{}/timestamp/{}", uri.trim_end_matches('/'), msg.to_hex());
+    let resp = http_request(ureq::get(&url), None)?;
+    let mut data = resp.as_slice();
+    OtsTree::parse(&mut data, 0)
+}
+
+fn read_byte(data: &mut &[u8]) -> Result<u8, OtsError> {
+    let (byte, rest) = data.split_first().ok_or(OtsError::Truncated)?;
+    *data = rest;
+    Ok(*byte)
+}
+
+fn read_slice<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], OtsError> {
+    if data.len() < len {
+        return Err(OtsError::Truncated);
+    }
+    let (slice, rest) = data.split_at(len);
+    *data = rest;
+    Ok(slice)
+}
+
+fn read_varuint(data: &mut &[u8]) -> Result<u64, OtsError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = read_byte(data)?;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(OtsError::TooLarge)
+}
+
+fn read_varbytes(data: &mut &[u8]) -> Result<Vec<u8>, OtsError> {
+    let len = read_varuint(data)?;
+    if len > u16::MAX as u64 {
+        return Err(OtsError::TooLarge);
+    }
+    read_slice(data, len as usize).map(<[u8]>::to_vec)
+}
+
+#[cfg(any(feature = "ots", test))]
+fn write_varuint(buf: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+#[cfg(any(feature = "ots", test))]
+fn write_varbytes(buf: &mut Vec<u8>, data: &[u8]) {
+    write_varuint(buf, data.len() as u64);
+    buf.extend(data);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Algo, Chain, SsiPair, SsiSecret};
+
+    fn proof(digest: &[u8], height: u32) -> (OtsProof, [u8; 32]) {
+        let nonce = vec![0xAA; 16];
+        let tree = OtsTree {
+            attestations: vec![],
+            ops: vec![
+                (OtsOp::Append(nonce.clone()), OtsTree {
+                    attestations: vec![],
+                    ops: vec![(OtsOp::Sha256, OtsTree {
+                        attestations: vec![OtsAttestation::Bitcoin(height)],
+                        ops: vec![],
+                    })],
+                }),
+                (OtsOp::Prepend(vec![0xBB; 4]), OtsTree {
+                    attestations: vec![OtsAttestation::Pending(s!("https://alice.btc.calendar"))],
+                    ops: vec![],
+                }),
+            ],
+        };
+        let root = Sha256::digest([digest, &nonce].concat()).into();
+        (OtsProof::with(&tree).unwrap(), root)
+    }
+
+    #[test]
+    fn proof_roundtrip() {
+        let (proof, _) = proof(&[0u8; 32], 800_000);
+        assert_eq!(OtsProof::from_str(&proof.to_string()).unwrap(), proof);
+        assert_eq!(proof.attestations().unwrap(), vec![
+            OtsAttestation::Bitcoin(800_000),
+            OtsAttestation::Pending(s!("https://alice.btc.calendar"))
+        ]);
+        let data = proof.as_slice();
+        assert_eq!(OtsProof::from_slice(&data[..data.len() - 1]).unwrap_err(), OtsError::Truncated);
+    }
+
+    #[test]
+    fn cert_timestamp() {
+        let pair = SsiPair::from(SsiSecret::new(Algo::Ed25519, Chain::Bitcoin));
+        let mut cert = pair.sign("Some message");
+        assert_eq!(cert.verify_timestamp(|_| None), Err(OtsError::NoProof));
+
+        let (proof, root) = proof(&cert.timestamp_digest(), 800_000);
+        cert.timestamp = Some(proof);
+        let cert = SsiCert::from_str(&cert.to_string()).unwrap();
+        assert!(cert.timestamp.is_some());
+        assert_eq!(cert.verify_timestamp(|h| (h == 800_000).then_some(root)), Ok(800_000));
+        assert_eq!(cert.verify_timestamp(|_| Some([0u8; 32])), Err(OtsError::Mismatch(800_000)));
+    }
+
+    #[test]
+    fn block_height_range() {
+        let mut data = vec![TAG_ATTESTATION];
+        data.extend(ATTESTATION_BITCOIN);
+        let mut payload = vec![];
+        write_varuint(&mut payload, u32::MAX as u64 + 1);
+        write_varbytes(&mut data, &payload);
+        assert_eq!(
+            OtsProof::from_slice(&data).unwrap_err(),
+            OtsError::InvalidHeight(u32::MAX as u64 + 1)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "ots")]
+    fn upgrade_unknown_calendar() {
+        let pair = SsiPair::from(SsiSecret::new(Algo::Ed25519, Chain::Bitcoin));
+        let mut cert = pair.sign("Some message");
+        let (proof, _) = proof(&cert.timestamp_digest(), 800_000);
+        cert.timestamp = Some(proof.clone());
+        assert_eq!(cert.upgrade(&[]), Ok(false));
+        assert_eq!(cert.upgrade(&["https://bob.btc.calendar"]), Ok(false));
+        assert_eq!(cert.timestamp, Some(proof));
+    }
+}
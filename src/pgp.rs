@@ -0,0 +1,143 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cross-references to existing OpenPGP keys, bound to identities as UIDs
+//! with `pgp` schema.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use amplify::hex::{FromHex, ToHex};
+
+use crate::Uid;
+
+pub const PGP_SCHEMA: &str = "pgp";
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display("invalid OpenPGP fingerprint '{0}'")]
+pub struct InvalidPgpFingerprint(pub String);
+
+/// OpenPGP key fingerprint.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum PgpFingerprint {
+    /// Fingerprint of a v4 key.
+    V4([u8; 20]),
+    /// Fingerprint of a v5 or v6 key.
+    V6([u8; 32]),
+}
+
+impl PgpFingerprint {
+    pub fn as_slice(&self) -> &[u8] {
+        match self {
+            PgpFingerprint::V4(fp) => fp,
+            PgpFingerprint::V6(fp) => fp,
+        }
+    }
+}
+
+/// Displays fingerprint as an uppercase hex string without spaces.
+impl Display for PgpFingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.as_slice().to_hex().to_uppercase())
+    }
+}
+
+/// Parses fingerprint from a hex string in any case, ignoring whitespaces,
+/// as fingerprints are often displayed in groups of four characters.
+impl FromStr for PgpFingerprint {
+    type Err = InvalidPgpFingerprint;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.split_whitespace().collect::<String>();
+        let data = Vec::<u8>::from_hex(&hex).map_err(|_| InvalidPgpFingerprint(s.to_owned()))?;
+        match data.len() {
+            20 => Ok(PgpFingerprint::V4(data.try_into().expect("checked length"))),
+            32 => Ok(PgpFingerprint::V6(data.try_into().expect("checked length"))),
+            _ => Err(InvalidPgpFingerprint(s.to_owned())),
+        }
+    }
+}
+
+impl Uid {
+    /// Constructs UID binding OpenPGP key with the given fingerprint to an
+    /// identity.
+    pub fn pgp(name: impl Into<String>, fingerprint: PgpFingerprint) -> Uid {
+        Uid {
+            name: name.into(),
+            comment: None,
+            schema: PGP_SCHEMA.to_owned(),
+            id: fingerprint.to_string(),
+            primary: false,
+        }
+    }
+
+    /// Parses OpenPGP key fingerprint from a UID with `pgp` schema.
+    ///
+    /// Returns `None` if the UID uses a different schema or doesn't contain
+    /// a valid fingerprint.
+    pub fn pgp_fingerprint(&self) -> Option<PgpFingerprint> {
+        if self.schema != PGP_SCHEMA {
+            return None;
+        }
+        self.id.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Algo, Chain, Ssi, SsiSecret};
+
+    const FP: &str = "0123 4567 89AB CDEF 0123  4567 89AB CDEF 0123 4567";
+
+    #[test]
+    fn fingerprint() {
+        let fp = PgpFingerprint::from_str(FP).unwrap();
+        assert!(matches!(fp, PgpFingerprint::V4(_)));
+        assert_eq!(fp.to_string(), "0123456789ABCDEF0123456789ABCDEF01234567");
+        assert_eq!(PgpFingerprint::from_str(&fp.to_string().to_lowercase()).unwrap(), fp);
+
+        let v6 = PgpFingerprint::from_str(&"AB".repeat(32)).unwrap();
+        assert_eq!(v6, PgpFingerprint::V6([0xAB; 32]));
+
+        assert!(PgpFingerprint::from_str("0123456789").is_err());
+        assert!(PgpFingerprint::from_str(&"XY".repeat(20)).is_err());
+    }
+
+    #[test]
+    fn uid() {
+        let fp = PgpFingerprint::from_str(FP).unwrap();
+        let uid = Uid::pgp("Alice", fp);
+        assert_eq!(uid.to_string(), "Alice <pgp:0123456789ABCDEF0123456789ABCDEF01234567>");
+        assert_eq!(uid.pgp_fingerprint(), Some(fp));
+        assert_eq!(Uid::from_str(&uid.to_string()).unwrap().pgp_fingerprint(), Some(fp));
+
+        let mail = Uid::from_str("Alice <mailto:alice@example.com>").unwrap();
+        assert_eq!(mail.pgp_fingerprint(), None);
+        assert_eq!(Uid::from_str("Alice <pgp:123>").unwrap().pgp_fingerprint(), None);
+
+        let secret = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let ssi = Ssi::new(bset![uid.clone(), mail], None, &secret);
+        let parsed = Ssi::from_str(&ssi.to_string()).unwrap();
+        assert_eq!(parsed, ssi);
+        assert_eq!(parsed.uids.iter().find_map(Uid::pgp_fingerprint), Some(fp));
+    }
+}
@@ -19,20 +19,26 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::fmt::{self, Display, Formatter};
 use std::hash::Hash;
 use std::io;
 use std::str::FromStr;
 
+use amplify::hex::ToHex;
 use amplify::{hex, Bytes, Bytes32, Bytes64, Display};
-use baid64::{Baid64ParseError, DisplayBaid64, FromBaid64Str};
+use baid64::{Baid64ParseError, DisplayBaid64, FromBaid64Str, BAID64_ALPHABET};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use ripemd::Ripemd160;
 use sha2::{Digest, Sha256};
 use strict_encoding::{
-    DecodeError, ReadTuple, StrictDecode, StrictEncode, StrictProduct, StrictTuple, StrictType,
-    TypeName, TypedRead, TypedWrite, WriteTuple,
+    DecodeError, ReadTuple, StrictDecode, StrictDeserialize, StrictDumb, StrictEncode,
+    StrictProduct, StrictSerialize, StrictTuple, StrictType, TypeName, TypedRead, TypedWrite,
+    WriteTuple,
 };
 
-use crate::LIB_NAME_SSI;
+use crate::ots::{OtsParseError, OtsProof};
+use crate::{RevocationSet, Ssi, Validity, LIB_NAME_SSI};
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Display, Default)]
 #[non_exhaustive]
@@ -42,6 +48,8 @@ pub enum Algo {
     Ed25519,
     #[display("bip340")]
     Bip340,
+    #[display("ecdsa")]
+    Ecdsa,
     #[display("other({0})")]
     Other(u8),
 }
@@ -79,6 +87,7 @@ impl FromStr for Algo {
         match s {
             "ed25519" | "Ed25519" | "ED25519" => Ok(Algo::Ed25519),
             "bip340" | "Bip340" | "BIP340" => Ok(Algo::Bip340),
+            "ecdsa" | "Ecdsa" | "ECDSA" => Ok(Algo::Ecdsa),
             s => Err(UnknownAlgo(s.to_owned())),
         }
     }
@@ -93,6 +102,7 @@ impl From<u8> for Algo {
         match value {
             0x13 => Algo::Ed25519,
             0 => Algo::Bip340,
+            1 => Algo::Ecdsa,
             n => Algo::Other(n),
         }
     }
@@ -103,11 +113,48 @@ impl Algo {
         match self {
             Algo::Ed25519 => 0x13,
             Algo::Bip340 => 0,
+            Algo::Ecdsa => 1,
             Algo::Other(v) => *v,
         }
     }
+
+    /// Human-readable algorithm name; `"other"` for unknown algorithms.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algo::Ed25519 => "ed25519",
+            Algo::Bip340 => "bip340",
+            Algo::Ecdsa => "ecdsa",
+            Algo::Other(_) => "other",
+        }
+    }
+
+    /// Length of the public key, in bytes.
+    ///
+    /// ECDSA keys are represented by their X coordinate only, as BIP340 keys.
+    /// Keys of unknown algorithms are stored in the same 32-byte form.
+    pub fn public_key_len(&self) -> usize {
+        match self {
+            Algo::Ed25519 | Algo::Bip340 | Algo::Ecdsa | Algo::Other(_) => 32,
+        }
+    }
+
+    /// Length of the signature, in bytes, not counting the algorithm tag (see
+    /// [`SsiSig::tagged`]).
+    ///
+    /// ECDSA signatures are in compact form. Signatures of unknown algorithms
+    /// are stored in the same 64-byte form.
+    pub fn signature_len(&self) -> usize {
+        match self {
+            Algo::Ed25519 | Algo::Bip340 | Algo::Ecdsa | Algo::Other(_) => 64,
+        }
+    }
 }
 
+/// Chain the identity is scoped to.
+///
+/// The chain is informational only and is not a part of the signature
+/// verification domain: a signature produced with a key for one chain is valid
+/// for the same key under any other chain.
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Display)]
 #[display(lowercase)]
 #[non_exhaustive]
@@ -115,6 +162,8 @@ pub enum Chain {
     #[default]
     Bitcoin,
     Liquid,
+    Signet,
+    Regtest,
     #[display("other({0})")]
     Other(u8),
 }
@@ -152,6 +201,8 @@ impl FromStr for Chain {
         match s {
             "bitcoin" => Ok(Chain::Bitcoin),
             "liquid" => Ok(Chain::Liquid),
+            "signet" => Ok(Chain::Signet),
+            "regtest" => Ok(Chain::Regtest),
             s => Err(UnknownChain(s.to_owned())),
         }
     }
@@ -166,16 +217,50 @@ impl From<u8> for Chain {
         match value {
             0xB7 => Chain::Bitcoin,
             0x10 => Chain::Liquid,
+            0xB8 => Chain::Signet,
+            0xB9 => Chain::Regtest,
             n => Chain::Other(n),
         }
     }
 }
 
 impl Chain {
+    /// Named chains, in the order they should be offered to a user.
+    pub const KNOWN: [Chain; 4] = [Chain::Bitcoin, Chain::Liquid, Chain::Signet, Chain::Regtest];
+
+    /// Human-readable chain name for user interfaces.
+    ///
+    /// Names are stable and distinct for all named chains, including
+    /// [`Chain::Other`] values with a tag of a named chain; all other
+    /// [`Chain::Other`] values share the same generic name, which can't be
+    /// parsed back with [`Chain::from_display_name`].
+    pub fn display_name(&self) -> &'static str {
+        match Chain::from(self.to_u8()) {
+            Chain::Bitcoin => "Bitcoin",
+            Chain::Liquid => "Liquid",
+            Chain::Signet => "Bitcoin Signet",
+            Chain::Regtest => "Bitcoin Regtest",
+            Chain::Other(_) => "Unknown chain",
+        }
+    }
+
+    /// Parses chain from its [`Chain::display_name`] or the short name
+    /// accepted by [`FromStr`], ignoring the letter case and surrounding
+    /// whitespace.
+    pub fn from_display_name(s: &str) -> Option<Chain> {
+        let s = s.trim();
+        Self::KNOWN
+            .into_iter()
+            .find(|chain| chain.display_name().eq_ignore_ascii_case(s))
+            .or_else(|| Chain::from_str(&s.to_ascii_lowercase()).ok())
+    }
+
     pub fn to_u8(&self) -> u8 {
         match self {
             Chain::Bitcoin => 0xB7,
             Chain::Liquid => 0x10,
+            Chain::Signet => 0xB8,
+            Chain::Regtest => 0xB9,
             Chain::Other(v) => *v,
         }
     }
@@ -190,6 +275,9 @@ pub struct SsiPub {
     key: Bytes32,
 }
 
+impl StrictSerialize for SsiPub {}
+impl StrictDeserialize for SsiPub {}
+
 impl DisplayBaid64<34> for SsiPub {
     const HRI: &'static str = "ssi";
     const CHUNKING: bool = true;
@@ -212,6 +300,10 @@ impl From<SsiPub> for [u8; 34] {
     }
 }
 
+impl From<SsiPub> for [u8; 32] {
+    fn from(ssi: SsiPub) -> Self { ssi.to_byte_array() }
+}
+
 impl From<[u8; 34]> for SsiPub {
     fn from(value: [u8; 34]) -> Self {
         let algo = Algo::from(value[0]);
@@ -221,15 +313,41 @@ impl From<[u8; 34]> for SsiPub {
     }
 }
 
+#[derive(Clone, Eq, PartialEq, Debug, Display, From)]
+#[display(inner)]
+pub enum InvalidTag {
+    #[from]
+    Algo(UnknownAlgo),
+    #[from]
+    Chain(UnknownChain),
+}
+
+impl std::error::Error for InvalidTag {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InvalidTag::Algo(err) => Some(err),
+            InvalidTag::Chain(err) => Some(err),
+        }
+    }
+}
+
 impl SsiPub {
+    /// Constructs public key from its parts. Chain and algorithm given as
+    /// `Other` with a known code are normalized to the named variants, such
+    /// that the key is equal (and hashes the same) to the one decoded from
+    /// its binary or text representation.
     pub fn with(chain: Chain, algo: Algo, key: impl Into<[u8; 32]>) -> Self {
         Self {
-            chain,
-            algo,
+            chain: Chain::from(chain.to_u8()),
+            algo: Algo::from(algo.to_u8()),
             key: Bytes32::from(key.into()),
         }
     }
 
+    /// Human-readable name of the chain the key is bound to; see
+    /// [`Chain::display_name`].
+    pub fn chain_label(&self) -> &'static str { self.chain.display_name() }
+
     pub fn verify_text(self, text: &str, sig: SsiSig) -> Result<(), InvalidSig> {
         let msg = Sha256::digest(text);
         let digest = Sha256::digest(msg);
@@ -237,42 +355,339 @@ impl SsiPub {
     }
 
     pub fn verify(self, msg: [u8; 32], sig: SsiSig) -> Result<(), InvalidSig> {
+        if sig.algo().is_some_and(|algo| algo != self.algo) {
+            return Err(InvalidSig::AlgoMismatch);
+        }
         match self.algo {
             Algo::Ed25519 => self.verify_ed25519(msg, sig),
             Algo::Bip340 => self.verify_bip360(msg, sig),
+            Algo::Ecdsa => self.verify_ecdsa(msg, sig),
             Algo::Other(other) => Err(InvalidSig::UnsupportedAlgo(other)),
         }
     }
 
+    pub fn to_byte_array(&self) -> [u8; 32] { self.key.to_byte_array() }
+
+    /// Returns tagged binary representation of the key, which can be parsed
+    /// back with [`SsiPub::from_bytes`]. For the raw key bytes, without the
+    /// tags, use [`SsiPub::to_byte_array`].
+    pub fn to_bytes(&self) -> [u8; 34] { <[u8; 34]>::from(*self) }
+
+    /// Constructs public key from its tagged binary representation, as
+    /// produced by [`SsiPub::to_bytes`] and used for Baid64 encoding: one byte
+    /// of algorithm, one byte of chain, followed by 32 bytes of the key.
+    ///
+    /// Unlike `From<[u8; 34]>`, which converts unknown tag values into
+    /// [`Algo::Other`] and [`Chain::Other`], this function errors on them.
+    pub fn from_bytes(bytes: [u8; 34]) -> Result<Self, InvalidTag> {
+        let pk = SsiPub::from(bytes);
+        if let Algo::Other(algo) = pk.algo {
+            return Err(UnknownAlgo(format!("{algo:#04x}")).into());
+        }
+        if let Chain::Other(chain) = pk.chain {
+            return Err(UnknownChain(format!("{chain:#04x}")).into());
+        }
+        Ok(pk)
+    }
+
     pub fn fingerprint(self) -> Fingerprint {
         Fingerprint([self.key[0], self.key[1], self.key[2], self.key[3], self.key[4], self.key[5]])
     }
+
+    /// Parses leading part of the key string representation, as it may be
+    /// typed by a user, for matching against known keys with
+    /// [`SsiPubPrefix::matches`].
+    ///
+    /// The prefix may include `ssi:pub:` or `ssi:` part and chunk separators.
+    pub fn parse_prefix(s: &str) -> Result<SsiPubPrefix, InvalidPrefix> {
+        let encoded = s
+            .strip_prefix(SSI_PUB_PREFIX)
+            .or_else(|| s.strip_prefix("ssi:"))
+            .unwrap_or(s);
+        let mut data = Vec::with_capacity(34);
+        let mut bits = 0usize;
+        for c in encoded.chars().filter(|c| *c != '-') {
+            let val = BAID64_ALPHABET
+                .find(c)
+                .ok_or_else(|| InvalidPrefix(s.to_owned()))?;
+            if bits >= 34 * 8 {
+                return Err(InvalidPrefix(s.to_owned()));
+            }
+            for i in (0..6).rev() {
+                if bits % 8 == 0 {
+                    data.push(0u8);
+                }
+                if (val >> i) & 1 == 1 {
+                    *data.last_mut().expect("pushed above") |= 0x80 >> (bits % 8);
+                }
+                bits += 1;
+            }
+        }
+        // the last base64 character may contain padding bits
+        bits = bits.min(34 * 8);
+        data.truncate(34);
+        Ok(SsiPubPrefix { data, bits })
+    }
 }
 
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display("invalid public key prefix '{0}'")]
+pub struct InvalidPrefix(pub String);
+
+/// Leading bits of a public key binary representation (see
+/// [`SsiPub::parse_prefix`]).
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct SsiPubPrefix {
+    data: Vec<u8>,
+    bits: usize,
+}
+
+impl SsiPubPrefix {
+    /// Checks whether the key starts with the prefix.
+    pub fn matches(&self, pk: &SsiPub) -> bool {
+        let payload = <[u8; 34]>::from(*pk);
+        let full = self.bits / 8;
+        if payload[..full] != self.data[..full] {
+            return false;
+        }
+        match self.bits % 8 {
+            0 => true,
+            rem => {
+                let mask = 0xFFu8 << (8 - rem);
+                payload[full] & mask == self.data[full] & mask
+            }
+        }
+    }
+}
+
+/// Prefix of the public key string representation.
+pub const SSI_PUB_PREFIX: &str = "ssi:pub:";
+/// Prefix of the signature string representation.
+pub const SSI_SIG_PREFIX: &str = "ssi:sig:";
+/// Tag of the message signed by certificates binding the signing time (see
+/// [`SsiCert::signed_message`]).
+pub const SSI_SIGNED_AT_TAG: &str = "urn:ubideco:ssi:signed-at:v1";
+
+impl SsiPub {
+    /// Returns the key representation used inside SSI URIs and certificates,
+    /// where the `ssi:` scheme already identifies the data as a key.
+    pub fn to_uri_string(&self) -> String { self.to_baid64_string() }
+}
+
+/// Displays the key with [`SSI_PUB_PREFIX`], making it distinguishable from
+/// a signature; the alternate form is the key fingerprint.
 impl Display for SsiPub {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         if !f.alternate() {
-            self.fmt_baid64(f)
+            let s = self.to_baid64_string();
+            write!(f, "{SSI_PUB_PREFIX}{}", s.strip_prefix("ssi:").unwrap_or(&s))
         } else {
             write!(f, "{}", self.fingerprint())
         }
     }
 }
 
+/// Error parsing string representation of a key or a signature.
+#[derive(Debug, Display, From)]
+#[display(doc_comments)]
+pub enum Baid64Error {
+    #[from]
+    #[display(inner)]
+    Baid64(Baid64ParseError),
+
+    /// '{0}' is not a canonical string representation.
+    NonCanonical(String),
+
+    /// '{0}' is a string representation of a different type of data.
+    MismatchedType(String),
+
+    /// SSI URI '{0}' doesn't contain a key.
+    NoKey(String),
+}
+
+impl std::error::Error for Baid64Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Baid64Error::Baid64(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Baid64Error {
+    /// Classifies the error, allowing user interfaces to hint on the likely
+    /// cause of the failure.
+    pub fn kind(&self) -> Baid64ErrorKind {
+        match self {
+            Baid64Error::Baid64(err) => Baid64ErrorKind::of(err),
+            Baid64Error::NonCanonical(_) => Baid64ErrorKind::NonCanonical,
+            Baid64Error::MismatchedType(_) => Baid64ErrorKind::MismatchedType,
+            Baid64Error::NoKey(_) => Baid64ErrorKind::Length,
+        }
+    }
+}
+
+/// Class of a key, signature or fingerprint string parse failure.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(doc_comments)]
+pub enum Baid64ErrorKind {
+    /// checksum mismatch - likely a typo.
+    Checksum,
+    /// wrong length - the string is truncated or contains extra characters.
+    Length,
+    /// the string contains characters which are not a part of Baid64 alphabet.
+    Charset,
+    /// wrong human-readable prefix.
+    Prefix,
+    /// the string encodes invalid data.
+    Payload,
+    /// non-canonical encoding.
+    NonCanonical,
+    /// the string represents a different type of data.
+    MismatchedType,
+}
+
+impl Baid64ErrorKind {
+    pub fn of(err: &Baid64ParseError) -> Self {
+        use base64::DecodeError;
+
+        match err {
+            Baid64ParseError::InvalidHri(..) => Baid64ErrorKind::Prefix,
+            Baid64ParseError::InvalidLen(_) => Baid64ErrorKind::Length,
+            Baid64ParseError::InvalidChecksum(..)
+            | Baid64ParseError::InvalidMnemonicLen(_)
+            | Baid64ParseError::InvalidMnemonic(_) => Baid64ErrorKind::Checksum,
+            Baid64ParseError::Base64(DecodeError::InvalidLength(_)) => Baid64ErrorKind::Length,
+            Baid64ParseError::Base64(_) => Baid64ErrorKind::Charset,
+            Baid64ParseError::InvalidPayload(_) => Baid64ErrorKind::Payload,
+        }
+    }
+}
+
 impl FromStr for SsiPub {
-    type Err = Baid64ParseError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::from_baid64_str(s) }
+    type Err = Baid64Error;
+
+    /// Parses key from its canonical string representation. Other encodings
+    /// of the same key, accepted by Baid64 (with different chunking, checksum
+    /// or mnemonic), are rejected. Both [`SSI_PUB_PREFIX`] and the legacy
+    /// `ssi:` prefix are accepted; the prefix may also be omitted, since in
+    /// SSI URIs it is the scheme. Signatures are rejected.
+    ///
+    /// If the string is a full SSI URI, like `ssi:<key>?uid=...`, only its
+    /// path is parsed as the key, and the query and fragment are ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with(SSI_SIG_PREFIX) {
+            return Err(Baid64Error::MismatchedType(s.to_owned()));
+        }
+        let s = if s.starts_with("ssi:") {
+            let path = s.split(['?', '#']).next().unwrap_or_default();
+            if path == "ssi:" || path == SSI_PUB_PREFIX {
+                return Err(Baid64Error::NoKey(s.to_owned()));
+            }
+            path
+        } else {
+            s
+        };
+        let uri = match s.strip_prefix(SSI_PUB_PREFIX) {
+            Some(bare) => format!("ssi:{bare}"),
+            None => s.to_owned(),
+        };
+        let pk = Self::from_baid64_str(&uri)?;
+        let canonical = pk.to_uri_string();
+        if uri != canonical && Some(uri.as_str()) != canonical.strip_prefix("ssi:") {
+            return Err(Baid64Error::NonCanonical(s.to_owned()));
+        }
+        Ok(pk)
+    }
 }
 
-#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, From)]
-#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
-#[strict_type(lib = LIB_NAME_SSI)]
-pub struct SsiSig(#[from([u8; 64])] Bytes64);
+/// Signature, which may be optionally tagged with the signature algorithm.
+///
+/// Untagged signatures are displayed as plain Baid64 strings; tagged
+/// signatures use `sig:` prefix and include the algorithm byte, allowing
+/// verification dispatch when the public key is not known upfront.
+///
+/// Strict encoding keeps the type and the layout of untagged signatures from
+/// the previous versions, i.e. just the 64 signature bytes. The algorithm tag
+/// is not encoded, thus the decoded signatures are always untagged.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct SsiSig {
+    algo: Option<Algo>,
+    sig: Bytes64,
+}
+
+impl StrictType for SsiSig {
+    const STRICT_LIB_NAME: &'static str = LIB_NAME_SSI;
+    fn strict_name() -> Option<TypeName> { Some(tn!("SsiSig")) }
+}
+impl StrictDumb for SsiSig {
+    fn strict_dumb() -> Self { SsiSig::from([0u8; 64]) }
+}
+impl StrictProduct for SsiSig {}
+impl StrictTuple for SsiSig {
+    const FIELD_COUNT: u8 = 1;
+}
+impl StrictEncode for SsiSig {
+    fn strict_encode<W: TypedWrite>(&self, writer: W) -> io::Result<W> {
+        writer.write_tuple::<Self>(|w| Ok(w.write_field(&self.sig)?.complete()))
+    }
+}
+impl StrictDecode for SsiSig {
+    fn strict_decode(reader: &mut impl TypedRead) -> Result<Self, DecodeError> {
+        reader.read_tuple(|r| {
+            let sig = r.read_field::<Bytes64>()?;
+            Ok(Self { algo: None, sig })
+        })
+    }
+}
+impl StrictSerialize for SsiSig {}
+impl StrictDeserialize for SsiSig {}
+
+impl From<[u8; 64]> for SsiSig {
+    fn from(sig: [u8; 64]) -> Self {
+        SsiSig {
+            algo: None,
+            sig: sig.into(),
+        }
+    }
+}
 
 impl SsiSig {
-    pub fn as_slice(&self) -> &[u8] { self.0.as_slice() }
+    /// Constructs signature tagged with the signature algorithm, normalizing
+    /// `Algo::Other` with a known code like [`SsiPub::with`] does.
+    pub fn tagged(algo: Algo, bytes: [u8; 64]) -> Self {
+        SsiSig {
+            algo: Some(Algo::from(algo.to_u8())),
+            sig: bytes.into(),
+        }
+    }
+
+    /// Returns signature algorithm for tagged signatures.
+    pub fn algo(&self) -> Option<Algo> { self.algo }
+
+    /// Returns the same signature without the algorithm tag.
+    pub fn to_untagged(&self) -> Self { SsiSig::from(self.to_byte_array()) }
+
+    pub fn as_slice(&self) -> &[u8] { self.sig.as_slice() }
+
+    pub fn to_byte_array(&self) -> [u8; 64] { self.sig.to_byte_array() }
 }
 
+#[derive(From)]
+struct TaggedSig(#[from] [u8; 65]);
+
+impl DisplayBaid64<65> for TaggedSig {
+    const HRI: &'static str = "sig";
+    const CHUNKING: bool = false;
+    const PREFIX: bool = true;
+    const EMBED_CHECKSUM: bool = false;
+    const MNEMONIC: bool = false;
+
+    fn to_baid64_payload(&self) -> [u8; 65] { self.0 }
+}
+
+impl FromBaid64Str<65> for TaggedSig {}
+
 impl DisplayBaid64<64> for SsiSig {
     const HRI: &'static str = "";
     const CHUNKING: bool = false;
@@ -280,18 +695,72 @@ impl DisplayBaid64<64> for SsiSig {
     const EMBED_CHECKSUM: bool = false;
     const MNEMONIC: bool = false;
 
-    fn to_baid64_payload(&self) -> [u8; 64] { self.0.to_byte_array() }
+    fn to_baid64_payload(&self) -> [u8; 64] { self.sig.to_byte_array() }
 }
 
 impl FromBaid64Str<64> for SsiSig {}
 
 impl FromStr for SsiSig {
-    type Err = Baid64ParseError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::from_baid64_str(s) }
+    type Err = Baid64Error;
+
+    /// Parses signature from its canonical string representation, rejecting
+    /// other Baid64 encodings of the same signature. Both the
+    /// [`SSI_SIG_PREFIX`]-prefixed form and the bare form used inside SSI URIs
+    /// are accepted; public keys are rejected.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with(SSI_PUB_PREFIX) {
+            return Err(Baid64Error::MismatchedType(s.to_owned()));
+        }
+        let Some(bare) = s.strip_prefix(SSI_SIG_PREFIX) else {
+            let sig = Self::from_uri_str(s)?;
+            if sig.to_uri_string() != s {
+                return Err(Baid64Error::NonCanonical(s.to_owned()));
+            }
+            return Ok(sig);
+        };
+        // Tagged signatures are one byte longer, thus the bare payload can be
+        // decoded as exactly one of the two forms
+        let sig = Self::from_uri_str(bare)
+            .or_else(|_| Self::from_uri_str(&format!("sig:{bare}")))?;
+        if sig.to_string() != s {
+            return Err(Baid64Error::NonCanonical(s.to_owned()));
+        }
+        Ok(sig)
+    }
+}
+
+impl SsiSig {
+    fn from_uri_str(s: &str) -> Result<Self, Baid64Error> {
+        if !s.starts_with("sig:") {
+            return Ok(Self::from_baid64_str(s)?);
+        }
+        let TaggedSig(data) = TaggedSig::from_baid64_str(s)?;
+        let mut sig = [0u8; 64];
+        sig.copy_from_slice(&data[1..]);
+        Ok(SsiSig::tagged(Algo::from(data[0]), sig))
+    }
+
+    /// Returns the signature representation used inside SSI URIs and
+    /// certificates: a plain Baid64 string for untagged signatures and
+    /// `sig:`-prefixed one for the tagged signatures.
+    pub fn to_uri_string(&self) -> String {
+        let Some(algo) = self.algo else {
+            return self.to_baid64_string();
+        };
+        let mut data = [0u8; 65];
+        data[0] = algo.to_u8();
+        data[1..].copy_from_slice(self.as_slice());
+        TaggedSig(data).to_baid64_string()
+    }
 }
 
+/// Displays the signature with [`SSI_SIG_PREFIX`], making it distinguishable
+/// from a public key.
 impl Display for SsiSig {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { self.fmt_baid64(f) }
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = self.to_uri_string();
+        write!(f, "{SSI_SIG_PREFIX}{}", s.strip_prefix("sig:").unwrap_or(&s))
+    }
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
@@ -311,8 +780,18 @@ pub enum InvalidSig {
     /// signature doesn't match the given identity and a message.
     InvalidSig,
 
+    /// signature is malleated (has high S value).
+    Malleated,
+
+    /// signature algorithm doesn't match the identity key algorithm.
+    AlgoMismatch,
+
     /// can't verify signature - unsupported signature method {0}.
     UnsupportedAlgo(u8),
+
+    /// can't verify signature - identity is referenced by its fingerprint
+    /// only.
+    CannotVerify,
 }
 
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default, Debug, Display, From)]
@@ -330,11 +809,11 @@ pub enum SsiQuery {
 }
 
 impl FromStr for SsiQuery {
-    type Err = Baid64ParseError;
+    type Err = Baid64Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if s.len() == 8 {
-            Fingerprint::from_str(s).map(Self::Fp)
+            Ok(Fingerprint::from_str(s).map(Self::Fp)?)
         } else if s.starts_with("ssi:") || (s.contains('-') && (s.len() == 48 || s.len() == 52)) {
             SsiPub::from_str(s).map(Self::Pub)
         } else {
@@ -370,50 +849,384 @@ impl FromStr for Fingerprint {
     fn from_str(s: &str) -> Result<Self, Self::Err> { Self::from_baid64_str(s) }
 }
 
+impl Fingerprint {
+    /// Computes key fingerprint using the given scheme. Fingerprints of
+    /// different schemes never compare equal.
+    pub fn compute(pk: &SsiPub, scheme: FingerprintScheme) -> KeyFingerprint {
+        let key = pk.key.to_byte_array();
+        match scheme {
+            FingerprintScheme::Prefix48 => KeyFingerprint::Prefix48(pk.fingerprint()),
+            FingerprintScheme::Sha256Trunc160 => {
+                let mut fp = [0u8; 20];
+                fp.copy_from_slice(&Sha256::digest(key)[..20]);
+                KeyFingerprint::Sha256Trunc160(fp)
+            }
+            FingerprintScheme::Ripemd160 => {
+                KeyFingerprint::Ripemd160(Ripemd160::digest(key).into())
+            }
+        }
+    }
+}
+
+/// Method of deriving a key fingerprint, for the interoperability with
+/// systems mandating a specific fingerprint algorithm.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Display)]
+#[display(lowercase)]
+pub enum FingerprintScheme {
+    /// The first six bytes of the key, used by [`Fingerprint`].
+    #[default]
+    Prefix48,
+    /// SHA256 hash of the key, truncated to 20 bytes.
+    Sha256Trunc160,
+    /// RIPEMD160 hash of the key.
+    Ripemd160,
+}
+
+/// Key fingerprint tagged with the scheme it was computed with (see
+/// [`Fingerprint::compute`]).
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum KeyFingerprint {
+    Prefix48(Fingerprint),
+    Sha256Trunc160([u8; 20]),
+    Ripemd160([u8; 20]),
+}
+
+impl KeyFingerprint {
+    pub fn scheme(&self) -> FingerprintScheme {
+        match self {
+            KeyFingerprint::Prefix48(_) => FingerprintScheme::Prefix48,
+            KeyFingerprint::Sha256Trunc160(_) => FingerprintScheme::Sha256Trunc160,
+            KeyFingerprint::Ripemd160(_) => FingerprintScheme::Ripemd160,
+        }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        match self {
+            KeyFingerprint::Prefix48(fp) => &fp.0,
+            KeyFingerprint::Sha256Trunc160(fp) | KeyFingerprint::Ripemd160(fp) => fp,
+        }
+    }
+
+    /// Checks whether the fingerprint is computed from the key.
+    pub fn matches(&self, pk: &SsiPub) -> bool { Fingerprint::compute(pk, self.scheme()) == *self }
+}
+
+/// Displays [`FingerprintScheme::Prefix48`] fingerprints in the same way as
+/// [`Fingerprint`]; other schemes are displayed as hex strings prefixed with
+/// the scheme name.
+impl Display for KeyFingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyFingerprint::Prefix48(fp) => Display::fmt(fp, f),
+            _ => write!(f, "{}:{}", self.scheme(), self.as_slice().to_hex()),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 #[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
 #[strict_type(lib = LIB_NAME_SSI)]
 pub struct SsiCert {
     pub fp: Fingerprint,
     pub pk: Option<SsiPub>,
+    pub subject: Option<SsiPub>,
     pub msg: Bytes32,
     pub sig: SsiSig,
+    /// Signing time bound by the signature, in seconds since the Unix epoch
+    /// (see [`SsiCert::signed_at`]).
+    pub signed_at: Option<i64>,
+    pub timestamp: Option<OtsProof>,
 }
 
-#[derive(Debug, Display, Error, From)]
+#[derive(Debug, Display, From)]
 #[display(inner)]
 pub enum VerifyError {
     #[display("the certificate has no identity, verification impossible.")]
     NoIdentity,
-    #[from]
+    #[display("can't verify signature - unsupported signature method {0}.")]
+    UnsupportedAlgo(u8),
     InvalidSig(InvalidSig),
     #[display("the provided text doesn't match the signed message")]
     MessageMismatch,
+    #[display("the certificate is issued for a different identity")]
+    SubjectMismatch,
+    #[display("the certificate is not signed by the provided identity")]
+    IdentityMismatch,
+    #[display("the signer identity has no valid self-signature")]
+    BrokenIdentity,
+    #[display("the signer identity has expired")]
+    Expired,
+    #[display("the signer key is revoked")]
+    Revoked,
+    #[display("the certificate is not a valid self-signed key revocation")]
+    InvalidRevocation,
+    #[display("the signer key for fingerprint {0} can't be resolved")]
+    UnresolvedSigner(Fingerprint),
+    #[display("the identity has no endorsement by {0}")]
+    NoEndorsement(Fingerprint),
+    #[display("the certificate doesn't bind the signing time")]
+    NoSigningTime,
+    #[display("the certificate was signed at {0}, which is too long ago")]
+    TooOld(DateTime<Utc>),
+    #[display("the certificate signing time {0} is in the future")]
+    SignedInFuture(DateTime<Utc>),
+    #[display("the certificate chain is empty")]
+    EmptyChain,
+    #[display("certificate #{0} in the chain doesn't certify an identity")]
+    NoSubject(usize),
+    #[display("the subject of certificate #{0} in the chain is not valid: {1}")]
+    InvalidSubject(usize, Validity),
+    #[display("certificate #{0} in the chain is not issued by the previous subject")]
+    BrokenLink(usize),
+    #[display("the certificate chain loops back to {0}")]
+    Cycle(Fingerprint),
+}
+
+impl std::error::Error for VerifyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VerifyError::InvalidSig(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<InvalidSig> for VerifyError {
+    fn from(err: InvalidSig) -> Self {
+        match err {
+            InvalidSig::UnsupportedAlgo(algo) => VerifyError::UnsupportedAlgo(algo),
+            err => VerifyError::InvalidSig(err),
+        }
+    }
+}
+
+/// Verifier of signatures over message digests, allowing generic code to
+/// accept keys in different forms.
+pub trait VerifyingIdentity {
+    fn verify(&self, msg: [u8; 32], sig: SsiSig) -> Result<(), InvalidSig>;
+}
+
+impl VerifyingIdentity for SsiPub {
+    fn verify(&self, msg: [u8; 32], sig: SsiSig) -> Result<(), InvalidSig> {
+        SsiPub::verify(*self, msg, sig)
+    }
+}
+
+/// Source of identity keys used to verify certificates which carry just the
+/// signer fingerprint.
+pub trait KeyResolver {
+    fn resolve(&self, query: &SsiQuery) -> Option<SsiPub>;
+}
+
+impl KeyResolver for HashMap<Fingerprint, SsiPub> {
+    fn resolve(&self, query: &SsiQuery) -> Option<SsiPub> {
+        match query {
+            SsiQuery::Pub(pk) => self.get(&pk.fingerprint()).filter(|known| *known == pk).copied(),
+            SsiQuery::Fp(fp) => self.get(fp).copied(),
+            SsiQuery::Default | SsiQuery::Id(_) => None,
+        }
+    }
 }
 
 impl SsiCert {
+    /// Fingerprint of the identity which has issued (signed) the certificate.
+    pub fn issuer(&self) -> Fingerprint { self.fp }
+
+    /// Identity certified by the certificate, if the certificate is a
+    /// certification of other identity and not a signature over some message.
+    pub fn subject(&self) -> Option<SsiPub> { self.subject }
+
+    /// Time at which the certificate was signed, if the certificate binds one
+    /// (see [`SsiSecret::certify_at`]).
+    ///
+    /// [`SsiSecret::certify_at`]: crate::SsiSecret::certify_at
+    pub fn signed_at(&self) -> Option<DateTime<Utc>> {
+        self.signed_at.and_then(|secs| DateTime::from_timestamp(secs, 0))
+    }
+
+    /// Message covered by the certificate signature. For certificates binding
+    /// the signing time this is a tagged hash of the message digest and the
+    /// time; otherwise it is the message digest itself.
+    pub fn signed_message(&self) -> [u8; 32] {
+        let Some(secs) = self.signed_at else {
+            return self.msg.to_byte_array();
+        };
+        let tag = Sha256::digest(SSI_SIGNED_AT_TAG);
+        let mut engine = Sha256::new();
+        engine.update(tag);
+        engine.update(tag);
+        engine.update(self.msg);
+        engine.update(secs.to_le_bytes());
+        engine.finalize().into()
+    }
+
     pub fn verify(&self) -> Result<(), VerifyError> {
         let Some(pk) = self.pk else {
             return Err(VerifyError::NoIdentity);
         };
-        Ok(pk.verify(self.msg.to_byte_array(), self.sig)?)
+        Ok(pk.verify(self.signed_message(), self.sig)?)
+    }
+
+    /// Verifies the certificate, requiring it to bind the signing time which
+    /// is not older than `max_age` at the moment `now` and is not after `now`.
+    /// Returns the signing time.
+    pub fn verify_with_max_age(
+        &self,
+        max_age: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<DateTime<Utc>, VerifyError> {
+        self.verify()?;
+        let signed_at = self.signed_at().ok_or(VerifyError::NoSigningTime)?;
+        if signed_at > now {
+            return Err(VerifyError::SignedInFuture(signed_at));
+        }
+        if now - signed_at > max_age {
+            return Err(VerifyError::TooOld(signed_at));
+        }
+        Ok(signed_at)
+    }
+
+    /// Digest signed by certificates over the given data, which is a double
+    /// SHA256 hash of it.
+    pub fn data_digest(data: impl AsRef<[u8]>) -> [u8; 32] {
+        let msg = Sha256::digest(data);
+        Sha256::digest(msg).into()
     }
 
-    pub fn verify_text(&self, text: &str) -> Result<(), VerifyError> {
+    pub fn verify_text(&self, text: &str) -> Result<(), VerifyError> { self.verify_data(text) }
+
+    /// Verifies that the certificate signs the given data, like the content of
+    /// a document with a detached signature.
+    pub fn verify_data(&self, data: impl AsRef<[u8]>) -> Result<(), VerifyError> {
         let Some(pk) = self.pk else {
             return Err(VerifyError::NoIdentity);
         };
-        let msg = Sha256::digest(text);
-        let digest = Sha256::digest(msg);
-        let msg = <[u8; 32]>::from(digest);
+        let msg = Self::data_digest(data);
         if self.msg.to_byte_array() != msg {
             return Err(VerifyError::MessageMismatch);
         }
-        Ok(pk.verify(digest.into(), self.sig)?)
+        Ok(pk.verify(self.signed_message(), self.sig)?)
+    }
+
+    /// Verifies the certificate against the full identity of the signer,
+    /// checking that the identity has an intact self-signature, wasn't
+    /// expired at the moment `at` and its key is not revoked.
+    pub fn verify_against(
+        &self,
+        signer: &Ssi,
+        at: DateTime<Utc>,
+        revocations: &RevocationSet,
+    ) -> Result<(), VerifyError> {
+        if self.fp != signer.pk.fingerprint() || self.pk.is_some_and(|pk| pk != signer.pk) {
+            return Err(VerifyError::IdentityMismatch);
+        }
+        match signer.validity(at) {
+            Validity::Valid => {}
+            Validity::Expired { .. } => return Err(VerifyError::Expired),
+            Validity::NotSigned | Validity::BadSignature(_) => {
+                return Err(VerifyError::BrokenIdentity)
+            }
+        }
+        if revocations.is_revoked(&signer.pk) {
+            return Err(VerifyError::Revoked);
+        }
+        Ok(signer.pk.verify(self.signed_message(), self.sig)?)
+    }
+
+    /// Verifies the certificate, looking up the signer key with the `resolver`
+    /// if the certificate carries just the signer fingerprint.
+    pub fn verify_with(&self, resolver: &impl KeyResolver) -> Result<(), VerifyError> {
+        let pk = match self.pk {
+            Some(pk) => pk,
+            None => resolver
+                .resolve(&SsiQuery::Fp(self.fp))
+                .ok_or(VerifyError::UnresolvedSigner(self.fp))?,
+        };
+        if pk.fingerprint() != self.fp {
+            return Err(VerifyError::IdentityMismatch);
+        }
+        Ok(pk.verify(self.signed_message(), self.sig)?)
+    }
+
+    /// Verifies that the certificate is a valid certification of the `subject`
+    /// identity in its current form (i.e. with the current set of UIDs and
+    /// expiry).
+    pub fn verify_certification(&self, subject: &Ssi) -> Result<(), VerifyError> {
+        if self.subject != Some(subject.pk) {
+            return Err(VerifyError::SubjectMismatch);
+        }
+        if self.msg.to_byte_array() != subject.to_message() {
+            return Err(VerifyError::MessageMismatch);
+        }
+        self.verify()
     }
 }
 
-#[derive(Debug, Display, Error, From)]
+/// Certification path from a trust anchor to some identity, where each
+/// certificate is issued by the subject of the previous one: the anchor
+/// certifies the first subject, which certifies the second one, and so on.
+///
+/// Each certificate is stored together with the subject identity it
+/// certifies.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct CertChain(Vec<(SsiCert, Ssi)>);
+
+impl CertChain {
+    pub fn new() -> Self { Self::default() }
+
+    /// Appends certificate issued by the subject of the last certificate,
+    /// together with the identity it certifies.
+    pub fn push(&mut self, cert: SsiCert, subject: Ssi) { self.0.push((cert, subject)) }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&SsiCert, &Ssi)> {
+        self.0.iter().map(|(cert, subject)| (cert, subject))
+    }
+
+    pub fn len(&self) -> usize { self.0.len() }
+
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+
+    /// Verifies that the first certificate is issued by the `anchor`, each
+    /// next one is issued by the subject of the previous one, each certificate
+    /// is a valid certification of its subject identity in its current form
+    /// (see [`SsiCert::verify_certification`]), all subject identities are
+    /// self-signed and not expired at the moment `at`, and no identity appears
+    /// in the path twice. Returns the subject of the last certificate.
+    pub fn verify(&self, anchor: SsiPub, at: DateTime<Utc>) -> Result<&Ssi, VerifyError> {
+        let mut issuer = anchor;
+        let mut seen = bset![anchor];
+        for (no, (cert, subject)) in self.0.iter().enumerate() {
+            if cert.fp != issuer.fingerprint() || cert.pk.is_some_and(|pk| pk != issuer) {
+                return Err(VerifyError::BrokenLink(no));
+            }
+            if cert.subject.is_none() {
+                return Err(VerifyError::NoSubject(no));
+            }
+            let cert = SsiCert {
+                pk: Some(issuer),
+                ..cert.clone()
+            };
+            cert.verify_certification(subject)?;
+            let validity = subject.validity(at);
+            if !validity.is_valid() {
+                return Err(VerifyError::InvalidSubject(no, validity));
+            }
+            if !seen.insert(subject.pk) {
+                return Err(VerifyError::Cycle(subject.pk.fingerprint()));
+            }
+            issuer = subject.pk;
+        }
+        self.0.last().map(|(_, subject)| subject).ok_or(VerifyError::EmptyChain)
+    }
+}
+
+impl FromIterator<(SsiCert, Ssi)> for CertChain {
+    fn from_iter<T: IntoIterator<Item = (SsiCert, Ssi)>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+#[derive(Debug, Display, From)]
 #[display(doc_comments)]
 pub enum CertParseError {
     /// SSI URI lacks signature or message information.
@@ -421,28 +1234,61 @@ pub enum CertParseError {
     /// invalid certificate identity fingerprint - {0}.
     InvalidFingerprint(Baid64ParseError),
     /// invalid certificate identity key - {0}.
-    InvalidPub(Baid64ParseError),
+    InvalidPub(Baid64Error),
     /// invalid message digest - {0}.
     #[from]
     InvalidMessage(hex::Error),
     #[from]
     /// invalid signature data - {0}
-    InvalidSig(Baid64ParseError),
+    InvalidSig(Baid64Error),
+    /// invalid certified identity key - {0}.
+    InvalidSubject(Baid64Error),
+    /// certificate contains invalid attribute '{0}'.
+    InvalidQueryParam(String),
+    /// certificate contains unknown attribute '{0}'.
+    UnknownParam(String),
+    /// invalid timestamp proof - {0}.
+    InvalidTimestamp(OtsParseError),
+    /// invalid signing time '{0}'.
+    InvalidSigningTime(String),
+}
+
+impl std::error::Error for CertParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CertParseError::InvalidFingerprint(err) => Some(err),
+            CertParseError::InvalidPub(err) => Some(err),
+            CertParseError::InvalidMessage(err) => Some(err),
+            CertParseError::InvalidSig(err) => Some(err),
+            CertParseError::InvalidSubject(err) => Some(err),
+            CertParseError::InvalidTimestamp(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl CertParseError {
+    /// Class of the key, signature or fingerprint parse failure, if the error
+    /// is caused by one.
+    pub fn baid64_kind(&self) -> Option<Baid64ErrorKind> {
+        match self {
+            CertParseError::InvalidFingerprint(err) => Some(Baid64ErrorKind::of(err)),
+            CertParseError::InvalidPub(err)
+            | CertParseError::InvalidSig(err)
+            | CertParseError::InvalidSubject(err) => Some(err.kind()),
+            _ => None,
+        }
+    }
 }
 
 impl FromStr for SsiCert {
     type Err = CertParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (fp, rest) = s
+        let (fp, query) = s
             .trim_start_matches("ssi:")
             .split_once('?')
             .ok_or(CertParseError::DataMissed)?;
-        let (msg, rest) = rest
-            .trim_start_matches("msg=")
-            .split_once('&')
-            .ok_or(CertParseError::DataMissed)?;
-        let sig = rest.trim_start_matches("sig=");
         let (fp, pk) = match fp.len() {
             8 => (Fingerprint::from_str(fp).map_err(CertParseError::InvalidFingerprint)?, None),
             _ => {
@@ -450,19 +1296,485 @@ impl FromStr for SsiCert {
                 (pk.fingerprint(), Some(pk))
             }
         };
-        let msg = Bytes32::from_str(msg)?;
-        let sig = SsiSig::from_str(sig)?;
-        Ok(SsiCert { fp, pk, msg, sig })
+
+        let mut msg = None;
+        let mut sig = None;
+        let mut subject = None;
+        let mut signed_at = None;
+        let mut timestamp = None;
+        for p in query.split('&') {
+            let (k, v) = p
+                .split_once('=')
+                .ok_or_else(|| CertParseError::InvalidQueryParam(p.to_owned()))?;
+            match k {
+                "msg" => msg = Some(Bytes32::from_str(v)?),
+                "sig" => sig = Some(SsiSig::from_str(v)?),
+                "subject" => {
+                    subject = Some(SsiPub::from_str(v).map_err(CertParseError::InvalidSubject)?)
+                }
+                "time" => {
+                    let time = DateTime::parse_from_rfc3339(v)
+                        .map_err(|_| CertParseError::InvalidSigningTime(v.to_owned()))?;
+                    signed_at = Some(time.timestamp())
+                }
+                "ots" => {
+                    timestamp =
+                        Some(OtsProof::from_str(v).map_err(CertParseError::InvalidTimestamp)?)
+                }
+                other => return Err(CertParseError::UnknownParam(other.to_owned())),
+            }
+        }
+        let msg = msg.ok_or(CertParseError::DataMissed)?;
+        let sig = sig.ok_or(CertParseError::DataMissed)?;
+
+        Ok(SsiCert {
+            fp,
+            pk,
+            subject,
+            msg,
+            sig,
+            signed_at,
+            timestamp,
+        })
     }
 }
 
 impl Display for SsiCert {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        if f.alternate() {
-            if let Some(pk) = self.pk {
-                return write!(f, "{pk}?msg={msg}&sig={sig}", msg = self.msg, sig = self.sig);
-            }
+        match self.pk {
+            Some(pk) if f.alternate() => write!(f, "{}", pk.to_uri_string())?,
+            _ => write!(f, "ssi:{}", self.fp)?,
+        }
+        write!(f, "?msg={msg}&sig={sig}", msg = self.msg, sig = self.sig.to_uri_string())?;
+        if let Some(subject) = self.subject {
+            write!(f, "&subject={}", subject.to_uri_string())?;
+        }
+        if let Some(signed_at) = self.signed_at() {
+            write!(f, "&time={}", signed_at.to_rfc3339_opts(SecondsFormat::Secs, true))?;
+        }
+        if let Some(timestamp) = &self.timestamp {
+            write!(f, "&ots={timestamp}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hash_of(val: impl Hash) -> u64 {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::Hasher;
+        let mut hasher = DefaultHasher::new();
+        val.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn chain_display_names() {
+        let names = Chain::KNOWN.map(|chain| chain.display_name());
+        assert_eq!(names, ["Bitcoin", "Liquid", "Bitcoin Signet", "Bitcoin Regtest"]);
+        assert_eq!(names.iter().collect::<std::collections::BTreeSet<_>>().len(), names.len());
+        for chain in Chain::KNOWN {
+            assert_eq!(Chain::from_display_name(chain.display_name()), Some(chain));
+            let upper = chain.display_name().to_uppercase();
+            assert_eq!(Chain::from_display_name(&format!(" {upper} ")), Some(chain));
+            assert_ne!(chain.display_name(), Chain::Other(0).display_name());
+        }
+        assert_eq!(Chain::from_display_name(Chain::Other(1).display_name()), None);
+        assert_eq!(Chain::from_display_name("bitcoin regtest"), Some(Chain::Regtest));
+        assert_eq!(Chain::from_display_name(" Signet"), Some(Chain::Signet));
+        assert_eq!(Chain::from_display_name("regtest"), Some(Chain::Regtest));
+        assert_eq!(Chain::Other(0xB7).display_name(), Chain::Bitcoin.display_name());
+        assert_eq!(Chain::from_display_name("testnet"), None);
+
+        let pk = SsiPub::with(Chain::Liquid, Algo::Bip340, [1u8; 32]);
+        assert_eq!(pk.chain_label(), "Liquid");
+        let pk = SsiPub::with(Chain::Other(0xB8), Algo::Bip340, [1u8; 32]);
+        assert_eq!(pk.chain_label(), "Bitcoin Signet");
+    }
+
+    #[test]
+    fn eq_across_encodings() {
+        use crate::SsiSecret;
+
+        let sk = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        let pk = sk.to_public();
+        let parsed = SsiPub::from_str(&pk.to_uri_string()).unwrap();
+        let from_bytes = SsiPub::from(<[u8; 34]>::from(pk));
+        let with = SsiPub::with(Chain::Other(0xB7), Algo::Other(0), pk.to_byte_array());
+        for other in [parsed, from_bytes, with] {
+            assert_eq!(other, pk);
+            assert_eq!(hash_of(other), hash_of(pk));
+        }
+        let map = HashMap::from([(parsed, "alice")]);
+        assert_eq!(map.get(&with), Some(&"alice"));
+
+        let sig = sk.sign([0xA5; 32]);
+        let parsed = SsiSig::from_str(&sig.to_uri_string()).unwrap();
+        let from_bytes = SsiSig::from(sig.to_byte_array());
+        for other in [parsed, from_bytes] {
+            assert_eq!(other, sig);
+            assert_eq!(hash_of(other), hash_of(sig));
+        }
+
+        let tagged = SsiSig::tagged(Algo::Bip340, sig.to_byte_array());
+        let parsed = SsiSig::from_str(&tagged.to_uri_string()).unwrap();
+        let other = SsiSig::tagged(Algo::Other(0), sig.to_byte_array());
+        for other in [parsed, other] {
+            assert_eq!(other, tagged);
+            assert_eq!(hash_of(other), hash_of(tagged));
+        }
+        assert_eq!(tagged.to_untagged(), sig);
+    }
+
+    #[test]
+    fn sig_strict_encoding() {
+        use amplify::confinement::Confined;
+
+        // Signatures encoded by the versions without the algorithm tags
+        let data = Confined::try_from(vec![0xA5u8; 64]).unwrap();
+        let sig = SsiSig::from_strict_serialized::<64>(data.clone()).unwrap();
+        assert_eq!(sig, SsiSig::from([0xA5; 64]));
+        assert_eq!(sig.algo(), None);
+        assert_eq!(sig.to_strict_serialized::<64>().unwrap(), data);
+
+        let tagged = SsiSig::tagged(Algo::Bip340, [0xA5; 64]);
+        assert_eq!(tagged.to_strict_serialized::<64>().unwrap(), data);
+        assert_eq!(SsiSig::from_strict_serialized::<64>(data).unwrap(), tagged.to_untagged());
+        assert_eq!(SsiSig::strict_name(), Some(tn!("SsiSig")));
+    }
+
+    #[test]
+    fn pub_from_uri() {
+        use crate::SsiSecret;
+
+        let pk = SsiSecret::new(Algo::Bip340, Chain::Bitcoin).to_public();
+        let uri = pk.to_uri_string();
+        for s in [
+            format!("{uri}?uid=Alice&expiry=2030-01-01"),
+            format!("{uri}?"),
+            format!("{uri}#fragment"),
+            format!("{pk}?v=sha256"),
+        ] {
+            assert_eq!(SsiPub::from_str(&s).unwrap(), pk, "{s}");
+        }
+        for s in ["ssi:", "ssi:?uid=Alice", "ssi:pub:#x"] {
+            let err = SsiPub::from_str(s).unwrap_err();
+            assert!(matches!(err, Baid64Error::NoKey(_)), "{s}");
+            assert_eq!(err.kind(), Baid64ErrorKind::Length);
+        }
+        assert!(SsiPub::from_str("ssi:invalid?uid=Alice").is_err());
+        let truncated = format!("{}?uid=Alice", &uri[..uri.len() - 1]);
+        assert!(SsiPub::from_str(&truncated).is_err());
+    }
+
+    #[test]
+    fn pub_bytes() {
+        let pk = SsiPub::with(Chain::Bitcoin, Algo::Ed25519, [0xAB; 32]);
+        assert_eq!(pk.to_byte_array(), [0xAB; 32]);
+        assert_eq!(<[u8; 32]>::from(pk), [0xAB; 32]);
+        assert_eq!(pk.to_bytes(), <[u8; 34]>::from(pk));
+        assert_eq!(SsiPub::from_bytes(pk.to_bytes()), Ok(pk));
+
+        let mut bytes = pk.to_bytes();
+        bytes[0] = 0xFE;
+        assert_eq!(SsiPub::from_bytes(bytes), Err(UnknownAlgo(s!("0xfe")).into()));
+        assert_eq!(SsiPub::from(bytes).algo(), &Algo::Other(0xFE));
+
+        let mut bytes = <[u8; 34]>::from(pk);
+        bytes[1] = 0xFE;
+        assert_eq!(SsiPub::from_bytes(bytes), Err(UnknownChain(s!("0xfe")).into()));
+    }
+
+    #[test]
+    fn non_canonical() {
+        use crate::SsiSecret;
+
+        let sk = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let pk = sk.to_public();
+        let canonical = pk.to_uri_string();
+        assert_eq!(SsiPub::from_str(&canonical).unwrap(), pk);
+        assert_eq!(SsiPub::from_str(&canonical["ssi:".len()..]).unwrap(), pk);
+
+        let unchunked = canonical.replace('-', "");
+        let rechunked = format!("{}-{}", &unchunked[..12], &unchunked[12..]);
+        let spaced = canonical.replace('-', "--");
+        for s in [unchunked, rechunked, spaced] {
+            assert!(matches!(SsiPub::from_str(&s), Err(Baid64Error::NonCanonical(_))), "{s}");
+        }
+        assert!(SsiPub::from_str(&format!("ssi:{canonical}")).is_err());
+
+        let sig = sk.sign([0xA5; 32]);
+        let s = sig.to_uri_string();
+        assert_eq!(SsiSig::from_str(&s).unwrap(), sig);
+        let rechunked = format!("{}-{}", &s[..10], &s[10..]);
+        assert!(matches!(SsiSig::from_str(&rechunked), Err(Baid64Error::NonCanonical(_))));
+        assert!(matches!(SsiSig::from_str(&format!(":{s}")), Err(Baid64Error::NonCanonical(_))));
+
+        let tagged = SsiSig::tagged(Algo::Ed25519, sig.to_byte_array()).to_uri_string();
+        let rechunked = format!("{}-{}", &tagged[..10], &tagged[10..]);
+        assert!(matches!(SsiSig::from_str(&rechunked), Err(Baid64Error::NonCanonical(_))));
+    }
+
+    #[test]
+    fn type_prefixes() {
+        use crate::SsiSecret;
+
+        let sk = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        let pk = sk.to_public();
+        let s = pk.to_string();
+        assert!(s.starts_with(SSI_PUB_PREFIX));
+        assert_eq!(SsiPub::from_str(&s).unwrap(), pk);
+        assert_eq!(SsiPub::from_str(&pk.to_uri_string()).unwrap(), pk);
+        assert_eq!(SsiPub::from_str(&s[SSI_PUB_PREFIX.len()..]).unwrap(), pk);
+        assert_eq!(format!("ssi:{}", &s[SSI_PUB_PREFIX.len()..]), pk.to_uri_string());
+
+        let sig = sk.sign([0xA5; 32]);
+        let tagged = SsiSig::tagged(Algo::Bip340, sig.to_byte_array());
+        for sig in [sig, tagged] {
+            let s = sig.to_string();
+            assert!(s.starts_with(SSI_SIG_PREFIX));
+            assert_eq!(SsiSig::from_str(&s).unwrap(), sig);
+            assert_eq!(SsiSig::from_str(&sig.to_uri_string()).unwrap(), sig);
+
+            assert!(matches!(SsiPub::from_str(&s), Err(Baid64Error::MismatchedType(_))));
+            assert!(SsiPub::from_str(&sig.to_uri_string()).is_err());
+        }
+        assert!(matches!(SsiSig::from_str(&pk.to_string()), Err(Baid64Error::MismatchedType(_))));
+        assert!(SsiSig::from_str(&pk.to_uri_string()).is_err());
+    }
+
+    #[test]
+    fn fingerprint_schemes() {
+        let pk = SsiPub::with(Chain::Bitcoin, Algo::Ed25519, [0xAB; 32]);
+        let default = Fingerprint::compute(&pk, FingerprintScheme::default());
+        assert_eq!(default, KeyFingerprint::Prefix48(pk.fingerprint()));
+        assert_eq!(default.to_string(), pk.fingerprint().to_string());
+
+        let sha = Fingerprint::compute(&pk, FingerprintScheme::Sha256Trunc160);
+        let ripemd = Fingerprint::compute(&pk, FingerprintScheme::Ripemd160);
+        assert_eq!(sha.as_slice(), &Sha256::digest([0xAB; 32])[..20]);
+        assert_eq!(ripemd.as_slice().len(), 20);
+        assert_eq!(sha.scheme(), FingerprintScheme::Sha256Trunc160);
+        assert!(sha.to_string().starts_with("sha256trunc160:"));
+        assert!(ripemd.to_string().starts_with("ripemd160:"));
+
+        let mut data = [0u8; 20];
+        data.copy_from_slice(sha.as_slice());
+        assert_ne!(KeyFingerprint::Ripemd160(data), sha);
+
+        for fp in [default, sha, ripemd] {
+            assert!(fp.matches(&pk));
+            assert!(!fp.matches(&SsiPub::with(Chain::Bitcoin, Algo::Ed25519, [0xBA; 32])));
+        }
+    }
+
+    #[test]
+    fn error_source() {
+        use std::error::Error;
+
+        let errors = [
+            CertParseError::InvalidFingerprint(Fingerprint::from_str("invalid").unwrap_err()),
+            CertParseError::InvalidPub(SsiPub::from_str("invalid").unwrap_err()),
+            CertParseError::InvalidMessage(Bytes32::from_str("invalid").unwrap_err()),
+            CertParseError::InvalidSig(SsiSig::from_str("invalid").unwrap_err()),
+            CertParseError::InvalidSubject(SsiPub::from_str("invalid").unwrap_err()),
+            CertParseError::InvalidTimestamp(OtsProof::from_str("invalid").unwrap_err()),
+        ];
+        for err in errors {
+            assert!(err.source().is_some(), "{err}");
+        }
+        assert!(CertParseError::DataMissed.source().is_none());
+
+        assert!(Baid64Error::NonCanonical(s!("invalid")).source().is_none());
+        assert!(VerifyError::InvalidSig(InvalidSig::InvalidData).source().is_some());
+        assert!(InvalidTag::from(UnknownAlgo(s!("0xfe"))).source().is_some());
+    }
+
+    #[test]
+    fn baid64_error_kinds() {
+        let pk = SsiPub::with(Chain::Bitcoin, Algo::Ed25519, [0xAB; 32]);
+        let kind = |s: &str| SsiPub::from_str(s).unwrap_err().kind();
+        let s = pk.to_uri_string();
+
+        let mut mistyped = pk.display_baid64().embed_checksum().to_string().into_bytes();
+        mistyped[10] = if mistyped[10] == b'A' { b'B' } else { b'A' };
+        let mistyped = String::from_utf8(mistyped).unwrap();
+        assert_eq!(kind(&mistyped), Baid64ErrorKind::Checksum);
+        // fragments of full SSI URIs are ignored, thus the key is given without the scheme
+        assert_eq!(kind(&format!("{}#invalid-mnemonic", &s[4..])), Baid64ErrorKind::Checksum);
+        // 40 characters remain, which decode into 30 bytes without leftover bits
+        let unchunked = s.replace('-', "");
+        let truncated = &unchunked[..unchunked.len() - 6];
+        assert_eq!(kind(truncated), Baid64ErrorKind::Length);
+        assert_eq!(kind(&format!("{s}AAAA")), Baid64ErrorKind::Length);
+        assert_eq!(kind(&s.replacen('-', "*", 1)), Baid64ErrorKind::Charset);
+        assert_eq!(kind(&format!("pgp:{}", &s[4..])), Baid64ErrorKind::Prefix);
+        assert_eq!(kind(&unchunked), Baid64ErrorKind::NonCanonical);
+        assert_eq!(kind(&format!("{SSI_SIG_PREFIX}{}", &s[4..])), Baid64ErrorKind::MismatchedType);
+
+        let cert = format!("{truncated}?msg={}&sig=A", Bytes32::from([0; 32]));
+        let err = SsiCert::from_str(&cert).unwrap_err();
+        assert_eq!(err.baid64_kind(), Some(Baid64ErrorKind::Length));
+    }
+
+    #[test]
+    fn tagged_sig() {
+        use crate::SsiSecret;
+
+        let sk = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        let msg = [0xA5; 32];
+        let sig = sk.sign(msg);
+        assert_eq!(sig.algo(), None);
+        assert_eq!(SsiSig::from_str(&sig.to_string()).unwrap(), sig);
+
+        let tagged = SsiSig::tagged(Algo::Bip340, sig.to_byte_array());
+        assert_eq!(tagged.algo(), Some(Algo::Bip340));
+        assert!(tagged.to_uri_string().starts_with("sig:"));
+        assert!(tagged.to_string().starts_with(SSI_SIG_PREFIX));
+        assert_eq!(SsiSig::from_str(&tagged.to_string()).unwrap(), tagged);
+        assert_eq!(tagged.to_untagged(), sig);
+        assert_ne!(tagged, sig);
+
+        let pk = sk.to_public();
+        pk.verify(msg, sig).unwrap();
+        pk.verify(msg, tagged).unwrap();
+        let mistagged = SsiSig::tagged(Algo::Ed25519, sig.to_byte_array());
+        assert_eq!(pk.verify(msg, mistagged), Err(InvalidSig::AlgoMismatch));
+    }
+
+    #[test]
+    fn verify_with_resolver() {
+        use crate::{SsiPair, SsiSecret};
+
+        let sk = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        let pk = sk.to_public();
+        let mut cert = SsiPair::from(sk).sign("message");
+        cert.pk = None;
+
+        let mut resolver = HashMap::new();
+        assert!(matches!(
+            cert.verify_with(&resolver),
+            Err(VerifyError::UnresolvedSigner(fp)) if fp == pk.fingerprint()
+        ));
+
+        resolver.insert(pk.fingerprint(), pk);
+        cert.verify_with(&resolver).unwrap();
+        assert_eq!(resolver.resolve(&SsiQuery::Pub(pk)), Some(pk));
+
+        let other = SsiSecret::new(Algo::Bip340, Chain::Bitcoin).to_public();
+        resolver.insert(pk.fingerprint(), other);
+        assert!(matches!(cert.verify_with(&resolver), Err(VerifyError::IdentityMismatch)));
+    }
+
+    #[test]
+    fn cert_chain() {
+        use crate::SsiSecret;
+
+        let keys = (0..4)
+            .map(|_| SsiSecret::new(Algo::Bip340, Chain::Bitcoin))
+            .collect::<Vec<_>>();
+        let ssi = keys.iter().map(|sk| Ssi::new(bset![], None, sk)).collect::<Vec<_>>();
+        let anchor = ssi[0].pk;
+        let now = Utc::now();
+        let link = |issuer: usize, subject: usize| {
+            (keys[issuer].certify(&ssi[subject]), ssi[subject].clone())
+        };
+
+        let chain = (0..3).map(|i| link(i, i + 1)).collect::<CertChain>();
+        assert_eq!(chain.len(), 3);
+        assert_eq!(chain.verify(anchor, now).unwrap(), &ssi[3]);
+        assert!(matches!(chain.verify(ssi[1].pk, now), Err(VerifyError::BrokenLink(0))));
+        assert!(matches!(CertChain::new().verify(anchor, now), Err(VerifyError::EmptyChain)));
+
+        let broken = [link(0, 1), link(0, 2), link(2, 3)].into_iter().collect::<CertChain>();
+        assert!(matches!(broken.verify(anchor, now), Err(VerifyError::BrokenLink(1))));
+
+        let mut forged = chain.clone();
+        forged.0[1].1 = ssi[3].clone();
+        assert!(matches!(forged.verify(anchor, now), Err(VerifyError::SubjectMismatch)));
+        forged.0[1].0.subject = Some(ssi[3].pk);
+        assert!(matches!(forged.verify(anchor, now), Err(VerifyError::MessageMismatch)));
+
+        let mut changed = chain.clone();
+        changed.0[2].1.comment = Some(s!("changed"));
+        assert!(matches!(changed.verify(anchor, now), Err(VerifyError::MessageMismatch)));
+
+        let expiring = Ssi::new(bset![], Some(now + Duration::days(1)), &keys[1]);
+        let mut expired = chain.clone();
+        expired.0[0] = (keys[0].certify(&expiring), expiring);
+        assert!(expired.verify(anchor, now).is_ok());
+        assert!(matches!(
+            expired.verify(anchor, now + Duration::days(2)),
+            Err(VerifyError::InvalidSubject(0, Validity::Expired { .. }))
+        ));
+
+        let mut cyclic = chain.clone();
+        cyclic.push(keys[3].certify(&ssi[1]), ssi[1].clone());
+        assert!(matches!(
+            cyclic.verify(anchor, now),
+            Err(VerifyError::Cycle(fp)) if fp == ssi[1].pk.fingerprint()
+        ));
+        let back = [link(0, 1), link(1, 0)].into_iter().collect::<CertChain>();
+        assert!(matches!(back.verify(anchor, now), Err(VerifyError::Cycle(_))));
+    }
+
+    #[test]
+    fn pub_prefix() {
+        let pk = SsiPub::with(Chain::Bitcoin, Algo::Ed25519, [0xAB; 32]);
+        let s = pk.to_string();
+        for len in SSI_PUB_PREFIX.len()..=s.len() {
+            let prefix = SsiPub::parse_prefix(&s[..len]).unwrap();
+            assert!(prefix.matches(&pk), "{}", &s[..len]);
+        }
+        let other = SsiPub::with(Chain::Liquid, Algo::Ed25519, [0xAB; 32]);
+        assert!(SsiPub::parse_prefix("ssi:").unwrap().matches(&other));
+        assert!(!SsiPub::parse_prefix(&s).unwrap().matches(&other));
+        assert!(SsiPub::parse_prefix("ssi:A*").is_err());
+        assert!(SsiPub::parse_prefix(&format!("{s}A")).is_err());
+    }
+
+    #[test]
+    fn algo_meta() {
+        assert_eq!(Algo::Ed25519.name(), "ed25519");
+        assert_eq!(Algo::Bip340.name(), "bip340");
+        assert_eq!(Algo::Ecdsa.name(), "ecdsa");
+        assert_eq!(Algo::Other(0x42).name(), "other");
+        for algo in [Algo::Ed25519, Algo::Bip340, Algo::Ecdsa] {
+            assert_eq!(Algo::from_str(algo.name()), Ok(algo));
+            assert_eq!(algo.public_key_len(), 32);
+            assert_eq!(algo.signature_len(), 64);
+
+            let sk = crate::SsiSecret::new(algo, Chain::Bitcoin);
+            assert_eq!(sk.to_public().to_byte_array().len(), algo.public_key_len());
+            assert_eq!(sk.sign([0xA5; 32]).to_byte_array().len(), algo.signature_len());
+        }
+        assert_eq!(Algo::Other(0x42).public_key_len(), 32);
+        assert_eq!(Algo::Other(0x42).signature_len(), 64);
+    }
+
+    #[test]
+    fn chains() {
+        use crate::SsiSecret;
+
+        assert_eq!(Chain::Bitcoin.to_u8(), 0xB7);
+        assert_eq!(Chain::Liquid.to_u8(), 0x10);
+        for chain in [Chain::Bitcoin, Chain::Liquid, Chain::Signet, Chain::Regtest] {
+            assert_eq!(Chain::from_str(&chain.to_string()), Ok(chain));
+            assert_eq!(Chain::from(chain.to_u8()), chain);
+
+            let sk = SsiSecret::new(Algo::Bip340, chain);
+            let pk = sk.to_public();
+            assert_eq!(pk.chain(), &chain);
+            assert_eq!(SsiPub::from_str(&pk.to_string()).unwrap(), pk);
+            assert_eq!(SsiPub::from_bytes(pk.to_bytes()), Ok(pk));
+
+            let msg = [0xA5; 32];
+            let sig = sk.sign(msg);
+            let other = SsiPub::with(Chain::Bitcoin, Algo::Bip340, pk.to_byte_array());
+            other.verify(msg, sig).unwrap();
         }
-        write!(f, "ssi:{fp}?msg={msg}&sig={sig}", fp = self.fp, msg = self.msg, sig = self.sig)
     }
 }
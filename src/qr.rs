@@ -0,0 +1,118 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Encoding of identity keys for QR codes.
+
+use bech32::primitives::decode::{CheckedHrpstring, CheckedHrpstringError};
+use bech32::{Bech32m, Hrp};
+
+use crate::SsiPub;
+
+const HRP_SSI: Hrp = Hrp::parse_unchecked("ssi");
+
+#[derive(Clone, Debug, Display, From)]
+#[display(doc_comments)]
+pub enum QrKeyError {
+    #[from]
+    /// invalid bech32m encoding - {0}
+    Bech32(CheckedHrpstringError),
+
+    /// invalid key prefix '{0}'.
+    InvalidHrp(String),
+
+    /// invalid length of key data.
+    InvalidData,
+}
+
+impl std::error::Error for QrKeyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            QrKeyError::Bech32(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl SsiPub {
+    /// Encodes the key, including its algorithm and chain, as an uppercase
+    /// bech32m string with `SSI1` prefix. Unlike the default baid64 form, the
+    /// string consists only of characters of the QR code alphanumeric mode,
+    /// which makes the QR code smaller.
+    pub fn to_qr_str(&self) -> String {
+        bech32::encode_upper::<Bech32m>(HRP_SSI, &<[u8; 34]>::from(*self))
+            .expect("key data always fits bech32m string")
+    }
+
+    /// Parses the key from the form produced by [`SsiPub::to_qr_str`], in
+    /// either upper or lower case. Strings with bech32 (rather than bech32m)
+    /// checksum are rejected.
+    pub fn from_qr_str(s: &str) -> Result<Self, QrKeyError> {
+        let checked = CheckedHrpstring::new::<Bech32m>(s)?;
+        if checked.hrp() != HRP_SSI {
+            return Err(QrKeyError::InvalidHrp(checked.hrp().to_string()));
+        }
+        let data = checked.byte_iter().collect::<Vec<_>>();
+        let data = <[u8; 34]>::try_from(data).map_err(|_| QrKeyError::InvalidData)?;
+        Ok(SsiPub::from(data))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Algo, Chain, SsiSecret};
+
+    const QR_ALPHANUMERIC: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+    #[test]
+    fn qr_str() {
+        for algo in [Algo::Ed25519, Algo::Bip340, Algo::Ecdsa] {
+            for chain in [Chain::Bitcoin, Chain::Liquid, Chain::Signet, Chain::Regtest] {
+                let pk = SsiSecret::new(algo, chain).to_public();
+                let s = pk.to_qr_str();
+                assert!(s.starts_with("SSI1"), "{s}");
+                assert!(s.chars().all(|c| QR_ALPHANUMERIC.contains(c)), "{s}");
+                assert_eq!(SsiPub::from_qr_str(&s).unwrap(), pk);
+                assert_eq!(SsiPub::from_qr_str(&s.to_lowercase()).unwrap(), pk);
+            }
+        }
+
+        let pk = SsiPub::with(Chain::Other(0x77), Algo::Other(0x42), [7u8; 32]);
+        assert_eq!(SsiPub::from_qr_str(&pk.to_qr_str()).unwrap(), pk);
+    }
+
+    #[test]
+    fn qr_str_invalid() {
+        let pk = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin).to_public();
+        let s = pk.to_qr_str();
+        let last = if s.ends_with('Q') { 'P' } else { 'Q' };
+        let corrupted = format!("{}{last}", &s[..s.len() - 1]);
+        assert!(matches!(SsiPub::from_qr_str(&corrupted), Err(QrKeyError::Bech32(_))));
+
+        let data = <[u8; 34]>::from(pk);
+        let npub = bech32::encode_upper::<Bech32m>(Hrp::parse_unchecked("npub"), &data).unwrap();
+        assert!(matches!(SsiPub::from_qr_str(&npub), Err(QrKeyError::InvalidHrp(_))));
+        let short = bech32::encode_upper::<Bech32m>(HRP_SSI, &data[..33]).unwrap();
+        assert!(matches!(SsiPub::from_qr_str(&short), Err(QrKeyError::InvalidData)));
+        let bech32 = bech32::encode_upper::<bech32::Bech32>(HRP_SSI, &data).unwrap();
+        assert!(matches!(SsiPub::from_qr_str(&bech32), Err(QrKeyError::Bech32(_))));
+    }
+}
@@ -0,0 +1,151 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeSet;
+
+use sha2::{Digest, Sha256};
+
+use crate::{SsiCert, SsiPub};
+
+pub const SSI_REVOCATION_TAG: &str = "urn:ubideco:ssi:revocation:v1";
+
+/// Reason for revoking an identity key, which is committed to by the
+/// revocation certificate.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Display)]
+#[display(lowercase)]
+#[repr(u8)]
+pub enum RevocationReason {
+    Unspecified = 0,
+    /// Secret key was compromised.
+    Compromised = 1,
+    /// Key was replaced with a new one.
+    Superseded = 2,
+    /// Key is no longer used.
+    Retired = 3,
+}
+
+impl RevocationReason {
+    pub const ALL: [RevocationReason; 4] = [
+        RevocationReason::Unspecified,
+        RevocationReason::Compromised,
+        RevocationReason::Superseded,
+        RevocationReason::Retired,
+    ];
+}
+
+/// Computes message signed by the revocation certificate for the key `pk`.
+pub(crate) fn revocation_message(pk: SsiPub, reason: RevocationReason) -> [u8; 32] {
+    let tag = Sha256::digest(SSI_REVOCATION_TAG);
+    let mut engine = Sha256::new();
+    engine.update(tag);
+    engine.update(tag);
+    engine.update(<[u8; 34]>::from(pk));
+    engine.update([reason as u8]);
+    engine.finalize().into()
+}
+
+impl SsiCert {
+    /// Returns revocation reason if the certificate is a self-signed
+    /// revocation of the subject key, without checking the signature (see
+    /// [`SsiPub::is_revoked_by`]).
+    pub fn revocation_reason(&self) -> Option<RevocationReason> {
+        let pk = self.subject?;
+        if self.pk != Some(pk) || self.fp != pk.fingerprint() {
+            return None;
+        }
+        RevocationReason::ALL
+            .into_iter()
+            .find(|reason| revocation_message(pk, *reason) == self.msg.to_byte_array())
+    }
+}
+
+impl SsiPub {
+    /// Checks whether the certificate is a valid revocation of this key,
+    /// which must be signed by the key itself.
+    pub fn is_revoked_by(&self, cert: &SsiCert) -> bool {
+        cert.subject == Some(*self) &&
+            cert.revocation_reason().is_some() &&
+            self.verify(cert.signed_message(), cert.sig).is_ok()
+    }
+}
+
+/// Set of revoked identity keys.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct RevocationSet(BTreeSet<SsiPub>);
+
+impl RevocationSet {
+    pub fn new() -> Self { Self::default() }
+
+    /// Marks key as revoked. Returns `false` if the key was already revoked.
+    pub fn insert(&mut self, pk: SsiPub) -> bool { self.0.insert(pk) }
+
+    pub fn is_revoked(&self, pk: &SsiPub) -> bool { self.0.contains(pk) }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SsiPub> { self.0.iter() }
+
+    pub fn len(&self) -> usize { self.0.len() }
+
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+}
+
+impl FromIterator<SsiPub> for RevocationSet {
+    fn from_iter<T: IntoIterator<Item = SsiPub>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Algo, Chain, SsiPair, SsiSecret};
+
+    #[test]
+    fn revoke() {
+        let sk = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let pk = sk.to_public();
+        let cert = sk.revoke(RevocationReason::Compromised);
+        assert_eq!(cert.revocation_reason(), Some(RevocationReason::Compromised));
+        assert!(pk.is_revoked_by(&cert));
+        assert!(!pk.is_revoked_by(&SsiPair::from(sk.clone()).sign("revocation")));
+
+        let other = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        assert!(!other.to_public().is_revoked_by(&cert));
+    }
+
+    #[test]
+    fn foreign_revocation() {
+        let sk = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let pk = sk.to_public();
+        let mallory = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+
+        let mut cert = mallory.revoke(RevocationReason::Retired);
+        cert.subject = Some(pk);
+        assert!(!pk.is_revoked_by(&cert));
+
+        let msg = revocation_message(pk, RevocationReason::Retired);
+        cert.fp = pk.fingerprint();
+        cert.pk = Some(pk);
+        cert.msg = msg.into();
+        cert.sig = mallory.sign(msg);
+        assert_eq!(cert.revocation_reason(), Some(RevocationReason::Retired));
+        assert!(!pk.is_revoked_by(&cert));
+    }
+}
@@ -21,19 +21,22 @@
 
 pub const SSI_DIR: &str = "~/.ssi";
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, VecDeque};
 use std::fs;
 use std::io::{self, BufRead, Write};
 use std::os::unix::fs::PermissionsExt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use baid64::Baid64ParseError;
+use chrono::{DateTime, Utc};
 
 use crate::{
-    EncryptedSecret, Fingerprint, SecretParseError, Ssi, SsiPair, SsiParseError, SsiQuery,
+    CertParseError, EncryptedSecret, Fingerprint, KeyResolver, KeyUsage, RevocationReason,
+    RevocationSet, SecretParseError, SigFileError, Ssi, SsiCert, SsiPair, SsiParseError, SsiPub,
+    SsiQuery, SsiSecret, VerifyError,
 };
 
-#[derive(Debug, Display, Error, From)]
+#[derive(Debug, Display, From)]
 #[display(inner)]
 pub enum LoadError {
     #[from]
@@ -47,6 +50,51 @@ pub enum LoadError {
 
     #[from]
     Ssi(SsiParseError),
+
+    #[from]
+    Cert(CertParseError),
+
+    #[display("file '{0}' doesn't contain a valid revocation certificate")]
+    InvalidRevocation(String),
+
+    #[display("secret key {0} doesn't match the stored identity")]
+    KeyMismatch(Fingerprint),
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(err) => Some(err),
+            LoadError::Baid64(err) => Some(err),
+            LoadError::Secret(err) => Some(err),
+            LoadError::Ssi(err) => Some(err),
+            LoadError::Cert(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Display, From)]
+#[display(inner)]
+pub enum VerifyFileError {
+    #[from]
+    Io(io::Error),
+
+    #[from]
+    SigFile(SigFileError),
+
+    #[from]
+    Verify(VerifyError),
+}
+
+impl std::error::Error for VerifyFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VerifyFileError::Io(err) => Some(err),
+            VerifyFileError::SigFile(err) => Some(err),
+            VerifyFileError::Verify(err) => Some(err),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error, From)]
@@ -56,20 +104,137 @@ pub enum SignerError {
     UnknownIdentity,
     /// wrong password.
     WrongPassword,
+    /// the secret key doesn't match the identity.
+    KeyMismatch,
+    /// secrets are not available, the runtime is in a verify-only mode.
+    NoSecret,
+    /// the runtime is loaded for verification only and never accesses secrets.
+    VerifyOnly,
+    /// the identity expiry date {0} is already in the past.
+    Expired(DateTime<Utc>),
+    /// no secret key matching the identity is known.
+    NoSuchKey,
+    /// the key is not authorized for {0} usage.
+    UsageNotAllowed(KeyUsage),
+}
+
+/// Operation on the runtime reported to the audit sink (see
+/// [`SsiRuntime::set_audit_sink`]). Events never contain secret material.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(doc_comments)]
+pub enum AuditEvent {
+    /// signed a message with {fingerprint}.
+    Signed { fingerprint: Fingerprint },
+    /// revealed secret key of {fingerprint}.
+    Revealed { fingerprint: Fingerprint },
+    /// failed to reveal secret key of {fingerprint}.
+    FailedReveal { fingerprint: Fingerprint },
+    /// imported certification of {subject} issued by {issuer}.
+    Imported {
+        issuer: Fingerprint,
+        subject: Fingerprint,
+    },
+    /// revoked {fingerprint}.
+    Revoked { fingerprint: Fingerprint },
 }
 
+/// Receiver of [`AuditEvent`]s (see [`SsiRuntime::set_audit_sink`]).
+pub type AuditSink = Box<dyn Fn(&AuditEvent) + Send + Sync>;
+
+#[derive(Default)]
 pub struct SsiRuntime {
     pub secrets: BTreeSet<EncryptedSecret>,
     pub identities: BTreeSet<Ssi>,
+    pub certs: BTreeSet<SsiCert>,
+    pub revocations: RevocationSet,
+    /// Revocation certificates stored with [`SsiRuntime::store_revocation`].
+    pub revocation_certs: BTreeSet<SsiCert>,
+    /// Runtime has no access to secrets and can be used only for
+    /// verification.
+    pub verify_only: bool,
+    /// Runtime was loaded with [`SsiRuntime::load_public_only`] and never
+    /// accesses secrets.
+    pub public_only: bool,
+    audit_sink: Option<AuditSink>,
 }
 
 impl SsiRuntime {
     pub fn load() -> Result<Self, LoadError> {
-        let data_dir = PathBuf::from(shellexpand::tilde(SSI_DIR).to_string());
+        Self::load_from(PathBuf::from(shellexpand::tilde(SSI_DIR).to_string()))
+    }
+
+    /// Loads runtime data from a given directory instead of [`SSI_DIR`].
+    pub fn load_from(data_dir: impl AsRef<Path>) -> Result<Self, LoadError> {
         fs::create_dir_all(&data_dir)?;
+        Self::load_split(&data_dir, &data_dir)
+    }
+
+    /// Loads secrets and public data (identities and certificates) from
+    /// separate directories.
+    ///
+    /// If the secrets directory doesn't exist, the runtime is loaded in a
+    /// verify-only mode, where it refuses to sign with
+    /// [`SignerError::NoSecret`] and never writes secrets on
+    /// [`SsiRuntime::store_split`].
+    pub fn load_split(
+        secrets_dir: impl AsRef<Path>,
+        identities_dir: impl AsRef<Path>,
+    ) -> Result<Self, LoadError> {
+        Self::load_dirs(Some(secrets_dir.as_ref()), identities_dir.as_ref())
+    }
+
+    /// Loads only public data (identities and certificates) from a given
+    /// directory, without reading any secrets. Signing with such runtime
+    /// always fails with [`SignerError::VerifyOnly`].
+    pub fn load_public_only(data_dir: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let mut runtime = Self::load_dirs(None, data_dir.as_ref())?;
+        runtime.public_only = true;
+        Ok(runtime)
+    }
+
+    /// Iterates over identities stored in a given directory, parsing them one
+    /// at a time without reading secrets and certificates. Unlike loading the
+    /// whole runtime, allows a lookup to stop as soon as the identity is found.
+    pub fn iter_public(data_dir: impl AsRef<Path>) -> impl Iterator<Item = Result<Ssi, LoadError>> {
+        let (file, err) = match fs::File::open(data_dir.as_ref().join("identities")) {
+            Ok(file) => (Some(file), None),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (None, None),
+            Err(err) => (None, Some(LoadError::Io(err))),
+        };
+        let lines = file
+            .into_iter()
+            .flat_map(|file| io::BufReader::new(file).lines())
+            .map(|line| -> Result<Ssi, LoadError> { Ok(Ssi::from_str_unsigned(&line?)?) });
+        err.map(Err).into_iter().chain(lines)
+    }
+
+    fn load_dirs(secrets_dir: Option<&Path>, data_dir: &Path) -> Result<Self, LoadError> {
+        let data_dir = data_dir.to_path_buf();
+        fs::create_dir_all(&data_dir)?;
+
+        let secrets_dir = secrets_dir.filter(|dir| dir.is_dir());
+        let verify_only = secrets_dir.is_none();
+        let mut secrets: BTreeSet<EncryptedSecret> = bset![];
+        if let Some(secrets_dir) = secrets_dir {
+            let mut path = secrets_dir.to_path_buf();
+            path.push("secrets");
+            let file = fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)?;
+            let mut permissions = file.metadata()?.permissions();
+            permissions.set_mode(0o600);
+            let reader = io::BufReader::new(file);
+            for line in reader.lines() {
+                let line = line?;
+                secrets.insert(line.parse()?);
+            }
+        }
 
         let mut path = data_dir.clone();
-        path.push("secrets");
+        path.push("identities");
         let file = fs::OpenOptions::new()
             .read(true)
             .write(true)
@@ -79,46 +244,103 @@ impl SsiRuntime {
         let mut permissions = file.metadata()?.permissions();
         permissions.set_mode(0o600);
         let reader = io::BufReader::new(file);
-        let mut secrets = bset![];
+        // Own identities may be kept unsigned until signed by the runtime
+        let mut identities: BTreeSet<Ssi> = bset![];
         for line in reader.lines() {
             let line = line?;
-            secrets.insert(line.parse()?);
+            identities.insert(Ssi::from_str_unsigned(&line)?);
         }
 
+        // Secret data doesn't store the chain, so it is taken from the identity, which must use
+        // the same key algorithm.
+        let secrets = secrets
+            .into_iter()
+            .map(|mut secret| {
+                if let Some(ssi) = identities.iter().find(|ssi| ssi.pk.fingerprint() == secret.fp) {
+                    if *ssi.pk.algo() != secret.algo {
+                        return Err(LoadError::KeyMismatch(secret.fp));
+                    }
+                    secret.chain = *ssi.pk.chain();
+                }
+                Ok(secret)
+            })
+            .collect::<Result<_, _>>()?;
+
         let mut path = data_dir.clone();
-        path.push("identities");
+        path.push("certs");
         let file = fs::OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
             .truncate(false)
             .open(path)?;
-        let mut permissions = file.metadata()?.permissions();
-        permissions.set_mode(0o600);
         let reader = io::BufReader::new(file);
-        let mut identities = bset![];
+        let mut certs = bset![];
         for line in reader.lines() {
             let line = line?;
-            identities.insert(line.parse()?);
+            certs.insert(line.parse()?);
+        }
+
+        let mut revocations = RevocationSet::new();
+        let mut revocation_certs = bset![];
+        let mut path = data_dir.clone();
+        path.push("revocations");
+        if path.is_dir() {
+            for entry in fs::read_dir(path)? {
+                let path = entry?.path();
+                let cert: SsiCert = fs::read_to_string(&path)?.trim().parse()?;
+                let pk = cert
+                    .subject()
+                    .filter(|pk| pk.is_revoked_by(&cert))
+                    .ok_or_else(|| LoadError::InvalidRevocation(path.display().to_string()))?;
+                revocations.insert(pk);
+                revocation_certs.insert(cert);
+            }
         }
 
         Ok(Self {
             secrets,
             identities,
+            certs,
+            revocations,
+            revocation_certs,
+            verify_only,
+            public_only: false,
+            audit_sink: None,
         })
     }
 
     pub fn store(&self) -> io::Result<()> {
-        let data_dir = PathBuf::from(shellexpand::tilde(SSI_DIR).to_string());
-        fs::create_dir_all(&data_dir)?;
+        self.store_to(PathBuf::from(shellexpand::tilde(SSI_DIR).to_string()))
+    }
 
-        let mut path = data_dir.clone();
-        path.push("secrets");
-        let mut file = fs::File::create(path)?;
-        for secret in &self.secrets {
-            writeln!(file, "{secret}")?;
+    /// Stores runtime data into a given directory instead of [`SSI_DIR`].
+    pub fn store_to(&self, data_dir: impl AsRef<Path>) -> io::Result<()> {
+        self.store_split(&data_dir, &data_dir)
+    }
+
+    /// Stores secrets and public data (identities and certificates) into
+    /// separate directories. In verify-only mode the secrets are not stored.
+    pub fn store_split(
+        &self,
+        secrets_dir: impl AsRef<Path>,
+        identities_dir: impl AsRef<Path>,
+    ) -> io::Result<()> {
+        if !self.verify_only {
+            let secrets_dir = secrets_dir.as_ref().to_path_buf();
+            fs::create_dir_all(&secrets_dir)?;
+
+            let mut path = secrets_dir.clone();
+            path.push("secrets");
+            let mut file = fs::File::create(path)?;
+            for secret in &self.secrets {
+                writeln!(file, "{secret}")?;
+            }
         }
 
+        let data_dir = identities_dir.as_ref().to_path_buf();
+        fs::create_dir_all(&data_dir)?;
+
         let mut path = data_dir.clone();
         path.push("identities");
         let mut file = fs::File::create(path)?;
@@ -126,9 +348,38 @@ impl SsiRuntime {
             writeln!(file, "{ssi}")?;
         }
 
+        let mut path = data_dir.clone();
+        path.push("certs");
+        let mut file = fs::File::create(path)?;
+        for cert in &self.certs {
+            writeln!(file, "{cert:#}")?;
+        }
+
+        if !self.revocation_certs.is_empty() {
+            let mut dir = data_dir.clone();
+            dir.push("revocations");
+            fs::create_dir_all(&dir)?;
+            for cert in &self.revocation_certs {
+                let mut file = fs::File::create(dir.join(format!("{}.rev", cert.issuer())))?;
+                writeln!(file, "{cert:#}")?;
+            }
+        }
+
         Ok(())
     }
 
+    /// Adds an identity, replacing known copies of it with the same content
+    /// but a different signature (see [`Ssi::content_hash`]). Returns
+    /// whether the identity content was not known before.
+    pub fn add_identity(&mut self, ssi: Ssi) -> bool {
+        let hash = ssi.content_hash();
+        let count = self.identities.len();
+        self.identities.retain(|known| known.content_hash() != hash);
+        let is_new = self.identities.len() == count;
+        self.identities.insert(ssi);
+        is_new
+    }
+
     pub fn find_identity(&self, query: impl Into<SsiQuery>) -> Option<&Ssi> {
         let query = query.into();
         self.identities.iter().find(|ssi| match query {
@@ -145,11 +396,62 @@ impl SsiRuntime {
         })
     }
 
+    /// Finds identities which keys start with a given (partial) string
+    /// representation, like the one typed by a user in a search box. Returns
+    /// all matching identities for ambiguous prefixes, and none for invalid
+    /// ones.
+    pub fn find_by_prefix(&self, prefix: &str) -> Vec<&Ssi> {
+        let Ok(prefix) = SsiPub::parse_prefix(prefix) else {
+            return vec![];
+        };
+        self.identities
+            .iter()
+            .filter(|ssi| prefix.matches(&ssi.pk))
+            .collect()
+    }
+
+    /// Verifies the certificate against the signer identity known to the
+    /// runtime, taking into account the identity expiration and known
+    /// revocations. See [`SsiCert::verify_against`] for the details.
+    pub fn verify_cert(&self, cert: &SsiCert, at: DateTime<Utc>) -> Result<(), VerifyError> {
+        let signer = match cert.pk {
+            Some(pk) => self.find_identity(pk),
+            None => self.find_identity(cert.fp),
+        };
+        let signer = signer.ok_or(VerifyError::NoIdentity)?;
+        cert.verify_against(signer, at, &self.revocations)
+    }
+
+    /// Verifies document at `doc_path` against the detached signature file
+    /// at `sig_path` (see [`SsiCert::to_sig_file`]), checking the signer
+    /// identity with [`SsiRuntime::verify_cert`] at the current time.
+    ///
+    /// Returns the certificate read from the signature file.
+    pub fn verify_file(
+        &self,
+        doc_path: impl AsRef<Path>,
+        sig_path: impl AsRef<Path>,
+    ) -> Result<SsiCert, VerifyFileError> {
+        let cert = SsiCert::from_sig_file(&fs::read_to_string(sig_path)?)?;
+        let doc = fs::read(doc_path)?;
+        if cert.msg.to_byte_array() != SsiCert::data_digest(doc) {
+            return Err(VerifyError::MessageMismatch.into());
+        }
+        self.verify_cert(&cert, Utc::now())?;
+        Ok(cert)
+    }
+
     pub fn find_signer(
         &self,
         query: impl Into<SsiQuery>,
         passwd: &str,
     ) -> Result<SsiPair, SignerError> {
+        if self.public_only {
+            return Err(SignerError::VerifyOnly);
+        }
+        if self.verify_only {
+            return Err(SignerError::NoSecret);
+        }
         let ssi = self
             .find_identity(query.into())
             .cloned()
@@ -165,9 +467,820 @@ impl SsiRuntime {
                     None
                 }
             })
-            .ok_or(SignerError::WrongPassword)?;
-        Ok(SsiPair::new(ssi, sk))
+            .ok_or(SignerError::WrongPassword);
+        let fingerprint = ssi.pk.fingerprint();
+        match sk {
+            Ok(sk) => {
+                self.audit(AuditEvent::Revealed { fingerprint });
+                Ok(SsiPair::new(ssi, sk))
+            }
+            Err(err) => {
+                self.audit(AuditEvent::FailedReveal { fingerprint });
+                Err(err)
+            }
+        }
+    }
+
+    /// Signs the message with the identity matching the `query`.
+    pub fn sign(
+        &self,
+        query: impl Into<SsiQuery>,
+        passwd: &str,
+        msg: impl AsRef<[u8]>,
+    ) -> Result<SsiCert, SignerError> {
+        let signer = self.find_signer(query, passwd)?;
+        let cert = signer.sign(msg);
+        self.audit(AuditEvent::Signed {
+            fingerprint: cert.fp,
+        });
+        Ok(cert)
+    }
+
+    /// Signs the identity with the secret key matching its public key,
+    /// replacing the existing signature (see [`Ssi::resign`]). The identity
+    /// doesn't have to be known to the runtime.
+    ///
+    /// The identity is left unchanged on errors.
+    pub fn sign_identity(&self, ssi: &mut Ssi, passwd: &str) -> Result<(), SignerError> {
+        if self.public_only {
+            return Err(SignerError::VerifyOnly);
+        }
+        if self.verify_only {
+            return Err(SignerError::NoSecret);
+        }
+        let fingerprint = ssi.pk.fingerprint();
+        let mut candidates = self.secrets.iter().filter(|s| s.fp == fingerprint).peekable();
+        if candidates.peek().is_none() {
+            return Err(SignerError::NoSuchKey);
+        }
+        let Some(sk) = candidates
+            .filter_map(|s| s.reveal(passwd).ok())
+            .find(|sk| sk.to_public() == ssi.pk)
+        else {
+            self.audit(AuditEvent::FailedReveal { fingerprint });
+            return Err(SignerError::WrongPassword);
+        };
+        self.audit(AuditEvent::Revealed { fingerprint });
+        ssi.resign(&sk)?;
+        self.audit(AuditEvent::Signed { fingerprint });
+        Ok(())
+    }
+
+    /// Adds the key to the set of revoked keys.
+    pub fn revoke(&mut self, pk: SsiPub) {
+        self.revocations.insert(pk);
+        self.audit(AuditEvent::Revoked {
+            fingerprint: pk.fingerprint(),
+        });
+    }
+
+    /// Rotates identity of the `old` key to a freshly generated key of the
+    /// same algorithm and chain, protecting the new secret with the same
+    /// password.
+    ///
+    /// The old identity is replaced with a copy linked forward to the new key
+    /// (see [`Ssi::rotate_to`]), which acts as a supersession statement signed
+    /// by the old key, and the old key is revoked with the given `reason`.
+    /// Returns the new identity and the revocation certificate, which is also
+    /// kept for the next [`SsiRuntime::store`].
+    pub fn rotate_key(
+        &mut self,
+        old: &SsiPub,
+        passwd: &str,
+        reason: RevocationReason,
+    ) -> Result<(Ssi, SsiCert), SignerError> {
+        let signer = self.find_signer(*old, passwd)?;
+        let ssi = self
+            .find_identity(*old)
+            .cloned()
+            .ok_or(SignerError::UnknownIdentity)?;
+        let new_secret = SsiSecret::new(signer.sk.algorithm(), *old.chain());
+        let (superseded, new) = ssi.rotate_to(&signer.sk, &new_secret)?;
+        let revocation = signer.sk.revoke(reason);
+
+        self.identities.remove(&ssi);
+        self.identities.insert(superseded);
+        self.identities.insert(new.clone());
+        self.secrets.insert(new_secret.conceal(passwd));
+        self.revoke(*old);
+        self.revocation_certs.insert(revocation.clone());
+        Ok((new, revocation))
+    }
+
+    /// Imports self-signed revocation certificate (see [`SsiSecret::revoke`]),
+    /// marking the revoked key as such.
+    ///
+    /// [`SsiSecret::revoke`]: crate::SsiSecret::revoke
+    pub fn import_revocation(&mut self, cert: &SsiCert) -> Result<(), VerifyError> {
+        let pk = cert.subject().ok_or(VerifyError::NoIdentity)?;
+        if !pk.is_revoked_by(cert) {
+            return Err(VerifyError::InvalidRevocation);
+        }
+        self.revoke(pk);
+        Ok(())
+    }
+
+    /// Imports revocation certificate like [`SsiRuntime::import_revocation`],
+    /// keeping it to be written into `revocations` directory by the next
+    /// [`SsiRuntime::store`]. Stored revocations are applied when the runtime
+    /// is loaded, so certificates pre-generated with
+    /// [`SsiSecret::generate_revocation`] must be kept elsewhere until the
+    /// key has to be revoked.
+    ///
+    /// [`SsiSecret::generate_revocation`]: crate::SsiSecret::generate_revocation
+    pub fn store_revocation(&mut self, cert: SsiCert) -> Result<(), VerifyError> {
+        self.import_revocation(&cert)?;
+        self.revocation_certs.insert(cert);
+        Ok(())
+    }
+
+    /// Sets a sink receiving [`AuditEvent`]s for every signing, secret
+    /// reveal, certificate import and key revocation done by the runtime,
+    /// replacing the previous one.
+    pub fn set_audit_sink(&mut self, sink: AuditSink) {
+        self.audit_sink = Some(sink);
+    }
+
+    fn audit(&self, event: AuditEvent) {
+        if let Some(sink) = &self.audit_sink {
+            sink(&event);
+        }
+    }
+
+    /// Adds certification of some identity issued by other identity.
+    ///
+    /// The certificate is checked against the certified identity, if it is
+    /// known to the runtime.
+    pub fn import_cert(&mut self, cert: SsiCert) -> Result<(), VerifyError> {
+        let subject = cert.subject().ok_or(VerifyError::NoIdentity)?;
+        match self.find_identity(subject) {
+            Some(ssi) => cert.verify_certification(ssi)?,
+            None => cert.verify()?,
+        }
+        self.audit(AuditEvent::Imported {
+            issuer: cert.issuer(),
+            subject: subject.fingerprint(),
+        });
+        self.certs.insert(cert);
+        Ok(())
+    }
+
+    /// Returns certifications issued for the given identity.
+    pub fn certifications(&self, subject: SsiPub) -> impl Iterator<Item = &SsiCert> {
+        self.certs
+            .iter()
+            .filter(move |cert| cert.subject() == Some(subject))
+    }
+
+    /// Finds a path of certifications from a trusted identity `from` to the
+    /// identity `to`, which must not be longer than `max_depth`
+    /// certifications.
+    ///
+    /// The search is a breadth-first one, so the shortest path is returned.
+    /// The path includes both `from` and `to` keys. All identities on the path
+    /// must be known to the runtime, not revoked and valid at the moment `at`
+    /// (see [`Ssi::validity`]). Certifications with invalid signatures and
+    /// certifications not matching the current state of the certified
+    /// identity are skipped.
+    pub fn trust_path(
+        &self,
+        from: &SsiPub,
+        to: &SsiPub,
+        max_depth: usize,
+        at: DateTime<Utc>,
+    ) -> Option<Vec<SsiPub>> {
+        if !self.is_valid_key(*from, at) {
+            return None;
+        }
+        let mut visited = bset![*from];
+        let mut queue = VecDeque::from([vec![*from]]);
+        while let Some(path) = queue.pop_front() {
+            let issuer = *path.last().expect("path is never empty");
+            if issuer == *to {
+                return Some(path);
+            }
+            if path.len() > max_depth {
+                continue;
+            }
+            for cert in &self.certs {
+                let Some(subject) = cert.subject() else {
+                    continue;
+                };
+                if cert.issuer() != issuer.fingerprint() ||
+                    visited.contains(&subject) ||
+                    !self.is_valid_key(subject, at)
+                {
+                    continue;
+                }
+                // The subject key is not covered by the signature, thus the certified message
+                // must be checked against the subject identity
+                let Some(ssi) = self.find_identity(subject) else {
+                    continue;
+                };
+                let cert = SsiCert { pk: Some(issuer), ..cert.clone() };
+                if cert.verify_certification(ssi).is_err() {
+                    continue;
+                }
+                visited.insert(subject);
+                let mut next = path.clone();
+                next.push(subject);
+                queue.push_back(next);
+            }
+        }
+        None
+    }
+
+    fn is_valid_key(&self, pk: SsiPub, at: DateTime<Utc>) -> bool {
+        !self.revocations.is_revoked(&pk) &&
+            self.find_identity(pk).is_some_and(|ssi| ssi.validity(at).is_valid())
     }
 
     pub fn is_signing(&self, fp: Fingerprint) -> bool { self.secrets.iter().any(|s| s.fp == fp) }
 }
+
+impl KeyResolver for SsiRuntime {
+    fn resolve(&self, query: &SsiQuery) -> Option<SsiPub> {
+        self.find_identity(query.clone()).map(|ssi| ssi.pk)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::{
+        verify_rotation, Algo, Chain, Commitment, IntegrityStatus, KeyLink, KeyUsage, SsiSig,
+    };
+
+    fn identity(runtime: &mut SsiRuntime, expiry: Option<DateTime<Utc>>) -> (SsiPub, SsiSecret) {
+        let secret = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let ssi = Ssi::new(bset![], expiry, &secret);
+        runtime.identities.insert(ssi);
+        (secret.to_public(), secret)
+    }
+
+    #[test]
+    fn add_identity() {
+        let mut runtime = SsiRuntime::default();
+        let secret = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let ssi = Ssi::new(bset![], None, &secret);
+        assert!(runtime.add_identity(ssi.clone()));
+
+        let mut resigned = ssi.clone();
+        resigned.sig = Some(SsiSig::from([7u8; 64]));
+        assert!(!runtime.add_identity(resigned.clone()));
+        assert_eq!(runtime.identities, bset![resigned]);
+
+        let other = Ssi::new(bset![], None, &SsiSecret::new(Algo::Ed25519, Chain::Bitcoin));
+        assert!(runtime.add_identity(other));
+        assert_eq!(runtime.identities.len(), 2);
+    }
+
+    #[test]
+    fn unknown_algo_roundtrip() {
+        use std::str::FromStr;
+
+        use strict_encoding::{StrictDeserialize, StrictSerialize};
+
+        use crate::{InvalidSig, Uid};
+
+        let pk = SsiPub::with(Chain::Other(0x77), Algo::Other(0x42), [7u8; 32]);
+        let data = pk.to_strict_serialized::<34>().unwrap();
+        assert_eq!(SsiPub::from_strict_serialized::<34>(data).unwrap(), pk);
+
+        let ssi = Ssi {
+            pk,
+            uids: bset![Uid::from_str("Alice <mailto:alice@example.com>").unwrap()],
+            expiry: None,
+            comment: None,
+            usage: KeyUsage::ALL,
+            services: bmap![],
+            avatar: None,
+            subkeys: vec![],
+            next: None,
+            prev: None,
+            pow: None,
+            extras: bmap![],
+            commitment: Commitment::Binary,
+            sig: Some(SsiSig::from([9u8; 64])),
+            endorsements: vec![],
+        };
+        let s = ssi.to_string();
+        let parsed = Ssi::from_str(&s).unwrap();
+        assert_eq!(parsed, ssi);
+        assert_eq!(parsed.to_string(), s);
+        assert_eq!(parsed.pk.algo(), &Algo::Other(0x42));
+        assert_eq!(parsed.pk.chain(), &Chain::Other(0x77));
+        assert_eq!(
+            parsed.integrity(),
+            IntegrityStatus::SignatureInvalid(InvalidSig::UnsupportedAlgo(0x42))
+        );
+
+        let mut runtime = SsiRuntime::default();
+        runtime.identities.insert(parsed);
+        let dir = std::env::temp_dir().join(format!("ssi-test-{}", std::process::id()));
+        runtime.store_to(&dir).unwrap();
+        let loaded = SsiRuntime::load_from(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.identities, runtime.identities);
+        assert_eq!(loaded.identities.first().unwrap().to_string(), s);
+
+        let cert = SsiCert {
+            fp: pk.fingerprint(),
+            pk: Some(pk),
+            subject: None,
+            msg: [0u8; 32].into(),
+            sig: SsiSig::from([9u8; 64]),
+            signed_at: None,
+            timestamp: None,
+        };
+        assert!(matches!(cert.verify(), Err(VerifyError::UnsupportedAlgo(0x42))));
+    }
+
+    #[test]
+    fn sign_identity() {
+        use std::str::FromStr;
+
+        use crate::Uid;
+
+        let mut runtime = SsiRuntime::default();
+        let secret = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        runtime.secrets.insert(secret.conceal(s!("passwd")));
+
+        let uid = Uid::from_str("Alice <mailto:alice@example.com>").unwrap();
+        let mut ssi = Ssi::new(bset![], None, &secret).without_signature();
+        ssi.uids.insert(uid);
+        assert_eq!(ssi.integrity(), IntegrityStatus::Unsigned);
+        runtime.sign_identity(&mut ssi, "passwd").unwrap();
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+
+        let mut unsigned = ssi.without_signature();
+        assert_eq!(runtime.sign_identity(&mut unsigned, "wrong"), Err(SignerError::WrongPassword));
+        assert_eq!(unsigned.sig, None);
+
+        let other = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        let mut unknown = Ssi::new(bset![], None, &other);
+        let before = unknown.clone();
+        assert_eq!(runtime.sign_identity(&mut unknown, "passwd"), Err(SignerError::NoSuchKey));
+        assert_eq!(unknown, before);
+    }
+
+    fn certify(runtime: &mut SsiRuntime, issuer: &SsiSecret, subject: SsiPub) {
+        let ssi = runtime.find_identity(subject).unwrap().clone();
+        runtime.import_cert(issuer.certify(&ssi)).unwrap();
+    }
+
+    #[test]
+    fn trust_path_diamond() {
+        let now = Utc::now();
+        let mut runtime = SsiRuntime::default();
+        let (a, a_sk) = identity(&mut runtime, None);
+        let (b, b_sk) = identity(&mut runtime, None);
+        let (c, c_sk) = identity(&mut runtime, None);
+        let (d, _) = identity(&mut runtime, None);
+        certify(&mut runtime, &a_sk, b);
+        certify(&mut runtime, &a_sk, c);
+        certify(&mut runtime, &b_sk, d);
+        certify(&mut runtime, &c_sk, d);
+
+        let path = runtime.trust_path(&a, &d, 3, now).unwrap();
+        assert_eq!(path.len(), 3);
+        assert_eq!(path[0], a);
+        assert!(path[1] == b || path[1] == c);
+        assert_eq!(path[2], d);
+        assert_eq!(runtime.trust_path(&a, &d, 1, now), None);
+        assert_eq!(runtime.trust_path(&d, &a, 3, now), None);
+        assert_eq!(runtime.trust_path(&a, &a, 0, now), Some(vec![a]));
+    }
+
+    #[test]
+    fn trust_path_skips_expired() {
+        let now = Utc::now();
+        let mut runtime = SsiRuntime::default();
+        let (a, a_sk) = identity(&mut runtime, None);
+        let (b, b_sk) = identity(&mut runtime, Some(now + Duration::days(1)));
+        let (c, c_sk) = identity(&mut runtime, None);
+        let (d, _) = identity(&mut runtime, None);
+        certify(&mut runtime, &a_sk, b);
+        certify(&mut runtime, &b_sk, d);
+        assert_eq!(runtime.trust_path(&a, &d, 5, now), Some(vec![a, b, d]));
+        let later = now + Duration::days(2);
+        assert_eq!(runtime.trust_path(&a, &d, 5, later), None);
+
+        certify(&mut runtime, &a_sk, c);
+        certify(&mut runtime, &c_sk, d);
+        assert_eq!(runtime.trust_path(&a, &d, 5, later), Some(vec![a, c, d]));
+    }
+
+    #[test]
+    fn trust_path_skips_revoked() {
+        let now = Utc::now();
+        let mut runtime = SsiRuntime::default();
+        let (a, a_sk) = identity(&mut runtime, None);
+        let (b, b_sk) = identity(&mut runtime, None);
+        let (c, c_sk) = identity(&mut runtime, None);
+        let (d, _) = identity(&mut runtime, None);
+        certify(&mut runtime, &a_sk, b);
+        certify(&mut runtime, &b_sk, d);
+        certify(&mut runtime, &a_sk, c);
+        certify(&mut runtime, &c_sk, d);
+        runtime.revoke(b);
+        assert_eq!(runtime.trust_path(&a, &d, 5, now), Some(vec![a, c, d]));
+        assert_eq!(runtime.trust_path(&a, &b, 5, now), None);
+
+        runtime.revoke(a);
+        assert_eq!(runtime.trust_path(&a, &d, 5, now), None);
+        assert_eq!(runtime.trust_path(&a, &a, 0, now), None);
+    }
+
+    #[test]
+    fn trust_path_unknown_subject() {
+        let now = Utc::now();
+        let mut runtime = SsiRuntime::default();
+        let (a, a_sk) = identity(&mut runtime, None);
+        let (b, _) = identity(&mut runtime, None);
+        certify(&mut runtime, &a_sk, b);
+        let unknown = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin).to_public();
+
+        // The subject key is not signed, so it may be replaced in a valid certificate
+        let mut forged = runtime.certifications(b).next().unwrap().clone();
+        forged.subject = Some(unknown);
+        runtime.certs.insert(forged);
+        assert_eq!(runtime.trust_path(&a, &unknown, 5, now), None);
+        assert_eq!(runtime.trust_path(&unknown, &unknown, 0, now), None);
+        assert_eq!(runtime.trust_path(&a, &b, 5, now), Some(vec![a, b]));
+    }
+
+    #[test]
+    fn trust_path_cycle() {
+        let now = Utc::now();
+        let mut runtime = SsiRuntime::default();
+        let (a, a_sk) = identity(&mut runtime, None);
+        let (b, b_sk) = identity(&mut runtime, None);
+        let (c, c_sk) = identity(&mut runtime, None);
+        let (d, _) = identity(&mut runtime, None);
+        certify(&mut runtime, &a_sk, b);
+        certify(&mut runtime, &b_sk, c);
+        certify(&mut runtime, &c_sk, a);
+
+        assert_eq!(runtime.trust_path(&a, &d, 10, now), None);
+        assert_eq!(runtime.trust_path(&b, &a, 10, now), Some(vec![b, c, a]));
+    }
+
+    #[test]
+    fn verify_cert() {
+        let mut runtime = SsiRuntime::default();
+        let (a, a_sk) = identity(&mut runtime, None);
+        let (_, b_sk) = identity(&mut runtime, Some(Utc::now() + Duration::days(1)));
+        let a_cert = SsiPair::from(a_sk).sign("message");
+        let b_cert = SsiPair::from(b_sk).sign("message");
+
+        runtime.verify_cert(&a_cert, Utc::now()).unwrap();
+        runtime.verify_cert(&b_cert, Utc::now()).unwrap();
+        assert!(matches!(
+            runtime.verify_cert(&b_cert, Utc::now() + Duration::days(2)),
+            Err(VerifyError::Expired)
+        ));
+
+        let signer = runtime.find_identity(a).unwrap();
+        assert!(matches!(
+            b_cert.verify_against(signer, Utc::now(), &RevocationSet::new()),
+            Err(VerifyError::IdentityMismatch)
+        ));
+        assert!(matches!(
+            a_cert.verify_against(&signer.without_signature(), Utc::now(), &RevocationSet::new()),
+            Err(VerifyError::BrokenIdentity)
+        ));
+
+        runtime.revocations.insert(a);
+        assert!(matches!(runtime.verify_cert(&a_cert, Utc::now()), Err(VerifyError::Revoked)));
+        runtime.verify_cert(&b_cert, Utc::now()).unwrap();
+
+        let unknown = SsiPair::from(SsiSecret::new(Algo::Ed25519, Chain::Bitcoin)).sign("message");
+        assert!(matches!(runtime.verify_cert(&unknown, Utc::now()), Err(VerifyError::NoIdentity)));
+    }
+
+    #[test]
+    fn import_revocation() {
+        let mut runtime = SsiRuntime::default();
+        let (a, a_sk) = identity(&mut runtime, None);
+        let (b, b_sk) = identity(&mut runtime, None);
+        let a_cert = SsiPair::from(a_sk.clone()).sign("message");
+        runtime.verify_cert(&a_cert, Utc::now()).unwrap();
+
+        let mut forged = b_sk.revoke(RevocationReason::Compromised);
+        forged.subject = Some(a);
+        assert!(matches!(runtime.import_revocation(&forged), Err(VerifyError::InvalidRevocation)));
+        assert!(!runtime.revocations.is_revoked(&a));
+        runtime.verify_cert(&a_cert, Utc::now()).unwrap();
+
+        runtime.import_revocation(&a_sk.revoke(RevocationReason::Compromised)).unwrap();
+        assert!(runtime.revocations.is_revoked(&a));
+        assert!(!runtime.revocations.is_revoked(&b));
+        assert!(matches!(runtime.verify_cert(&a_cert, Utc::now()), Err(VerifyError::Revoked)));
+    }
+
+    #[test]
+    fn resolve_signer() {
+        let mut runtime = SsiRuntime::default();
+        let (_, sk) = identity(&mut runtime, None);
+        let mut cert = SsiPair::from(sk).sign("message");
+        cert.pk = None;
+        cert.verify_with(&runtime).unwrap();
+
+        let mut other =
+            SsiPair::from(SsiSecret::new(Algo::Ed25519, Chain::Bitcoin)).sign("message");
+        other.pk = None;
+        assert!(matches!(
+            other.verify_with(&runtime),
+            Err(VerifyError::UnresolvedSigner(fp)) if fp == other.fp
+        ));
+    }
+
+    #[test]
+    fn verify_only() {
+        let base = std::env::temp_dir().join(format!("ssi-test-split-{}", std::process::id()));
+        let secrets_dir = base.join("secrets");
+        let identities_dir = base.join("identities");
+
+        let mut runtime = SsiRuntime::default();
+        let (pk, sk) = identity(&mut runtime, None);
+        let cert = SsiPair::from(sk).sign("message");
+        runtime.store_split(&secrets_dir, &identities_dir).unwrap();
+        fs::remove_dir_all(&secrets_dir).unwrap();
+
+        let loaded = SsiRuntime::load_split(&secrets_dir, &identities_dir).unwrap();
+        assert!(loaded.verify_only);
+        assert!(loaded.secrets.is_empty());
+        assert_eq!(loaded.identities, runtime.identities);
+        loaded.verify_cert(&cert, Utc::now()).unwrap();
+        assert!(matches!(loaded.find_signer(pk, ""), Err(SignerError::NoSecret)));
+
+        loaded.store_split(&secrets_dir, &identities_dir).unwrap();
+        assert!(!secrets_dir.exists());
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn stored_revocation() {
+        let dir = std::env::temp_dir().join(format!("ssi-test-revocation-{}", std::process::id()));
+        let mut runtime = SsiRuntime::default();
+        let (pk, sk) = identity(&mut runtime, None);
+        let (other, _) = identity(&mut runtime, None);
+        let cert = SsiPair::from(sk.clone()).sign("message");
+        let revocation = sk.generate_revocation();
+        assert!(pk.is_revoked_by(&revocation));
+
+        runtime.store_to(&dir).unwrap();
+        let mut loaded = SsiRuntime::load_from(&dir).unwrap();
+        loaded.verify_cert(&cert, Utc::now()).unwrap();
+
+        loaded.store_revocation(revocation.clone()).unwrap();
+        loaded.store_to(&dir).unwrap();
+        let loaded = SsiRuntime::load_from(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(loaded.revocations.is_revoked(&pk));
+        assert!(!loaded.revocations.is_revoked(&other));
+        assert_eq!(loaded.revocation_certs, bset![revocation]);
+        assert!(matches!(loaded.verify_cert(&cert, Utc::now()), Err(VerifyError::Revoked)));
+    }
+
+    #[test]
+    fn rotate_key() {
+        let mut runtime = SsiRuntime::default();
+        let (pk, sk) = identity(&mut runtime, None);
+        runtime.secrets.insert(sk.conceal(s!("passwd")));
+        let old = runtime.find_identity(pk).unwrap().clone();
+
+        assert!(matches!(
+            runtime.rotate_key(&pk, "wrong", RevocationReason::Superseded),
+            Err(SignerError::WrongPassword)
+        ));
+        let (new, revocation) = runtime
+            .rotate_key(&pk, "passwd", RevocationReason::Superseded)
+            .unwrap();
+        assert_ne!(new.pk, pk);
+        assert_eq!(new.uids, old.uids);
+        assert!(pk.is_revoked_by(&revocation));
+        assert!(runtime.revocations.is_revoked(&pk));
+        assert_eq!(runtime.revocation_certs, bset![revocation]);
+
+        let superseded = runtime.find_identity(pk).unwrap();
+        assert_eq!(superseded.next, Some(KeyLink::Pub(new.pk)));
+        assert_eq!(superseded.integrity(), IntegrityStatus::SelfSigned);
+        verify_rotation(superseded, &new).unwrap();
+        assert_eq!(runtime.find_identity(new.pk), Some(&new));
+        runtime.find_signer(new.pk, "passwd").unwrap();
+    }
+
+    #[test]
+    fn verify_file() {
+        let dir = std::env::temp_dir().join(format!("ssi-test-sigfile-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let doc = dir.join("doc.txt");
+        let sig = dir.join("doc.txt.sig");
+        let mut runtime = SsiRuntime::default();
+        let (pk, sk) = identity(&mut runtime, None);
+        fs::write(&doc, b"Document content\n").unwrap();
+        let cert = SsiPair::from(sk).sign(fs::read(&doc).unwrap());
+        fs::write(&sig, cert.to_sig_file()).unwrap();
+
+        assert_eq!(runtime.verify_file(&doc, &sig).unwrap(), cert);
+        fs::write(&doc, b"Modified content\n").unwrap();
+        assert!(matches!(
+            runtime.verify_file(&doc, &sig),
+            Err(VerifyFileError::Verify(VerifyError::MessageMismatch))
+        ));
+        fs::write(&doc, b"Document content\n").unwrap();
+        runtime.revoke(pk);
+        assert!(matches!(
+            runtime.verify_file(&doc, &sig),
+            Err(VerifyFileError::Verify(VerifyError::Revoked))
+        ));
+        fs::write(&sig, "garbage").unwrap();
+        assert!(matches!(runtime.verify_file(&doc, &sig), Err(VerifyFileError::SigFile(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn public_only() {
+        let dir = std::env::temp_dir().join(format!("ssi-test-public-{}", std::process::id()));
+        let mut runtime = SsiRuntime::default();
+        let (pk, sk) = identity(&mut runtime, None);
+        runtime.secrets.insert(sk.conceal(s!("")));
+        let cert = SsiPair::from(sk).sign("message");
+        runtime.store_to(&dir).unwrap();
+        runtime.find_signer(pk, "").unwrap();
+
+        let loaded = SsiRuntime::load_public_only(&dir).unwrap();
+        assert!(loaded.secrets.is_empty());
+        assert_eq!(loaded.identities, runtime.identities);
+        loaded.verify_cert(&cert, Utc::now()).unwrap();
+        assert!(matches!(loaded.find_signer(pk, ""), Err(SignerError::VerifyOnly)));
+
+        loaded.store_to(&dir).unwrap();
+        assert!(SsiRuntime::load_from(&dir).unwrap().secrets == runtime.secrets);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tampered_secret() {
+        let dir = std::env::temp_dir().join(format!("ssi-test-tampered-{}", std::process::id()));
+        let mut runtime = SsiRuntime::default();
+        let secret = SsiSecret::new(Algo::Ed25519, Chain::Liquid);
+        let pk = secret.to_public();
+        runtime.identities.insert(Ssi::new(bset![], None, &secret));
+        runtime.secrets.insert(secret.conceal(""));
+        runtime.store_to(&dir).unwrap();
+
+        let loaded = SsiRuntime::load_from(&dir).unwrap();
+        assert!(loaded.secrets == runtime.secrets);
+        assert_eq!(loaded.find_signer(pk, "").unwrap().sk.chain(), Chain::Liquid);
+
+        let secrets = fs::read_to_string(dir.join("secrets")).unwrap();
+        fs::write(dir.join("secrets"), secrets.replace("ed25519-priv", "bip340-priv")).unwrap();
+        assert!(matches!(
+            SsiRuntime::load_from(&dir),
+            Err(LoadError::KeyMismatch(fp)) if fp == pk.fingerprint()
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn iter_public() {
+        let dir = std::env::temp_dir().join(format!("ssi-test-iter-{}", std::process::id()));
+        assert_eq!(SsiRuntime::iter_public(&dir).count(), 0);
+
+        let mut runtime = SsiRuntime::default();
+        for _ in 0..3 {
+            let (_, sk) = identity(&mut runtime, None);
+            runtime.secrets.insert(sk.conceal(s!("")));
+        }
+        runtime.store_to(&dir).unwrap();
+        // Neither secrets nor identities after the one looked up are parsed
+        fs::write(dir.join("secrets"), "garbage\n").unwrap();
+        let mut identities = fs::read_to_string(dir.join("identities")).unwrap();
+        identities.push_str("garbage\n");
+        fs::write(dir.join("identities"), identities).unwrap();
+
+        let target = runtime.identities.iter().nth(1).unwrap();
+        let found = SsiRuntime::iter_public(&dir)
+            .find(|ssi| matches!(ssi, Ok(ssi) if ssi.pk == target.pk))
+            .unwrap()
+            .unwrap();
+        assert_eq!(&found, target);
+
+        let all = SsiRuntime::iter_public(&dir).collect::<Vec<_>>();
+        assert_eq!(all.len(), 4);
+        assert!(all[..3].iter().all(Result::is_ok));
+        assert!(matches!(all[3], Err(LoadError::Ssi(_))));
+        assert!(SsiRuntime::load_from(&dir).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn store_unsigned() {
+        let dir = std::env::temp_dir().join(format!("ssi-test-unsigned-{}", std::process::id()));
+        let mut runtime = SsiRuntime::default();
+        let secret = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let unsigned = Ssi::new(bset![], None, &secret).without_signature();
+        runtime.identities.insert(unsigned.clone());
+        runtime.store_to(&dir).unwrap();
+
+        let loaded = SsiRuntime::load_from(&dir).unwrap();
+        assert_eq!(loaded.identities, bset![unsigned.clone()]);
+        let all = SsiRuntime::iter_public(&dir)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(all, vec![unsigned.clone()]);
+
+        // Unknown parameters are not admitted together with unsigned identities
+        let mut extended = unsigned;
+        extended.extras.insert(s!("color"), s!("blue"));
+        fs::write(dir.join("identities"), format!("{extended}\n")).unwrap();
+        assert!(matches!(SsiRuntime::load_from(&dir), Err(LoadError::Ssi(_))));
+        assert!(matches!(SsiRuntime::iter_public(&dir).next(), Some(Err(LoadError::Ssi(_)))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn find_by_prefix() {
+        let mut runtime = SsiRuntime::default();
+        let mut key = [0xAB; 32];
+        for no in 0..3u8 {
+            key[31] = no;
+            runtime.identities.insert(Ssi {
+                pk: SsiPub::with(Chain::Bitcoin, Algo::Ed25519, key),
+                uids: bset![],
+                expiry: None,
+                comment: None,
+                usage: KeyUsage::ALL,
+                services: bmap![],
+                avatar: None,
+                subkeys: vec![],
+                next: None,
+                prev: None,
+                pow: None,
+                extras: bmap![],
+                commitment: Commitment::Binary,
+                sig: None,
+                endorsements: vec![],
+            });
+        }
+        let first = runtime.identities.first().unwrap().pk.to_string();
+
+        assert_eq!(runtime.find_by_prefix("ssi:").len(), 3);
+        assert_eq!(runtime.find_by_prefix(&first[..20]).len(), 3);
+        let found = runtime.find_by_prefix(&first);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].pk.to_string(), first);
+        assert!(runtime.find_by_prefix("ssi:*").is_empty());
+    }
+
+    #[test]
+    fn audit_log() {
+        use std::sync::{Arc, Mutex};
+
+        let mut runtime = SsiRuntime::default();
+        let (pk, sk) = identity(&mut runtime, None);
+        runtime.secrets.insert(sk.conceal(s!("passwd")));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        runtime.set_audit_sink(Box::new(move |event| sink.lock().unwrap().push(event.clone())));
+
+        let fingerprint = pk.fingerprint();
+        runtime.sign(pk, "passwd", b"message").unwrap();
+        assert_eq!(*events.lock().unwrap(), vec![
+            AuditEvent::Revealed { fingerprint },
+            AuditEvent::Signed { fingerprint }
+        ]);
+
+        events.lock().unwrap().clear();
+        let mut ssi = runtime.find_identity(pk).unwrap().clone();
+        runtime.sign_identity(&mut ssi, "passwd").unwrap();
+        assert_eq!(*events.lock().unwrap(), vec![
+            AuditEvent::Revealed { fingerprint },
+            AuditEvent::Signed { fingerprint }
+        ]);
+
+        events.lock().unwrap().clear();
+        assert_eq!(runtime.sign(pk, "wrong", b"message"), Err(SignerError::WrongPassword));
+        assert_eq!(*events.lock().unwrap(), vec![AuditEvent::FailedReveal { fingerprint }]);
+
+        events.lock().unwrap().clear();
+        let (other, _) = identity(&mut runtime, None);
+        let ssi = runtime.find_identity(other).unwrap().clone();
+        runtime.import_cert(sk.certify(&ssi)).unwrap();
+        runtime.revoke(other);
+        assert_eq!(*events.lock().unwrap(), vec![
+            AuditEvent::Imported {
+                issuer: fingerprint,
+                subject: other.fingerprint()
+            },
+            AuditEvent::Revoked {
+                fingerprint: other.fingerprint()
+            }
+        ]);
+    }
+}
@@ -0,0 +1,282 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Well-known UID schemas with validation and normalization of their ids.
+
+use std::collections::BTreeSet;
+use std::convert::Infallible;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+
+use crate::{Ssi, SsiSecret, Uid};
+
+/// Schema of a UID. Schemas are case-insensitive; `email` is an alias for
+/// `mailto`.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Display)]
+#[display(lowercase)]
+pub enum UidSchema {
+    Mailto,
+    Tel,
+    Https,
+    Dns,
+    Nostr,
+    Tg,
+    Xmpp,
+    #[display("{0}")]
+    Other(String),
+}
+
+impl FromStr for UidSchema {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "mailto" | "email" => UidSchema::Mailto,
+            "tel" => UidSchema::Tel,
+            "https" => UidSchema::Https,
+            "dns" => UidSchema::Dns,
+            "nostr" => UidSchema::Nostr,
+            "tg" => UidSchema::Tg,
+            "xmpp" => UidSchema::Xmpp,
+            _ => UidSchema::Other(s.to_owned()),
+        })
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum UidValidationError {
+    /// UID has an empty id.
+    Empty,
+    /// '{0}' is not a valid email address.
+    InvalidEmail(String),
+    /// '{0}' is not a valid phone number in E.164 format.
+    InvalidPhone(String),
+    /// '{0}' is not a valid domain name.
+    InvalidDomain(String),
+    /// '{0}' is not a valid Telegram username.
+    InvalidTelegram(String),
+}
+
+fn normalize_domain(domain: &str) -> Result<String, UidValidationError> {
+    let domain = domain.strip_suffix('.').unwrap_or(domain).to_lowercase();
+    let valid_label = |label: &str| {
+        (1..=63).contains(&label.len()) &&
+            !label.starts_with('-') &&
+            !label.ends_with('-') &&
+            label.chars().all(|c| c.is_alphanumeric() || c == '-')
+    };
+    if domain.len() > 253 || !domain.split('.').all(valid_label) {
+        return Err(UidValidationError::InvalidDomain(domain));
+    }
+    Ok(domain)
+}
+
+impl UidSchema {
+    /// Validates UID id according to the schema, returning its normalized
+    /// form. Normalization is idempotent: a normalized id is returned
+    /// unchanged.
+    ///
+    /// Ids of unknown schemas are returned as is.
+    pub fn normalize(&self, id: &str) -> Result<String, UidValidationError> {
+        if id.is_empty() {
+            return Err(UidValidationError::Empty);
+        }
+        match self {
+            UidSchema::Mailto => {
+                let (local, domain) = id
+                    .rsplit_once('@')
+                    .filter(|(local, _)| !local.is_empty() && !local.contains(char::is_whitespace))
+                    .ok_or_else(|| UidValidationError::InvalidEmail(id.to_owned()))?;
+                let domain = normalize_domain(domain)
+                    .map_err(|_| UidValidationError::InvalidEmail(id.to_owned()))?;
+                Ok(format!("{local}@{domain}"))
+            }
+            UidSchema::Tel => {
+                let digits = id
+                    .strip_prefix('+')
+                    .ok_or_else(|| UidValidationError::InvalidPhone(id.to_owned()))?
+                    .replace([' ', '-', '.', '(', ')'], "");
+                if !(1..=15).contains(&digits.len()) ||
+                    digits.starts_with('0') ||
+                    !digits.chars().all(|c| c.is_ascii_digit())
+                {
+                    return Err(UidValidationError::InvalidPhone(id.to_owned()));
+                }
+                Ok(format!("+{digits}"))
+            }
+            UidSchema::Https => {
+                let (prefix, rest) = match id.strip_prefix("//") {
+                    Some(rest) => ("//", rest),
+                    None => ("", id),
+                };
+                let rest = rest.trim_end_matches('/');
+                let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+                let host = normalize_domain(host)?;
+                Ok(match path {
+                    "" => format!("{prefix}{host}"),
+                    path => format!("{prefix}{host}/{path}"),
+                })
+            }
+            UidSchema::Dns => normalize_domain(id),
+            UidSchema::Nostr => Ok(id.to_lowercase()),
+            UidSchema::Tg => {
+                let name = id.strip_prefix('@').unwrap_or(id).to_lowercase();
+                if !(5..=32).contains(&name.len()) ||
+                    !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                {
+                    return Err(UidValidationError::InvalidTelegram(id.to_owned()));
+                }
+                Ok(name)
+            }
+            UidSchema::Xmpp => {
+                let (jid, resource) = match id.split_once('/') {
+                    Some((jid, resource)) => (jid, Some(resource)),
+                    None => (id, None),
+                };
+                let (local, domain) = match jid.rsplit_once('@') {
+                    Some((local, domain)) if !local.is_empty() => (Some(local), domain),
+                    _ => (None, jid),
+                };
+                let mut jid = normalize_domain(domain)?;
+                if let Some(local) = local {
+                    jid = format!("{}@{jid}", local.to_lowercase());
+                }
+                if let Some(resource) = resource {
+                    jid = format!("{jid}/{resource}");
+                }
+                Ok(jid)
+            }
+            UidSchema::Other(_) => Ok(id.to_owned()),
+        }
+    }
+}
+
+impl Uid {
+    pub fn uid_schema(&self) -> UidSchema {
+        UidSchema::from_str(&self.schema).expect("infallible")
+    }
+
+    /// Returns UID with a normalized schema name and id (see
+    /// [`UidSchema::normalize`]), keeping the name, comment and the primary
+    /// flag.
+    pub fn normalized(&self) -> Result<Uid, UidValidationError> {
+        let schema = self.uid_schema();
+        Ok(Uid {
+            id: schema.normalize(&self.id)?,
+            schema: schema.to_string(),
+            ..self.clone()
+        })
+    }
+}
+
+impl Ssi {
+    /// Constructs and signs a new identity like [`Ssi::new`], normalizing all
+    /// UIDs and failing if any of them is invalid for its schema.
+    pub fn new_strict(
+        uids: BTreeSet<Uid>,
+        expiry: Option<DateTime<Utc>>,
+        secret: &SsiSecret,
+    ) -> Result<Self, UidValidationError> {
+        let uids = uids.iter().map(Uid::normalized).collect::<Result<_, _>>()?;
+        Ok(Ssi::new(uids, expiry, secret))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Algo, Chain, IntegrityStatus};
+
+    fn normalized(s: &str) -> Result<String, UidValidationError> {
+        let uid = Uid::from_str(s).unwrap().normalized()?;
+        assert_eq!(uid.normalized().as_ref(), Ok(&uid));
+        Ok(uid.to_string())
+    }
+
+    #[test]
+    fn schemas() {
+        assert_eq!(UidSchema::from_str("MailTo").unwrap(), UidSchema::Mailto);
+        assert_eq!(UidSchema::from_str("email").unwrap(), UidSchema::Mailto);
+        assert_eq!(UidSchema::from_str("did").unwrap(), UidSchema::Other(s!("did")));
+        assert_eq!(UidSchema::Mailto.to_string(), "mailto");
+        assert_eq!(UidSchema::Other(s!("did")).to_string(), "did");
+    }
+
+    #[test]
+    fn normalization() {
+        assert_eq!(
+            normalized("Alice <MailTo:Alice@Example.COM>").unwrap(),
+            "Alice <mailto:Alice@example.com>"
+        );
+        assert_eq!(
+            normalized("Alice <email:alice@example.com>").unwrap(),
+            "Alice <mailto:alice@example.com>"
+        );
+        assert_eq!(normalized("Alice <tel:+1-555-010-9999>").unwrap(), "Alice <tel:+15550109999>");
+        assert_eq!(
+            normalized("Alice <https://Alice.Example.com/blog//>").unwrap(),
+            "Alice <https://alice.example.com/blog>"
+        );
+        assert_eq!(
+            normalized("Alice <https:alice.example.com>").unwrap(),
+            "Alice <https:alice.example.com>"
+        );
+        assert_eq!(normalized("Alice <dns:Example.com.>").unwrap(), "Alice <dns:example.com>");
+        assert_eq!(normalized("Alice <tg:@Alice_Bob>").unwrap(), "Alice <tg:alice_bob>");
+        assert_eq!(
+            normalized("Alice <xmpp:Alice@Example.com/Phone>").unwrap(),
+            "Alice <xmpp:alice@example.com/Phone>"
+        );
+        assert_eq!(normalized("Alice <did:Key:Z6Mk>").unwrap(), "Alice <did:Key:Z6Mk>");
+
+        assert!(matches!(
+            normalized("Alice <mailto:alice.example.com>"),
+            Err(UidValidationError::InvalidEmail(_))
+        ));
+        assert!(matches!(
+            normalized("Alice <tel:5550109999>"),
+            Err(UidValidationError::InvalidPhone(_))
+        ));
+        assert!(matches!(
+            normalized("Alice <dns:exa_mple.com>"),
+            Err(UidValidationError::InvalidDomain(_))
+        ));
+        assert!(matches!(normalized("Alice <tg:al>"), Err(UidValidationError::InvalidTelegram(_))));
+        assert_eq!(normalized("Alice <mailto:>"), Err(UidValidationError::Empty));
+    }
+
+    #[test]
+    fn strict() {
+        let secret = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let upper = Uid::from_str("Alice <MAILTO:alice@EXAMPLE.com>").unwrap();
+        let lower = Uid::from_str("Alice <mailto:alice@example.com>").unwrap();
+        let ssi = Ssi::new_strict(bset![upper.clone(), lower.clone()], None, &secret).unwrap();
+        assert_eq!(ssi.uids, bset![lower.clone()]);
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+        assert_eq!(Ssi::new_strict(ssi.uids.clone(), None, &secret).unwrap(), ssi);
+
+        let invalid = Uid::from_str("Alice <mailto:alice>").unwrap();
+        assert!(Ssi::new_strict(bset![lower.clone(), invalid.clone()], None, &secret).is_err());
+        assert!(Ssi::new(bset![lower, invalid], None, &secret).integrity().is_self_signed());
+    }
+}
@@ -0,0 +1,508 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Selective disclosure JWTs (SD-JWT, draft-ietf-oauth-selective-disclosure-jwt)
+//! issued by identities.
+//!
+//! Only object properties can be made selectively disclosable; array elements
+//! are always disclosed as a whole. The holder key, used for the key binding,
+//! is specified by its `did:key` in the `cnf.kid` claim.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::Utc;
+use rand::random;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::vc::is_known_key;
+use crate::{DidError, Jws, JwsError, KeyResolver, SsiPair, SsiPub};
+
+pub const SD_JWT_TYP: &str = "sd+jwt";
+pub const KB_JWT_TYP: &str = "kb+jwt";
+const SD_ALG: &str = "sha-256";
+
+#[derive(Debug, Display, From)]
+#[display(doc_comments)]
+pub enum SdJwtError {
+    /// SD-JWT claims must be a JSON object.
+    InvalidClaims,
+    /// claim '{0}' can't be made selectively disclosable since it is absent.
+    UnknownClaim(String),
+    /// SD-JWT must consist of '~'-separated JWT, disclosures and key binding.
+    Malformed,
+    /// SD-JWT uses unsupported hash algorithm {0}.
+    UnsupportedHashAlg(Value),
+    /// SD-JWT has invalid list of disclosure digests.
+    InvalidDigests,
+    /// invalid SD-JWT disclosure '{0}'.
+    InvalidDisclosure(String),
+    /// SD-JWT disclosure '{0}' is repeated.
+    DuplicateDisclosure(String),
+    /// SD-JWT disclosure '{0}' is not referenced by the issuer.
+    UnreferencedDisclosure(String),
+    /// SD-JWT disclosure of claim '{0}' overrides other claim.
+    ClaimCollision(String),
+    /// SD-JWT `iss` claim doesn't match the signer key.
+    IssuerMismatch,
+    /// SD-JWT issuer key {0} is not known to the resolver.
+    UnknownIssuer(SsiPub),
+    /// SD-JWT doesn't specify holder key.
+    NoHolderKey,
+    /// SD-JWT doesn't have a required key binding.
+    MissingKeyBinding,
+    /// SD-JWT key binding doesn't match the presentation.
+    KeyBindingMismatch,
+
+    #[from]
+    #[display(inner)]
+    Jws(JwsError),
+
+    #[from]
+    #[display(inner)]
+    Did(DidError),
+
+    #[from]
+    /// SD-JWT has invalid JSON - {0}
+    Json(serde_json::Error),
+
+    #[from]
+    /// SD-JWT has invalid base64 encoding - {0}
+    Base64(base64::DecodeError),
+}
+
+impl std::error::Error for SdJwtError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SdJwtError::Jws(err) => Some(err),
+            SdJwtError::Did(err) => Some(err),
+            SdJwtError::Json(err) => Some(err),
+            SdJwtError::Base64(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+fn digest(data: &str) -> String { URL_SAFE_NO_PAD.encode(Sha256::digest(data)) }
+
+fn decode_disclosure(disclosure: &str) -> Result<(String, Value), SdJwtError> {
+    let data = URL_SAFE_NO_PAD.decode(disclosure)?;
+    match serde_json::from_slice::<Value>(&data)? {
+        Value::Array(items) => match <[Value; 3]>::try_from(items) {
+            Ok([Value::String(_), Value::String(name), value]) => Ok((name, value)),
+            _ => Err(SdJwtError::InvalidDisclosure(disclosure.to_owned())),
+        },
+        _ => Err(SdJwtError::InvalidDisclosure(disclosure.to_owned())),
+    }
+}
+
+/// Replaces properties of `value` with paths from `disclosable` with digests
+/// of their disclosures, starting from the most nested ones.
+fn conceal(
+    value: &mut Value,
+    path: &str,
+    disclosable: &BTreeSet<&str>,
+    decoys: usize,
+    disclosures: &mut Vec<String>,
+) -> Result<(), SdJwtError> {
+    let Value::Object(map) = value else {
+        return Ok(());
+    };
+    let mut digests = vec![];
+    for name in map.keys().cloned().collect::<Vec<_>>() {
+        let path = format!("{path}/{name}");
+        let child = map.get_mut(&name).expect("key is taken from the map");
+        conceal(child, &path, disclosable, decoys, disclosures)?;
+        if disclosable.contains(path.as_str()) {
+            let value = map.remove(&name).expect("key is taken from the map");
+            let salt = URL_SAFE_NO_PAD.encode(random::<[u8; 16]>());
+            let disclosure = serde_json::to_vec(&json!([salt, name, value]))?;
+            let disclosure = URL_SAFE_NO_PAD.encode(disclosure);
+            digests.push(digest(&disclosure));
+            disclosures.push(disclosure);
+        }
+    }
+    if !digests.is_empty() {
+        digests.extend((0..decoys).map(|_| URL_SAFE_NO_PAD.encode(random::<[u8; 32]>())));
+        digests.sort();
+        map.insert(s!("_sd"), json!(digests));
+    }
+    Ok(())
+}
+
+/// Replaces digests in `value` with the disclosed claims, removing used
+/// disclosures from `disclosures`. Digests without disclosures (decoys and
+/// withheld claims) are dropped.
+fn reveal(
+    value: &mut Value,
+    disclosures: &mut BTreeMap<String, (String, Value)>,
+) -> Result<(), SdJwtError> {
+    match value {
+        Value::Object(map) => {
+            if let Some(digests) = map.remove("_sd") {
+                let Value::Array(digests) = digests else {
+                    return Err(SdJwtError::InvalidDigests);
+                };
+                for digest in digests {
+                    let Value::String(digest) = digest else {
+                        return Err(SdJwtError::InvalidDigests);
+                    };
+                    let Some((name, value)) = disclosures.remove(&digest) else {
+                        continue;
+                    };
+                    if name == "_sd" || map.contains_key(&name) {
+                        return Err(SdJwtError::ClaimCollision(name));
+                    }
+                    map.insert(name, value);
+                }
+            }
+            for value in map.values_mut() {
+                reveal(value, disclosures)?;
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                reveal(item, disclosures)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// SD-JWT, consisting of the issuer-signed JWT, disclosures and an optional
+/// key binding JWT signed by the holder.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct SdJwt {
+    pub jwt: String,
+    pub disclosures: Vec<String>,
+    pub kb_jwt: Option<String>,
+}
+
+impl SdJwt {
+    /// Names of the disclosed claims, in the order of disclosures.
+    pub fn disclosed_claims(&self) -> Result<Vec<String>, SdJwtError> {
+        self.disclosures
+            .iter()
+            .map(|disclosure| decode_disclosure(disclosure).map(|(name, _)| name))
+            .collect()
+    }
+
+    /// Removes disclosures of claims with a given `name`, such that they are
+    /// not revealed to the verifier. Since this changes the presentation, the
+    /// key binding is removed as well.
+    pub fn withhold(&mut self, name: &str) -> Result<(), SdJwtError> {
+        let names = self.disclosed_claims()?;
+        let mut names = names.iter();
+        self.disclosures
+            .retain(|_| names.next().is_some_and(|disclosed| disclosed != name));
+        self.kb_jwt = None;
+        Ok(())
+    }
+
+    /// Binds the presentation to the `holder` key, audience and nonce.
+    pub fn bind(&mut self, holder: &SsiPair, aud: &str, nonce: &str) -> Result<(), SdJwtError> {
+        let payload = json!({
+            "iat": Utc::now().timestamp(),
+            "aud": aud,
+            "nonce": nonce,
+            "sd_hash": digest(&self.to_unbound_string()),
+        });
+        let kb_jwt = holder.sign_jws_typed(Some(KB_JWT_TYP), &serde_json::to_vec(&payload)?)?;
+        self.kb_jwt = Some(kb_jwt);
+        Ok(())
+    }
+
+    /// Verifies SD-JWT issued by a key known to the `resolver`, returning only
+    /// the disclosed claims. Withheld claims are absent from the result.
+    ///
+    /// If `key_binding` audience and nonce are given, requires a valid key
+    /// binding JWT signed by the holder.
+    pub fn verify(
+        &self,
+        resolver: &impl KeyResolver,
+        key_binding: Option<(&str, &str)>,
+    ) -> Result<Value, SdJwtError> {
+        let jws = Jws::from_str(&self.jwt)?;
+        let issuer = jws.verify()?;
+        if !is_known_key(resolver, issuer) {
+            return Err(SdJwtError::UnknownIssuer(issuer));
+        }
+
+        let mut claims = serde_json::from_slice::<Value>(&jws.payload)?;
+        let Value::Object(map) = &mut claims else {
+            return Err(SdJwtError::InvalidClaims);
+        };
+        match map.remove("_sd_alg") {
+            None => {}
+            Some(alg) if alg == SD_ALG => {}
+            Some(alg) => return Err(SdJwtError::UnsupportedHashAlg(alg)),
+        }
+        let iss = map.get("iss").and_then(Value::as_str);
+        if iss.map(SsiPub::from_did_key).transpose()? != Some(issuer) {
+            return Err(SdJwtError::IssuerMismatch);
+        }
+
+        let mut disclosures = BTreeMap::new();
+        for disclosure in &self.disclosures {
+            if disclosures
+                .insert(digest(disclosure), decode_disclosure(disclosure)?)
+                .is_some()
+            {
+                return Err(SdJwtError::DuplicateDisclosure(disclosure.clone()));
+            }
+        }
+        reveal(&mut claims, &mut disclosures)?;
+        if let Some((digest, _)) = disclosures.first_key_value() {
+            let disclosure = self
+                .disclosures
+                .iter()
+                .find(|disclosure| self::digest(disclosure) == *digest)
+                .expect("disclosure is taken from the list");
+            return Err(SdJwtError::UnreferencedDisclosure(disclosure.clone()));
+        }
+
+        let Some((aud, nonce)) = key_binding else {
+            return Ok(claims);
+        };
+        let kb_jwt = self.kb_jwt.as_deref().ok_or(SdJwtError::MissingKeyBinding)?;
+        let holder = claims
+            .pointer("/cnf/kid")
+            .and_then(Value::as_str)
+            .ok_or(SdJwtError::NoHolderKey)?;
+        let holder = SsiPub::from_did_key(holder)?;
+        let kb = Jws::from_str(kb_jwt)?;
+        if kb.header.typ.as_deref() != Some(KB_JWT_TYP) {
+            return Err(SdJwtError::KeyBindingMismatch);
+        }
+        kb.verify_with(holder)?;
+        let payload = serde_json::from_slice::<Value>(&kb.payload)?;
+        if payload["aud"] != aud ||
+            payload["nonce"] != nonce ||
+            payload["sd_hash"] != digest(&self.to_unbound_string())
+        {
+            return Err(SdJwtError::KeyBindingMismatch);
+        }
+        Ok(claims)
+    }
+
+    fn to_unbound_string(&self) -> String {
+        let mut s = self.jwt.clone();
+        for disclosure in &self.disclosures {
+            s.push('~');
+            s.push_str(disclosure);
+        }
+        s.push('~');
+        s
+    }
+}
+
+impl Display for SdJwt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_unbound_string())?;
+        if let Some(kb_jwt) = &self.kb_jwt {
+            f.write_str(kb_jwt)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SdJwt {
+    type Err = SdJwtError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('~').collect::<Vec<_>>();
+        if parts.len() < 2 {
+            return Err(SdJwtError::Malformed);
+        }
+        let kb_jwt = parts.pop().filter(|kb_jwt| !kb_jwt.is_empty());
+        let jwt = parts.remove(0);
+        Ok(SdJwt {
+            jwt: jwt.to_owned(),
+            disclosures: parts.into_iter().map(str::to_owned).collect(),
+            kb_jwt: kb_jwt.map(str::to_owned),
+        })
+    }
+}
+
+impl SsiPair {
+    /// Issues SD-JWT with `claims`, making claims at the `disclosable` paths
+    /// (in JSON pointer format, like `/address/street`) selectively
+    /// disclosable. Each object with selectively disclosable claims receives
+    /// `decoys` number of additional fake digests, hiding the real number of
+    /// claims.
+    ///
+    /// If `holder` key is provided, it is put into `cnf.kid` claim, allowing
+    /// the holder to produce key binding.
+    pub fn issue_sd_jwt(
+        &self,
+        mut claims: Value,
+        disclosable: &[&str],
+        decoys: usize,
+        holder: Option<&SsiPub>,
+    ) -> Result<SdJwt, SdJwtError> {
+        if !claims.is_object() {
+            return Err(SdJwtError::InvalidClaims);
+        }
+        for path in disclosable {
+            if path.is_empty() || claims.pointer(path).is_none() {
+                return Err(SdJwtError::UnknownClaim(path.to_string()));
+            }
+        }
+        let disclosable = disclosable.iter().copied().collect::<BTreeSet<_>>();
+        let mut disclosures = vec![];
+        conceal(&mut claims, "", &disclosable, decoys, &mut disclosures)?;
+
+        let map = claims.as_object_mut().expect("checked above");
+        map.insert(s!("iss"), json!(self.pk.to_did_key()?));
+        map.insert(s!("iat"), json!(Utc::now().timestamp()));
+        map.insert(s!("_sd_alg"), json!(SD_ALG));
+        if let Some(holder) = holder {
+            map.insert(s!("cnf"), json!({ "kid": holder.to_did_key()? }));
+        }
+        let jwt = self.sign_jws_typed(Some(SD_JWT_TYP), &serde_json::to_vec(&claims)?)?;
+        Ok(SdJwt {
+            jwt,
+            disclosures,
+            kb_jwt: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{Algo, Chain, Fingerprint, SsiSecret};
+
+    fn issue() -> (SsiPair, SsiPair, HashMap<Fingerprint, SsiPub>, SdJwt) {
+        let issuer = SsiPair::from(SsiSecret::new(Algo::Ed25519, Chain::Bitcoin));
+        let holder = SsiPair::from(SsiSecret::new(Algo::Ecdsa, Chain::Bitcoin));
+        let resolver = HashMap::from([(issuer.pk.fingerprint(), issuer.pk)]);
+        let claims = json!({
+            "given_name": "Alice",
+            "family_name": "Doe",
+            "address": { "street": "Main st. 1", "country": "CH" },
+            "nationalities": ["CH", "DE"],
+        });
+        let paths = ["/given_name", "/family_name", "/address", "/address/street"];
+        let sd_jwt = issuer.issue_sd_jwt(claims, &paths, 2, Some(&holder.pk)).unwrap();
+        (issuer, holder, resolver, sd_jwt)
+    }
+
+    #[test]
+    fn disclose_all() {
+        let (issuer, _, resolver, sd_jwt) = issue();
+        assert_eq!(sd_jwt.disclosures.len(), 4);
+
+        let payload = Jws::from_str(&sd_jwt.jwt).unwrap().payload;
+        let payload = serde_json::from_slice::<Value>(&payload).unwrap();
+        assert_eq!(payload["_sd"].as_array().unwrap().len(), 3 + 2);
+        assert_eq!(payload.get("given_name"), None);
+        assert_eq!(payload.get("address"), None);
+
+        let parsed = SdJwt::from_str(&sd_jwt.to_string()).unwrap();
+        assert_eq!(parsed, sd_jwt);
+        let claims = parsed.verify(&resolver, None).unwrap();
+        assert_eq!(claims["given_name"], "Alice");
+        assert_eq!(claims["family_name"], "Doe");
+        assert_eq!(claims["address"], json!({ "street": "Main st. 1", "country": "CH" }));
+        assert_eq!(claims["nationalities"], json!(["CH", "DE"]));
+        assert_eq!(claims["iss"], issuer.pk.to_did_key().unwrap());
+        assert_eq!(claims.get("_sd"), None);
+        assert_eq!(claims.get("_sd_alg"), None);
+
+        assert!(matches!(
+            sd_jwt.verify(&HashMap::<Fingerprint, SsiPub>::new(), None),
+            Err(SdJwtError::UnknownIssuer(_))
+        ));
+    }
+
+    #[test]
+    fn withhold() {
+        let (_, _, resolver, mut sd_jwt) = issue();
+        sd_jwt.withhold("family_name").unwrap();
+        sd_jwt.withhold("street").unwrap();
+        assert_eq!(sd_jwt.disclosed_claims().unwrap().len(), 2);
+
+        let claims = sd_jwt.verify(&resolver, None).unwrap();
+        assert_eq!(claims["given_name"], "Alice");
+        assert_eq!(claims.get("family_name"), None);
+        assert_eq!(claims["address"], json!({ "country": "CH" }));
+    }
+
+    #[test]
+    fn tampered() {
+        let (_, _, resolver, mut sd_jwt) = issue();
+        let (name, _) = decode_disclosure(&sd_jwt.disclosures[0]).unwrap();
+        let forged = URL_SAFE_NO_PAD.encode(json!(["salt", name, "Mallory"]).to_string());
+        sd_jwt.disclosures[0] = forged;
+        assert!(matches!(
+            sd_jwt.verify(&resolver, None),
+            Err(SdJwtError::UnreferencedDisclosure(_))
+        ));
+
+        let (_, _, resolver, mut sd_jwt) = issue();
+        sd_jwt.disclosures.push(sd_jwt.disclosures[0].clone());
+        assert!(matches!(
+            sd_jwt.verify(&resolver, None),
+            Err(SdJwtError::DuplicateDisclosure(_))
+        ));
+    }
+
+    #[test]
+    fn key_binding() {
+        let (issuer, holder, resolver, mut sd_jwt) = issue();
+        sd_jwt.withhold("family_name").unwrap();
+        assert!(matches!(
+            sd_jwt.verify(&resolver, Some(("verifier", "123"))),
+            Err(SdJwtError::MissingKeyBinding)
+        ));
+
+        sd_jwt.bind(&holder, "verifier", "123").unwrap();
+        let parsed = SdJwt::from_str(&sd_jwt.to_string()).unwrap();
+        assert_eq!(parsed, sd_jwt);
+        parsed.verify(&resolver, Some(("verifier", "123"))).unwrap();
+        assert!(matches!(
+            parsed.verify(&resolver, Some(("verifier", "456"))),
+            Err(SdJwtError::KeyBindingMismatch)
+        ));
+
+        let mut stripped = sd_jwt.clone();
+        stripped.disclosures.pop();
+        assert!(matches!(
+            stripped.verify(&resolver, Some(("verifier", "123"))),
+            Err(SdJwtError::KeyBindingMismatch)
+        ));
+
+        let mut forged = sd_jwt.clone();
+        forged.bind(&issuer, "verifier", "123").unwrap();
+        assert!(matches!(
+            forged.verify(&resolver, Some(("verifier", "123"))),
+            Err(SdJwtError::Jws(_))
+        ));
+    }
+}
@@ -19,7 +19,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::fmt::{self, Display, Formatter};
+use std::fmt::{self, Debug, Display, Formatter};
 use std::hash::Hash;
 use std::str::FromStr;
 
@@ -36,15 +36,17 @@ use chrono::{DateTime, Utc};
 use sha2::{Digest, Sha256};
 
 use crate::{
-    decrypt, encrypt, Algo, Bip340Secret, Chain, Ed25519Secret, Fingerprint, Ssi, SsiCert, SsiPub,
-    SsiSig,
+    decrypt, encrypt, Algo, Bip340Secret, Chain, EcdsaSecret, Ed25519Secret, Fingerprint,
+    InvalidSecret, KeyUsage, RevocationReason, SignerError, Ssi, SsiCert, SsiPub, SsiSig,
 };
+use crate::revocation::revocation_message;
 
 #[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error, From)]
 #[display(doc_comments)]
 pub enum RevealError {
     #[from(ec25519::Error)]
     #[from(secp256k1::Error)]
+    #[from(InvalidSecret)]
     /// invalid password.
     InvalidPassword,
 
@@ -70,15 +72,22 @@ impl EncryptedSecret {
         let sk = decrypt(&self.key, self.nonce, passwd.as_ref())?;
         match self.algo {
             Algo::Ed25519 => {
-                Ok(Ed25519Secret::with(self.chain, ec25519::SecretKey::from_slice(&sk)?).into())
+                let pair = ec25519::KeyPair::from_seed(ec25519::Seed::from_slice(&sk)?);
+                Ok(Ed25519Secret::with(self.chain, pair.sk).into())
+            }
+            Algo::Bip340 => {
+                let key = <[u8; 32]>::try_from(sk.as_slice()).map_err(|_| RevealError::Decrypt)?;
+                Ok(Bip340Secret::with(self.chain, key)?.into())
+            }
+            Algo::Ecdsa => {
+                Ok(EcdsaSecret::with(self.chain, secp256k1::SecretKey::from_slice(&sk)?).into())
             }
-            Algo::Bip340 => Ok(secp256k1::SecretKey::from_slice(&sk)?.into()),
             Algo::Other(algo) => Err(RevealError::Unsupported(algo)),
         }
     }
 }
 
-#[derive(Debug, Display, Error, From)]
+#[derive(Debug, Display, From)]
 #[display(doc_comments)]
 pub enum SecretParseError {
     /// incomplete private key data.
@@ -102,6 +111,17 @@ pub enum SecretParseError {
     Decode(base64::DecodeError),
 }
 
+impl std::error::Error for SecretParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SecretParseError::InvalidNonce(err) => Some(err),
+            SecretParseError::InvalidFingerprint(err) => Some(err),
+            SecretParseError::Decode(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
 impl FromStr for EncryptedSecret {
     type Err = SecretParseError;
 
@@ -119,6 +139,7 @@ impl FromStr for EncryptedSecret {
         let algo = match schema {
             "bip340-priv" => Algo::Bip340,
             "ed25519-priv" => Algo::Ed25519,
+            "ecdsa-priv" => Algo::Ecdsa,
             other => return Err(SecretParseError::UnsupportedAlgo(other.to_owned())),
         };
 
@@ -128,6 +149,9 @@ impl FromStr for EncryptedSecret {
             fp,
             nonce,
             algo,
+            // The chain is not a part of the serialized secret; the runtime restores it from the
+            // identity with the same fingerprint on load.
+            chain: Chain::default(),
             key,
         })
     }
@@ -154,6 +178,15 @@ pub enum SsiSecret {
     Bip340(Bip340Secret),
     #[from]
     Ed25519(Ed25519Secret),
+    #[from]
+    Ecdsa(EcdsaSecret),
+}
+
+/// Debug output contains only the public key, never the secret key material.
+impl Debug for SsiSecret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SsiSecret").field(&self.to_public()).finish()
+    }
 }
 
 impl SsiSecret {
@@ -161,6 +194,7 @@ impl SsiSecret {
         match algo {
             Algo::Ed25519 => Self::new_ed25519(chain),
             Algo::Bip340 => Self::new_bip340(chain),
+            Algo::Ecdsa => Self::new_ecdsa(chain),
             Algo::Other(other) => panic!("unsupported algorithm {}", other),
         }
     }
@@ -175,6 +209,11 @@ impl SsiSecret {
         Self::Bip340(sk)
     }
 
+    pub fn new_ecdsa(chain: Chain) -> Self {
+        let sk = EcdsaSecret::new(chain);
+        Self::Ecdsa(sk)
+    }
+
     pub fn vanity(prefix: &str, algo: Algo, chain: Chain, threads: u8) -> Self {
         let (tx, rx) = crossbeam_channel::bounded(1);
         for _ in 0..threads {
@@ -185,7 +224,7 @@ impl SsiSecret {
                     let sk = Self::new(algo, chain);
                     let pk = sk.to_public();
                     let start = format!("ssi:{prefix}");
-                    if pk.to_string().starts_with(&start) {
+                    if pk.to_uri_string().starts_with(&start) {
                         tx.send(sk).expect("unable to send key");
                     }
                 }
@@ -194,10 +233,17 @@ impl SsiSecret {
         rx.recv().expect("threading failed")
     }
 
+    /// Signature algorithm of the key, derived from its public key.
+    pub fn algo(&self) -> Algo { *self.to_public().algo() }
+
+    /// Chain the key is bound to, derived from its public key.
+    pub fn chain(&self) -> Chain { *self.to_public().chain() }
+
     pub fn algorithm(&self) -> Algo {
         match self {
             SsiSecret::Bip340(_) => Algo::Bip340,
             SsiSecret::Ed25519(_) => Algo::Ed25519,
+            SsiSecret::Ecdsa(_) => Algo::Ecdsa,
         }
     }
 
@@ -205,6 +251,17 @@ impl SsiSecret {
         match self {
             SsiSecret::Bip340(sk) => sk.to_public(),
             SsiSecret::Ed25519(sk) => sk.to_public(),
+            SsiSecret::Ecdsa(sk) => sk.to_public(),
+        }
+    }
+
+    /// Returns the full public key point in SEC1 uncompressed form for keys
+    /// over secp256k1 curve, or `None` for Ed25519 keys.
+    pub fn public_uncompressed(&self) -> Option<[u8; 65]> {
+        match self {
+            SsiSecret::Bip340(sk) => Some(sk.to_sec_pubkey_uncompressed()),
+            SsiSecret::Ed25519(_) => None,
+            SsiSecret::Ecdsa(sk) => Some(sk.to_sec_pubkey_uncompressed()),
         }
     }
 
@@ -212,23 +269,89 @@ impl SsiSecret {
         match self {
             SsiSecret::Bip340(sk) => sk.sign(msg),
             SsiSecret::Ed25519(sk) => sk.sign(msg),
+            SsiSecret::Ecdsa(sk) => sk.sign(msg),
         }
     }
 
+    /// Certifies other identity, attesting it with the signature of this
+    /// secret key. The certificate covers the full identity data (UIDs and
+    /// expiry), such that any change to the identity invalidates it.
+    pub fn certify(&self, subject: &Ssi) -> SsiCert {
+        let pk = self.to_public();
+        let msg = subject.to_message();
+        SsiCert {
+            fp: pk.fingerprint(),
+            pk: Some(pk),
+            subject: Some(subject.pk),
+            msg: Bytes32::from_byte_array(msg),
+            sig: self.sign(msg),
+            signed_at: None,
+            timestamp: None,
+        }
+    }
+
+    /// Signs the `subject` data binding the signing time `when` (truncated to
+    /// seconds) into the signed message, such that the time can't be altered
+    /// without invalidating the signature (see [`SsiCert::signed_at`] and
+    /// [`SsiCert::verify_with_max_age`]).
+    pub fn certify_at(&self, subject: &[u8], when: DateTime<Utc>) -> SsiCert {
+        let pk = self.to_public();
+        let mut cert = SsiCert {
+            fp: pk.fingerprint(),
+            pk: Some(pk),
+            subject: None,
+            msg: Bytes32::from_byte_array(SsiCert::data_digest(subject)),
+            sig: SsiSig::from([0u8; 64]),
+            signed_at: Some(when.timestamp()),
+            timestamp: None,
+        };
+        cert.sig = self.sign(cert.signed_message());
+        cert
+    }
+
+    /// Produces self-signed certificate revoking the key, which commits to
+    /// the revocation `reason`. See [`SsiPub::is_revoked_by`].
+    pub fn revoke(&self, reason: RevocationReason) -> SsiCert {
+        let pk = self.to_public();
+        let msg = revocation_message(pk, reason);
+        SsiCert {
+            fp: pk.fingerprint(),
+            pk: Some(pk),
+            subject: Some(pk),
+            msg: Bytes32::from_byte_array(msg),
+            sig: self.sign(msg),
+            signed_at: None,
+            timestamp: None,
+        }
+    }
+
+    /// Generates revocation certificate for the key with unspecified reason,
+    /// meant to be created together with the key and kept in a safe place,
+    /// such that the key can be revoked even if the secret is lost.
+    pub fn generate_revocation(&self) -> SsiCert { self.revoke(RevocationReason::Unspecified) }
+
     pub fn conceal(&self, passwd: impl AsRef<str>) -> EncryptedSecret {
-        let (nonce, key) = encrypt(self.secret_bytes().to_vec(), passwd.as_ref());
+        // Ed25519 secret scalar is a hash of the seed, so the seed has to be stored instead
+        let data = match self {
+            SsiSecret::Ed25519(sk) => sk.key.seed().to_vec(),
+            _ => self.secret_bytes().to_vec(),
+        };
+        let (nonce, key) = encrypt(data, passwd.as_ref());
+        let pk = self.to_public();
         EncryptedSecret {
-            fp: self.to_public().fingerprint(),
+            fp: pk.fingerprint(),
             nonce,
             algo: self.algorithm(),
+            chain: *pk.chain(),
             key,
         }
     }
 
     pub fn secret_bytes(&self) -> [u8; 32] {
         match self {
-            SsiSecret::Bip340(sk) => sk.key.secret_bytes(),
+            SsiSecret::Bip340(sk) => sk.key,
             SsiSecret::Ed25519(sk) => sk.key.seed().scalar(),
+            SsiSecret::Ecdsa(sk) => sk.key.secret_bytes(),
         }
     }
 }
@@ -243,6 +366,8 @@ pub struct SsiPair {
     pub pk: SsiPub,
     pub sk: SsiSecret,
     pub expiry: Option<DateTime<Utc>>,
+    /// Usages of the key from its identity (see [`Ssi::usage`]).
+    pub usage: KeyUsage,
 }
 
 impl From<SsiSecret> for SsiPair {
@@ -251,6 +376,7 @@ impl From<SsiSecret> for SsiPair {
             pk: sk.to_public(),
             sk,
             expiry: None,
+            usage: KeyUsage::ALL,
         }
     }
 }
@@ -261,6 +387,7 @@ impl SsiPair {
             pk: ssi.pk,
             sk,
             expiry: ssi.expiry,
+            usage: ssi.usage,
         }
     }
 
@@ -271,8 +398,193 @@ impl SsiPair {
         SsiCert {
             fp: self.pk.fingerprint(),
             pk: Some(self.pk),
+            subject: None,
             msg: Bytes32::from_byte_array(digest),
             sig,
+            signed_at: None,
+            timestamp: None,
+        }
+    }
+
+    /// Signs the identity with the pair secret key, replacing its existing
+    /// signature (see [`Ssi::resign`]).
+    pub fn sign_identity(&self, ssi: &mut Ssi) -> Result<(), SignerError> { ssi.resign(&self.sk) }
+
+    /// Certifies other identity with the pair secret key (see
+    /// [`SsiSecret::certify`]), unless the key is not authorized for signing.
+    pub fn certify(&self, subject: &Ssi) -> Result<SsiCert, SignerError> {
+        if !self.usage.contains(KeyUsage::SIGN) {
+            return Err(SignerError::UsageNotAllowed(KeyUsage::SIGN));
+        }
+        Ok(self.sk.certify(subject))
+    }
+}
+
+/// Source of signatures over message digests, which may be either a local
+/// secret key or a remote signer.
+pub trait SigningIdentity {
+    /// Public key corresponding to the signatures produced.
+    fn public(&self) -> SsiPub;
+
+    fn sign(&self, msg: [u8; 32]) -> Result<SsiSig, SignerError>;
+}
+
+impl SigningIdentity for SsiSecret {
+    fn public(&self) -> SsiPub { self.to_public() }
+
+    fn sign(&self, msg: [u8; 32]) -> Result<SsiSig, SignerError> { Ok(SsiSecret::sign(self, msg)) }
+}
+
+/// Signs with the pair secret key, refusing to sign once the identity has
+/// expired or if the secret key doesn't match the pair public key.
+impl SigningIdentity for SsiPair {
+    fn public(&self) -> SsiPub { self.pk }
+
+    fn sign(&self, msg: [u8; 32]) -> Result<SsiSig, SignerError> {
+        if self.sk.to_public() != self.pk {
+            return Err(SignerError::KeyMismatch);
+        }
+        if let Some(expiry) = self.expiry.filter(|expiry| *expiry <= Utc::now()) {
+            return Err(SignerError::Expired(expiry));
+        }
+        Ok(self.sk.sign(msg))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{IntegrityStatus, Uid, VerifyError, VerifyingIdentity};
+
+    #[test]
+    fn conceal_reveal() {
+        for algo in [Algo::Ed25519, Algo::Bip340, Algo::Ecdsa] {
+            let secret = SsiSecret::new(algo, Chain::Liquid);
+            let encrypted = secret.conceal("passwd");
+            assert!(encrypted.reveal("wrong").is_err());
+            let revealed = encrypted.reveal("passwd").unwrap();
+            assert_eq!(revealed.to_public(), secret.to_public());
+            assert_eq!(revealed.secret_bytes(), secret.secret_bytes());
+        }
+    }
+
+    #[test]
+    fn algo_chain() {
+        for algo in [Algo::Ed25519, Algo::Bip340, Algo::Ecdsa] {
+            for chain in [Chain::Bitcoin, Chain::Liquid, Chain::Regtest] {
+                let secret = SsiSecret::new(algo, chain);
+                assert_eq!(secret.algo(), algo);
+                assert_eq!(secret.algo(), secret.algorithm());
+                assert_eq!(secret.chain(), chain);
+            }
+        }
+    }
+
+    #[test]
+    fn cross_certification() {
+        let alice = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let bob = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let uid = Uid::from_str("Bob <mailto:bob@example.com>").unwrap();
+        let mut ssi = Ssi::new(bset![uid], None, &bob);
+
+        let cert = alice.certify(&ssi);
+        assert_eq!(cert.issuer(), alice.to_public().fingerprint());
+        assert_eq!(cert.subject(), Some(bob.to_public()));
+        cert.verify().unwrap();
+        cert.verify_certification(&ssi).unwrap();
+        assert_eq!(SsiCert::from_str(&cert.to_string()).unwrap().subject(), cert.subject());
+
+        let uid = Uid::from_str("Mallory <mailto:mallory@example.com>").unwrap();
+        ssi.uids.insert(uid);
+        assert!(matches!(cert.verify_certification(&ssi), Err(VerifyError::MessageMismatch)));
+    }
+
+    #[test]
+    fn certify_at() {
+        use chrono::Duration;
+
+        let sk = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        let when = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let cert = sk.certify_at(b"document", when);
+        assert_eq!(cert.signed_at(), Some(when));
+        cert.verify().unwrap();
+        cert.verify_data(b"document").unwrap();
+
+        let parsed = SsiCert::from_str(&format!("{cert:#}")).unwrap();
+        assert_eq!(parsed, cert);
+        assert!(cert.to_string().contains("&time=2023-11-14T22:13:20Z"));
+
+        let max_age = Duration::hours(1);
+        assert_eq!(cert.verify_with_max_age(max_age, when).unwrap(), when);
+        assert_eq!(cert.verify_with_max_age(max_age, when + max_age).unwrap(), when);
+        assert!(matches!(
+            cert.verify_with_max_age(max_age, when + max_age + Duration::seconds(1)),
+            Err(VerifyError::TooOld(time)) if time == when
+        ));
+        assert!(matches!(
+            cert.verify_with_max_age(max_age, when - Duration::seconds(1)),
+            Err(VerifyError::SignedInFuture(_))
+        ));
+
+        let mut backdated = cert.clone();
+        backdated.signed_at = Some(1_600_000_000);
+        assert!(matches!(backdated.verify(), Err(VerifyError::InvalidSig(_))));
+        let mut stripped = cert.clone();
+        stripped.signed_at = None;
+        assert!(stripped.verify().is_err());
+        let plain = SsiPair::from(sk).sign("document");
+        assert!(matches!(
+            plain.verify_with_max_age(max_age, when),
+            Err(VerifyError::NoSigningTime)
+        ));
+    }
+
+    #[test]
+    fn pair_operations() {
+        let alice = SsiPair::from(SsiSecret::new(Algo::Ed25519, Chain::Bitcoin));
+        let bob = SsiSecret::new(Algo::Bip340, Chain::Bitcoin);
+        let uid = Uid::from_str("Alice <mailto:alice@example.com>").unwrap();
+        let mut ssi = Ssi::new(bset![uid.clone()], None, &alice.sk);
+        ssi.uids.insert(Uid::from_str("Alice <https:alice.example.com>").unwrap());
+        assert!(ssi.integrity().is_invalid());
+        alice.sign_identity(&mut ssi).unwrap();
+        assert_eq!(ssi.integrity(), IntegrityStatus::SelfSigned);
+
+        let mut other = Ssi::new(bset![uid], None, &bob);
+        assert_eq!(alice.sign_identity(&mut other), Err(SignerError::KeyMismatch));
+
+        let cert = alice.certify(&other).unwrap();
+        assert_eq!(cert.issuer(), alice.pk.fingerprint());
+        cert.verify_certification(&other).unwrap();
+
+        ssi.usage = KeyUsage::ENCRYPT;
+        alice.sign_identity(&mut ssi).unwrap();
+        let encrypt_only = SsiPair::new(ssi, alice.sk.clone());
+        assert_eq!(encrypt_only.certify(&other), Err(SignerError::UsageNotAllowed(KeyUsage::SIGN)));
+    }
+
+    fn sign_and_verify(signer: &impl SigningIdentity, verifier: &impl VerifyingIdentity) {
+        let msg = [0xA5; 32];
+        let sig = signer.sign(msg).unwrap();
+        verifier.verify(msg, sig).unwrap();
+        assert!(verifier.verify([0x5A; 32], sig).is_err());
+    }
+
+    #[test]
+    fn signing_identity() {
+        for algo in [Algo::Ed25519, Algo::Bip340] {
+            let sk = SsiSecret::new(algo, Chain::Bitcoin);
+            let pk = sk.to_public();
+            sign_and_verify(&sk, &pk);
+            let mut pair = SsiPair::from(sk);
+            assert_eq!(SigningIdentity::public(&pair), pk);
+            sign_and_verify(&pair, &pk);
+
+            pair.expiry = Some(Utc::now() - chrono::Duration::days(1));
+            assert!(matches!(SigningIdentity::sign(&pair, [0; 32]), Err(SignerError::Expired(_))));
+            pair.expiry = None;
+            pair.pk = SsiSecret::new(algo, Chain::Bitcoin).to_public();
+            assert_eq!(SigningIdentity::sign(&pair, [0; 32]), Err(SignerError::KeyMismatch));
         }
     }
 }
@@ -0,0 +1,237 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Detached signature files (`.sig`), keeping the signer key, the signed
+//! digest, the signature and an optional timestamp proof in a text form
+//! readable without the signed document:
+//!
+//! ```text
+//! -----BEGIN SSI SIGNATURE-----
+//! Signer: ssi:...
+//! Digest: <hex of the double SHA256 hash of the document>
+//! Created: <signing time in RFC 3339 format>
+//! Signature: <signature>
+//! Timestamp: <hex of the OpenTimestamps proof>
+//! -----END SSI SIGNATURE-----
+//! ```
+//!
+//! The `Created` field is present only if the signature binds the signing time
+//! (see [`SsiSecret::certify_at`]), and the `Timestamp` field only if the
+//! certificate was timestamped.
+//!
+//! [`SsiSecret::certify_at`]: crate::SsiSecret::certify_at
+//! Fields unknown to this version are ignored for forward compatibility.
+
+use std::str::FromStr;
+
+use amplify::Bytes32;
+use chrono::{DateTime, SecondsFormat};
+
+use crate::{CertParseError, Fingerprint, OtsProof, SsiCert, SsiPub, SsiSig};
+
+pub const SIG_FILE_BEGIN: &str = "-----BEGIN SSI SIGNATURE-----";
+pub const SIG_FILE_END: &str = "-----END SSI SIGNATURE-----";
+
+#[derive(Debug, Display, From)]
+#[display(doc_comments)]
+pub enum SigFileError {
+    /// signature file doesn't start with '-----BEGIN SSI SIGNATURE-----'.
+    NoBegin,
+    /// signature file doesn't end with '-----END SSI SIGNATURE-----'.
+    NoEnd,
+    /// signature file contains invalid line '{0}'.
+    InvalidLine(String),
+    /// signature file contains repeated field '{0}'.
+    RepeatedField(String),
+    /// signature file lacks required field '{0}'.
+    MissingField(&'static str),
+    /// invalid signature file data - {0}
+    #[from]
+    Data(CertParseError),
+}
+
+impl std::error::Error for SigFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SigFileError::Data(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl SsiCert {
+    /// Serializes the certificate as a detached signature file (see the
+    /// module docs for the format). Certifications of other identities are
+    /// not supported by the format and lose their subject.
+    pub fn to_sig_file(&self) -> String {
+        let signer = match self.pk {
+            Some(pk) => pk.to_uri_string(),
+            None => format!("ssi:{}", self.fp),
+        };
+        let mut s = format!("{SIG_FILE_BEGIN}\nSigner: {signer}\nDigest: {}\n", self.msg);
+        if let Some(signed_at) = self.signed_at() {
+            let created = signed_at.to_rfc3339_opts(SecondsFormat::Secs, true);
+            s.push_str(&format!("Created: {created}\n"));
+        }
+        s.push_str(&format!("Signature: {}\n", self.sig.to_uri_string()));
+        if let Some(timestamp) = &self.timestamp {
+            s.push_str(&format!("Timestamp: {timestamp}\n"));
+        }
+        s.push_str(SIG_FILE_END);
+        s.push('\n');
+        s
+    }
+
+    /// Parses detached signature file produced by [`SsiCert::to_sig_file`].
+    pub fn from_sig_file(s: &str) -> Result<Self, SigFileError> {
+        let mut lines = s.trim().lines().map(str::trim);
+        if lines.next() != Some(SIG_FILE_BEGIN) {
+            return Err(SigFileError::NoBegin);
+        }
+        if lines.next_back() != Some(SIG_FILE_END) {
+            return Err(SigFileError::NoEnd);
+        }
+
+        let mut signer = None;
+        let mut msg = None;
+        let mut signed_at = None;
+        let mut sig = None;
+        let mut timestamp = None;
+        for line in lines.filter(|line| !line.is_empty()) {
+            let (k, v) = line
+                .split_once(':')
+                .ok_or_else(|| SigFileError::InvalidLine(line.to_owned()))?;
+            let v = v.trim();
+            let repeated = match k {
+                "Signer" => signer.replace(v).is_some(),
+                "Digest" => msg
+                    .replace(Bytes32::from_str(v).map_err(CertParseError::from)?)
+                    .is_some(),
+                "Created" => signed_at
+                    .replace(
+                        DateTime::parse_from_rfc3339(v)
+                            .map_err(|_| CertParseError::InvalidSigningTime(v.to_owned()))?
+                            .timestamp(),
+                    )
+                    .is_some(),
+                "Signature" => sig
+                    .replace(SsiSig::from_str(v).map_err(CertParseError::from)?)
+                    .is_some(),
+                "Timestamp" => timestamp
+                    .replace(OtsProof::from_str(v).map_err(CertParseError::InvalidTimestamp)?)
+                    .is_some(),
+                _ => false,
+            };
+            if repeated {
+                return Err(SigFileError::RepeatedField(k.to_owned()));
+            }
+        }
+
+        let signer = signer.ok_or(SigFileError::MissingField("Signer"))?;
+        let signer = signer.trim_start_matches("ssi:");
+        let (fp, pk) = match signer.len() {
+            8 => (Fingerprint::from_str(signer).map_err(CertParseError::InvalidFingerprint)?, None),
+            _ => {
+                let pk = SsiPub::from_str(signer).map_err(CertParseError::InvalidPub)?;
+                (pk.fingerprint(), Some(pk))
+            }
+        };
+        Ok(SsiCert {
+            fp,
+            pk,
+            subject: None,
+            msg: msg.ok_or(SigFileError::MissingField("Digest"))?,
+            sig: sig.ok_or(SigFileError::MissingField("Signature"))?,
+            signed_at,
+            timestamp,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Algo, Chain, SsiPair, SsiSecret, VerifyError};
+
+    #[test]
+    fn sig_file_roundtrip() {
+        let pair = SsiPair::from(SsiSecret::new(Algo::Bip340, Chain::Bitcoin));
+        let doc = b"Document content\n";
+        let cert = pair.sign(doc);
+        let file = cert.to_sig_file();
+        assert!(file.starts_with(SIG_FILE_BEGIN));
+        assert!(file.contains(&format!("Signer: {}\n", pair.pk.to_uri_string())));
+
+        let parsed = SsiCert::from_sig_file(&file).unwrap();
+        assert_eq!(parsed, cert);
+        parsed.verify_data(doc).unwrap();
+        assert!(matches!(parsed.verify_data(b"Other\n"), Err(VerifyError::MessageMismatch)));
+
+        let crlf = file.replace('\n', "\r\n");
+        assert_eq!(SsiCert::from_sig_file(&crlf).unwrap(), cert);
+        let extended = file.replace("Signature:", "Comment: release\nSignature:");
+        assert_eq!(SsiCert::from_sig_file(&extended).unwrap(), cert);
+
+        let when = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let cert = pair.sk.certify_at(doc, when);
+        let file = cert.to_sig_file();
+        assert!(file.contains("Created: 2023-11-14T22:13:20Z\n"));
+        let parsed = SsiCert::from_sig_file(&file).unwrap();
+        assert_eq!(parsed, cert);
+        assert_eq!(parsed.signed_at(), Some(when));
+        parsed.verify_data(doc).unwrap();
+    }
+
+    #[test]
+    fn sig_file_errors() {
+        let pair = SsiPair::from(SsiSecret::new(Algo::Ed25519, Chain::Bitcoin));
+        let file = pair.sign("message").to_sig_file();
+
+        assert!(matches!(SsiCert::from_sig_file(&file[5..]), Err(SigFileError::NoBegin)));
+        let unterminated = file.replace(SIG_FILE_END, "");
+        assert!(matches!(SsiCert::from_sig_file(&unterminated), Err(SigFileError::NoEnd)));
+
+        let (_, rest) = file.split_once("Digest:").unwrap();
+        let (digest, _) = rest.split_once('\n').unwrap();
+        let repeated = file.replace("Signature:", &format!("Digest:{digest}\nSignature:"));
+        assert!(matches!(
+            SsiCert::from_sig_file(&repeated),
+            Err(SigFileError::RepeatedField(field)) if field == "Digest"
+        ));
+        let garbage = file.replace("Signature:", "Garbage\nSignature:");
+        assert!(matches!(
+            SsiCert::from_sig_file(&garbage),
+            Err(SigFileError::InvalidLine(line)) if line == "Garbage"
+        ));
+        let invalid = file.replace("Digest: ", "Digest: zz");
+        assert!(matches!(
+            SsiCert::from_sig_file(&invalid),
+            Err(SigFileError::Data(CertParseError::InvalidMessage(_)))
+        ));
+
+        let (before, _) = file.split_once("Signature:").unwrap();
+        let truncated = format!("{before}{SIG_FILE_END}\n");
+        assert!(matches!(
+            SsiCert::from_sig_file(&truncated),
+            Err(SigFileError::MissingField("Signature"))
+        ));
+    }
+}
@@ -0,0 +1,321 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! W3C Verifiable Credentials (VC data model 1.1) issued by identities.
+//!
+//! Issuers, subjects and holders are represented by their `did:key`s. Proofs
+//! are detached JWS (see [`crate::Jws`]) over the JSON serialization of the
+//! credential or presentation without the `proof` field, with object keys in
+//! lexicographic order. Such proofs are not compatible with JSON-LD signature
+//! suites, which require RDF canonicalization.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{DidError, Jws, JwsError, KeyResolver, SsiPair, SsiPub, SsiQuery};
+
+pub const VC_CONTEXT: &str = "https://www.w3.org/2018/credentials/v1";
+pub const VC_PROOF_TYPE: &str = "JsonWebSignature2020";
+
+#[derive(Debug, Display, From)]
+#[display(doc_comments)]
+pub enum VcError {
+    /// credential claims must be a JSON object.
+    InvalidClaims,
+    /// credential or presentation has no proof.
+    NoProof,
+    /// proof type '{0}' is not supported.
+    UnsupportedProof(String),
+    /// proof verification method doesn't belong to the issuer.
+    IssuerMismatch,
+    /// issuer key {0} is not known to the resolver.
+    UnknownIssuer(SsiPub),
+    /// credential is issued on {0}, which is in the future.
+    NotYetValid(DateTime<Utc>),
+    /// credential has expired on {0}.
+    Expired(DateTime<Utc>),
+    /// credential subject has no valid `id`.
+    NoSubject,
+    /// presented credential is issued for a different subject.
+    SubjectMismatch,
+
+    #[from]
+    #[display(inner)]
+    Did(DidError),
+
+    #[from]
+    #[display(inner)]
+    Jws(JwsError),
+
+    #[from]
+    /// invalid credential JSON - {0}
+    Json(serde_json::Error),
+}
+
+impl std::error::Error for VcError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VcError::Did(err) => Some(err),
+            VcError::Jws(err) => Some(err),
+            VcError::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Checks that the key, decoded from a `did:key` (and thus lacking chain
+/// information), is known to the `resolver`.
+pub(crate) fn is_known_key(resolver: &impl KeyResolver, key: SsiPub) -> bool {
+    resolver
+        .resolve(&SsiQuery::Fp(key.fingerprint()))
+        .is_some_and(|pk| pk.algo() == key.algo() && pk.to_byte_array() == key.to_byte_array())
+}
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Proof {
+    #[serde(rename = "type")]
+    pub proof_type: String,
+    pub created: DateTime<Utc>,
+    pub verification_method: String,
+    pub proof_purpose: String,
+    pub jws: String,
+}
+
+impl Proof {
+    fn sign(pair: &SsiPair, purpose: &str, unsigned: &impl Serialize) -> Result<Proof, VcError> {
+        let did = pair.pk.to_did_key()?;
+        let payload = serde_json::to_vec(&serde_json::to_value(unsigned)?)?;
+        Ok(Proof {
+            proof_type: VC_PROOF_TYPE.to_owned(),
+            created: Utc::now(),
+            verification_method: format!("{did}#{}", &did[crate::did::DID_KEY_PREFIX.len()..]),
+            proof_purpose: purpose.to_owned(),
+            jws: pair.sign_jws_detached(&payload)?,
+        })
+    }
+
+    /// Verifies the proof against the controller `did` of the verification
+    /// method, returning the controller key.
+    fn verify(&self, did: &str, unsigned: &impl Serialize) -> Result<SsiPub, VcError> {
+        if self.proof_type != VC_PROOF_TYPE {
+            return Err(VcError::UnsupportedProof(self.proof_type.clone()));
+        }
+        let (controller, _) = self
+            .verification_method
+            .split_once('#')
+            .unwrap_or((&self.verification_method, ""));
+        if controller != did {
+            return Err(VcError::IssuerMismatch);
+        }
+        let pk = SsiPub::from_did_key(did)?;
+        let payload = serde_json::to_vec(&serde_json::to_value(unsigned)?)?;
+        let jws = Jws::from_detached(&self.jws, &payload)?;
+        if jws.signer()? != pk {
+            return Err(VcError::IssuerMismatch);
+        }
+        jws.verify_with(pk)?;
+        Ok(pk)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Credential {
+    #[serde(rename = "@context")]
+    pub context: Vec<String>,
+    #[serde(rename = "type")]
+    pub types: Vec<String>,
+    pub issuer: String,
+    pub issuance_date: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub expiration_date: Option<DateTime<Utc>>,
+    pub credential_subject: Value,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub proof: Option<Proof>,
+}
+
+impl Credential {
+    /// Returns key of the credential subject.
+    pub fn subject(&self) -> Result<SsiPub, VcError> {
+        let did = self.credential_subject["id"]
+            .as_str()
+            .ok_or(VcError::NoSubject)?;
+        Ok(SsiPub::from_did_key(did)?)
+    }
+
+    /// Verifies credential proof, validity period at the moment `now`, and
+    /// that the issuer key is known to the `resolver`.
+    pub fn verify(&self, resolver: &impl KeyResolver, now: DateTime<Utc>) -> Result<(), VcError> {
+        let proof = self.proof.as_ref().ok_or(VcError::NoProof)?;
+        let unsigned = Credential {
+            proof: None,
+            ..self.clone()
+        };
+        let issuer = proof.verify(&self.issuer, &unsigned)?;
+
+        if !is_known_key(resolver, issuer) {
+            return Err(VcError::UnknownIssuer(issuer));
+        }
+        if self.issuance_date > now {
+            return Err(VcError::NotYetValid(self.issuance_date));
+        }
+        if let Some(expiry) = self.expiration_date.filter(|expiry| *expiry <= now) {
+            return Err(VcError::Expired(expiry));
+        }
+        self.subject()?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Presentation {
+    #[serde(rename = "@context")]
+    pub context: Vec<String>,
+    #[serde(rename = "type")]
+    pub types: Vec<String>,
+    pub holder: String,
+    pub verifiable_credential: Vec<Credential>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub proof: Option<Proof>,
+}
+
+impl Presentation {
+    /// Verifies holder proof and all presented credentials, which must be
+    /// issued to the holder.
+    pub fn verify(&self, resolver: &impl KeyResolver, now: DateTime<Utc>) -> Result<(), VcError> {
+        let proof = self.proof.as_ref().ok_or(VcError::NoProof)?;
+        let unsigned = Presentation {
+            proof: None,
+            ..self.clone()
+        };
+        let holder = proof.verify(&self.holder, &unsigned)?;
+        for credential in &self.verifiable_credential {
+            credential.verify(resolver, now)?;
+            if credential.subject()? != holder {
+                return Err(VcError::SubjectMismatch);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SsiPair {
+    /// Issues credential with `claims` about the `subject`, signing it with
+    /// the key of this identity.
+    pub fn issue_credential(
+        &self,
+        subject: &SsiPub,
+        claims: Value,
+        expiry: Option<DateTime<Utc>>,
+    ) -> Result<Credential, VcError> {
+        let Value::Object(mut claims) = claims else {
+            return Err(VcError::InvalidClaims);
+        };
+        claims.insert(s!("id"), Value::String(subject.to_did_key()?));
+        let mut credential = Credential {
+            context: vec![VC_CONTEXT.to_owned()],
+            types: vec![s!("VerifiableCredential")],
+            issuer: self.pk.to_did_key()?,
+            issuance_date: Utc::now(),
+            expiration_date: expiry,
+            credential_subject: Value::Object(claims),
+            proof: None,
+        };
+        credential.proof = Some(Proof::sign(self, "assertionMethod", &credential)?);
+        Ok(credential)
+    }
+
+    /// Presents credentials issued to this identity.
+    pub fn present(&self, credentials: Vec<Credential>) -> Result<Presentation, VcError> {
+        let mut presentation = Presentation {
+            context: vec![VC_CONTEXT.to_owned()],
+            types: vec![s!("VerifiablePresentation")],
+            holder: self.pk.to_did_key()?,
+            verifiable_credential: credentials,
+            proof: None,
+        };
+        presentation.proof = Some(Proof::sign(self, "authentication", &presentation)?);
+        Ok(presentation)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use chrono::Duration;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Algo, Chain, Fingerprint, SsiSecret};
+
+    #[test]
+    fn credential() {
+        let issuer = SsiPair::from(SsiSecret::new(Algo::Ed25519, Chain::Bitcoin));
+        let holder = SsiPair::from(SsiSecret::new(Algo::Ecdsa, Chain::Bitcoin));
+        let resolver = HashMap::from([(issuer.pk.fingerprint(), issuer.pk)]);
+
+        let claims = json!({ "memberOf": { "name": "Org X", "role": "member" } });
+        let expiry = Utc::now() + Duration::days(1);
+        let credential = issuer.issue_credential(&holder.pk, claims, Some(expiry)).unwrap();
+        let now = Utc::now();
+        assert_eq!(credential.subject().unwrap(), holder.pk);
+        credential.verify(&resolver, now).unwrap();
+
+        let json = serde_json::to_string(&credential).unwrap();
+        let parsed = serde_json::from_str::<Credential>(&json).unwrap();
+        assert_eq!(parsed, credential);
+        parsed.verify(&resolver, now).unwrap();
+
+        let mut tampered = credential.clone();
+        tampered.credential_subject["memberOf"]["role"] = json!("admin");
+        assert!(matches!(tampered.verify(&resolver, now), Err(VcError::Jws(_))));
+
+        let mut tampered = credential.clone();
+        tampered.expiration_date = None;
+        assert!(matches!(tampered.verify(&resolver, now), Err(VcError::Jws(_))));
+
+        assert!(matches!(
+            credential.verify(&resolver, now + Duration::days(2)),
+            Err(VcError::Expired(_))
+        ));
+        assert!(matches!(
+            credential.verify(&resolver, now - Duration::days(1)),
+            Err(VcError::NotYetValid(_))
+        ));
+        assert!(matches!(
+            credential.verify(&HashMap::<Fingerprint, SsiPub>::new(), now),
+            Err(VcError::UnknownIssuer(_))
+        ));
+        assert!(matches!(
+            issuer.issue_credential(&holder.pk, json!("claim"), None),
+            Err(VcError::InvalidClaims)
+        ));
+
+        let presentation = holder.present(vec![credential.clone()]).unwrap();
+        presentation.verify(&resolver, now).unwrap();
+        let presentation = issuer.present(vec![credential]).unwrap();
+        assert!(matches!(presentation.verify(&resolver, now), Err(VcError::SubjectMismatch)));
+    }
+}
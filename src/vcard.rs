@@ -0,0 +1,200 @@
+// Self-sovereign identity
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Export of identities as vCard 4.0 (RFC 6350) contacts for address book
+//! applications.
+
+use std::str::FromStr;
+
+use crate::{Ssi, Uid, UidSchema};
+
+/// Maximal length of a vCard content line, in octets, before it is folded.
+const VCARD_LINE_MAX: usize = 75;
+
+/// Escapes vCard text value.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ',' => escaped.push_str("\\,"),
+            ';' => escaped.push_str("\\;"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Folds content line into lines of at most [`VCARD_LINE_MAX`] octets,
+/// terminating each with CRLF. Multi-byte characters are never split.
+fn fold(line: &str, vcard: &mut String) {
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > VCARD_LINE_MAX {
+            vcard.push_str("\r\n ");
+            len = 1;
+        }
+        vcard.push(c);
+        len += c.len_utf8();
+    }
+    vcard.push_str("\r\n");
+}
+
+/// Formats UID as a vCard property, if its schema has a vCard counterpart.
+fn uid_property(uid: &Uid) -> Option<String> {
+    let schema = UidSchema::from_str(&uid.schema).expect("infallible");
+    Some(match schema {
+        UidSchema::Mailto => format!("EMAIL:{}", escape(&uid.id)),
+        UidSchema::Tel => format!("TEL;VALUE=uri:tel:{}", uid.id),
+        UidSchema::Https => match uid.id.strip_prefix("//") {
+            Some(url) => format!("URL:https://{url}"),
+            None => format!("URL:https://{}", uid.id),
+        },
+        UidSchema::Xmpp | UidSchema::Nostr | UidSchema::Tg => format!("IMPP:{schema}:{}", uid.id),
+        UidSchema::Dns | UidSchema::Other(_) => return None,
+    })
+}
+
+impl Ssi {
+    /// Represents the identity as vCard 4.0 contact.
+    ///
+    /// The formatted name (`FN`) is the name of the [`Ssi::primary_uid`], or
+    /// its id if the name is empty. UIDs with `mailto`, `tel`, `https` and
+    /// instant messaging schemas become `EMAIL`, `TEL`, `URL` and `IMPP`
+    /// properties, with the primary one marked as preferred; UIDs of other
+    /// schemas are omitted. The `KEY` property contains the identity URI,
+    /// and `REV` the identity expiry, if any.
+    pub fn to_vcard(&self) -> String {
+        let mut vcard = String::new();
+        fold("BEGIN:VCARD", &mut vcard);
+        fold("VERSION:4.0", &mut vcard);
+
+        let primary = self.primary_uid();
+        let name = match primary {
+            Some(uid) if !uid.name.is_empty() => uid.name.as_str(),
+            Some(uid) => uid.id.as_str(),
+            None => "",
+        };
+        fold(&format!("FN:{}", escape(name)), &mut vcard);
+
+        for uid in self.uids_by_schema() {
+            let Some(property) = uid_property(uid) else {
+                continue;
+            };
+            match property.split_once(':') {
+                Some((name, value)) if Some(uid) == primary => {
+                    fold(&format!("{name};PREF=1:{value}"), &mut vcard)
+                }
+                _ => fold(&property, &mut vcard),
+            }
+        }
+
+        fold(&format!("KEY:{self}"), &mut vcard);
+        if let Some(expiry) = self.expiry {
+            fold(&format!("REV:{}", expiry.format("%Y%m%dT%H%M%SZ")), &mut vcard);
+        }
+        fold("END:VCARD", &mut vcard);
+        vcard
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_expiry, Algo, Chain, SsiSecret};
+
+    fn uid(name: &str, schema: &str, id: &str) -> Uid {
+        Uid {
+            name: name.to_owned(),
+            comment: None,
+            schema: schema.to_owned(),
+            id: id.to_owned(),
+            primary: false,
+        }
+    }
+
+    fn unfold(vcard: &str) -> Vec<String> {
+        vcard
+            .strip_suffix("\r\n")
+            .unwrap()
+            .replace("\r\n ", "")
+            .split("\r\n")
+            .map(str::to_owned)
+            .collect()
+    }
+
+    #[test]
+    fn vcard() {
+        let secret = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let mut mail = uid("Doe, John; Jr.\nthe 2nd \\ more", "mailto", "john@example.com");
+        mail.primary = true;
+        let uids = bset![
+            mail,
+            uid("John", "tel", "+12025550123"),
+            uid("", "https", "example.com/john"),
+            uid("", "xmpp", "john@jabber.org"),
+            uid("", "dns", "example.com"),
+        ];
+        let ssi = Ssi::new(uids, parse_expiry("2030-01-01").ok(), &secret);
+        let vcard = ssi.to_vcard();
+
+        for line in vcard.split("\r\n") {
+            assert!(line.len() <= VCARD_LINE_MAX, "{line}");
+        }
+        assert_eq!(unfold(&vcard), [
+            s!("BEGIN:VCARD"),
+            s!("VERSION:4.0"),
+            s!("FN:Doe\\, John\\; Jr.\\nthe 2nd \\\\ more"),
+            s!("URL:https://example.com/john"),
+            s!("EMAIL;PREF=1:john@example.com"),
+            s!("TEL;VALUE=uri:tel:+12025550123"),
+            s!("IMPP:xmpp:john@jabber.org"),
+            format!("KEY:{ssi}"),
+            s!("REV:20300101T235959Z"),
+            s!("END:VCARD"),
+        ]);
+    }
+
+    #[test]
+    fn vcard_minimal() {
+        let secret = SsiSecret::new(Algo::Ed25519, Chain::Bitcoin);
+        let ssi = Ssi::new(bset![uid("", "dns", "example.com")], None, &secret);
+        assert_eq!(unfold(&ssi.to_vcard()), [
+            s!("BEGIN:VCARD"),
+            s!("VERSION:4.0"),
+            s!("FN:example.com"),
+            format!("KEY:{ssi}"),
+            s!("END:VCARD"),
+        ]);
+    }
+
+    #[test]
+    fn fold_multibyte() {
+        let mut vcard = String::new();
+        fold(&format!("FN:{}", "ж".repeat(80)), &mut vcard);
+        for line in vcard.split("\r\n") {
+            assert!(line.len() <= VCARD_LINE_MAX);
+        }
+        assert_eq!(unfold(&vcard), [format!("FN:{}", "ж".repeat(80))]);
+    }
+}
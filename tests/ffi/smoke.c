@@ -0,0 +1,54 @@
+/* SPDX-License-Identifier: Apache-2.0 */
+
+/* Smoke test for the C ABI; run with `make -C tests/ffi`. */
+
+#include <assert.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "ssi.h"
+
+/* Key from RFC 8032 test 1 with an identity self-signed by it */
+static const char *PK = "ssi:4xVNMfW1-Wh4AjzL-B~jF9P0-Sw3k5oy-zgc8oMl-0XfezmT-H6W";
+static const char *SSI = "ssi:4xVNMfW1-Wh4AjzL-B~jF9P0-Sw3k5oy-zgc8oMl-0XfezmT-H6W?sig="
+                         "UrDbWenDqG2DFBok95L84SZBkqdMbzyCT52_yu5nEWx3woJkgzQwNYw4VrY9wWbM3i4WNuZlEaPzN8bXsVlY0W";
+
+/* Signature of the same key over 32 bytes of 0xA5 */
+static const uint8_t SIG[64] = {
+    0xca, 0xb8, 0xb8, 0x76, 0x59, 0x9d, 0x14, 0x87,
+    0xee, 0x5f, 0xf9, 0xfd, 0xef, 0xb0, 0x16, 0x8d,
+    0xa6, 0x66, 0xe8, 0xd6, 0x13, 0xcc, 0x84, 0x12,
+    0x8d, 0xd3, 0xe2, 0xa4, 0x93, 0xe5, 0x6e, 0x64,
+    0xd5, 0x8d, 0x86, 0x9c, 0xc8, 0xb5, 0xea, 0xb5,
+    0xc1, 0x90, 0x5d, 0x23, 0xd3, 0xf2, 0xf1, 0x41,
+    0x29, 0x4b, 0xe5, 0x08, 0xa7, 0x60, 0xc3, 0x08,
+    0xd1, 0xa5, 0x40, 0xb5, 0xee, 0xfe, 0xbc, 0x02,
+};
+
+int main(void) {
+    assert(ssi_verify((const uint8_t *)SSI, strlen(SSI)) == SSI_OK);
+    assert(ssi_verify((const uint8_t *)SSI, strlen(PK)) == SSI_ERR_UNSIGNED);
+
+    SsiPubHandle *alice = NULL;
+    assert(ssi_pub_from_str((const uint8_t *)PK, strlen(PK), &alice) == SSI_OK);
+    assert(alice != NULL);
+    uint8_t msg[32];
+    memset(msg, 0xA5, sizeof(msg));
+    assert(ssi_sig_verify(alice, msg, SIG) == SSI_OK);
+    msg[0] ^= 1;
+    assert(ssi_sig_verify(alice, msg, SIG) == SSI_ERR_INVALID_SIG);
+    ssi_pub_free(alice);
+
+    const char *invalid = "ssi:invalid";
+    assert(ssi_verify((const uint8_t *)invalid, strlen(invalid)) == SSI_ERR_PARSE);
+    assert(ssi_verify(NULL, 0) == SSI_ERR_NULL);
+
+    SsiPubHandle *pk = NULL;
+    assert(ssi_pub_from_str((const uint8_t *)invalid, strlen(invalid), &pk) == SSI_ERR_PARSE);
+    assert(pk == NULL);
+    assert(ssi_sig_verify(pk, NULL, NULL) == SSI_ERR_NULL);
+    ssi_pub_free(pk);
+
+    printf("ssi ffi smoke test passed\n");
+    return 0;
+}